@@ -0,0 +1,275 @@
+use crate::terminal::*;
+
+pub const CTRL_D: i32 = 4;
+pub const CTRL_U: i32 = 21;
+pub const CTRL_V: i32 = 22;
+pub const CTRL_Y: i32 = 25;
+pub const CTRL_Z: i32 = 26;
+pub const ESCAPE: i32 = 27;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    Up,
+    Down,
+    DragUp,
+    DragDown,
+    First,
+    Last,
+    PageUp,
+    PageDown,
+    HalfPageUp,
+    HalfPageDown,
+    Rename,
+    Insert,
+    Delete,
+    ClearDone,
+    Copy,
+    OpenUrl,
+    Paste,
+    Transfer,
+    SwitchPanel,
+    ToggleWrap,
+    ToggleLineNumbers,
+    CycleLayout,
+    ToggleDone,
+    ToggleDetails,
+    Calendar,
+    ClearFilter,
+    Stats,
+    Focus,
+    Repeat,
+    Command,
+    Record,
+    Replay,
+    PrevBoard,
+    NextBoard,
+    GrowTodo,
+    ShrinkTodo,
+    Help,
+    Suspend,
+    Quit,
+}
+
+impl Action {
+    pub const ALL: &'static [Action] = &[
+        Action::Up,
+        Action::Down,
+        Action::DragUp,
+        Action::DragDown,
+        Action::First,
+        Action::Last,
+        Action::PageUp,
+        Action::PageDown,
+        Action::HalfPageUp,
+        Action::HalfPageDown,
+        Action::Rename,
+        Action::Insert,
+        Action::Delete,
+        Action::ClearDone,
+        Action::Copy,
+        Action::OpenUrl,
+        Action::Paste,
+        Action::Transfer,
+        Action::SwitchPanel,
+        Action::ToggleWrap,
+        Action::ToggleLineNumbers,
+        Action::CycleLayout,
+        Action::ToggleDone,
+        Action::ToggleDetails,
+        Action::Calendar,
+        Action::ClearFilter,
+        Action::Stats,
+        Action::Focus,
+        Action::Repeat,
+        Action::Command,
+        Action::Record,
+        Action::Replay,
+        Action::PrevBoard,
+        Action::NextBoard,
+        Action::GrowTodo,
+        Action::ShrinkTodo,
+        Action::Help,
+        Action::Suspend,
+        Action::Quit,
+    ];
+
+    // How the action is called in the [keys] section of the config
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::Up => "up",
+            Action::Down => "down",
+            Action::DragUp => "drag_up",
+            Action::DragDown => "drag_down",
+            Action::First => "first",
+            Action::Last => "last",
+            Action::PageUp => "page_up",
+            Action::PageDown => "page_down",
+            Action::HalfPageUp => "half_page_up",
+            Action::HalfPageDown => "half_page_down",
+            Action::Rename => "rename",
+            Action::Insert => "insert",
+            Action::Delete => "delete",
+            Action::ClearDone => "clear_done",
+            Action::Copy => "copy",
+            Action::OpenUrl => "open_url",
+            Action::Paste => "paste",
+            Action::Transfer => "transfer",
+            Action::SwitchPanel => "switch_panel",
+            Action::ToggleWrap => "toggle_wrap",
+            Action::ToggleLineNumbers => "toggle_line_numbers",
+            Action::CycleLayout => "cycle_layout",
+            Action::ToggleDone => "toggle_done",
+            Action::ToggleDetails => "toggle_details",
+            Action::Calendar => "calendar",
+            Action::ClearFilter => "clear_filter",
+            Action::Stats => "stats",
+            Action::Focus => "focus",
+            Action::Repeat => "repeat",
+            Action::Command => "command",
+            Action::Record => "record",
+            Action::Replay => "replay",
+            Action::PrevBoard => "prev_board",
+            Action::NextBoard => "next_board",
+            Action::GrowTodo => "grow_todo",
+            Action::ShrinkTodo => "shrink_todo",
+            Action::Help => "help",
+            Action::Suspend => "suspend",
+            Action::Quit => "quit",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .find(|action| action.name() == name)
+            .copied()
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            Action::Up => "Move cursor up",
+            Action::Down => "Move cursor down",
+            Action::DragUp => "Drag the current item up",
+            Action::DragDown => "Drag the current item down",
+            Action::First => "Jump to the start of the list",
+            Action::Last => "Jump to the end of the list",
+            Action::PageUp => "Scroll the list a page up",
+            Action::PageDown => "Scroll the list a page down",
+            Action::HalfPageUp => "Scroll the list half a page up",
+            Action::HalfPageDown => "Scroll the list half a page down",
+            Action::Rename => "Rename the current item",
+            Action::Insert => "Insert a new item",
+            Action::Delete => "Delete the current item",
+            Action::ClearDone => "Delete all the DONE items",
+            Action::Copy => "Copy the current item to the clipboard",
+            Action::OpenUrl => "Open the link in the current item",
+            Action::Paste => "Paste the clipboard as new items",
+            Action::Transfer => "Move the current item to the other panel",
+            Action::SwitchPanel => "Switch between the TODO and DONE panels",
+            Action::ToggleWrap => "Toggle wrapping of the long items",
+            Action::ToggleLineNumbers => "Cycle line numbers: off, absolute, relative",
+            Action::CycleLayout => "Cycle panel layouts: split, stacked, single panel",
+            Action::ToggleDone => "Collapse or expand the DONE panel",
+            Action::ToggleDetails => "Show or hide the details of the current item",
+            Action::Calendar => "Show the calendar of the due and done items",
+            Action::ClearFilter => "Show all the items again",
+            Action::Stats => "Show the statistics",
+            Action::Focus => "Focus on the current TODO item",
+            Action::Repeat => "Repeat the last change",
+            Action::Command => "Type a command, e.g. :w, :sort, :filter #home, :open <file>",
+            Action::Record => "Record a macro into the register typed next, again to stop",
+            Action::Replay => "Replay the macro from the register typed next, @ for the last one",
+            Action::PrevBoard => "Switch to the previous open file",
+            Action::NextBoard => "Switch to the next open file",
+            Action::GrowTodo => "Give more space to the TODO panel",
+            Action::ShrinkTodo => "Give more space to the DONE panel",
+            Action::Help => "Show this help",
+            Action::Suspend => "Suspend the app, `fg` in the shell brings it back",
+            Action::Quit => "Quit",
+        }
+    }
+
+    // Whether the action changes the list in a way that Action::Repeat can repeat
+    pub fn is_change(&self) -> bool {
+        matches!(
+            self,
+            Action::DragUp | Action::DragDown | Action::Delete | Action::Transfer
+        )
+    }
+
+    // Whether the action changes the lists, which the read-only files don't allow
+    pub fn mutates(&self) -> bool {
+        matches!(
+            self,
+            Action::DragUp
+                | Action::DragDown
+                | Action::Rename
+                | Action::Insert
+                | Action::Delete
+                | Action::ClearDone
+                | Action::Paste
+                | Action::Transfer
+        )
+    }
+
+    // Whether the action does something to the current item rather than to the list or the app
+    pub fn on_item(&self) -> bool {
+        matches!(
+            self,
+            Action::DragUp
+                | Action::DragDown
+                | Action::Rename
+                | Action::Delete
+                | Action::Copy
+                | Action::Transfer
+        )
+    }
+}
+
+// The default keys of the lists. The [keys] section of the config can change any of them, see Keymap.
+pub const BINDINGS: &[(i32, Action)] = &[
+    ('k' as i32, Action::Up),
+    (constants::KEY_UP, Action::Up),
+    ('j' as i32, Action::Down),
+    (constants::KEY_DOWN, Action::Down),
+    ('K' as i32, Action::DragUp),
+    (constants::KEY_SR, Action::DragUp),
+    ('J' as i32, Action::DragDown),
+    (constants::KEY_SF, Action::DragDown),
+    ('g' as i32, Action::First),
+    ('G' as i32, Action::Last),
+    (constants::KEY_PPAGE, Action::PageUp),
+    (constants::KEY_NPAGE, Action::PageDown),
+    (CTRL_U, Action::HalfPageUp),
+    (CTRL_D, Action::HalfPageDown),
+    ('r' as i32, Action::Rename),
+    ('i' as i32, Action::Insert),
+    ('d' as i32, Action::Delete),
+    ('D' as i32, Action::ClearDone),
+    ('y' as i32, Action::Copy),
+    ('o' as i32, Action::OpenUrl),
+    ('p' as i32, Action::Paste),
+    (CTRL_V, Action::Paste),
+    ('\n' as i32, Action::Transfer),
+    ('\t' as i32, Action::SwitchPanel),
+    ('w' as i32, Action::ToggleWrap),
+    ('N' as i32, Action::ToggleLineNumbers),
+    ('L' as i32, Action::CycleLayout),
+    ('z' as i32, Action::ToggleDone),
+    ('v' as i32, Action::ToggleDetails),
+    ('c' as i32, Action::Calendar),
+    (ESCAPE, Action::ClearFilter),
+    ('S' as i32, Action::Stats),
+    ('f' as i32, Action::Focus),
+    ('.' as i32, Action::Repeat),
+    (':' as i32, Action::Command),
+    ('q' as i32, Action::Record),
+    ('@' as i32, Action::Replay),
+    ('[' as i32, Action::PrevBoard),
+    (']' as i32, Action::NextBoard),
+    ('>' as i32, Action::GrowTodo),
+    ('<' as i32, Action::ShrinkTodo),
+    ('?' as i32, Action::Help),
+    (CTRL_Z, Action::Suspend),
+    ('Q' as i32, Action::Quit),
+];
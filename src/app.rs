@@ -0,0 +1,1810 @@
+use std::cmp;
+use std::collections::BTreeSet;
+#[cfg(unix)]
+use std::ffi::CString;
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::action::*;
+use crate::batch::find_todo;
+#[cfg(feature = "dbus")]
+use crate::bus;
+use crate::caldav::CalDav;
+use crate::cli::STDIN_PATH;
+use crate::command::{self, Command, Scope};
+#[cfg(unix)]
+use crate::control;
+use crate::event::Event;
+use crate::git::{self, AutoCommit};
+use crate::github::GitHub;
+use crate::hooks::{Hook, Hooks};
+use crate::keymap::Keymap;
+use crate::notifications::Notifications;
+use crate::remind::Reminders;
+#[cfg(feature = "scripting")]
+use crate::script;
+use crate::sync::Service;
+use crate::terminal::*;
+use crate::ui::*;
+use crate::view::*;
+use crate::{browser, clipboard, session};
+use todo_rs::crypt::Cipher;
+use todo_rs::date::Date;
+use todo_rs::header::Header;
+use todo_rs::items::{Items, Unsaved};
+use todo_rs::lock::FileLock;
+use todo_rs::*;
+
+#[derive(Clone, Copy)]
+pub enum Answer {
+    Yes,
+    No,
+    Cancel,
+}
+
+impl Answer {
+    // The answers to Ui::dialog()
+    pub fn from_key(key: i32) -> Option<Self> {
+        match key {
+            key if key == 'y' as i32 || key == 'Y' as i32 => Some(Answer::Yes),
+            key if key == 'n' as i32 || key == 'N' as i32 => Some(Answer::No),
+            ESCAPE => Some(Answer::Cancel),
+            _ => None,
+        }
+    }
+}
+
+// Moves the cursor of Ui::calendar() with h/j/k/l and returns Yes once the user picks the day under it
+pub fn calendar_key(cursor: &mut Date, key: i32) -> Option<Answer> {
+    match key {
+        key if key == 'h' as i32 || key == constants::KEY_LEFT => *cursor = cursor.add_days(-1),
+        key if key == 'l' as i32 || key == constants::KEY_RIGHT => *cursor = cursor.add_days(1),
+        key if key == 'k' as i32 || key == constants::KEY_UP => *cursor = cursor.add_days(-7),
+        key if key == 'j' as i32 || key == constants::KEY_DOWN => *cursor = cursor.add_days(7),
+        key if key == '\n' as i32 => return Some(Answer::Yes),
+        key if key == ESCAPE || key == 'q' as i32 || key == 'c' as i32 => {
+            return Some(Answer::Cancel)
+        }
+        _ => {}
+    }
+    None
+}
+
+// Picks one of the len items of Ui::picker() with j/k and Enter, or right away by its number
+pub fn picker_key(curr: &mut usize, len: usize, key: i32) -> Option<Answer> {
+    match key {
+        key if key == 'k' as i32 || key == constants::KEY_UP => *curr = curr.saturating_sub(1),
+        key if key == 'j' as i32 || key == constants::KEY_DOWN => {
+            *curr = cmp::min(*curr + 1, len.saturating_sub(1))
+        }
+        key if ('1' as i32..='9' as i32).contains(&key) => {
+            let index = (key - '1' as i32) as usize;
+            if index < len {
+                *curr = index;
+                return Some(Answer::Yes);
+            }
+        }
+        key if key == '\n' as i32 => return Some(Answer::Yes),
+        key if key == ESCAPE || key == 'q' as i32 => return Some(Answer::Cancel),
+        _ => {}
+    }
+    None
+}
+
+// Everything but the item the user is working on is hidden
+pub struct Focus {
+    pub started: Instant,
+    pub timer: bool,
+}
+
+impl Focus {
+    pub fn start() -> Self {
+        Self {
+            started: Instant::now(),
+            timer: false,
+        }
+    }
+
+    // HH:MM:SS since the focus began
+    pub fn elapsed(&self, now: Instant) -> String {
+        let secs = now.duration_since(self.started).as_secs();
+        format!("{:02}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+    }
+
+    // When the shown timer changes next, if it's shown at all
+    pub fn next_tick(&self, now: Instant) -> Option<Instant> {
+        if self.timer {
+            let secs = now.duration_since(self.started).as_secs() + 1;
+            Some(self.started + Duration::from_secs(secs))
+        } else {
+            None
+        }
+    }
+}
+
+// Where the user is in the merge conflict resolver, and what they picked so far
+pub struct Resolver {
+    // 0 is our side of the conflict, 1 is theirs
+    pub side: usize,
+    pub curr: usize,
+    pub picked: [Vec<bool>; 2],
+}
+
+impl Resolver {
+    // Starts with all the items of both sides, but the ones that are on both only once
+    pub fn new(conflict: &Conflict) -> Self {
+        let theirs = conflict
+            .theirs
+            .iter()
+            .map(|item| !conflict.ours.contains(item))
+            .collect();
+        Self {
+            side: 0,
+            curr: 0,
+            picked: [vec![true; conflict.ours.len()], theirs],
+        }
+    }
+
+    // Space picks the items to keep from either side of the conflict and Enter (Yes) puts the picked
+    // ones into the lists
+    pub fn key(&mut self, key: i32, conflict: &Conflict) -> Option<Answer> {
+        let answer = match key {
+            key if key == 'k' as i32 || key == constants::KEY_UP => {
+                self.curr = self.curr.saturating_sub(1);
+                None
+            }
+            key if key == 'j' as i32 || key == constants::KEY_DOWN => {
+                self.curr += 1;
+                None
+            }
+            key if key == 'h' as i32
+                || key == 'l' as i32
+                || key == '\t' as i32
+                || key == constants::KEY_LEFT
+                || key == constants::KEY_RIGHT =>
+            {
+                self.side = 1 - self.side;
+                None
+            }
+            key if key == ' ' as i32 => {
+                if let Some(picked) = self.picked[self.side].get_mut(self.curr) {
+                    *picked = !*picked;
+                }
+                None
+            }
+            key if key == 'o' as i32 || key == 't' as i32 => {
+                let side = if key == 'o' as i32 { 0 } else { 1 };
+                for (index, picked) in self.picked.iter_mut().enumerate() {
+                    picked.iter_mut().for_each(|picked| *picked = index == side);
+                }
+                None
+            }
+            key if key == '\n' as i32 => Some(Answer::Yes),
+            ESCAPE => Some(Answer::Cancel),
+            _ => None,
+        };
+        let side_len = [&conflict.ours, &conflict.theirs][self.side].len();
+        self.curr = cmp::min(self.curr, side_len.saturating_sub(1));
+        answer
+    }
+}
+
+// The last change to the lists, for Action::Repeat
+pub enum Change {
+    Action(Action, usize),
+    Paste(String),
+    Insert(String),
+    Rename(String),
+}
+
+#[derive(Clone, Copy)]
+pub enum Confirmation {
+    // That many items starting from the current one
+    Delete(usize),
+    ClearDone,
+    Quit,
+    // The file changed on disk while there are unsaved changes here
+    Reload,
+    // The first of the ill-formed lines of the file, to fix, discard or keep as it is
+    Problem,
+    // Saving failed, either on the way out or not
+    RetrySave { quit: bool },
+}
+
+impl Confirmation {
+    pub fn question(&self, state: &mut App) -> String {
+        match self {
+            Confirmation::Delete(1) => "Delete the item for good?".to_string(),
+            Confirmation::Delete(count) => format!("Delete {} items for good?", count),
+            Confirmation::ClearDone => "Delete all the DONE items for good?".to_string(),
+            Confirmation::Quit => format!(
+                "Save the changes to {} before quitting?",
+                state.unsaved().join(", ")
+            ),
+            Confirmation::Reload => format!(
+                "{} changed on disk. Reload it and lose the changes made here?",
+                state.board().file_path
+            ),
+            Confirmation::RetrySave { quit } => format!(
+                "{}. The changes are NOT saved. Try again?{}",
+                state.save_error,
+                if *quit { " No quits anyway" } else { "" }
+            ),
+            Confirmation::Problem => {
+                let board = state.board();
+                let problems = &board.extras.problems;
+                let problem = &problems[0];
+                let what = match &problem.fix {
+                    Some((status, title)) => format!(
+                        "Turn it into `{}: {}`? No discards it, Esc keeps it as it is",
+                        status.name(),
+                        title
+                    ),
+                    None => "Discard it? No or Esc keeps it as it is".to_string(),
+                };
+                format!(
+                    "{}:{}: {}: `{}` ({} left). {}",
+                    board.file_path,
+                    problem.line,
+                    problem.reason,
+                    problem.text,
+                    problems.len(),
+                    what
+                )
+            }
+        }
+    }
+}
+
+pub fn copy_to_clipboard(text: &str, notifications: &mut Notifications) {
+    match clipboard::copy(text) {
+        Ok(()) => notifications.info("Copied to clipboard"),
+        Err(error) => notifications.error(format!("Could not copy: {}", error)),
+    }
+}
+
+// Not being able to start the command is worth telling, what it does after that is its own business
+pub fn run_hook(
+    hooks: &mut Hooks,
+    hook: Hook,
+    file_path: &str,
+    item: &str,
+    notifications: &mut Notifications,
+) {
+    if let Err(error) = hooks.run(hook, file_path, item) {
+        notifications.error(format!("Could not run the {} hook: {}", hook.name(), error));
+    }
+}
+
+// The autosaves leave the commit to the next save by hand
+pub fn commit_file(
+    autocommit: &mut AutoCommit,
+    file_path: &str,
+    changes: Vec<String>,
+    autosave: bool,
+    notifications: &mut Notifications,
+) {
+    if autosave {
+        autocommit.defer(file_path, changes);
+    } else if let Err(error) = autocommit.commit_changes(file_path, changes) {
+        notifications.error(format!("Could not commit {}: {}", file_path, error));
+    }
+}
+
+pub fn paste_titles(text: &str) -> impl Iterator<Item = &str> {
+    text.split(['\n', '\r'])
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty())
+}
+
+// The tags of the TODO and the DONE lists for the completion, along with the revisions of the lists
+// they are of. The revisions are unique to all the lists of all the files.
+#[derive(Default)]
+pub struct Tags {
+    lists: [Option<(usize, BTreeSet<String>)>; 2],
+}
+
+impl Tags {
+    // All the tags of the lists, sorted. Only the lists that changed since the last time are gone
+    // through again, and the DONE items left in the file are read one at a time rather than all
+    // into the list.
+    pub fn of(&mut self, lists: [&Items; 2]) -> Vec<String> {
+        for (cached, items) in self.lists.iter_mut().zip(lists) {
+            if cached.as_ref().map(|(revision, _)| *revision) == Some(items.revision()) {
+                continue;
+            }
+            let mut tags = BTreeSet::new();
+            for title in (0..items.len()).filter_map(|index| items.title(index)) {
+                tags.extend(item_tags(&title).map(str::to_string));
+            }
+            *cached = Some((items.revision(), tags));
+        }
+        let tags: BTreeSet<&String> = self
+            .lists
+            .iter()
+            .flatten()
+            .flat_map(|(_, tags)| tags)
+            .collect();
+        tags.into_iter().cloned().collect()
+    }
+}
+
+// One of the open files with everything that belongs to it, the lists and where the cursors are
+#[derive(Default)]
+pub struct Board {
+    pub file_path: String,
+    // Held for as long as the file is open, dropping it lets the other instances have the file
+    pub _file_lock: Option<FileLock>,
+    // How the file is encrypted, if it is
+    pub cipher: Option<Cipher>,
+    pub format: format::Kind,
+    pub todos: Items,
+    pub todo_curr: usize,
+    pub todo_scroll: usize,
+    pub dones: Items,
+    pub done_curr: usize,
+    pub done_scroll: usize,
+    // The lines of the file that are not items
+    pub extras: Extras,
+    // What the file looks like on disk, to tell whether there is anything unsaved
+    pub saved_todos: Items,
+    pub saved_dones: Items,
+    pub unsaved: Unsaved,
+    // When the file was modified as far as we know, to tell whether somebody else changed it
+    pub saved_mtime: Option<SystemTime>,
+    // Nothing changes in the lists and the file is never written to, because another instance
+    // has it open, because it's not writable or because the user said so
+    pub read_only: bool,
+    // The lists are not saved to the file, they come from stdin or go to stdout
+    pub detached: bool,
+    pub last_change: Option<Change>,
+    // What the header of the file calls the panels, see panel_name()
+    pub columns: Option<[String; 2]>,
+}
+
+impl Board {
+    pub fn new(
+        file_path: String,
+        file_lock: Option<FileLock>,
+        cipher: Option<Cipher>,
+        todos: Vec<String>,
+        dones: Vec<String>,
+        extras: Extras,
+    ) -> Self {
+        let dones = Items::with_tail(dones, extras.tail().cloned());
+        Self {
+            saved_mtime: file_mtime(&file_path),
+            saved_todos: todos.clone().into(),
+            // Of the same revision, so the tail is not read just to tell that they are the same
+            saved_dones: dones.clone(),
+            file_path,
+            _file_lock: file_lock,
+            cipher,
+            todos: todos.into(),
+            dones,
+            extras,
+            ..Self::default()
+        }
+    }
+
+    pub fn modified(&mut self) -> bool {
+        let lists = [&self.todos, &self.dones];
+        self.unsaved
+            .check(lists, [&self.saved_todos, &self.saved_dones])
+            || self.extras.changed
+    }
+
+    // Replaces the lists with what's in the file now, keeping the cursors where they were as much
+    // as possible
+    pub fn load(&mut self) -> io::Result<()> {
+        if self.file_path == STDIN_PATH {
+            // stdin can't be read again, but what came from it is still here
+            self.todos = self.saved_todos.clone();
+            self.dones = self.saved_dones.clone();
+            self.extras.changed = false;
+        } else {
+            let data = read_state(&self.file_path, self.cipher.as_ref())?;
+            let mut todos = Vec::new();
+            let mut dones = Vec::new();
+            let mut extras = Extras::default();
+            parse_state_lazily(&mut todos, &mut dones, &mut extras, &data, self.format);
+            self.todos = todos.into();
+            self.dones = Items::with_tail(dones, extras.tail().cloned());
+            self.extras = extras;
+            self.saved_todos = self.todos.clone();
+            self.saved_dones = self.dones.clone();
+        }
+        self.todo_curr = cmp::min(self.todo_curr, self.todos.len().saturating_sub(1));
+        self.done_curr = cmp::min(self.done_curr, self.dones.len().saturating_sub(1));
+        self.saved_mtime = file_mtime(&self.file_path);
+        Ok(())
+    }
+}
+
+// Everything the actions act upon. The frames are rendered from it and App::handle() changes it in
+// between the frames.
+pub struct App {
+    // All the open files, the one on the screen is boards[board_index]
+    pub boards: Vec<Board>,
+    pub board_index: usize,
+    pub todo_view: ListView,
+    pub done_view: ListView,
+    // Everything opened is read-only, --readonly
+    pub all_read_only: bool,
+    pub panel: Status,
+    pub editing: bool,
+    pub edit: EditField,
+    pub tags: Tags,
+    // The edited item was just inserted rather than renamed
+    pub inserting: bool,
+    pub word_wrap: bool,
+    pub line_numbers: LineNumbers,
+    pub panel_layout: PanelLayout,
+    // Set by --layout, the headers of the files don't change it then
+    pub layout_flag: bool,
+    pub done_collapsed: bool,
+    pub details: bool,
+    // Opened on the day under the cursor
+    pub calendar: Option<Date>,
+    // Opened on the first of extras.conflicts
+    pub resolver: Option<Resolver>,
+    // The links of the current item to pick from, and the one under the cursor
+    pub links: Option<(Vec<String>, usize)>,
+    pub filter: Filter,
+    pub split: i32,
+    // Where the split is remembered for the next runs, nowhere for the tests
+    pub session_path: Option<PathBuf>,
+    pub help: bool,
+    pub stats: bool,
+    pub focus: Option<Focus>,
+    pub confirmation: Option<Confirmation>,
+    // The text of the `:` command line while it's open
+    pub command_line: Option<(String, EditField)>,
+    pub notifications: Notifications,
+    // Why the last save failed, for Confirmation::RetrySave
+    pub save_error: String,
+    pub age_identity: Option<PathBuf>,
+    // How the files opened from the app find out how they are encrypted. The frontend may need the
+    // terminal for that, like gpg does for the passphrase.
+    pub open_cipher: fn(&str, Option<&Path>) -> io::Result<Option<Cipher>>,
+    // The same for the links, which may open in a browser in the terminal
+    pub open_url: fn(&str) -> io::Result<()>,
+    // The lists of the first file go to stdout on quit, --stdout
+    pub to_stdout: bool,
+    pub hooks: Hooks,
+    pub autocommit: AutoCommit,
+    pub caldav: Option<CalDav>,
+    pub github: Option<GitHub>,
+    pub reminders: Reminders,
+    #[cfg(feature = "scripting")]
+    pub script: Option<script::Script>,
+    // Quitting throws the unsaved changes away, :q! or No to Confirmation::Quit
+    pub discard: bool,
+    pub quit: bool,
+}
+
+impl App {
+    pub fn new(line_numbers: LineNumbers, split: i32) -> Self {
+        Self {
+            boards: Vec::new(),
+            board_index: 0,
+            todo_view: ListView::default(),
+            done_view: ListView::default(),
+            all_read_only: false,
+            panel: Status::Todo,
+            editing: false,
+            edit: EditField::default(),
+            tags: Tags::default(),
+            inserting: false,
+            word_wrap: false,
+            line_numbers,
+            panel_layout: PanelLayout::Split,
+            layout_flag: false,
+            done_collapsed: false,
+            details: false,
+            calendar: None,
+            links: None,
+            resolver: None,
+            filter: Filter::default(),
+            split,
+            session_path: None,
+            help: false,
+            stats: false,
+            focus: None,
+            confirmation: None,
+            command_line: None,
+            notifications: Notifications::default(),
+            save_error: String::new(),
+            age_identity: None,
+            open_cipher: Cipher::for_file,
+            open_url: browser::open,
+            to_stdout: false,
+            hooks: Hooks::default(),
+            autocommit: AutoCommit::default(),
+            caldav: None,
+            github: None,
+            reminders: Reminders::default(),
+            #[cfg(feature = "scripting")]
+            script: None,
+            discard: false,
+            quit: false,
+        }
+    }
+
+    // The file on the screen. The methods that need the other fields of the app along with it take
+    // it from boards[board_index] right away, so that the borrows stay apart.
+    pub fn board(&self) -> &Board {
+        &self.boards[self.board_index]
+    }
+
+    pub fn board_mut(&mut self) -> &mut Board {
+        &mut self.boards[self.board_index]
+    }
+
+    pub fn modified(&mut self) -> bool {
+        self.board_mut().modified()
+    }
+
+    // Nothing is considered saved unless it made it to the disk
+    pub fn save(&mut self, backups: usize, autosave: bool) -> io::Result<()> {
+        self.save_board(self.board_index, backups, autosave)
+    }
+
+    fn save_board(&mut self, index: usize, backups: usize, autosave: bool) -> io::Result<()> {
+        let board = &mut self.boards[index];
+        save_state(
+            &board.todos,
+            &board.dones,
+            &board.extras,
+            &board.file_path,
+            backups,
+            board.cipher.as_ref(),
+            board.format.format(),
+        )?;
+        let changes = git::changes(
+            (&board.saved_todos, &board.saved_dones),
+            (&board.todos, &board.dones),
+        );
+        commit_file(
+            &mut self.autocommit,
+            &board.file_path,
+            changes,
+            autosave,
+            &mut self.notifications,
+        );
+        board.saved_todos = board.todos.clone();
+        board.saved_dones = board.dones.clone();
+        board.saved_mtime = file_mtime(&board.file_path);
+        board.extras.changed = false;
+        run_hook(
+            &mut self.hooks,
+            Hook::Save,
+            &board.file_path,
+            "",
+            &mut self.notifications,
+        );
+        Ok(())
+    }
+
+    // Saves every file with changes in it, going on with the rest if some of them fail. The error
+    // names all the files that are NOT saved.
+    pub fn save_all(&mut self, backups: usize, autosave: bool) -> Result<(), String> {
+        let mut errors = Vec::new();
+        for index in 0..self.boards.len() {
+            let board = &mut self.boards[index];
+            if board.modified() && !board.read_only && !board.detached {
+                if let Err(error) = self.save_board(index, backups, autosave) {
+                    errors.push(format!("{}: {}", self.boards[index].file_path, error));
+                }
+            }
+        }
+        // The files that have nothing new get the commits of their autosaves too
+        if !autosave {
+            self.commit_pending();
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(format!("Could not save {}", errors.join(", ")))
+        }
+    }
+
+    // The files with the changes that are going to be saved on quit
+    pub fn unsaved(&mut self) -> Vec<&str> {
+        let modified: Vec<bool> = self
+            .boards
+            .iter_mut()
+            .map(|board| board.modified() && !board.read_only && !board.detached)
+            .collect();
+        self.boards
+            .iter()
+            .zip(modified)
+            .filter(|(_, modified)| *modified)
+            .map(|(board, _)| board.file_path.as_str())
+            .collect()
+    }
+
+    // Replaces the lists with what's in the file now, keeping the cursors where they were as much
+    // as possible
+    pub fn reload(&mut self) {
+        let board = &mut self.boards[self.board_index];
+        match board.load() {
+            Ok(()) => {
+                let message = format!("Reloaded {} changed on disk", board.file_path);
+                self.notifications.info(message);
+            }
+            Err(error) => {
+                let message = format!("Could not reload {}: {}", board.file_path, error);
+                self.notifications.error(message);
+            }
+        }
+    }
+
+    // Throws away the changes made since the file was last saved
+    pub fn revert(&mut self) {
+        let board = &mut self.boards[self.board_index];
+        if !board.modified() {
+            self.notifications.info("Nothing to revert");
+            return;
+        }
+        match board.load() {
+            Ok(()) => {
+                let message = format!("Reverted {} to the saved state", board.file_path);
+                self.notifications.info(message);
+            }
+            Err(error) => {
+                let message = format!("Could not revert {}: {}", board.file_path, error);
+                self.notifications.error(message);
+            }
+        }
+    }
+
+    // The name of the panel of the items with the status, TODO and DONE unless the file says
+    // otherwise
+    pub fn panel_name(&self, status: Status) -> &str {
+        match &self.board().columns {
+            Some(columns) => &columns[status as usize],
+            None => status.name(),
+        }
+    }
+
+    pub fn switch_board(&mut self, index: usize) {
+        if index != self.board_index && index < self.boards.len() {
+            self.board_index = index;
+            let message = format!(
+                "{} ({}/{})",
+                self.board().file_path,
+                index + 1,
+                self.boards.len()
+            );
+            self.notifications.info(message);
+        }
+    }
+
+    // Switches to the file if it's open already and opens it as a new board otherwise
+    pub fn open(&mut self, file_path: String) {
+        if file_path == self.board().file_path {
+            return;
+        }
+        if let Some(index) = self
+            .boards
+            .iter()
+            .position(|board| board.file_path == file_path)
+        {
+            self.switch_board(index);
+            return;
+        }
+        let cipher = match (self.open_cipher)(&file_path, self.age_identity.as_deref()) {
+            Ok(cipher) => cipher,
+            Err(error) => {
+                let message = format!("Could not open {}: {}", file_path, error);
+                self.notifications.error(message);
+                return;
+            }
+        };
+        let read_only = self.all_read_only || !writable(&file_path);
+        // Only the ones that write to the file need to keep the others away from it
+        let file_lock = if read_only {
+            Ok(None)
+        } else {
+            lock::acquire(&file_path)
+        };
+        let data = read_state(&file_path, cipher.as_ref());
+        let format = format::Kind::detect(&file_path, data.as_deref().unwrap_or_default());
+        match (file_lock, data) {
+            (Ok(None), _) if !read_only => {
+                let message = format!("{} is open in another instance", file_path);
+                self.notifications.error(message);
+            }
+            (_, Err(error)) if error.kind() != ErrorKind::NotFound => {
+                let message = format!("Could not open {}: {}", file_path, error);
+                self.notifications.error(message);
+            }
+            (file_lock, data) => {
+                let file_lock = file_lock.ok().flatten();
+                let mut todos = Vec::new();
+                let mut dones = Vec::new();
+                let mut extras = Extras::default();
+                let mut header = Header::default();
+                if let Ok(data) = data {
+                    parse_state_lazily(&mut todos, &mut dones, &mut extras, &data, format);
+                    header = file_header(&file_path, &data, &mut self.notifications);
+                }
+                let layout = header.layout.and_then(PanelLayout::from_name);
+                let mut board = Board::new(file_path, file_lock, cipher, todos, dones, extras);
+                board.read_only = read_only;
+                board.format = format;
+                board.columns = header.columns;
+                self.boards.push(board);
+                self.switch_board(self.boards.len() - 1);
+                if let Some(layout) = layout.filter(|_| !self.layout_flag) {
+                    self.panel_layout = layout;
+                }
+            }
+        }
+    }
+
+    // Does what the action says, count times if the action takes a count. The actions that need more
+    // keys after them (Record, Replay) and Repeat are taken care of in the main loop as they are typed.
+    pub fn handle(&mut self, action: Action, count: Option<usize>) {
+        if action.mutates() && self.board().read_only {
+            self.read_only_error();
+            return;
+        }
+        if action.is_change() {
+            self.board_mut().last_change = Some(Change::Action(action, count.unwrap_or(1)));
+        }
+        match action {
+            Action::Quit => {
+                if !self.unsaved().is_empty() {
+                    self.confirmation = Some(Confirmation::Quit);
+                } else {
+                    self.quit = true;
+                }
+            }
+            Action::ClearDone if !self.board().dones.is_empty() => {
+                self.confirmation = Some(Confirmation::ClearDone);
+            }
+            Action::ToggleWrap => self.word_wrap = !self.word_wrap,
+            Action::ToggleLineNumbers => self.line_numbers = self.line_numbers.next(),
+            Action::GrowTodo => self.resize_split(SPLIT_STEP),
+            Action::ShrinkTodo => self.resize_split(-SPLIT_STEP),
+            Action::ToggleDetails => self.details = !self.details,
+            Action::Calendar => self.calendar = Some(self.filter.day.unwrap_or_else(Date::today)),
+            Action::OpenUrl => {
+                let urls: Vec<String> = self
+                    .current()
+                    .filter(|item| self.filter.shows(item))
+                    .map(|item| item_urls(item).map(str::to_string).collect())
+                    .unwrap_or_default();
+                match urls.as_slice() {
+                    [] => self.notifications.warning("There is no link in the item"),
+                    [url] => self.open_url(&url.clone()),
+                    _ => self.links = Some((urls, 0)),
+                }
+            }
+            Action::ClearFilter if !self.filter.is_empty() => {
+                self.filter = Filter::default();
+                self.notifications.info("Showing all the items");
+            }
+            Action::ToggleDone => {
+                self.done_collapsed = !self.done_collapsed;
+                if self.done_collapsed {
+                    self.panel = Status::Todo;
+                }
+            }
+            Action::CycleLayout => {
+                self.panel_layout = self.panel_layout.next();
+                let message = format!("Layout: {}", self.panel_layout.name());
+                self.notifications.info(message);
+            }
+            Action::Help => self.help = true,
+            Action::Stats => self.stats = true,
+            Action::Command => self.command_line = Some((String::new(), EditField::default())),
+            Action::Focus => {
+                let board = self.board();
+                if self.panel == Status::Todo && board.todo_curr < board.todos.len() {
+                    self.focus = Some(Focus::start());
+                } else {
+                    self.notifications
+                        .warning("Only TODO items can be focused on");
+                }
+            }
+            Action::PrevBoard | Action::NextBoard if self.boards.len() < 2 => {
+                self.notifications
+                    .info("Only one file is open. :open another one");
+            }
+            Action::PrevBoard => {
+                let len = self.boards.len();
+                self.switch_board((self.board_index + len - 1) % len);
+            }
+            Action::NextBoard => self.switch_board((self.board_index + 1) % self.boards.len()),
+            Action::SwitchPanel => {
+                self.panel = self.panel.toggle();
+                self.done_collapsed = false;
+            }
+            Action::Paste if self.panel == Status::Done => {
+                self.notifications
+                    .warning("Can't insert new DONE items. Only TODO is allowed.");
+            }
+            Action::Paste => match clipboard::paste() {
+                Ok(text) => self.paste(&text),
+                Err(error) => {
+                    let message = format!("Could not paste: {}", error);
+                    self.notifications.error(message);
+                }
+            },
+            Action::Record | Action::Replay | Action::Repeat | Action::Suspend => {}
+            _ => self.handle_on_list(action, count),
+        }
+    }
+
+    // The keys and the pastes of the text fields and the modal screens, which take them as they are
+    // rather than as the actions of the keymap. Only the focus key still works in the focus mode.
+    pub fn handle_raw(&mut self, event: Event, keymap: &Keymap, backups: usize) {
+        let key = match event {
+            Event::Key(key) => key,
+            Event::Paste(text) => {
+                if let Some((item, field)) = self.edited() {
+                    field.paste(item, &text);
+                } else if let Some((line, field)) = self.command_line.as_mut() {
+                    field.paste(line, &text);
+                }
+                return;
+            }
+            _ => return,
+        };
+        if let Some(current) = self.focus.as_mut() {
+            if key == ESCAPE || keymap.lookup(&[key]) == Some(Action::Focus) {
+                self.focus = None;
+            } else if key == 't' as i32 {
+                current.timer = !current.timer;
+            }
+        } else if let Some(current) = self.confirmation {
+            if let Some(answer) = Answer::from_key(key) {
+                self.confirmation = None;
+                self.answer(current, answer, backups);
+            }
+        } else if let Some(resolver) = self.resolver.as_mut() {
+            match resolver.key(key, &self.boards[self.board_index].extras.conflicts[0]) {
+                Some(Answer::Yes) => {
+                    let picked = mem::take(&mut resolver.picked);
+                    self.resolver = None;
+                    self.resolve_conflict(&picked);
+                }
+                Some(_) => {
+                    // Stays in the file as it is, to be resolved some other time
+                    self.board_mut().extras.conflicts.remove(0);
+                    self.resolver = None;
+                }
+                None => {}
+            }
+        } else if let Some(cursor) = self.calendar.as_mut() {
+            match calendar_key(cursor, key) {
+                Some(Answer::Yes) => {
+                    let day = *cursor;
+                    self.filter.day = Some(day);
+                    self.notifications
+                        .info(format!("Showing the items of {}", day));
+                    self.calendar = None;
+                }
+                Some(_) => self.calendar = None,
+                None => {}
+            }
+        } else if let Some((urls, curr)) = self.links.as_mut() {
+            match picker_key(curr, urls.len(), key) {
+                Some(Answer::Yes) => {
+                    let url = urls[*curr].clone();
+                    self.links = None;
+                    self.open_url(&url);
+                }
+                Some(_) => self.links = None,
+                None => {}
+            }
+        } else if self.editing {
+            self.edit_key(key);
+        } else if let Some((line, field)) = self.command_line.as_mut() {
+            match key {
+                9 => {
+                    let (completed, candidates) = command::complete(line);
+                    if candidates.len() > 1 {
+                        self.notifications.info(candidates.join(" "));
+                    }
+                    *line = completed;
+                    *field = EditField::at(line.len());
+                }
+                key if key == '\n' as i32 => {
+                    let line = line.clone();
+                    self.command_line = None;
+                    self.run_line(&line, backups);
+                }
+                ESCAPE => self.command_line = None,
+                key => {
+                    field.type_key(line, key);
+                }
+            }
+        }
+    }
+
+    // The item being edited and the field it's edited in
+    pub fn edited(&mut self) -> Option<(&mut String, &mut EditField)> {
+        if !self.editing {
+            return None;
+        }
+        let board = &mut self.boards[self.board_index];
+        let item = match self.panel {
+            Status::Todo => board.todos.get_mut(board.todo_curr),
+            Status::Done => board.dones.get_mut(board.done_curr),
+        }?;
+        Some((item, &mut self.edit))
+    }
+
+    // The tags to complete the word under the cursor of the edited item with, none unless the word
+    // is the start of a tag
+    pub fn completion_tags(&mut self) -> Vec<String> {
+        let board = &self.boards[self.board_index];
+        let item = match self.panel {
+            Status::Todo => board.todos.title(board.todo_curr),
+            Status::Done => board.dones.title(board.done_curr),
+        };
+        let typing_tag = item.is_some_and(|item| tag_prefix(&item, &self.edit).is_some());
+        if self.editing && typing_tag {
+            self.tags.of([&board.todos, &board.dones])
+        } else {
+            Vec::new()
+        }
+    }
+
+    // Enter is the end of the editing, whichever list the item is in
+    pub fn edit_key(&mut self, key: i32) {
+        let tags = self.completion_tags();
+        let Some((item, field)) = self.edited() else {
+            return;
+        };
+        if field.complete_tag(item, key, &tags) || field.type_key(item, key) {
+            return;
+        }
+        match key {
+            key if key == '\n' as i32 => {
+                let item = item.clone();
+                self.editing = false;
+                self.board_mut().last_change = Some(if self.inserting {
+                    Change::Insert(item)
+                } else {
+                    Change::Rename(item)
+                });
+                if self.inserting {
+                    self.inserting = false;
+                    let today = Date::today().to_string();
+                    let board = &mut self.boards[self.board_index];
+                    let todo = &mut board.todos[board.todo_curr];
+                    item_set_meta(todo, "created", Some(&today));
+                    run_hook(
+                        &mut self.hooks,
+                        Hook::Add,
+                        &board.file_path,
+                        todo,
+                        &mut self.notifications,
+                    );
+                }
+            }
+            CTRL_Y => {
+                let item = item.clone();
+                copy_to_clipboard(&item, &mut self.notifications);
+            }
+            _ => {}
+        }
+    }
+
+    // Lays the panels out on the screen of the size, scrolling the lists to their current items.
+    // Each frame does it, and so does the resize for the keys that come before the next frame.
+    pub fn layout(&mut self, size: Vec2) -> Panels {
+        let Vec2 { x, y } = size;
+        let current_layout = match self.panel_layout {
+            PanelLayout::Split if x < MIN_SPLIT_WIDTH => PanelLayout::Stacked,
+            layout => layout,
+        };
+        // The details pane never takes more than half of what's left for the panels
+        let details_height = if self.details {
+            cmp::min(DETAILS_HEIGHT, (y - 3) / 2)
+        } else {
+            0
+        };
+        // Everything between the gauge and the status bar, minus the details pane
+        let panels_height = y - 3 - details_height;
+        // The size of each panel, headers included
+        let (panels_kind, todo_panel, done_panel) = match current_layout {
+            // The collapsed DONE panel is just a single line under the TODO one
+            _ if self.done_collapsed => (
+                LayoutKind::Vert,
+                Vec2::new(x, panels_height - 1),
+                Vec2::new(x, 1),
+            ),
+            PanelLayout::Split => {
+                let todo_width = x * self.split / 100;
+                (
+                    LayoutKind::Horz,
+                    Vec2::new(todo_width, panels_height),
+                    Vec2::new(x - todo_width, panels_height),
+                )
+            }
+            PanelLayout::Stacked => {
+                let todo_height = panels_height * self.split / 100;
+                (
+                    LayoutKind::Vert,
+                    Vec2::new(x, todo_height),
+                    Vec2::new(x, panels_height - todo_height),
+                )
+            }
+            PanelLayout::Single => (
+                LayoutKind::Horz,
+                Vec2::new(x, panels_height),
+                Vec2::new(x, panels_height),
+            ),
+        };
+        // In the single panel layout the inactive panel takes no space at all
+        let todo_size = match (current_layout, &self.panel) {
+            (PanelLayout::Single, Status::Done) => Vec2::new(0, 0),
+            _ => todo_panel,
+        };
+        let done_size = match (current_layout, &self.panel) {
+            (PanelLayout::Single, Status::Todo) => Vec2::new(0, 0),
+            _ => done_panel,
+        };
+        Panels {
+            details_height,
+            panels_kind,
+            todo: self.layout_list(Status::Todo, todo_panel, todo_size),
+            done: self.layout_list(Status::Done, done_panel, done_size),
+        }
+    }
+
+    // Scrolls the list to its current item in the panel of the size
+    pub fn layout_list(&mut self, status: Status, outer: Vec2, size: Vec2) -> Panel {
+        let board = &mut self.boards[self.board_index];
+        let (items, curr, scroll, view) = match status {
+            Status::Todo => (
+                &board.todos,
+                board.todo_curr,
+                &mut board.todo_scroll,
+                &mut self.todo_view,
+            ),
+            Status::Done => (
+                &board.dones,
+                board.done_curr,
+                &mut board.done_scroll,
+                &mut self.done_view,
+            ),
+        };
+        // Minus the frame
+        let list_height = outer.y - 2;
+        let gutter = gutter_width(self.line_numbers, items.len());
+        let width = outer.x - 2 - gutter;
+        let heights = Heights {
+            items,
+            status,
+            filter: &self.filter,
+            width,
+            wrapped: self.word_wrap,
+        };
+        list_scroll(&heights, curr, scroll, list_height);
+        let end = list_visible_end(&heights, *scroll, list_height);
+        *view = ListView {
+            width,
+            rows: list_height,
+        };
+        Panel {
+            outer,
+            size,
+            list_height,
+            gutter,
+            width,
+            end,
+        }
+    }
+
+    pub fn resize_split(&mut self, step: i32) {
+        let split = (self.split + step).clamp(MIN_SPLIT, MAX_SPLIT);
+        if split == self.split {
+            return;
+        }
+        self.split = split;
+        if let Some(path) = &self.session_path {
+            if let Err(error) = session::save_split(path, split) {
+                self.notifications.error(format!(
+                    "Could not save the split to {}: {}",
+                    path.display(),
+                    error
+                ));
+            }
+        }
+    }
+
+    // The list and its cursor
+    pub fn list_mut(&mut self, status: Status) -> (&mut Vec<String>, &mut usize) {
+        let board = &mut self.boards[self.board_index];
+        match status {
+            Status::Todo => (&mut board.todos, &mut board.todo_curr),
+            Status::Done => (&mut board.dones, &mut board.done_curr),
+        }
+    }
+
+    pub fn open_url(&mut self, url: &str) {
+        match (self.open_url)(url) {
+            Ok(()) => self.notifications.info(format!("Opened {}", url)),
+            Err(error) => self
+                .notifications
+                .error(format!("Could not open {}: {}", url, error)),
+        }
+    }
+
+    // The item under the cursor of the current list, if there is one
+    pub fn current(&self) -> Option<&String> {
+        let board = self.board();
+        match self.panel {
+            Status::Todo => board.todos.get(board.todo_curr),
+            Status::Done => board.dones.get(board.done_curr),
+        }
+    }
+
+    // The actions on the items of the current list
+    pub fn handle_on_list(&mut self, action: Action, count: Option<usize>) {
+        let times = count.unwrap_or(1);
+        let board = &mut self.boards[self.board_index];
+        let (list, curr, scroll, view, other) = match self.panel {
+            Status::Todo => (
+                &mut board.todos,
+                &mut board.todo_curr,
+                &mut board.todo_scroll,
+                &self.todo_view,
+                &mut board.dones,
+            ),
+            Status::Done => (
+                &mut board.dones,
+                &mut board.done_curr,
+                &mut board.done_scroll,
+                &self.done_view,
+                &mut board.todos,
+            ),
+        };
+        // There is no current item as far as the user can see
+        let filter = &self.filter;
+        let hidden = list.get(*curr).is_some_and(|item| !filter.shows(item));
+        let heights = Heights {
+            items: list,
+            status: self.panel,
+            filter,
+            width: view.width,
+            wrapped: self.word_wrap,
+        };
+        if hidden && action.on_item() {
+            return;
+        }
+        let notifications = &mut self.notifications;
+        match action {
+            Action::Up => list_up(curr, times),
+            Action::Down => list_down(list, curr, times),
+            Action::DragUp => list_drag_up(list, curr, times),
+            Action::DragDown => list_drag_down(list, curr, times),
+            Action::First | Action::Last if count.is_some() => list_goto(list, curr, times),
+            Action::First => list_first(curr),
+            Action::Last => list_last(list, curr),
+            Action::PageUp | Action::HalfPageUp => {
+                list_page_up(&heights, curr, scroll, page_rows(view.rows, action, times))
+            }
+            Action::PageDown | Action::HalfPageDown => {
+                list_page_down(&heights, curr, scroll, page_rows(view.rows, action, times))
+            }
+            Action::Rename => {
+                if let Some(item) = list.get(*curr) {
+                    self.edit = EditField::at(item.len());
+                    self.editing = true;
+                }
+            }
+            Action::Copy => {
+                if let Some(item) = list.get(*curr) {
+                    copy_to_clipboard(item, notifications);
+                }
+            }
+            Action::Insert if self.panel == Status::Todo => {
+                list.insert(*curr, String::new());
+                self.edit = EditField::default();
+                self.editing = true;
+                self.inserting = true;
+                notifications.info("What needs to be done?");
+            }
+            Action::Insert => {
+                notifications.warning("Can't insert new DONE items. Only TODO is allowed.");
+            }
+            Action::Delete if self.panel == Status::Todo => {
+                notifications.warning("Can't remove items from TODO. Mark it as DONE first.");
+            }
+            Action::Delete if *curr < list.len() => {
+                let count = cmp::min(times, list.len() - *curr);
+                self.confirmation = Some(Confirmation::Delete(count));
+            }
+            Action::Transfer => {
+                let today = Date::today().to_string();
+                // Only the DONE items know when they were done
+                let done = match self.panel {
+                    Status::Todo => Some(today.as_str()),
+                    Status::Done => None,
+                };
+                let mut transferred = 0;
+                while transferred < times && list_transfer(other, list, curr) {
+                    if let Some(item) = other.last_mut() {
+                        item_set_meta(item, "done", done);
+                        if done.is_some() {
+                            let file_path = &board.file_path;
+                            run_hook(&mut self.hooks, Hook::Done, file_path, item, notifications);
+                        }
+                    }
+                    transferred += 1;
+                }
+                if transferred > 0 {
+                    notifications.info(match self.panel {
+                        Status::Todo => "DONE!",
+                        Status::Done => "No, not done yet...",
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Pasting into the TODO list inserts an item per line of the text
+    pub fn paste(&mut self, text: &str) {
+        if self.board().read_only {
+            self.read_only_error();
+            return;
+        }
+        if self.panel == Status::Done {
+            self.notifications
+                .warning("Can't insert new DONE items. Only TODO is allowed.");
+            return;
+        }
+        let board = &mut self.boards[self.board_index];
+        board.last_change = Some(Change::Paste(text.to_string()));
+        let mut count = 0;
+        let today = Date::today().to_string();
+        for title in paste_titles(text) {
+            let mut todo = title.to_string();
+            item_set_meta(&mut todo, "created", Some(&today));
+            run_hook(
+                &mut self.hooks,
+                Hook::Add,
+                &board.file_path,
+                &todo,
+                &mut self.notifications,
+            );
+            board.todos.insert(board.todo_curr + count, todo);
+            count += 1;
+        }
+        self.notifications.info(format!("Pasted {} items", count));
+    }
+
+    // Writes a copy of the lists to another file, in the format its extension asks for unless the
+    // format is given
+    pub fn export(&mut self, scope: Scope, format: Option<&str>, path: &str) {
+        let board = self.board();
+        let filter = &self.filter;
+        let shown = |list: &[String]| -> Vec<String> {
+            list.iter()
+                .filter(|item| filter.shows(item))
+                .cloned()
+                .collect()
+        };
+        let (todos, dones, extras) = match scope {
+            Scope::All => (
+                board.todos.to_vec(),
+                board.dones.to_vec(),
+                board.extras.clone(),
+            ),
+            Scope::Panel => match self.panel {
+                Status::Todo => (board.todos.to_vec(), Vec::new(), Extras::default()),
+                Status::Done => (Vec::new(), board.dones.to_vec(), Extras::default()),
+            },
+            Scope::Filter => (shown(&board.todos), shown(&board.dones), Extras::default()),
+        };
+        let extension = Path::new(path).extension().and_then(|ext| ext.to_str());
+        let format = format.unwrap_or_else(|| {
+            EXPORT_FORMATS
+                .iter()
+                .copied()
+                .find(|&name| extension == Some(name))
+                .unwrap_or_else(|| format::Kind::from_path(path).unwrap_or_default().name())
+        });
+        // Neither backups nor encryption, the copy is for whoever asked for it
+        let result = match export_document(format, &board.file_path, &todos, &dones) {
+            Some(document) => fs::write(path, document),
+            None => {
+                let format = format::Kind::from_name(format).unwrap_or_default().format();
+                save_state(&todos, &dones, &extras, path, 0, None, format)
+            }
+        };
+        match result {
+            Ok(()) => {
+                let message = format!("Exported {} items to {}", todos.len() + dones.len(), path);
+                self.notifications.info(message);
+            }
+            Err(error) => {
+                let message = format!("Could not export to {}: {}", path, error);
+                self.notifications.error(message);
+            }
+        }
+    }
+
+    // Replaces the lines of the first conflict of the file with the items picked from its sides
+    pub fn resolve_conflict(&mut self, picked: &[Vec<bool>; 2]) {
+        let board = &mut self.boards[self.board_index];
+        let conflict = board.extras.conflicts.remove(0);
+        let mut place = None;
+        for number in conflict.line..conflict.line + conflict.lines.len() {
+            let line_place = board.extras.take_line(number);
+            place = place.or(line_place);
+        }
+        let mut next = [0, 0];
+        for (status, list) in [(Status::Todo, &board.todos), (Status::Done, &board.dones)].iter() {
+            next[*status as usize] = match place {
+                Some((place_status, index)) if place_status == *status => {
+                    cmp::min(index, list.len())
+                }
+                _ => list.len(),
+            };
+        }
+        let sides = [&conflict.ours, &conflict.theirs];
+        let mut count = 0;
+        for (items, picked) in sides.iter().zip(picked.iter()) {
+            for ((status, title), _) in items
+                .iter()
+                .zip(picked.iter())
+                .filter(|(_, picked)| **picked)
+            {
+                let list = match status {
+                    Status::Todo => &mut board.todos,
+                    Status::Done => &mut board.dones,
+                };
+                list.insert(next[*status as usize], title.clone());
+                next[*status as usize] += 1;
+                count += 1;
+            }
+        }
+        let message = format!(
+            "Resolved the conflict at line {} with {} items",
+            conflict.line, count
+        );
+        self.notifications.info(message);
+    }
+
+    pub fn read_only_error(&mut self) {
+        let message = format!(
+            "{} is read-only, nothing can be changed in it",
+            self.board().file_path
+        );
+        self.notifications.error(message);
+    }
+
+    pub fn commit_pending(&mut self) {
+        for error in self.autocommit.flush() {
+            self.notifications
+                .error(format!("Could not commit {}", error));
+        }
+    }
+
+    // :w, asking whether to try again if it fails
+    pub fn save_or_ask(&mut self, backups: usize) {
+        match self.save(backups, false) {
+            Ok(()) => {
+                let message = format!("Saved {}", self.board().file_path);
+                self.notifications.info(message);
+            }
+            Err(error) => {
+                self.save_error = format!("Could not save {}: {}", self.board().file_path, error);
+                self.confirmation = Some(Confirmation::RetrySave { quit: false });
+            }
+        }
+    }
+
+    // Does the last change again, with the count if there is one
+    pub fn repeat(&mut self, count: Option<usize>) {
+        // Every change is a change to the lists, whichever file it was made in
+        let board = self.board();
+        if board.read_only && board.last_change.is_some() {
+            return self.read_only_error();
+        }
+        match &self.board().last_change {
+            // A new count replaces the one of the repeated action, like in vim
+            Some(Change::Action(action, last_count)) => {
+                let count = count.unwrap_or(*last_count);
+                self.handle(*action, Some(count));
+            }
+            Some(Change::Paste(text)) => {
+                let text = text.clone();
+                self.paste(&text);
+            }
+            Some(Change::Insert(title)) if self.panel == Status::Todo => {
+                let mut todo = title.clone();
+                item_set_meta(&mut todo, "created", Some(&Date::today().to_string()));
+                let board = &mut self.boards[self.board_index];
+                run_hook(
+                    &mut self.hooks,
+                    Hook::Add,
+                    &board.file_path,
+                    &todo,
+                    &mut self.notifications,
+                );
+                let index = cmp::min(board.todo_curr, board.todos.len());
+                board.todos.insert(index, todo);
+            }
+            Some(Change::Insert(_)) => self
+                .notifications
+                .warning("Can't insert new DONE items. Only TODO is allowed."),
+            Some(Change::Rename(title)) => {
+                let title = title.clone();
+                let (list, curr) = self.list_mut(self.panel);
+                if let Some(item) = list.get_mut(*curr) {
+                    *item = title;
+                }
+            }
+            None => self.notifications.info("Nothing to repeat"),
+        }
+    }
+
+    // Does what the user agreed to, or didn't
+    pub fn answer(&mut self, confirmation: Confirmation, answer: Answer, backups: usize) {
+        match (confirmation, answer) {
+            (Confirmation::Delete(count), Answer::Yes) => {
+                let board = &mut self.boards[self.board_index];
+                for _ in 0..count {
+                    if let Some(done) = board.dones.get(board.done_curr) {
+                        run_hook(
+                            &mut self.hooks,
+                            Hook::Delete,
+                            &board.file_path,
+                            done,
+                            &mut self.notifications,
+                        );
+                    }
+                    list_delete(&mut board.dones, &mut board.done_curr);
+                }
+                self.notifications.info("Into The Abyss!");
+            }
+            (Confirmation::ClearDone, Answer::Yes) => {
+                let board = &mut self.boards[self.board_index];
+                for done in mem::take(&mut board.dones) {
+                    run_hook(
+                        &mut self.hooks,
+                        Hook::Delete,
+                        &board.file_path,
+                        &done,
+                        &mut self.notifications,
+                    );
+                }
+                board.done_curr = 0;
+                self.notifications.info("Into The Abyss! All of them!");
+            }
+            (Confirmation::Quit, Answer::Yes) => self.quit = true,
+            (Confirmation::Reload, Answer::Yes) => self.reload(),
+            (Confirmation::RetrySave { quit: true }, Answer::Yes) => self.quit = true,
+            (Confirmation::RetrySave { quit: true }, Answer::No) => {
+                self.quit = true;
+                self.discard = true;
+            }
+            (Confirmation::RetrySave { quit: false }, Answer::Yes) => self.save_or_ask(backups),
+            (Confirmation::Problem, answer) => {
+                let problem = self.board_mut().extras.problems.remove(0);
+                match (problem.fix, answer) {
+                    (Some((status, title)), Answer::Yes) => {
+                        let place = self.board_mut().extras.take_line(problem.line);
+                        let (list, curr) = self.list_mut(status);
+                        let index = match place {
+                            Some((place_status, index)) if place_status == status => {
+                                cmp::min(index, list.len())
+                            }
+                            _ => list.len(),
+                        };
+                        list.insert(index, title);
+                        *curr = index;
+                        self.panel = status;
+                        let message = format!("Fixed line {}", problem.line);
+                        self.notifications.info(message);
+                    }
+                    (Some(_), Answer::No) | (None, Answer::Yes) => {
+                        self.board_mut().extras.take_line(problem.line);
+                        let message = format!("Discarded line {}", problem.line);
+                        self.notifications.info(message);
+                    }
+                    _ => {}
+                }
+            }
+            (Confirmation::Quit, Answer::No) => {
+                self.quit = true;
+                self.discard = true;
+            }
+            _ => {}
+        }
+    }
+
+    // The `:` commands
+    pub fn run(&mut self, command: Command, backups: usize) {
+        match command {
+            Command::Write | Command::WriteQuit if self.to_stdout && self.board_index == 0 => self
+                .notifications
+                .error("The lists are written to stdout on quit. :q to quit"),
+            Command::Write | Command::WriteQuit if self.board().file_path == STDIN_PATH => self
+                .notifications
+                .error("There is no file to write stdin to. :q to quit"),
+            Command::Write | Command::WriteQuit if self.board().read_only => self
+                .notifications
+                .error("The file is read-only. :q! to quit"),
+            Command::Write => self.save_or_ask(backups),
+            Command::Quit if !self.unsaved().is_empty() => self
+                .notifications
+                .error("There are unsaved changes. :w them or :q! to discard"),
+            Command::Quit => self.quit = true,
+            Command::ForceQuit => {
+                self.quit = true;
+                self.discard = true;
+            }
+            Command::WriteQuit => self.quit = true,
+            Command::Sort(_) if self.board().read_only => self.read_only_error(),
+            Command::Sort(key) => {
+                let (list, curr) = self.list_mut(self.panel);
+                list_sort(list, key.as_deref());
+                *curr = 0;
+                self.notifications.info(match key {
+                    Some(key) => format!("Sorted {} by {}", self.panel.name(), key),
+                    None => format!("Sorted {}", self.panel.name()),
+                });
+            }
+            Command::Filter(text) => {
+                self.notifications.info(match &text {
+                    Some(text) => format!("Showing the items with \"{}\"", text),
+                    None => "Showing all the items".to_string(),
+                });
+                self.filter.text = text;
+            }
+            Command::Open(path) => self.open(path),
+            Command::Revert => self.revert(),
+            Command::Export {
+                scope,
+                format,
+                path,
+            } => self.export(scope, format.as_deref(), &path),
+            Command::Sync => self.sync(backups, self.caldav.clone()),
+            Command::GitHub => self.sync(backups, self.github.clone()),
+            Command::Snooze(minutes) => self.snooze(minutes),
+            Command::Custom(name, args) => self.run_script(&name, &args),
+        }
+    }
+
+    // The reminders on the banner, wherever their items are. The read-only files keep theirs.
+    pub fn snooze(&mut self, minutes: u32) {
+        let todos = self
+            .boards
+            .iter_mut()
+            .filter(|board| !board.read_only)
+            .map(|board| &mut board.todos);
+        let snoozed = self.reminders.snooze(
+            todos.flat_map(|todos| todos.iter_mut()),
+            minutes,
+            &mut self.notifications,
+        );
+        match snoozed {
+            0 => self.notifications.error("No reminder to snooze"),
+            _ => self
+                .notifications
+                .info(format!("Snoozed for {} minutes", minutes)),
+        }
+    }
+
+    // Adds a TODO item for the control socket and D-Bus, at the end of the current file. Returns its
+    // number.
+    #[cfg(any(unix, feature = "dbus"))]
+    pub fn add_remote(&mut self, title: &str) -> Result<usize, String> {
+        self.check_remote()?;
+        let mut todo = title.split_whitespace().collect::<Vec<_>>().join(" ");
+        if todo.is_empty() {
+            return Err("the title of the item is empty".to_string());
+        }
+        item_set_meta(&mut todo, "created", Some(&Date::today().to_string()));
+        let board = &mut self.boards[self.board_index];
+        run_hook(
+            &mut self.hooks,
+            Hook::Add,
+            &board.file_path,
+            &todo,
+            &mut self.notifications,
+        );
+        self.notifications.info(format!("Added: {}", todo));
+        board.todos.push(todo);
+        Ok(board.todos.len())
+    }
+
+    // Moves a TODO item to DONE for the control socket and D-Bus, by find_todo(). Returns the item.
+    #[cfg(any(unix, feature = "dbus"))]
+    pub fn done_remote(&mut self, which: &str) -> Result<String, String> {
+        self.check_remote()?;
+        let board = &mut self.boards[self.board_index];
+        let index = find_todo(&board.todos, which)?;
+        let mut done = board.todos.remove(index);
+        if index < board.todo_curr || board.todo_curr >= board.todos.len() {
+            board.todo_curr = board.todo_curr.saturating_sub(1);
+        }
+        item_set_meta(&mut done, "done", Some(&Date::today().to_string()));
+        run_hook(
+            &mut self.hooks,
+            Hook::Done,
+            &board.file_path,
+            &done,
+            &mut self.notifications,
+        );
+        self.notifications.info(format!("Done: {}", done));
+        board.dones.push(done.clone());
+        Ok(done)
+    }
+
+    #[cfg(any(unix, feature = "dbus"))]
+    pub fn check_remote(&self) -> Result<(), String> {
+        if self.board().read_only {
+            return Err(format!("{} is read-only", self.board().file_path));
+        }
+        // The cursor and the item being edited stay where they are
+        if self.editing || self.confirmation.is_some() || self.resolver.is_some() {
+            return Err("the app is busy, try again in a moment".to_string());
+        }
+        Ok(())
+    }
+
+    // A line from the control socket: add <title>, done <number|pattern> or list, for the current
+    // file. The answer goes back to the client, the changes show up on the banner too.
+    #[cfg(unix)]
+    pub fn control(&mut self, request: &mut control::Request) {
+        let line = request.line.clone();
+        let (command, args) = line.split_once(' ').unwrap_or((&line, ""));
+        let answer = match command {
+            "list" => Ok(self
+                .board()
+                .todos
+                .iter()
+                .enumerate()
+                .map(|(index, todo)| format!("{}. {}", index + 1, todo))
+                .collect::<Vec<_>>()
+                .join("\n")),
+            "add" => self
+                .add_remote(args)
+                .map(|number| format!("Added item {}", number)),
+            "done" => self
+                .done_remote(args.trim())
+                .map(|done| format!("Done: {}", done)),
+            _ => Err(format!(
+                "unknown command `{}`, expected add <title>, done <number|pattern> or list",
+                command
+            )),
+        };
+        match answer {
+            Ok(answer) => request.reply(&answer),
+            Err(error) => request.reply(&format!("ERROR: {}", error)),
+        }
+    }
+
+    // A method call of the D-Bus service, like control() for the other side of the desktop
+    #[cfg(feature = "dbus")]
+    pub fn dbus(&mut self, call: bus::Call) {
+        match &call.method {
+            bus::Method::List => call.reply_list(self.board().todos.to_vec()),
+            bus::Method::Add(title) => match self.add_remote(title) {
+                Ok(number) => call.reply_number(number),
+                Err(error) => call.reply_error(&error),
+            },
+            bus::Method::Complete(which) => match self.done_remote(which) {
+                Ok(done) => call.reply_text(&done),
+                Err(error) => call.reply_error(&error),
+            },
+        }
+    }
+
+    // Saves the file right away, and the state of the sync along with it
+    pub fn sync<S: Service>(&mut self, backups: usize, service: Option<S>) {
+        let service = match service {
+            _ if self.board().read_only => return self.read_only_error(),
+            _ if self.board().detached => {
+                return self.notifications.error("There is no file to sync");
+            }
+            Some(service) => service,
+            None => {
+                let message = format!("There is no [{}] in the config", S::NAME);
+                return self.notifications.error(message);
+            }
+        };
+        // The lists stay as they were if nothing could be synced
+        let board = self.board();
+        let (mut todos, mut dones) = (board.todos.clone(), board.dones.clone());
+        let synced = match service.sync(&board.file_path, &mut todos, &mut dones) {
+            Ok(synced) => synced,
+            Err(error) => {
+                return self
+                    .notifications
+                    .error(format!("Could not sync: {}", error))
+            }
+        };
+        let board = self.board_mut();
+        board.todo_curr = cmp::min(board.todo_curr, todos.len().saturating_sub(1));
+        board.done_curr = cmp::min(board.done_curr, dones.len().saturating_sub(1));
+        board.todos = todos;
+        board.dones = dones;
+        let saved = self
+            .save(backups, false)
+            .and_then(|()| synced.save(&self.board().file_path));
+        match saved {
+            Err(error) => {
+                let message = format!("Could not save {}: {}", self.board().file_path, error);
+                self.notifications.error(message);
+            }
+            Ok(()) if synced.error.is_some() => self.notifications.error(synced.describe()),
+            Ok(()) => self.notifications.info(synced.describe()),
+        }
+    }
+
+    // A `:` command line as it was typed or bound to the keys
+    pub fn run_line(&mut self, line: &str, backups: usize) {
+        match Command::parse(line) {
+            Ok(command) => self.run(command, backups),
+            Err(error) => self.notifications.error(error),
+        }
+    }
+
+    #[cfg(feature = "scripting")]
+    pub fn run_script(&mut self, name: &str, args: &[String]) {
+        // The script gets the whole app to change, itself aside
+        match self.script.take() {
+            Some(mut script) if script.defines(name) => {
+                script.call(self, name, args);
+                self.script = Some(script);
+            }
+            script => {
+                self.script = script;
+                self.notifications
+                    .error(format!("Unknown command `{}`", name));
+            }
+        }
+    }
+
+    #[cfg(not(feature = "scripting"))]
+    pub fn run_script(&mut self, name: &str, _args: &[String]) {
+        self.notifications
+            .error(format!("Unknown command `{}`", name));
+    }
+}
+
+// Whether we may write to the file as far as its permissions go. The files that don't exist yet are
+// found out about when they are saved.
+#[cfg(unix)]
+pub fn writable(file_path: &str) -> bool {
+    if !Path::new(file_path).exists() {
+        return true;
+    }
+    match CString::new(file_path) {
+        Ok(path) => unsafe { libc::access(path.as_ptr(), libc::W_OK) == 0 },
+        Err(_) => true,
+    }
+}
+
+// The read-only attribute is all Windows has to say about it without going through the ACLs
+#[cfg(not(unix))]
+pub fn writable(file_path: &str) -> bool {
+    !fs::metadata(file_path).is_ok_and(|metadata| metadata.permissions().readonly())
+}
+
+// The header of the file, see header.rs, the default one if there is none. What the header gets
+// wrong is only warned about, the file may well be for a newer version of the app.
+pub fn file_header(file_path: &str, data: &[u8], notifications: &mut Notifications) -> Header {
+    let header = header::read(data).unwrap_or_default();
+    for problem in header.problems.iter() {
+        notifications.warning(format!("{}: {} in the header", file_path, problem));
+    }
+    header
+}
@@ -0,0 +1,609 @@
+use std::collections::{BTreeSet, HashSet};
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, ErrorKind, IsTerminal, Read, Write};
+
+use crate::app::Board;
+use crate::caldav::CalDav;
+use crate::cli::{Batch, Import, STDIN_PATH};
+use crate::git::{self, AutoCommit};
+use crate::github::GitHub;
+use crate::hooks::{Hook, Hooks};
+use crate::sync::{Service, Synced};
+use crate::{print, scan, serve, signals, status};
+use todo_rs::date::Date;
+use todo_rs::format;
+use todo_rs::stats::Stats;
+use todo_rs::*;
+
+// The TODO item by its number, starting from 1, or by a piece of its text that no other item has
+pub fn find_todo(todos: &[String], which: &str) -> Result<usize, String> {
+    match which.parse::<usize>() {
+        Ok(number) if number >= 1 && number <= todos.len() => Ok(number - 1),
+        Ok(number) => Err(format!("there is no TODO item {}", number)),
+        Err(_) => {
+            let pattern = which.to_lowercase();
+            let matches: Vec<usize> = (0..todos.len())
+                .filter(|&index| todos[index].to_lowercase().contains(&pattern))
+                .collect();
+            match matches.as_slice() {
+                [index] => Ok(*index),
+                [] => Err(format!("no TODO item matches `{}`", which)),
+                _ => {
+                    let items: Vec<String> = matches
+                        .iter()
+                        .map(|&index| format!("  {}. {}", index + 1, todos[index]))
+                        .collect();
+                    Err(format!(
+                        "several TODO items match `{}`, pick one by its number:\n{}",
+                        which,
+                        items.join("\n")
+                    ))
+                }
+            }
+        }
+    }
+}
+
+pub fn run_batch(
+    batch: Batch,
+    board: &mut Board,
+    hooks: &mut Hooks,
+    autocommit: &AutoCommit,
+    caldav: Option<&CalDav>,
+    github: Option<&GitHub>,
+    backups: usize,
+) -> Result<(), String> {
+    // Run once the changes are saved
+    let mut hooked = Vec::new();
+    // Saved once the lists are
+    let mut synced = None;
+    let message = match batch {
+        Batch::Export { format, path } => {
+            let data = match export_document(format, &board.file_path, &board.todos, &board.dones) {
+                Some(document) => document.into_bytes(),
+                None => {
+                    let format = format::Kind::from_name(format).unwrap_or_default();
+                    let mut data = Vec::new();
+                    write_state(
+                        &mut data,
+                        &board.todos,
+                        &board.dones,
+                        &board.extras,
+                        format.format(),
+                    )
+                    .expect("writing to memory does not fail");
+                    data
+                }
+            };
+            let result = match path.as_str() {
+                "-" => io::stdout().write_all(&data),
+                path => fs::write(path, &data),
+            };
+            return result.map_err(|error| format!("could not export to {}: {}", path, error));
+        }
+        Batch::List { done, json } => {
+            let (status, list) = if done {
+                (Status::Done, &board.dones)
+            } else {
+                (Status::Todo, &board.todos)
+            };
+            let mut out = BufWriter::new(io::stdout());
+            // A closed pipe, like the one of `| head`, is not worth an error
+            if json {
+                let items = list
+                    .iter()
+                    .enumerate()
+                    .map(|(index, title)| {
+                        let mut item = vec![(
+                            "number".to_string(),
+                            json::Value::Number((index + 1) as f64),
+                        )];
+                        item.extend(json::item(status, title));
+                        json::Value::Object(item)
+                    })
+                    .collect();
+                let _ = writeln!(out, "{}", json::Value::Array(items));
+            } else {
+                let width = list.len().to_string().len();
+                for (index, title) in list.iter().enumerate() {
+                    if writeln!(out, "{:>2$}. {}", index + 1, title, width).is_err() {
+                        break;
+                    }
+                }
+            }
+            let _ = out.flush();
+            return Ok(());
+        }
+        Batch::Stats { json } => {
+            let stats = Stats::new(&board.todos, &board.dones, Date::today());
+            let mut out = BufWriter::new(io::stdout());
+            if json {
+                let _ = writeln!(out, "{}", stats.to_json());
+            } else {
+                for line in stats.lines(usize::MAX) {
+                    if writeln!(out, "{}", line).is_err() {
+                        break;
+                    }
+                }
+            }
+            let _ = out.flush();
+            return Ok(());
+        }
+        Batch::Status(format) => {
+            let line = status::render(&format, &board.todos, &board.dones, Date::today())?;
+            // A status bar that went away is not worth an error either
+            let _ = writeln!(io::stdout(), "{}", line);
+            return Ok(());
+        }
+        Batch::Print => {
+            let color = io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none();
+            let board = print::render(&board.todos, &board.dones, print::terminal_width(), color);
+            // A closed pipe is not worth an error here either
+            let _ = io::stdout().write_all(board.as_bytes());
+            return Ok(());
+        }
+        Batch::Import(import, source_path) => {
+            let count = import_items(board, import, &source_path)?;
+            format!("Imported {} items from {}", count, source_path)
+        }
+        Batch::Add(title) => {
+            let mut todo = title.split_whitespace().collect::<Vec<_>>().join(" ");
+            if todo.is_empty() {
+                return Err("the title of the item is empty".to_string());
+            }
+            item_set_meta(&mut todo, "created", Some(&Date::today().to_string()));
+            hooked.push((Hook::Add, todo.clone()));
+            board.todos.push(todo);
+            format!("Added item {} to {}", board.todos.len(), board.file_path)
+        }
+        Batch::Done(which) => {
+            let index = find_todo(&board.todos, &which)?;
+            let mut done = board.todos.remove(index);
+            item_set_meta(&mut done, "done", Some(&Date::today().to_string()));
+            let message = format!("Done: {}", done);
+            hooked.push((Hook::Done, done.clone()));
+            board.dones.push(done);
+            message
+        }
+        Batch::Scan(dir) => {
+            let (found, files) = scan::scan(&dir, &board.file_path)?;
+            let merged = scan::merge(&dir, &found, &mut board.todos, &mut board.dones);
+            hooked.extend(merged.added.iter().map(|item| (Hook::Add, item.clone())));
+            hooked.extend(merged.done.iter().map(|item| (Hook::Done, item.clone())));
+            format!(
+                "Scanned {} files of {}: {} added, {} updated, {} done",
+                files,
+                dir,
+                merged.added.len(),
+                merged.updated,
+                merged.done.len()
+            )
+        }
+        Batch::Serve(options) => {
+            return serve_lists(board, options, hooks, autocommit, backups);
+        }
+        Batch::Sync => {
+            let result = batch_sync(caldav, board)?;
+            let message = result.describe();
+            synced = Some(result);
+            message
+        }
+        Batch::GitHub => {
+            let result = batch_sync(github, board)?;
+            let message = result.describe();
+            synced = Some(result);
+            message
+        }
+    };
+
+    let format = board.format.format();
+    let result = if board.detached {
+        let mut out = BufWriter::new(io::stdout());
+        write_state(&mut out, &board.todos, &board.dones, &board.extras, format)
+            .and_then(|()| out.flush())
+    } else if board.read_only {
+        Err(io::Error::new(
+            ErrorKind::PermissionDenied,
+            "the file is read-only",
+        ))
+    } else {
+        save_state(
+            &board.todos,
+            &board.dones,
+            &board.extras,
+            &board.file_path,
+            backups,
+            board.cipher.as_ref(),
+            format,
+        )
+    };
+    result.map_err(|error| format!("could not save {}: {}", board.file_path, error))?;
+    if !board.detached {
+        let message = git::message(
+            (&board.saved_todos, &board.saved_dones),
+            (&board.todos, &board.dones),
+        );
+        autocommit
+            .commit(&board.file_path, &message)
+            .map_err(|error| format!("could not commit {}: {}", board.file_path, error))?;
+        if let Some(synced) = &synced {
+            synced
+                .save(&board.file_path)
+                .map_err(|error| format!("could not save {}: {}", board.file_path, error))?;
+            if synced.error.is_some() {
+                return Err(message);
+            }
+        }
+        hooked.push((Hook::Save, String::new()));
+    }
+    for (hook, item) in hooked {
+        hooks
+            .run(hook, &board.file_path, &item)
+            .map_err(|error| format!("could not run the {} hook: {}", hook.name(), error))?;
+    }
+    eprintln!("{}", message);
+    Ok(())
+}
+
+// `todo-rs serve`: the lists over HTTP until Ctrl+C. The file is read again whenever it changes, and
+// the new items are saved right away like `todo-rs add` does.
+pub fn serve_lists(
+    board: &mut Board,
+    options: serve::Options,
+    hooks: &mut Hooks,
+    autocommit: &AutoCommit,
+    backups: usize,
+) -> Result<(), String> {
+    let listener = serve::listen(&options.address, options.port)?;
+    let url = format!("http://{}:{}/", options.address, options.port);
+    // Only the one who started it gets to add the items
+    let token = if options.allow_add {
+        let token = options.token.unwrap_or_else(serve::new_token);
+        eprintln!("Serving {} on {}?token={}", board.file_path, url, token);
+        Some(token)
+    } else {
+        eprintln!("Serving {} on {}", board.file_path, url);
+        None
+    };
+    // Ctrl+C ends the loop rather than the process, the other batch commands keep the default
+    // handling and end right away
+    if let Err(error) = signals::init() {
+        eprintln!("WARNING: could not handle the signals: {}", error);
+    }
+    while !signals::poll() {
+        let request = match serve::accept(&listener) {
+            Ok(Some(request)) => request,
+            Ok(None) => continue,
+            Err(error) => return Err(format!("could not accept a connection: {}", error)),
+        };
+        // Somebody else, like the app, may change the file in the meantime
+        let mtime = file_mtime(&board.file_path);
+        if !board.detached && mtime != board.saved_mtime {
+            let mut todos = Vec::new();
+            let mut dones = Vec::new();
+            let mut extras = Extras::default();
+            let loaded = load_state(
+                &mut todos,
+                &mut dones,
+                &mut extras,
+                &board.file_path,
+                board.cipher.as_ref(),
+                board.format.format(),
+            );
+            match loaded {
+                Ok(()) => {
+                    board.todos = todos.into();
+                    board.dones = dones.into();
+                    board.saved_todos = board.todos.clone();
+                    board.saved_dones = board.dones.clone();
+                    board.extras = extras;
+                    board.saved_mtime = mtime;
+                }
+                // What was loaded the last time is better than nothing
+                Err(error) => eprintln!("ERROR: could not reload {}: {}", board.file_path, error),
+            }
+        }
+        let response = serve_request(
+            &request,
+            token.as_deref(),
+            options.allow_add,
+            board,
+            hooks,
+            autocommit,
+            backups,
+        );
+        request.respond(response);
+    }
+    Ok(())
+}
+
+// The answer to a request of `todo-rs serve`, given the token it printed if any
+fn serve_request(
+    request: &serve::Request,
+    token: Option<&str>,
+    allow_add: bool,
+    board: &mut Board,
+    hooks: &mut Hooks,
+    autocommit: &AutoCommit,
+    backups: usize,
+) -> serve::Response {
+    use crate::serve::Response;
+
+    // The form is on the page only for those who came with the token
+    let token = token.filter(|token| request.has_token(token));
+    match (request.method.as_str(), request.path.as_str()) {
+        ("GET" | "HEAD", "/") => Response::new(
+            "200 OK",
+            "text/html; charset=utf-8",
+            html::page(
+                &board.file_path,
+                &board.todos,
+                &board.dones,
+                token
+                    .map(|token| format!("/items?token={}", token))
+                    .as_deref(),
+            ),
+        ),
+        ("GET" | "HEAD", "/items.json") => Response::new(
+            "200 OK",
+            "application/json",
+            json::export(&board.todos, &board.dones),
+        ),
+        ("POST", "/items") if !allow_add => Response::text(
+            "403 Forbidden",
+            "the items can only be added with todo-rs serve --allow-add",
+        ),
+        ("POST", "/items") if token.is_none() => Response::text(
+            "403 Forbidden",
+            "the items can only be added with the token todo-rs serve printed",
+        ),
+        ("POST", "/items") => {
+            let title = serve::form_value(&request.body, "title").unwrap_or_default();
+            let added = run_batch(
+                Batch::Add(title),
+                board,
+                hooks,
+                autocommit,
+                None,
+                None,
+                backups,
+            );
+            match added {
+                Ok(()) => {
+                    board.saved_todos = board.todos.clone();
+                    board.saved_dones = board.dones.clone();
+                    board.saved_mtime = file_mtime(&board.file_path);
+                    // Back to the page for the form, the scripts get the number
+                    let mut response = Response::text(
+                        "303 See Other",
+                        &format!("Added item {}", board.todos.len()),
+                    );
+                    response.location = token.map(|token| format!("/?token={}", token));
+                    response
+                }
+                Err(error) => {
+                    // Whatever made it to the file is read again on the next request
+                    board.todos = board.saved_todos.clone();
+                    board.dones = board.saved_dones.clone();
+                    Response::text("400 Bad Request", &format!("ERROR: {}", error))
+                }
+            }
+        }
+        (_, "/" | "/items.json" | "/items") => {
+            Response::text("405 Method Not Allowed", "ERROR: method not allowed")
+        }
+        _ => Response::text("404 Not Found", "ERROR: not found"),
+    }
+}
+
+pub fn batch_sync<S: Service>(service: Option<&S>, board: &mut Board) -> Result<Synced, String> {
+    let service = service.ok_or_else(|| format!("there is no [{}] in the config", S::NAME))?;
+    if board.detached {
+        return Err("there is no file to sync".to_string());
+    }
+    if board.read_only {
+        return Err(format!("{} is read-only", board.file_path));
+    }
+    service
+        .sync(&board.file_path, &mut board.todos, &mut board.dones)
+        .map_err(|error| format!("could not sync: {}", error))
+}
+
+// Adds the imported items to the lists, returning how many of them there were. The items that are
+// there already by their uuid: are left alone, so importing the same tasks again adds only the new
+// ones. The lines go by their titles instead, if they are deduplicated at all.
+pub fn import_items(board: &mut Board, import: Import, source_path: &str) -> Result<usize, String> {
+    let mut source = String::new();
+    let result = if source_path == STDIN_PATH {
+        io::stdin().read_to_string(&mut source).map(|_| ())
+    } else {
+        File::open(source_path).and_then(|mut file| file.read_to_string(&mut source).map(|_| ()))
+    };
+    result.map_err(|error| format!("could not read {}: {}", source_path, error))?;
+    let items = match import {
+        Import::Json => json::import(&source),
+        Import::Taskwarrior => taskwarrior::import(&source),
+        Import::Lines { .. } => Ok(import_lines(&source)),
+    };
+    let items = items.map_err(|error| format!("{}: {}", source_path, error))?;
+    let mut known_titles: Option<BTreeSet<String>> = match import {
+        Import::Lines { dedupe: true } => Some(
+            board
+                .todos
+                .iter()
+                .chain(board.dones.iter())
+                .map(|item| dedupe_key(item))
+                .collect(),
+        ),
+        _ => None,
+    };
+    // The imported items count as known too, the same task twice in the input is added once
+    let mut known_uuids: HashSet<&str> = board
+        .todos
+        .iter()
+        .chain(board.dones.iter())
+        .filter_map(|item| item_meta(item, "uuid"))
+        .collect();
+    let mut new = Vec::with_capacity(items.len());
+    for (_, title) in items.iter() {
+        let known = item_meta(title, "uuid").is_some_and(|uuid| !known_uuids.insert(uuid));
+        // The same line twice in the input is a duplicate too
+        let duplicate = !known
+            && known_titles
+                .as_mut()
+                .is_some_and(|known_titles| !known_titles.insert(dedupe_key(title)));
+        new.push(!known && !duplicate);
+    }
+    let mut count = 0;
+    for ((status, title), new) in items.into_iter().zip(new) {
+        if !new {
+            continue;
+        }
+        match status {
+            Status::Todo => board.todos.push(title),
+            Status::Done => board.dones.push(title),
+        }
+        count += 1;
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::net::{TcpListener, TcpStream};
+    use std::path::{Path, PathBuf};
+    use std::process;
+
+    // A directory of its own for the files of the test, which removes it when it's done with them
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("todo-rs-batch-test-{}-{}", process::id(), name));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn board(file_path: &Path, todos: &[&str]) -> Board {
+        let todos = todos.iter().map(|todo| todo.to_string()).collect();
+        let file_path = file_path.to_string_lossy().into_owned();
+        Board::new(file_path, None, None, todos, Vec::new(), Extras::default())
+    }
+
+    fn run(batch: Batch, board: &mut Board) -> Result<(), String> {
+        let autocommit = AutoCommit::default();
+        run_batch(
+            batch,
+            board,
+            &mut Hooks::default(),
+            &autocommit,
+            None,
+            None,
+            0,
+        )
+    }
+
+    #[test]
+    fn add_and_done_save_the_file() {
+        let dir = temp_dir("add");
+        let file_path = dir.join("add.todo");
+        let mut board = board(&file_path, &["buy milk"]);
+        run(Batch::Add("call   mom".to_string()), &mut board).unwrap();
+        run(Batch::Done("buy".to_string()), &mut board).unwrap();
+        let missing = run(Batch::Done("write code".to_string()), &mut board);
+        let saved = fs::read_to_string(&file_path);
+        fs::remove_dir_all(&dir).unwrap();
+
+        let today = Date::today();
+        assert_eq!(
+            saved.unwrap(),
+            format!(
+                "TODO: call mom | created:{}\nDONE: buy milk | done:{}\n",
+                today, today
+            )
+        );
+        assert_eq!(missing.unwrap_err(), "no TODO item matches `write code`");
+    }
+
+    #[test]
+    fn import_adds_the_same_items_once() {
+        let dir = temp_dir("import");
+        let source = dir.join("source.json");
+        let source_path = source.to_string_lossy().into_owned();
+        let mut board = board(&dir.join("import.todo"), &[]);
+        let exported = json::export(&["buy milk uuid:1".to_string()], &["call mom".to_string()]);
+        fs::write(&source, exported).unwrap();
+        let first = import_items(&mut board, Import::Json, &source_path);
+        let again = import_items(&mut board, Import::Json, &source_path);
+        fs::write(&source, "buy milk uuid:1\nwrite code\nwrite code\n").unwrap();
+        let lines = import_items(&mut board, Import::Lines { dedupe: true }, &source_path);
+        fs::remove_dir_all(&dir).unwrap();
+
+        // The DONE item has no uuid: to tell it's there already
+        assert_eq!((first.unwrap(), again.unwrap(), lines.unwrap()), (2, 1, 1));
+        let created = format!("write code created:{}", Date::today());
+        assert_eq!(board.todos[..], ["buy milk uuid:1".to_string(), created]);
+        assert_eq!(board.dones.len(), 2);
+    }
+
+    // The request as the server gets it from a client
+    fn request(listener: &TcpListener, text: &str) -> serve::Request {
+        TcpStream::connect(listener.local_addr().unwrap())
+            .and_then(|mut client| client.write_all(text.as_bytes()))
+            .unwrap();
+        loop {
+            if let Some(request) = serve::accept(listener).unwrap() {
+                return request;
+            }
+        }
+    }
+
+    #[test]
+    fn serve_adds_the_items_with_the_token_only() {
+        let dir = temp_dir("serve");
+        let file_path = dir.join("serve.todo");
+        let mut board = board(&file_path, &["buy milk"]);
+        let listener = serve::listen("127.0.0.1", 0).unwrap();
+        let mut respond = |text: &str, allow_add: bool| {
+            let request = request(&listener, text);
+            let response = serve_request(
+                &request,
+                Some("secret"),
+                allow_add,
+                &mut board,
+                &mut Hooks::default(),
+                &AutoCommit::default(),
+                0,
+            );
+            (response.status, response.body)
+        };
+        let post = |token: &str| {
+            let body = format!("title=call+mom&token={}", token);
+            format!(
+                "POST /items HTTP/1.1\r\nContent-Length: {}\r\n\r\n{}",
+                body.len(),
+                body
+            )
+        };
+        let items = respond("GET /items.json HTTP/1.1\r\n\r\n", true);
+        let not_allowed = respond(&post("secret"), false);
+        let no_token = respond(&post("guess"), true);
+        let added = respond(&post("secret"), true);
+        let missing = respond("GET /nope HTTP/1.1\r\n\r\n", true);
+        let saved = fs::read_to_string(&file_path);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(items.0, "200 OK");
+        assert!(items.1.contains("buy milk"));
+        assert_eq!(not_allowed.0, "403 Forbidden");
+        assert_eq!(no_token.0, "403 Forbidden");
+        assert_eq!(added, ("303 See Other", "Added item 2\n".to_string()));
+        assert_eq!(missing.0, "404 Not Found");
+        let created = Date::today();
+        assert_eq!(
+            saved.unwrap(),
+            format!("TODO: buy milk\nTODO: call mom | created:{}\n", created)
+        );
+        assert_eq!(board.saved_todos.len(), 2);
+    }
+}
@@ -0,0 +1,37 @@
+use std::io::{self, ErrorKind};
+use std::process::{Command, Stdio};
+
+// Whatever the desktop opens the links with, tried one by one like the clipboard tools. The URL goes
+// last.
+const OPEN_COMMANDS: &[&[&str]] = &[
+    &["xdg-open"],
+    &["open"],
+    &["rundll32", "url.dll,FileProtocolHandler"],
+];
+
+// Waits for the tool, which may be a browser in the terminal itself. The caller gets the terminal
+// out of its way first.
+pub fn open(url: &str) -> io::Result<()> {
+    for command in OPEN_COMMANDS {
+        let status = Command::new(command[0])
+            .args(&command[1..])
+            .arg(url)
+            .stderr(Stdio::null())
+            .status();
+        match status {
+            Ok(status) if status.success() => return Ok(()),
+            Ok(status) => {
+                return Err(io::Error::other(format!(
+                    "{} failed with {}",
+                    command[0], status
+                )))
+            }
+            Err(error) if error.kind() == ErrorKind::NotFound => continue,
+            Err(error) => return Err(error),
+        }
+    }
+    Err(io::Error::new(
+        ErrorKind::NotFound,
+        "no tool to open the links with found (tried xdg-open, open, rundll32)",
+    ))
+}
@@ -0,0 +1,251 @@
+use std::cell::RefCell;
+use std::collections::hash_map::DefaultHasher;
+use std::ffi::CString;
+use std::hash::{Hash, Hasher};
+#[cfg(all(unix, not(feature = "crossterm")))]
+use std::os::unix::io::RawFd;
+use std::rc::Rc;
+#[cfg(all(unix, not(feature = "crossterm")))]
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::time::Duration;
+
+use dbus::blocking::stdintf::org_freedesktop_dbus::RequestNameReply;
+use dbus::blocking::LocalConnection;
+use dbus::channel::{MatchingReceiver, Sender};
+use dbus::message::MatchRule;
+use dbus::strings::{ErrorName, Interface, Member};
+use dbus::{Message, Path};
+
+use todo_rs::items::Items;
+
+pub const NAME: &str = "org.tsoding.TodoRs";
+const PATH: &str = "/org/tsoding/TodoRs";
+const ERROR: &str = "org.tsoding.TodoRs.Error";
+const INTROSPECTABLE: &str = "org.freedesktop.DBus.Introspectable";
+
+// For `busctl introspect` and the bindings generated from it
+const INTROSPECTION: &str = r#"<!DOCTYPE node PUBLIC "-//freedesktop//DTD D-BUS Object Introspection 1.0//EN"
+ "http://www.freedesktop.org/standards/dbus/1.0/introspect.dtd">
+<node>
+  <interface name="org.tsoding.TodoRs">
+    <method name="Add">
+      <arg name="title" type="s" direction="in"/>
+      <arg name="number" type="u" direction="out"/>
+    </method>
+    <method name="List">
+      <arg name="items" type="as" direction="out"/>
+    </method>
+    <method name="Complete">
+      <arg name="which" type="s" direction="in"/>
+      <arg name="item" type="s" direction="out"/>
+    </method>
+    <signal name="Changed">
+      <arg name="todo" type="u"/>
+      <arg name="done" type="u"/>
+    </signal>
+  </interface>
+  <interface name="org.freedesktop.DBus.Introspectable">
+    <method name="Introspect">
+      <arg name="xml" type="s" direction="out"/>
+    </method>
+  </interface>
+</node>"#;
+
+// The connection, for the main loop to wake up on the calls like it does on the input
+#[cfg(all(unix, not(feature = "crossterm")))]
+static CONNECTION: AtomicI32 = AtomicI32::new(-1);
+
+// The running app as org.tsoding.TodoRs on the session bus, for the desktop widgets and the
+// extensions of the desktops:
+//
+//     $ busctl --user call org.tsoding.TodoRs /org/tsoding/TodoRs org.tsoding.TodoRs Add s 'call mom'
+//
+// The methods work on the current file like the control socket does, and Changed(todo, done)
+// comes with the numbers of the items whenever the lists change.
+pub struct Bus {
+    connection: Rc<LocalConnection>,
+    calls: Rc<RefCell<Vec<Message>>>,
+    // Of the lists the last Changed was about. They are only hashed once their revisions change,
+    // so the DONE items left in the file are not read for nothing.
+    revisions: [usize; 2],
+    fingerprint: Option<u64>,
+}
+
+// A method call of org.tsoding.TodoRs
+pub enum Method {
+    Add(String),
+    List,
+    Complete(String),
+}
+
+// A call along with the way to answer it
+pub struct Call {
+    pub method: Method,
+    message: Message,
+    connection: Rc<LocalConnection>,
+}
+
+impl Call {
+    // The caller that went away is its own business
+    fn send(&self, reply: Message) {
+        let _ = self.connection.send(reply);
+    }
+
+    pub fn reply_number(&self, number: usize) {
+        self.send(self.message.method_return().append1(number as u32));
+    }
+
+    pub fn reply_text(&self, text: &str) {
+        self.send(self.message.method_return().append1(text));
+    }
+
+    pub fn reply_list(&self, items: Vec<String>) {
+        self.send(self.message.method_return().append1(items));
+    }
+
+    pub fn reply_error(&self, error: &str) {
+        error_reply(&self.connection, &self.message, ERROR, error);
+    }
+}
+
+fn error_reply(connection: &LocalConnection, message: &Message, name: &str, error: &str) {
+    let error = CString::new(error.replace('\0', "")).unwrap_or_default();
+    let _ = connection.send(message.error(&ErrorName::from(name), &error));
+}
+
+fn fingerprint(todos: &[String], dones: &[String]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    todos.hash(&mut hasher);
+    dones.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(all(unix, not(feature = "crossterm")))]
+pub fn wakeup_fd() -> Option<RawFd> {
+    let fd = CONNECTION.load(Ordering::Relaxed);
+    if fd >= 0 {
+        Some(fd)
+    } else {
+        None
+    }
+}
+
+impl Bus {
+    // Without the session bus, like over ssh, there is nobody to talk to and that's fine. Some
+    // other app having the name already is what the message is for.
+    pub fn connect(todos: &Items, dones: &Items) -> (Option<Self>, Option<String>) {
+        let connection = match LocalConnection::new_session() {
+            Ok(connection) => connection,
+            Err(_) => return (None, None),
+        };
+        match connection.request_name(NAME, false, false, true) {
+            Ok(RequestNameReply::PrimaryOwner) | Ok(RequestNameReply::AlreadyOwner) => {}
+            Ok(_) => {
+                let message = format!("Another todo-rs has the D-Bus name {}", NAME);
+                return (None, Some(message));
+            }
+            Err(error) => {
+                let message = format!("Could not take the D-Bus name {}: {}", NAME, error);
+                return (None, Some(message));
+            }
+        }
+        let calls = Rc::new(RefCell::new(Vec::new()));
+        let queue = calls.clone();
+        connection.start_receive(
+            MatchRule::new_method_call(),
+            Box::new(move |message, _| {
+                queue.borrow_mut().push(message);
+                true
+            }),
+        );
+        #[cfg(all(unix, not(feature = "crossterm")))]
+        CONNECTION.store(connection.channel().watch().fd, Ordering::Relaxed);
+        let bus = Self {
+            connection: Rc::new(connection),
+            calls,
+            revisions: [todos.revision(), dones.revision()],
+            fingerprint: None,
+        };
+        (Some(bus), None)
+    }
+
+    // The calls of org.tsoding.TodoRs that came since the last time, never blocks. The rest of
+    // them are answered right here.
+    pub fn accept(&self) -> Vec<Call> {
+        // A broken connection has nothing more to say, the app goes on without it
+        let _ = self.connection.process(Duration::ZERO);
+        let messages: Vec<Message> = self.calls.borrow_mut().drain(..).collect();
+        let mut calls = Vec::new();
+        for message in messages {
+            let interface = message.interface().map(|interface| interface.to_string());
+            let member = message.member().map(|member| member.to_string());
+            let path = message.path().map(|path| path.to_string());
+            if path.as_deref() != Some(PATH) {
+                let error = format!("there is no object {}", path.unwrap_or_default());
+                let name = "org.freedesktop.DBus.Error.UnknownObject";
+                error_reply(&self.connection, &message, name, &error);
+                continue;
+            }
+            let method = match (interface.as_deref(), member.as_deref()) {
+                (Some(INTROSPECTABLE), Some("Introspect")) => {
+                    let reply = message.method_return().append1(INTROSPECTION);
+                    let _ = self.connection.send(reply);
+                    continue;
+                }
+                (Some(NAME) | None, Some("Add")) => message.read1().map(Method::Add),
+                (Some(NAME) | None, Some("List")) => Ok(Method::List),
+                (Some(NAME) | None, Some("Complete")) => message.read1().map(Method::Complete),
+                _ => {
+                    let error = format!(
+                        "unknown method {}.{}",
+                        interface.unwrap_or_default(),
+                        member.unwrap_or_default()
+                    );
+                    let name = "org.freedesktop.DBus.Error.UnknownMethod";
+                    error_reply(&self.connection, &message, name, &error);
+                    continue;
+                }
+            };
+            match method {
+                Ok(method) => calls.push(Call {
+                    method,
+                    message,
+                    connection: self.connection.clone(),
+                }),
+                Err(error) => {
+                    let name = "org.freedesktop.DBus.Error.InvalidArgs";
+                    error_reply(&self.connection, &message, name, &error.to_string());
+                }
+            }
+        }
+        calls
+    }
+
+    // Sends Changed if the lists are not what they were the last time
+    pub fn changed(&mut self, todos: &Items, dones: &Items) {
+        let revisions = [todos.revision(), dones.revision()];
+        if revisions == self.revisions {
+            return;
+        }
+        self.revisions = revisions;
+        let fingerprint = Some(fingerprint(todos, dones));
+        if fingerprint == self.fingerprint {
+            return;
+        }
+        self.fingerprint = fingerprint;
+        let signal = Message::signal(
+            &Path::from(PATH),
+            &Interface::from(NAME),
+            &Member::from("Changed"),
+        )
+        .append2(todos.len() as u32, dones.len() as u32);
+        let _ = self.connection.send(signal);
+    }
+}
+
+impl Drop for Bus {
+    fn drop(&mut self) {
+        #[cfg(all(unix, not(feature = "crossterm")))]
+        CONNECTION.store(-1, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,384 @@
+use std::process::{self, Stdio};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use todo_rs::{ics, item_meta, item_set_meta, Status};
+
+use crate::config::Config;
+use crate::hooks::shell;
+use crate::http;
+use crate::sync::{self, Service, State, Synced};
+
+// Syncs the lists with a calendar on a CalDAV server, like Nextcloud or Radicale. The items and the
+// VTODOs are matched by the uid: of the items, which the new items get on their first sync. The
+// state of the last sync is kept in <file>.caldav:
+//
+//     [caldav]
+//     url = "https://cloud.example.com/remote.php/dav/calendars/me/tasks/"
+//     user = "me"
+//     password_command = "pass show caldav"
+#[derive(Clone)]
+pub struct CalDav {
+    // Of the calendar collection, ends with a slash
+    url: String,
+    user: Option<String>,
+    password: Option<String>,
+    // Prints the password, run on every sync so the password is never kept around
+    password_command: Option<String>,
+}
+
+// Unique enough for the items of a single user, and free of anything that needs escaping in a URL
+fn new_uid(number: usize) -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_nanos());
+    format!("{:x}-{:x}-{:x}@todo-rs", nanos, process::id(), number)
+}
+
+fn xml_unescape(text: &str) -> String {
+    let text = text.trim();
+    let cdata = text
+        .strip_prefix("<![CDATA[")
+        .and_then(|text| text.strip_suffix("]]>"));
+    if let Some(cdata) = cdata {
+        return cdata.to_string();
+    }
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        result.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let end = match rest.find(';') {
+            Some(end) => end,
+            None => break,
+        };
+        let entity = &rest[1..end];
+        let c = match entity {
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "amp" => Some('&'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => entity
+                .strip_prefix("#x")
+                .or_else(|| entity.strip_prefix("#X"))
+                .map(|hex| u32::from_str_radix(hex, 16))
+                .or_else(|| entity.strip_prefix('#').map(str::parse))
+                .and_then(Result::ok)
+                .and_then(char::from_u32),
+        };
+        match c {
+            Some(c) => {
+                result.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                result.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+// The insides of the elements by their names without the namespace prefixes, which every server
+// picks as it likes. Good enough for the multistatus of a calendar query, not for XML in general.
+fn elements<'a>(xml: &'a str, name: &str) -> Vec<&'a str> {
+    let mut found = Vec::new();
+    let mut rest = xml;
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start + 1..];
+        let tag_end = rest
+            .find(|c: char| c.is_whitespace() || c == '>' || c == '/')
+            .unwrap_or(rest.len());
+        let tag = &rest[..tag_end];
+        if tag.is_empty() || tag.rsplit(':').next() != Some(name) {
+            continue;
+        }
+        let open_end = match rest.find('>') {
+            Some(open_end) => open_end,
+            None => break,
+        };
+        if rest[..open_end].ends_with('/') {
+            found.push("");
+            rest = &rest[open_end + 1..];
+            continue;
+        }
+        let inside = &rest[open_end + 1..];
+        let close = format!("</{}>", tag);
+        match inside.find(&close) {
+            Some(end) => {
+                found.push(&inside[..end]);
+                rest = &inside[end + close.len()..];
+            }
+            None => break,
+        }
+    }
+    found
+}
+
+const QUERY: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<c:calendar-query xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav">
+  <d:prop><d:getetag/><c:calendar-data/></d:prop>
+  <c:filter><c:comp-filter name="VCALENDAR"><c:comp-filter name="VTODO"/></c:comp-filter></c:filter>
+</c:calendar-query>
+"#;
+
+// An item of the server along with where it lives there
+struct Remote {
+    href: String,
+    status: Status,
+    title: String,
+}
+
+impl CalDav {
+    pub fn from_config(config: &Config) -> Result<Option<Self>, String> {
+        let text = |key: &str| {
+            config
+                .get("caldav", key)
+                .filter(|value| !value.is_empty())
+                .map(str::to_string)
+        };
+        let url = match text("url") {
+            Some(url) => url,
+            None if config.section("caldav").is_empty() => return Ok(None),
+            None => {
+                return Err(config.error("caldav", "url", "the url of the calendar is missing"))
+            }
+        };
+        if !url.starts_with("http://") && !url.starts_with("https://") {
+            let message = format!("expected an http:// or https:// url but got `{}`", url);
+            return Err(config.error("caldav", "url", &message));
+        }
+        let url = if url.ends_with('/') {
+            url
+        } else {
+            format!("{}/", url)
+        };
+        if text("password").is_some() && text("password_command").is_some() {
+            let message = "either the password or the password_command, not both";
+            return Err(config.error("caldav", "password_command", message));
+        }
+        Ok(Some(Self {
+            url,
+            user: text("user"),
+            password: text("password"),
+            password_command: text("password_command"),
+        }))
+    }
+
+    fn password(&self) -> Result<String, String> {
+        if let Some(password) = &self.password {
+            return Ok(password.clone());
+        }
+        let command = match &self.password_command {
+            Some(command) => command,
+            None => return Ok(String::new()),
+        };
+        let output = shell(command)
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .output()
+            .map_err(|error| format!("could not run the password_command: {}", error))?;
+        if !output.status.success() {
+            return Err(format!(
+                "the password_command failed with {}",
+                output.status
+            ));
+        }
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        Ok(stdout.lines().next().unwrap_or("").to_string())
+    }
+
+    // The href of the responses is usually the path only
+    fn resolve(&self, href: &str) -> String {
+        if href.contains("://") {
+            return href.to_string();
+        }
+        match href.strip_prefix('/') {
+            Some(path) => {
+                let host_start = self.url.find("://").map_or(0, |start| start + 3);
+                let host_end = self.url[host_start..]
+                    .find('/')
+                    .map_or(self.url.len(), |end| host_start + end);
+                format!("{}/{}", &self.url[..host_end], path)
+            }
+            None => format!("{}{}", self.url, href),
+        }
+    }
+
+    fn request(
+        &self,
+        password: &str,
+        method: &str,
+        url: &str,
+        headers: &[&str],
+        body: Option<&str>,
+    ) -> Result<String, String> {
+        let user = self
+            .user
+            .as_ref()
+            .map(|user| format!("{}:{}", user, password));
+        http::request(method, url, headers, user.as_deref(), body)
+    }
+
+    fn fetch(&self, password: &str) -> Result<Vec<Remote>, String> {
+        let headers = ["Depth: 1", "Content-Type: application/xml; charset=utf-8"];
+        let response = self.request(password, "REPORT", &self.url, &headers, Some(QUERY))?;
+        let mut remotes = Vec::new();
+        for response in elements(&response, "response") {
+            let href = match elements(response, "href").first() {
+                Some(href) => xml_unescape(href),
+                None => continue,
+            };
+            let data = match elements(response, "calendar-data").first() {
+                Some(data) => xml_unescape(data),
+                None => continue,
+            };
+            // A resource is a single to-do, with its recurrences at most
+            if let Some((status, title)) = ics::import(&data).into_iter().next() {
+                remotes.push(Remote {
+                    href,
+                    status,
+                    title,
+                });
+            }
+        }
+        Ok(remotes)
+    }
+
+    fn put(&self, password: &str, href: &str, status: Status, title: &str) -> Result<(), String> {
+        let uid = item_meta(title, "uid").unwrap_or_default();
+        let body = ics::calendar(status, title, uid);
+        let headers = ["Content-Type: text/calendar; charset=utf-8"];
+        self.request(password, "PUT", &self.resolve(href), &headers, Some(&body))
+            .map(|_| ())
+    }
+
+    fn reconcile(
+        &self,
+        password: &str,
+        base: &State,
+        remotes: &[Remote],
+        todos: &mut Vec<String>,
+        dones: &mut Vec<String>,
+        synced: &mut Synced,
+    ) -> Result<(), String> {
+        for (number, item) in todos.iter_mut().chain(dones.iter_mut()).enumerate() {
+            if item_meta(item, "uid").is_none() {
+                item_set_meta(item, "uid", Some(&new_uid(number)));
+            }
+        }
+
+        for remote in remotes.iter() {
+            let uid = item_meta(&remote.title, "uid")
+                .unwrap_or_default()
+                .to_string();
+            let local = todos
+                .iter()
+                .position(|item| item_meta(item, "uid") == Some(&uid))
+                .map(|index| (Status::Todo, index))
+                .or_else(|| {
+                    dones
+                        .iter()
+                        .position(|item| item_meta(item, "uid") == Some(&uid))
+                        .map(|index| (Status::Done, index))
+                });
+            match local {
+                Some((status, _)) if status == remote.status => {
+                    synced.state.insert(uid, (status, remote.href.clone()));
+                }
+                // It was the same on both sides the last time, so the server is the one that changed
+                Some((status, index)) if base.get(&uid).map(|(base, _)| *base) == Some(status) => {
+                    let (from, to) = match status {
+                        Status::Todo => (&mut *todos, &mut *dones),
+                        Status::Done => (&mut *dones, &mut *todos),
+                    };
+                    let mut item = from.remove(index);
+                    sync::set_status(&mut item, remote.status, item_meta(&remote.title, "done"));
+                    to.push(item);
+                    synced.pulled += 1;
+                    synced
+                        .state
+                        .insert(uid, (remote.status, remote.href.clone()));
+                }
+                // Either ours changed or both did, ours wins then
+                Some((status, index)) => {
+                    let item = match status {
+                        Status::Todo => &todos[index],
+                        Status::Done => &dones[index],
+                    };
+                    self.put(password, &remote.href, status, item)?;
+                    synced.pushed += 1;
+                    synced.state.insert(uid, (status, remote.href.clone()));
+                }
+                // Deleted here since the last sync
+                None if base.contains_key(&uid) => {
+                    let url = self.resolve(&remote.href);
+                    self.request(password, "DELETE", &url, &[], None)?;
+                    synced.deleted += 1;
+                }
+                None => {
+                    let mut item = remote.title.clone();
+                    if remote.status == Status::Done && item_meta(&item, "done").is_none() {
+                        sync::set_status(&mut item, Status::Done, None);
+                    }
+                    match remote.status {
+                        Status::Todo => todos.push(item),
+                        Status::Done => dones.push(item),
+                    }
+                    synced.pulled += 1;
+                    synced
+                        .state
+                        .insert(uid, (remote.status, remote.href.clone()));
+                }
+            }
+        }
+
+        // What's left is either new here or deleted on the server since the last sync
+        for (status, list) in [(Status::Todo, todos), (Status::Done, dones)] {
+            let mut index = 0;
+            while index < list.len() {
+                let uid = item_meta(&list[index], "uid")
+                    .unwrap_or_default()
+                    .to_string();
+                if synced.state.contains_key(&uid)
+                    || remotes
+                        .iter()
+                        .any(|remote| item_meta(&remote.title, "uid") == Some(&uid))
+                {
+                    index += 1;
+                } else if base.contains_key(&uid) {
+                    list.remove(index);
+                    synced.deleted += 1;
+                } else {
+                    let href = format!("{}{}.ics", self.url, uid);
+                    self.put(password, &href, status, &list[index])?;
+                    synced.pushed += 1;
+                    synced.state.insert(uid, (status, href));
+                    index += 1;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Service for CalDav {
+    const NAME: &'static str = "caldav";
+
+    fn sync(
+        &self,
+        file_path: &str,
+        todos: &mut Vec<String>,
+        dones: &mut Vec<String>,
+    ) -> Result<Synced, String> {
+        let password = self.password()?;
+        let base = sync::load_state(file_path, Self::NAME);
+        let remotes = self.fetch(&password)?;
+        let mut synced = Synced::new(Self::NAME);
+        let result = self.reconcile(&password, &base, &remotes, todos, dones, &mut synced);
+        synced.finish(base, result);
+        Ok(synced)
+    }
+}
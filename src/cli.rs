@@ -0,0 +1,738 @@
+use crate::completions::Shell;
+use todo_rs::{format, header, EXPORT_FORMATS};
+
+// The command line of the app. Everything about it is described here once, for the parser, the
+// help and the shell completions alike.
+
+pub struct Flag {
+    pub name: &'static str,
+    pub short: Option<char>,
+    // The placeholder of the value in the help, None for the flags without one
+    pub value: Option<&'static str>,
+    pub help: &'static str,
+}
+
+pub const FLAGS: &[Flag] = &[
+    Flag {
+        name: "help",
+        short: Some('h'),
+        value: None,
+        help: "Print this help",
+    },
+    Flag {
+        name: "version",
+        short: Some('V'),
+        value: None,
+        help: "Print the version",
+    },
+    Flag {
+        name: "format",
+        short: None,
+        value: Some("<format>"),
+        help: "Read and write the files in the format regardless of their names",
+    },
+    Flag {
+        name: "readonly",
+        short: None,
+        value: None,
+        help: "Open the files read-only",
+    },
+    Flag {
+        name: "keymap",
+        short: None,
+        value: Some("<preset>"),
+        help: "Start from the keymap preset: default, vim, emacs or arrows",
+    },
+    Flag {
+        name: "theme",
+        short: None,
+        value: Some("<preset>"),
+        help: "Use the theme preset: dark or light",
+    },
+    Flag {
+        name: "layout",
+        short: None,
+        value: Some("<layout>"),
+        help: "Lay out the panels: split, stacked or single",
+    },
+    Flag {
+        name: "no-autosave",
+        short: None,
+        value: None,
+        help: "Save the changes only when asked to, whatever the config says",
+    },
+    Flag {
+        name: "stdout",
+        short: None,
+        value: None,
+        help: "Write the lists to stdout on quit instead of saving them",
+    },
+    Flag {
+        name: "export-json",
+        short: None,
+        value: None,
+        help: "Print the items as JSON and quit",
+    },
+    Flag {
+        name: "export-ics",
+        short: None,
+        value: None,
+        help: "Print the items as iCalendar to-dos and quit",
+    },
+    Flag {
+        name: "import-json",
+        short: None,
+        value: Some("<json-path>"),
+        help: "Add the items of the JSON written by --export-json and quit",
+    },
+    Flag {
+        name: "import-taskwarrior",
+        short: None,
+        value: Some("<json-path>"),
+        help: "Add the tasks of `task export` and quit",
+    },
+];
+
+pub struct Subcommand {
+    pub name: &'static str,
+    // The arguments after the name, for the help
+    pub args: &'static str,
+    pub flags: &'static [Flag],
+    pub help: &'static str,
+}
+
+pub const SUBCOMMANDS: &[Subcommand] = &[
+    Subcommand {
+        name: "add",
+        args: "<title>",
+        flags: &[],
+        help: "Add a TODO item",
+    },
+    Subcommand {
+        name: "list",
+        args: "[--done] [--json]",
+        flags: &[
+            Flag {
+                name: "done",
+                short: None,
+                value: None,
+                help: "List the DONE items instead",
+            },
+            Flag {
+                name: "json",
+                short: None,
+                value: None,
+                help: "Print JSON instead",
+            },
+        ],
+        help: "Print the TODO items along with their numbers",
+    },
+    Subcommand {
+        name: "stats",
+        args: "[--json]",
+        flags: &[Flag {
+            name: "json",
+            short: None,
+            value: None,
+            help: "Print JSON instead",
+        }],
+        help: "Print the statistics of the lists",
+    },
+    Subcommand {
+        name: "status",
+        args: "[--format <format>]",
+        flags: &[Flag {
+            name: "format",
+            short: None,
+            value: Some("<format>"),
+            help: "What to print, e.g. \"{todo} todo, next: {next}\"",
+        }],
+        help: "Print a line about the lists for the status bars and the prompts",
+    },
+    Subcommand {
+        name: "print",
+        args: "",
+        flags: &[],
+        help: "Print both of the lists side by side, in color on a terminal",
+    },
+    Subcommand {
+        name: "done",
+        args: "<number|pattern>",
+        flags: &[],
+        help: "Mark the TODO item with the number or a part of the title as DONE",
+    },
+    Subcommand {
+        name: "export",
+        args: "--<format> <out-path>",
+        flags: &[],
+        help: "Write the items to the path in the format, `-` for stdout",
+    },
+    Subcommand {
+        name: "import",
+        args: "<lines-path>|--from-stdin [--dedupe]",
+        flags: &[
+            Flag {
+                name: "from-stdin",
+                short: None,
+                value: None,
+                help: "Read the lines from stdin",
+            },
+            Flag {
+                name: "dedupe",
+                short: None,
+                value: None,
+                help: "Skip the lines that are items already",
+            },
+        ],
+        help: "Add a TODO item for every line of the file",
+    },
+    Subcommand {
+        name: "scan",
+        args: "<dir>",
+        flags: &[],
+        help:
+            "Add the TODO comments of the source files under the directory, mark the gone ones DONE",
+    },
+    Subcommand {
+        name: "serve",
+        args: "[--bind <address>] [--port <port>] [--allow-add [--token <token>]]",
+        flags: &[
+            Flag {
+                name: "bind",
+                short: None,
+                value: Some("<address>"),
+                help: "The address to listen on, 127.0.0.1 by default",
+            },
+            Flag {
+                name: "port",
+                short: None,
+                value: Some("<port>"),
+                help: "The port to listen on, 8080 by default",
+            },
+            Flag {
+                name: "allow-add",
+                short: None,
+                value: None,
+                help: "Take the new TODO items from the page too",
+            },
+            Flag {
+                name: "token",
+                short: None,
+                value: Some("<token>"),
+                help: "What adding the items takes, made up at the start by default",
+            },
+        ],
+        help: "Show the lists on a web page and as JSON until Ctrl+C",
+    },
+    Subcommand {
+        name: "sync",
+        args: "",
+        flags: &[],
+        help: "Push and pull the items to and from the CalDAV calendar of the config",
+    },
+    Subcommand {
+        name: "github",
+        args: "",
+        flags: &[],
+        help: "Sync the items with the issues of the GitHub repository of the config",
+    },
+    Subcommand {
+        name: "completions",
+        args: "<shell>",
+        flags: &[],
+        help: "Print the completion script for bash, zsh or fish",
+    },
+];
+
+// What the app does to the file instead of opening the UI
+#[derive(Clone)]
+pub enum Batch {
+    // The lists in one of the export formats, to the path or to stdout for `-`
+    Export { format: &'static str, path: String },
+    Import(Import, String),
+    Add(String),
+    List { done: bool, json: bool },
+    Stats { json: bool },
+    // The line of status.rs in the format
+    Status(String),
+    Print,
+    // The TODO item by its number in the list or by a part of its title
+    Done(String),
+    // The TODO comments of the source files under the directory
+    Scan(String),
+    // The lists over HTTP, see serve.rs
+    Serve(crate::serve::Options),
+    Sync,
+    GitHub,
+}
+
+impl Batch {
+    // Whether the lists change, so the file has to be locked and saved
+    pub fn writes(&self) -> bool {
+        !matches!(
+            self,
+            Batch::Export { .. }
+                | Batch::List { .. }
+                | Batch::Stats { .. }
+                | Batch::Status(_)
+                | Batch::Print
+                | Batch::Serve(crate::serve::Options {
+                    allow_add: false,
+                    ..
+                })
+        )
+    }
+}
+
+// The formats the items can be imported from
+#[derive(Clone, Copy)]
+pub enum Import {
+    // The document written by --export-json
+    Json,
+    // The output of `task export`
+    Taskwarrior,
+    // A TODO item per line, like the notes or the output of grep
+    Lines { dedupe: bool },
+}
+
+// The file path that stands for stdin
+pub const STDIN_PATH: &str = "-";
+
+#[derive(Default, Clone)]
+pub struct Args {
+    pub file_paths: Vec<String>,
+    pub keymap: Option<String>,
+    pub theme: Option<String>,
+    // One of header::LAYOUTS
+    pub layout: Option<&'static str>,
+    pub no_autosave: bool,
+    pub format: Option<format::Kind>,
+    pub read_only: bool,
+    // The locked files the user agreed to open read-only, see main()
+    pub read_only_files: Vec<String>,
+    pub to_stdout: bool,
+    pub batch: Option<Batch>,
+}
+
+pub enum Mode {
+    Run(Args),
+    Help,
+    Version,
+    Completions(Shell),
+}
+
+// One of EXPORT_FORMATS or of the formats of the files
+pub fn export_format_name(name: &str) -> Option<&'static str> {
+    EXPORT_FORMATS
+        .iter()
+        .copied()
+        .find(|&format| format == name)
+        .or_else(|| format::Kind::from_name(name).map(format::Kind::name))
+}
+
+pub fn export_format_names() -> Vec<&'static str> {
+    let mut names = EXPORT_FORMATS.to_vec();
+    names.extend(format::Kind::ALL.iter().map(|kind| kind.name()));
+    names
+}
+
+// How many single character edits it takes to turn one into the other
+fn distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let above = row[j + 1];
+            row[j + 1] = if ca == *cb {
+                diagonal
+            } else {
+                1 + diagonal.min(above).min(row[j])
+            };
+            diagonal = above;
+        }
+    }
+    row[b.len()]
+}
+
+fn unknown_flag(flag: &str, flags: &[&Flag]) -> String {
+    let name = flag.trim_start_matches('-');
+    let closest = flags
+        .iter()
+        .map(|known| (distance(name, known.name), known.name))
+        .min();
+    match closest {
+        Some((distance, known)) if distance <= 2 => {
+            format!("unknown flag `{}`, did you mean `--{}`?", flag, known)
+        }
+        _ => format!("unknown flag `{}`", flag),
+    }
+}
+
+fn flag_value(
+    flag: &Flag,
+    inline_value: Option<String>,
+    args: &mut impl Iterator<Item = String>,
+) -> Result<String, String> {
+    match (flag.value, inline_value) {
+        (None, None) => Ok(String::new()),
+        (None, Some(_)) => Err(format!("`--{}` takes no value", flag.name)),
+        (Some(_), Some(value)) => Ok(value),
+        (Some(placeholder), None) => args
+            .next()
+            .ok_or_else(|| format!("`--{}` needs a {}", flag.name, placeholder)),
+    }
+}
+
+pub fn parse(mut args: impl Iterator<Item = String>) -> Result<Mode, String> {
+    let mut result = Args::default();
+    let mut positional = Vec::new();
+    let mut subcommand: Option<&Subcommand> = None;
+    // The flags of the subcommand that were given, with their values
+    let mut subcommand_flags: Vec<(&str, String)> = Vec::new();
+    let mut export_format = None;
+    let mut only_positional = false;
+    while let Some(arg) = args.next() {
+        if only_positional || arg == STDIN_PATH || !arg.starts_with('-') {
+            if subcommand.is_none() && result.file_paths.is_empty() && positional.is_empty() {
+                if let Some(found) = SUBCOMMANDS.iter().find(|known| known.name == arg) {
+                    if !only_positional {
+                        subcommand = Some(found);
+                        continue;
+                    }
+                }
+            }
+            positional.push(arg);
+            continue;
+        }
+        if arg == "--" {
+            only_positional = true;
+            continue;
+        }
+
+        let (name, inline_value) = match arg.strip_prefix("--") {
+            Some(rest) => match rest.split_once('=') {
+                Some((name, value)) => (name.to_string(), Some(value.to_string())),
+                None => (rest.to_string(), None),
+            },
+            None => {
+                let short = arg[1..].chars().next();
+                match FLAGS
+                    .iter()
+                    .find(|flag| flag.short.is_some() && flag.short == short)
+                {
+                    Some(flag) if arg.len() == 2 => (flag.name.to_string(), None),
+                    _ => return Err(format!("unknown flag `{}`", arg)),
+                }
+            }
+        };
+
+        // The flags of the subcommand go first, the export formats are flags of their own
+        if let Some(subcommand) = subcommand {
+            if let Some(flag) = subcommand.flags.iter().find(|flag| flag.name == name) {
+                let value = flag_value(flag, inline_value, &mut args)?;
+                subcommand_flags.push((flag.name, value));
+                continue;
+            }
+            if subcommand.name == "export" && export_format.is_none() {
+                if let Some(format) = export_format_name(&name) {
+                    export_format = Some(format);
+                    continue;
+                }
+            }
+        }
+        let flag = match FLAGS.iter().find(|flag| flag.name == name) {
+            Some(flag) => flag,
+            None => {
+                let mut known: Vec<&Flag> = FLAGS.iter().collect();
+                if let Some(subcommand) = subcommand {
+                    known.extend(subcommand.flags.iter());
+                }
+                return Err(unknown_flag(&arg, &known));
+            }
+        };
+        let value = flag_value(flag, inline_value, &mut args)?;
+        match flag.name {
+            "help" => return Ok(Mode::Help),
+            "version" => return Ok(Mode::Version),
+            "format" => {
+                let kind = format::Kind::from_name(&value).ok_or_else(|| {
+                    let names: Vec<&str> =
+                        format::Kind::ALL.iter().map(|kind| kind.name()).collect();
+                    format!(
+                        "unknown format `{}`, expected one of {}",
+                        value,
+                        names.join(", ")
+                    )
+                })?;
+                result.format = Some(kind);
+            }
+            "readonly" => result.read_only = true,
+            "keymap" => result.keymap = Some(value),
+            "theme" => result.theme = Some(value),
+            "layout" => {
+                let layout = header::LAYOUTS.iter().find(|&&layout| layout == value);
+                result.layout = Some(*layout.ok_or_else(|| {
+                    format!(
+                        "unknown layout `{}`, expected one of {}",
+                        value,
+                        header::LAYOUTS.join(", ")
+                    )
+                })?);
+            }
+            "no-autosave" => result.no_autosave = true,
+            "stdout" => result.to_stdout = true,
+            "export-json" | "export-ics" => {
+                result.batch = Some(Batch::Export {
+                    format: if flag.name == "export-json" {
+                        "json"
+                    } else {
+                        "ics"
+                    },
+                    path: STDIN_PATH.to_string(),
+                })
+            }
+            "import-json" => result.batch = Some(Batch::Import(Import::Json, value)),
+            "import-taskwarrior" => result.batch = Some(Batch::Import(Import::Taskwarrior, value)),
+            _ => unreachable!("the flag `--{}` is not handled", flag.name),
+        }
+    }
+
+    let mut positional = positional.into_iter();
+    if let Some(subcommand) = subcommand {
+        let usage = format!("todo-rs {} {}", subcommand.name, subcommand.args)
+            .trim_end()
+            .to_string();
+        let given = |name: &str| subcommand_flags.iter().any(|(flag, _)| *flag == name);
+        let mut arg = |what: &str| {
+            positional
+                .next()
+                .ok_or_else(|| format!("no {} is provided\nUsage: {}", what, usage))
+        };
+        result.batch = Some(match subcommand.name {
+            "add" => Batch::Add(arg("title")?),
+            "list" => Batch::List {
+                done: given("done"),
+                json: given("json"),
+            },
+            "stats" => Batch::Stats {
+                json: given("json"),
+            },
+            "status" => Batch::Status(
+                subcommand_flags
+                    .iter()
+                    .find(|(flag, _)| *flag == "format")
+                    .map_or(crate::status::DEFAULT_FORMAT.to_string(), |(_, format)| {
+                        format.clone()
+                    }),
+            ),
+            "print" => Batch::Print,
+            "sync" => Batch::Sync,
+            "github" => Batch::GitHub,
+            "scan" => Batch::Scan(arg("directory to scan")?),
+            "serve" => {
+                let port = match subcommand_flags.iter().find(|(flag, _)| *flag == "port") {
+                    Some((_, port)) => {
+                        port.parse().ok().filter(|&port| port > 0).ok_or_else(|| {
+                            format!(
+                                "expected the port as a number from 1 to 65535 but got `{}`",
+                                port
+                            )
+                        })?
+                    }
+                    None => crate::serve::DEFAULT_PORT,
+                };
+                let flag = |name: &str| {
+                    subcommand_flags
+                        .iter()
+                        .find(|(flag, _)| *flag == name)
+                        .map(|(_, value)| value.clone())
+                };
+                let token = flag("token");
+                if token.as_deref() == Some("") {
+                    return Err("the token can't be empty".to_string());
+                }
+                if token.is_some() && !given("allow-add") {
+                    return Err("--token is only for --allow-add".to_string());
+                }
+                Batch::Serve(crate::serve::Options {
+                    address: flag("bind")
+                        .unwrap_or_else(|| crate::serve::DEFAULT_ADDRESS.to_string()),
+                    port,
+                    allow_add: given("allow-add"),
+                    token,
+                })
+            }
+            "done" => Batch::Done(arg("number or pattern of the item")?),
+            "import" => {
+                let path = if given("from-stdin") {
+                    STDIN_PATH.to_string()
+                } else {
+                    arg("path of the lines")?
+                };
+                Batch::Import(
+                    Import::Lines {
+                        dedupe: given("dedupe"),
+                    },
+                    path,
+                )
+            }
+            "export" => {
+                let format = export_format.ok_or_else(|| {
+                    format!(
+                        "expected one of --{}\nUsage: {}",
+                        export_format_names().join(", --"),
+                        usage
+                    )
+                })?;
+                Batch::Export {
+                    format,
+                    path: arg("out path")?,
+                }
+            }
+            "completions" => {
+                let name = arg("shell")?;
+                let shell = Shell::from_name(&name).ok_or_else(|| {
+                    format!(
+                        "unknown shell `{}`, expected one of {}",
+                        name,
+                        Shell::NAMES.join(", ")
+                    )
+                })?;
+                return Ok(Mode::Completions(shell));
+            }
+            name => unreachable!("the subcommand `{}` is not handled", name),
+        });
+    }
+    result.file_paths = positional.collect();
+
+    if (result.to_stdout || result.file_paths.iter().any(|path| path == STDIN_PATH))
+        && result.file_paths.len() > 1
+    {
+        return Err("`-` and --stdout work with a single file only".to_string());
+    }
+    if result.batch.is_some() && result.file_paths.len() > 1 {
+        return Err(
+            "the subcommands, the exports and the imports work with a single file".to_string(),
+        );
+    }
+    if matches!(&result.batch, Some(Batch::Import(_, path)) if path == STDIN_PATH)
+        && result.file_paths.iter().any(|path| path == STDIN_PATH)
+    {
+        return Err("the file and the imported items can't both come from stdin".to_string());
+    }
+    Ok(Mode::Run(result))
+}
+
+fn flag_usage(flag: &Flag) -> String {
+    let mut usage = match flag.short {
+        Some(short) => format!("-{}, --{}", short, flag.name),
+        None => format!("    --{}", flag.name),
+    };
+    if let Some(value) = flag.value {
+        usage.push(' ');
+        usage.push_str(value);
+    }
+    usage
+}
+
+// The usages longer than that go on a line of their own in --help
+const MAX_USAGE_WIDTH: usize = 28;
+// What --help fits into, like the terminals do by default
+const HELP_WIDTH: usize = 80;
+
+// The words of the text in lines of at most the width, unless a single word is wider than that
+fn wrap_words(text: &str, width: usize) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for word in text.split_whitespace() {
+        match lines.last_mut() {
+            Some(line) if line.len() + 1 + word.len() <= width => {
+                line.push(' ');
+                line.push_str(word);
+            }
+            _ => lines.push(word.to_string()),
+        }
+    }
+    lines
+}
+
+pub fn help() -> String {
+    let mut rows: Vec<(String, &str)> = Vec::new();
+    for flag in FLAGS.iter() {
+        rows.push((flag_usage(flag), flag.help));
+    }
+    let flags_len = rows.len();
+    for subcommand in SUBCOMMANDS.iter() {
+        rows.push((
+            format!("{} {}", subcommand.name, subcommand.args)
+                .trim_end()
+                .to_string(),
+            subcommand.help,
+        ));
+    }
+    let width = rows
+        .iter()
+        .map(|(usage, _)| usage.len())
+        .filter(|&len| len <= MAX_USAGE_WIDTH)
+        .max()
+        .unwrap_or(0);
+
+    let mut help = String::new();
+    help.push_str("Simple Interactive Terminal Todo App\n\n");
+    help.push_str("Usage: todo-rs [flags] [file-path...|-]\n");
+    help.push_str("       todo-rs <subcommand> <args> [flags] [file-path]\n\n");
+    help.push_str(
+        "Without the file path opens the closest TODO or .todo file up from the current\n",
+    );
+    help.push_str("directory, $TODO_RS_FILE, `path` of [file] in the config or\n");
+    help.push_str("$XDG_DATA_HOME/todo-rs/TODO. `-` reads the items from stdin.\n\n");
+    help.push_str("The flags go over the settings of the files, which go over the config.\n\n");
+    help.push_str("Flags:\n");
+    for (index, (usage, text)) in rows.iter().enumerate() {
+        if index == flags_len {
+            help.push_str("\nSubcommands:\n");
+        }
+        // The longer usages get a line of their own, so the rest of the help stays narrow
+        let indent = width + 4;
+        let mut lines = wrap_words(text, HELP_WIDTH - indent).into_iter();
+        if usage.len() > width {
+            help.push_str(&format!("  {}\n", usage));
+        } else {
+            let first = lines.next().unwrap_or_default();
+            help.push_str(&format!("  {:width$}  {}\n", usage, first, width = width));
+        }
+        for line in lines {
+            help.push_str(&format!("{:indent$}{}\n", "", line, indent = indent));
+        }
+    }
+    help.push_str(&format!(
+        "\nFormats: {}\n",
+        format::Kind::ALL
+            .iter()
+            .map(|kind| kind.name())
+            .collect::<Vec<_>>()
+            .join(", ")
+    ));
+    help.push_str(&format!(
+        "Export formats: {}\n",
+        export_format_names().join(", ")
+    ));
+    help
+}
+
+pub fn version() -> String {
+    format!("todo-rs {}", env!("CARGO_PKG_VERSION"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_help_fits_the_terminal() {
+        for line in help().lines() {
+            assert!(line.len() <= HELP_WIDTH, "{:?}", line);
+        }
+    }
+}
@@ -0,0 +1,76 @@
+use std::io::{self, ErrorKind, Read, Write};
+use std::process::{Command, Stdio};
+
+// There is no portable way to talk to the system clipboard, so we just try the command line tools
+// of different platforms one by one until something works.
+const COPY_COMMANDS: &[&[&str]] = &[
+    &["wl-copy"],
+    &["xclip", "-selection", "clipboard"],
+    &["xsel", "--clipboard", "--input"],
+    &["pbcopy"],
+    &["clip"],
+];
+
+const PASTE_COMMANDS: &[&[&str]] = &[
+    &["wl-paste", "--no-newline"],
+    &["xclip", "-selection", "clipboard", "-o"],
+    &["xsel", "--clipboard", "--output"],
+    &["pbpaste"],
+    &["powershell", "-NoProfile", "-Command", "Get-Clipboard"],
+];
+
+fn no_clipboard_error() -> io::Error {
+    io::Error::new(
+        ErrorKind::NotFound,
+        "no clipboard tool found (tried wl-copy, xclip, xsel, pbcopy, clip)",
+    )
+}
+
+fn spawn(command: &[&str], stdin: Stdio, stdout: Stdio) -> io::Result<std::process::Child> {
+    Command::new(command[0])
+        .args(&command[1..])
+        .stdin(stdin)
+        .stdout(stdout)
+        .stderr(Stdio::null())
+        .spawn()
+}
+
+pub fn copy(text: &str) -> io::Result<()> {
+    for command in COPY_COMMANDS {
+        let mut child = match spawn(command, Stdio::piped(), Stdio::null()) {
+            Ok(child) => child,
+            Err(error) if error.kind() == ErrorKind::NotFound => continue,
+            Err(error) => return Err(error),
+        };
+        // Dropping stdin right after writing closes it, otherwise the tool waits for more input forever
+        child
+            .stdin
+            .take()
+            .expect("stdin of the clipboard tool is piped")
+            .write_all(text.as_bytes())?;
+        if child.wait()?.success() {
+            return Ok(());
+        }
+    }
+    Err(no_clipboard_error())
+}
+
+pub fn paste() -> io::Result<String> {
+    for command in PASTE_COMMANDS {
+        let mut child = match spawn(command, Stdio::null(), Stdio::piped()) {
+            Ok(child) => child,
+            Err(error) if error.kind() == ErrorKind::NotFound => continue,
+            Err(error) => return Err(error),
+        };
+        let mut text = String::new();
+        child
+            .stdout
+            .take()
+            .expect("stdout of the clipboard tool is piped")
+            .read_to_string(&mut text)?;
+        if child.wait()?.success() {
+            return Ok(text);
+        }
+    }
+    Err(no_clipboard_error())
+}
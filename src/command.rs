@@ -0,0 +1,153 @@
+// The commands of the `:` command line. Anything that doesn't deserve a key of its own goes here.
+pub enum Command {
+    Write,
+    Quit,
+    // Quit without saving
+    ForceQuit,
+    WriteQuit,
+    // By the title or by the value of the metadata key
+    Sort(Option<String>),
+    // Only show the items containing the text. No text shows everything again.
+    Filter(Option<String>),
+    Open(String),
+    // Throw away the unsaved changes
+    Revert,
+    // Write a copy somewhere else, the file that is open stays the same. Without the format the
+    // extension of the path decides.
+    Export {
+        scope: Scope,
+        format: Option<String>,
+        path: String,
+    },
+    // With the CalDAV calendar of the config, saving the file right away
+    Sync,
+    // With the issues of the GitHub repository of the config, the same way
+    GitHub,
+    // Moves the reminders on the banner this many minutes from now
+    Snooze(u32),
+    // Not one of ours, a command of the script if it has one by the name, with the words after it
+    Custom(String, Vec<String>),
+}
+
+// What goes into an export
+pub enum Scope {
+    // Everything, like the file itself
+    All,
+    // The current panel only
+    Panel,
+    // The items the filter shows
+    Filter,
+}
+
+pub use todo_rs::EXPORT_FORMATS;
+
+// The names for completion. The short aliases like `w` are not worth completing.
+pub const NAMES: &[&str] = &[
+    "write", "quit", "wq", "sort", "filter", "open", "revert", "export", "sync", "github", "snooze",
+];
+
+impl Command {
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let line = line.trim();
+        let (name, arg) = match line.split_once(' ') {
+            Some((name, arg)) => (name, Some(arg.trim()).filter(|arg| !arg.is_empty())),
+            None => (line, None),
+        };
+        match (name, arg) {
+            ("w" | "write", None) => Ok(Command::Write),
+            ("w" | "write", Some(path)) => Ok(Command::Export {
+                scope: Scope::All,
+                format: None,
+                path: path.to_string(),
+            }),
+            ("q" | "quit", None) => Ok(Command::Quit),
+            ("q!" | "quit!", None) => Ok(Command::ForceQuit),
+            ("wq" | "x", None) => Ok(Command::WriteQuit),
+            ("sort", arg) => Ok(Command::Sort(arg.map(str::to_string))),
+            ("filter", arg) => Ok(Command::Filter(arg.map(str::to_string))),
+            ("open" | "e" | "edit", Some(path)) => Ok(Command::Open(path.to_string())),
+            ("open" | "e" | "edit", None) => Err("Which file to open?".to_string()),
+            ("revert" | "e!" | "edit!", None) => Ok(Command::Revert),
+            ("export", Some(arg)) => {
+                let is_format = |name: &str| {
+                    EXPORT_FORMATS.contains(&name)
+                        || todo_rs::format::Kind::from_name(name).is_some()
+                };
+                let mut path = arg;
+                let mut format = None;
+                if let Some((name, rest)) = path.split_once(' ') {
+                    if is_format(name) {
+                        format = Some(name.to_string());
+                        path = rest.trim();
+                    }
+                }
+                let scope = match path.split_once(' ') {
+                    Some(("panel", rest)) => {
+                        path = rest.trim();
+                        Scope::Panel
+                    }
+                    Some(("filter", rest)) => {
+                        path = rest.trim();
+                        Scope::Filter
+                    }
+                    _ => Scope::All,
+                };
+                // `:export panel` is the panel to nowhere rather than the lists to a file named
+                // `panel`
+                if path == "panel" || path == "filter" || is_format(path) {
+                    return Err(format!("Where to export to? A file goes after `{}`", path));
+                }
+                Ok(Command::Export {
+                    scope,
+                    format,
+                    path: path.to_string(),
+                })
+            }
+            ("export", None) => Err("Where to export to?".to_string()),
+            ("sync", None) => Ok(Command::Sync),
+            ("github", None) => Ok(Command::GitHub),
+            ("snooze", None) => Ok(Command::Snooze(crate::remind::SNOOZE_MINUTES)),
+            ("snooze", Some(minutes)) => match minutes.parse() {
+                Ok(minutes) if (1..24 * 60).contains(&minutes) => Ok(Command::Snooze(minutes)),
+                _ => Err(format!(
+                    "Expected the minutes to snooze for, less than a day, but got `{}`",
+                    minutes
+                )),
+            },
+            ("", _) => Err("No command".to_string()),
+            (name, _) if NAMES.contains(&name) => Err(format!("Too many arguments for `{}`", name)),
+            (name, arg) => Ok(Command::Custom(
+                name.to_string(),
+                arg.map_or_else(Vec::new, |arg| {
+                    arg.split_whitespace().map(str::to_string).collect()
+                }),
+            )),
+        }
+    }
+}
+
+// Completes the name of the command in the line. Returns the completed line if there is a single
+// candidate or the longest common prefix of the candidates otherwise, along with all of them.
+pub fn complete(line: &str) -> (String, Vec<&'static str>) {
+    if line.contains(' ') {
+        return (line.to_string(), Vec::new());
+    }
+    let candidates: Vec<&'static str> = NAMES
+        .iter()
+        .filter(|name| name.starts_with(line))
+        .copied()
+        .collect();
+    match candidates.as_slice() {
+        [] => (line.to_string(), candidates),
+        [name] => (format!("{} ", name), candidates),
+        [first, rest @ ..] => {
+            let mut prefix = first.to_string();
+            for name in rest {
+                while !name.starts_with(&prefix) {
+                    prefix.pop();
+                }
+            }
+            (prefix, candidates)
+        }
+    }
+}
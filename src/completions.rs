@@ -0,0 +1,341 @@
+use crate::cli::{self, Flag, FLAGS, SUBCOMMANDS};
+use crate::{keymap, theme};
+use todo_rs::{format, header};
+
+// The completion scripts are generated from the definitions of cli, so whatever the parser takes
+// gets completed too. The numbers of the items for `done` come from `todo-rs list` as the script
+// runs.
+
+#[derive(Clone, Copy)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+impl Shell {
+    pub const NAMES: &'static [&'static str] = &["bash", "zsh", "fish"];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "bash" => Some(Shell::Bash),
+            "zsh" => Some(Shell::Zsh),
+            "fish" => Some(Shell::Fish),
+            _ => None,
+        }
+    }
+}
+
+pub fn script(shell: Shell) -> String {
+    match shell {
+        Shell::Bash => bash(),
+        Shell::Zsh => zsh(),
+        Shell::Fish => fish(),
+    }
+}
+
+// What goes after the flag
+enum Value {
+    Nothing,
+    Words(Vec<&'static str>),
+    File,
+}
+
+fn flag_value(flag: &Flag) -> Value {
+    match (flag.name, flag.value) {
+        (_, None) => Value::Nothing,
+        ("format", _) => Value::Words(format::Kind::ALL.iter().map(|kind| kind.name()).collect()),
+        ("keymap", _) => Value::Words(keymap::PRESETS.iter().map(|(name, _)| *name).collect()),
+        ("theme", _) => Value::Words(theme::preset_names()),
+        ("layout", _) => Value::Words(header::LAYOUTS.to_vec()),
+        _ => Value::File,
+    }
+}
+
+// The flags only the subcommand takes, the export formats of `export` included
+fn subcommand_flags(subcommand: &cli::Subcommand) -> Vec<(String, String)> {
+    let mut flags: Vec<(String, String)> = subcommand
+        .flags
+        .iter()
+        .map(|flag| (flag.name.to_string(), flag.help.to_string()))
+        .collect();
+    if subcommand.name == "export" {
+        for name in cli::export_format_names() {
+            flags.push((name.to_string(), format!("Write the items as {}", name)));
+        }
+    }
+    flags
+}
+
+fn long_flags(flags: impl Iterator<Item = String>) -> String {
+    flags
+        .map(|name| format!("--{}", name))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// In single quotes for bash and zsh
+fn quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "'\\''"))
+}
+
+// In single quotes for fish, which has escapes of its own there
+fn fish_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+fn bash() -> String {
+    let mut script = String::new();
+    script.push_str("# bash completion for todo-rs, generated by `todo-rs completions bash`\n\n");
+    script.push_str("_todo_rs() {\n");
+    script.push_str("    local cur=\"${COMP_WORDS[COMP_CWORD]}\"\n");
+    script.push_str("    local prev=\"${COMP_WORDS[COMP_CWORD-1]}\"\n\n");
+
+    script.push_str("    case \"$prev\" in\n");
+    for flag in FLAGS.iter() {
+        let reply = match flag_value(flag) {
+            Value::Nothing => continue,
+            Value::Words(words) => format!("compgen -W {} -- \"$cur\"", quote(&words.join(" "))),
+            Value::File => "compgen -f -- \"$cur\"".to_string(),
+        };
+        script.push_str(&format!(
+            "        --{}) COMPREPLY=($({})); return ;;\n",
+            flag.name, reply
+        ));
+    }
+    script.push_str("    esac\n\n");
+
+    // The subcommand is the first word that is neither a flag nor the value of one
+    let valued: Vec<String> = FLAGS
+        .iter()
+        .filter(|flag| flag.value.is_some())
+        .map(|flag| format!("--{}", flag.name))
+        .collect();
+    script.push_str("    local subcommand=\"\" positional=0 skip=0 word\n");
+    script.push_str("    for word in \"${COMP_WORDS[@]:1:COMP_CWORD-1}\"; do\n");
+    script.push_str("        if ((skip)); then skip=0; continue; fi\n");
+    script.push_str("        case \"$word\" in\n");
+    script.push_str(&format!("            {}) skip=1 ;;\n", valued.join("|")));
+    script.push_str("            -*) ;;\n");
+    script.push_str("            *)\n");
+    script.push_str("                if ((positional == 0)); then\n");
+    script.push_str("                    case \"$word\" in\n");
+    let names: Vec<&str> = SUBCOMMANDS
+        .iter()
+        .map(|subcommand| subcommand.name)
+        .collect();
+    script.push_str(&format!(
+        "                        {}) subcommand=\"$word\" ;;\n",
+        names.join("|")
+    ));
+    script.push_str("                    esac\n");
+    script.push_str("                fi\n");
+    script.push_str("                positional=$((positional + 1)) ;;\n");
+    script.push_str("        esac\n");
+    script.push_str("    done\n\n");
+
+    let mut flags: Vec<String> = Vec::new();
+    for flag in FLAGS.iter() {
+        if let Some(short) = flag.short {
+            flags.push(format!("-{}", short));
+        }
+        flags.push(format!("--{}", flag.name));
+    }
+    script.push_str(&format!("    local flags={}\n", quote(&flags.join(" "))));
+    script.push_str("    case \"$subcommand\" in\n");
+    for subcommand in SUBCOMMANDS.iter() {
+        let flags = subcommand_flags(subcommand);
+        if !flags.is_empty() {
+            script.push_str(&format!(
+                "        {}) flags=\"$flags {}\" ;;\n",
+                subcommand.name,
+                long_flags(flags.into_iter().map(|(name, _)| name))
+            ));
+        }
+    }
+    script.push_str("    esac\n\n");
+
+    script.push_str("    if [[ \"$cur\" == -* ]]; then\n");
+    script.push_str("        COMPREPLY=($(compgen -W \"$flags\" -- \"$cur\"))\n");
+    script.push_str("    elif [[ \"$subcommand\" == done && $positional == 1 ]]; then\n");
+    script.push_str(
+        "        COMPREPLY=($(compgen -W \"$(todo-rs list 2>/dev/null | sed -n 's/^ *\\([0-9]*\\)\\. .*/\\1/p')\" -- \"$cur\"))\n",
+    );
+    script.push_str("    elif [[ \"$subcommand\" == completions && $positional == 1 ]]; then\n");
+    script.push_str(&format!(
+        "        COMPREPLY=($(compgen -W {} -- \"$cur\"))\n",
+        quote(&Shell::NAMES.join(" "))
+    ));
+    script.push_str("    elif ((positional == 0)); then\n");
+    script.push_str(&format!(
+        "        COMPREPLY=($(compgen -W {} -- \"$cur\") $(compgen -f -- \"$cur\"))\n",
+        quote(&names.join(" "))
+    ));
+    script.push_str("    else\n");
+    script.push_str("        COMPREPLY=($(compgen -f -- \"$cur\"))\n");
+    script.push_str("    fi\n");
+    script.push_str("}\n\n");
+    script.push_str("complete -o filenames -F _todo_rs todo-rs\n");
+    script
+}
+
+fn zsh() -> String {
+    // The names of _describe can't have colons of their own
+    let describe =
+        |name: &str, help: &str| quote(&format!("{}:{}", name.replace(':', "\\:"), help));
+
+    let mut script = String::new();
+    script.push_str("#compdef todo-rs\n\n");
+    script.push_str("# zsh completion for todo-rs, generated by `todo-rs completions zsh`\n\n");
+    script.push_str("_todo_rs() {\n");
+
+    script.push_str("    case \"$words[CURRENT-1]\" in\n");
+    for flag in FLAGS.iter() {
+        let reply = match flag_value(flag) {
+            Value::Nothing => continue,
+            Value::Words(words) => format!("compadd -- {}", words.join(" ")),
+            Value::File => "_files".to_string(),
+        };
+        script.push_str(&format!("        --{}) {}; return ;;\n", flag.name, reply));
+    }
+    script.push_str("    esac\n\n");
+
+    let valued: Vec<String> = FLAGS
+        .iter()
+        .filter(|flag| flag.value.is_some())
+        .map(|flag| format!("--{}", flag.name))
+        .collect();
+    let names: Vec<&str> = SUBCOMMANDS
+        .iter()
+        .map(|subcommand| subcommand.name)
+        .collect();
+    script.push_str("    local subcommand=\"\" positional=0 skip=0 word\n");
+    script.push_str("    for word in \"${(@)words[2,CURRENT-1]}\"; do\n");
+    script.push_str("        if ((skip)); then skip=0; continue; fi\n");
+    script.push_str("        case \"$word\" in\n");
+    script.push_str(&format!("            {}) skip=1 ;;\n", valued.join("|")));
+    script.push_str("            -*) ;;\n");
+    script.push_str("            *)\n");
+    script.push_str(&format!(
+        "                if ((positional == 0)) && [[ \"$word\" == ({}) ]]; then\n",
+        names.join("|")
+    ));
+    script.push_str("                    subcommand=\"$word\"\n");
+    script.push_str("                fi\n");
+    script.push_str("                positional=$((positional + 1)) ;;\n");
+    script.push_str("        esac\n");
+    script.push_str("    done\n\n");
+
+    script.push_str("    local -a flags\n");
+    script.push_str("    flags=(\n");
+    for flag in FLAGS.iter() {
+        if let Some(short) = flag.short {
+            script.push_str(&format!(
+                "        {}\n",
+                describe(&format!("-{}", short), flag.help)
+            ));
+        }
+        script.push_str(&format!(
+            "        {}\n",
+            describe(&format!("--{}", flag.name), flag.help)
+        ));
+    }
+    script.push_str("    )\n");
+    script.push_str("    case \"$subcommand\" in\n");
+    for subcommand in SUBCOMMANDS.iter() {
+        let flags = subcommand_flags(subcommand);
+        if flags.is_empty() {
+            continue;
+        }
+        script.push_str(&format!("        {})\n", subcommand.name));
+        script.push_str("            flags+=(\n");
+        for (name, help) in flags {
+            script.push_str(&format!(
+                "                {}\n",
+                describe(&format!("--{}", name), &help)
+            ));
+        }
+        script.push_str("            ) ;;\n");
+    }
+    script.push_str("    esac\n\n");
+
+    script.push_str("    if [[ \"$PREFIX\" == -* ]]; then\n");
+    script.push_str("        _describe 'flag' flags\n");
+    script.push_str("    elif [[ \"$subcommand\" == done && $positional == 1 ]]; then\n");
+    script.push_str("        local -a items\n");
+    script.push_str(
+        "        items=(${(f)\"$(todo-rs list 2>/dev/null | sed -n 's/:/\\\\:/g; s/^ *\\([0-9]*\\)\\. /\\1:/p')\"})\n",
+    );
+    script.push_str("        _describe -V 'item' items\n");
+    script.push_str("    elif [[ \"$subcommand\" == completions && $positional == 1 ]]; then\n");
+    script.push_str(&format!("        compadd -- {}\n", Shell::NAMES.join(" ")));
+    script.push_str("    elif ((positional == 0)); then\n");
+    script.push_str("        local -a subcommands\n");
+    script.push_str("        subcommands=(\n");
+    for subcommand in SUBCOMMANDS.iter() {
+        script.push_str(&format!(
+            "            {}\n",
+            describe(subcommand.name, subcommand.help)
+        ));
+    }
+    script.push_str("        )\n");
+    script.push_str("        _describe 'subcommand' subcommands\n");
+    script.push_str("        _files\n");
+    script.push_str("    else\n");
+    script.push_str("        _files\n");
+    script.push_str("    fi\n");
+    script.push_str("}\n\n");
+    script.push_str("_todo_rs \"$@\"\n");
+    script
+}
+
+fn fish() -> String {
+    let mut script = String::new();
+    script.push_str("# fish completion for todo-rs, generated by `todo-rs completions fish`\n\n");
+
+    for subcommand in SUBCOMMANDS.iter() {
+        script.push_str(&format!(
+            "complete -c todo-rs -n __fish_use_subcommand -a {} -d {}\n",
+            subcommand.name,
+            fish_quote(subcommand.help)
+        ));
+    }
+    script.push('\n');
+
+    for flag in FLAGS.iter() {
+        let mut line = "complete -c todo-rs".to_string();
+        if let Some(short) = flag.short {
+            line.push_str(&format!(" -s {}", short));
+        }
+        line.push_str(&format!(" -l {}", flag.name));
+        match flag_value(flag) {
+            Value::Nothing => {}
+            Value::Words(words) => {
+                line.push_str(&format!(" -x -a {}", fish_quote(&words.join(" "))))
+            }
+            Value::File => line.push_str(" -r -F"),
+        }
+        line.push_str(&format!(" -d {}\n", fish_quote(flag.help)));
+        script.push_str(&line);
+    }
+    script.push('\n');
+
+    for subcommand in SUBCOMMANDS.iter() {
+        for (name, help) in subcommand_flags(subcommand) {
+            script.push_str(&format!(
+                "complete -c todo-rs -n '__fish_seen_subcommand_from {}' -l {} -d {}\n",
+                subcommand.name,
+                name,
+                fish_quote(&help)
+            ));
+        }
+    }
+    script.push_str(
+        "complete -c todo-rs -n '__fish_seen_subcommand_from done' -x -a '(todo-rs list 2>/dev/null | string replace -rf \"^ *(\\\\d+)\\\\. (.*)\" \"\\$1\"\\t\"\\$2\")'\n",
+    );
+    script.push_str(&format!(
+        "complete -c todo-rs -n '__fish_seen_subcommand_from completions' -x -a {}\n",
+        fish_quote(&Shell::NAMES.join(" "))
+    ));
+    script
+}
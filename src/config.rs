@@ -0,0 +1,182 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+
+// The sections the app knows about, the rest are typos
+const SECTIONS: &[&str] = &[
+    "theme", "ui", "file", "format", "hooks", "git", "caldav", "github", "control", "keys",
+    "script",
+];
+
+// A tiny subset of TOML that is just enough for the configuration of the app: `[section]` headers,
+// `key = value` pairs and `#` comments. Values are either "quoted strings" or bare words like
+// numbers and booleans, and we keep them all as strings leaving the interpretation to the users of
+// the config.
+#[derive(Default)]
+pub struct Config {
+    path: PathBuf,
+    entries: HashMap<(String, String), Entry>,
+}
+
+struct Entry {
+    value: String,
+    line: usize,
+}
+
+fn config_path() -> Option<PathBuf> {
+    let config_home = env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        // Windows
+        .or_else(|| env::var_os("APPDATA").map(PathBuf::from))?;
+    Some(config_home.join("todo-rs").join("config.toml"))
+}
+
+fn strip_comment(line: &str) -> &str {
+    let mut quoted = false;
+    let mut escaped = false;
+    for (index, c) in line.char_indices() {
+        match c {
+            '\\' if quoted && !escaped => {
+                escaped = true;
+                continue;
+            }
+            '"' if !escaped => quoted = !quoted,
+            '#' if !quoted => return &line[..index],
+            _ => {}
+        }
+        escaped = false;
+    }
+    line
+}
+
+fn parse_value(value: &str) -> Option<String> {
+    match value.strip_prefix('"') {
+        Some(rest) => {
+            let mut result = String::new();
+            let mut chars = rest.chars();
+            loop {
+                match chars.next()? {
+                    '"' => break,
+                    '\\' => match chars.next()? {
+                        'n' => result.push('\n'),
+                        't' => result.push('\t'),
+                        c => result.push(c),
+                    },
+                    c => result.push(c),
+                }
+            }
+            if chars.as_str().trim().is_empty() {
+                Some(result)
+            } else {
+                None
+            }
+        }
+        None if !value.is_empty() && !value.contains(char::is_whitespace) => {
+            Some(value.to_string())
+        }
+        None => None,
+    }
+}
+
+impl Config {
+    // A missing config file is not an error, it just means that everything is default
+    pub fn load() -> Result<Self, String> {
+        let path = match config_path() {
+            Some(path) => path,
+            None => return Ok(Self::default()),
+        };
+        match fs::read_to_string(&path) {
+            Ok(source) => Self::parse(path, &source),
+            Err(error) if error.kind() == ErrorKind::NotFound => Ok(Self {
+                path,
+                ..Self::default()
+            }),
+            Err(error) => Err(format!(
+                "{}: ERROR: could not read config: {}",
+                path.display(),
+                error
+            )),
+        }
+    }
+
+    pub fn parse(path: PathBuf, source: &str) -> Result<Self, String> {
+        let mut entries = HashMap::new();
+        let mut section = String::new();
+        for (index, line) in source.lines().enumerate() {
+            let line = strip_comment(line).trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let error =
+                |message: &str| format!("{}:{}: ERROR: {}", path.display(), index + 1, message);
+            if let Some(name) = line.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
+                section = name.trim().to_string();
+                if !SECTIONS.contains(&section.as_str()) {
+                    return Err(error(&format!(
+                        "unknown section `[{}]`, expected one of [{}]",
+                        section,
+                        SECTIONS.join("], [")
+                    )));
+                }
+                continue;
+            }
+
+            let (key, value) = match line.split_once('=') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => return Err(error("expected `key = value` or `[section]`")),
+            };
+            if key.is_empty() {
+                return Err(error("missing key"));
+            }
+            let value = parse_value(value).ok_or_else(|| error("ill-formed value"))?;
+            entries.insert(
+                (section.clone(), key.to_string()),
+                Entry {
+                    value,
+                    line: index + 1,
+                },
+            );
+        }
+        Ok(Self { path, entries })
+    }
+
+    pub fn get(&self, section: &str, key: &str) -> Option<&str> {
+        self.entries
+            .get(&(section.to_string(), key.to_string()))
+            .map(|entry| entry.value.as_str())
+    }
+
+    // All the keys and values of the section in the order they appear in the file
+    pub fn section(&self, section: &str) -> Vec<(&str, &str)> {
+        let mut entries: Vec<(&str, &Entry)> = self
+            .entries
+            .iter()
+            .filter(|((entry_section, _), _)| entry_section == section)
+            .map(|((_, key), entry)| (key.as_str(), entry))
+            .collect();
+        entries.sort_by_key(|(_, entry)| entry.line);
+        entries
+            .into_iter()
+            .map(|(key, entry)| (key, entry.value.as_str()))
+            .collect()
+    }
+
+    // Where the config lives, for the files that go next to it
+    #[cfg(feature = "scripting")]
+    pub fn dir(&self) -> Option<&std::path::Path> {
+        self.path.parent()
+    }
+
+    // Formats an error about the value of the key, pointing at where it's located in the file
+    pub fn error(&self, section: &str, key: &str, message: &str) -> String {
+        match self.entries.get(&(section.to_string(), key.to_string())) {
+            Some(entry) => format!("{}:{}: ERROR: {}", self.path.display(), entry.line, message),
+            None => format!("{}: ERROR: {}", self.path.display(), message),
+        }
+    }
+}
@@ -0,0 +1,227 @@
+use std::env;
+use std::fs;
+use std::io::{self, ErrorKind, Read, Write};
+use std::os::unix::io::AsRawFd;
+#[cfg(not(feature = "crossterm"))]
+use std::os::unix::io::RawFd;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::config::Config;
+
+// How long a client has to say something more before it's hung up on. Long enough for somebody
+// typing the commands into socat, or a script that does something else between them.
+const IDLE_TIMEOUT: Duration = Duration::from_secs(30);
+
+// How long an answer may take to go out to a client that doesn't read it
+const WRITE_TIMEOUT: Duration = Duration::from_millis(100);
+
+// How often the clients are read while some are connected, nothing wakes the app up for them
+pub const CLIENT_CHECK_INTERVAL: Duration = Duration::from_millis(20);
+
+// The lines taken from all the clients together at a time, the rest wait for the next frame
+const MAX_LINES: usize = 64;
+
+// A client that goes on longer than this without a newline is hung up on
+const MAX_LINE_LENGTH: usize = 64 * 1024;
+
+// The listener, for the main loop to wake up on the new connections like it does on the input
+static LISTENER: AtomicI32 = AtomicI32::new(-1);
+
+// The running app takes the commands from the scripts and the editors on a unix socket, a line
+// each, and answers each of them on the same connection:
+//
+//     $ echo 'add call mom' | nc -U "$XDG_RUNTIME_DIR/todo-rs.sock"
+//
+// Only one app at a time has the socket, the ones started after it go without.
+pub struct Control {
+    listener: UnixListener,
+    path: PathBuf,
+    clients: Vec<Client>,
+}
+
+// A connection along with what it sent that's not taken yet
+struct Client {
+    stream: UnixStream,
+    buffer: Vec<u8>,
+    // Said it's done, or broke
+    closed: bool,
+    last_read: Instant,
+}
+
+impl Client {
+    // Whatever there is to read right now, never blocks
+    fn read(&mut self, now: Instant) {
+        let mut chunk = [0; 4096];
+        while !self.closed && self.buffer.len() <= MAX_LINE_LENGTH {
+            match self.stream.read(&mut chunk) {
+                Ok(0) => self.closed = true,
+                Ok(n) => {
+                    self.buffer.extend_from_slice(&chunk[..n]);
+                    self.last_read = now;
+                }
+                Err(error) if error.kind() == ErrorKind::WouldBlock => break,
+                Err(error) if error.kind() == ErrorKind::Interrupted => {}
+                Err(_) => self.closed = true,
+            }
+        }
+    }
+
+    // The next whole line, or the last one without a newline once the client is done
+    fn line(&mut self) -> Option<String> {
+        let end = match self.buffer.iter().position(|&byte| byte == b'\n') {
+            Some(newline) => newline + 1,
+            None if self.closed && !self.buffer.is_empty() => self.buffer.len(),
+            None => return None,
+        };
+        let line: Vec<u8> = self.buffer.drain(..end).collect();
+        Some(String::from_utf8_lossy(&line).trim().to_string())
+    }
+
+    fn is_done(&self, now: Instant) -> bool {
+        if self.buffer.contains(&b'\n') {
+            return false;
+        }
+        self.closed && self.buffer.is_empty()
+            || self.buffer.len() > MAX_LINE_LENGTH
+            || now.duration_since(self.last_read) >= IDLE_TIMEOUT
+    }
+}
+
+// A line from a client along with the way to answer it
+pub struct Request {
+    pub line: String,
+    stream: UnixStream,
+}
+
+impl Request {
+    // A closed connection is the client's business. The connection is read without blocking, the
+    // answer is written with it for a little while so it goes out whole.
+    pub fn reply(&mut self, text: &str) {
+        let _ = self
+            .stream
+            .set_nonblocking(false)
+            .and_then(|()| self.stream.set_write_timeout(Some(WRITE_TIMEOUT)))
+            .and_then(|()| writeln!(self.stream, "{}", text));
+        let _ = self.stream.set_nonblocking(true);
+    }
+}
+
+// $XDG_RUNTIME_DIR is only for the user, /tmp is for everybody so the user id goes into the name
+fn default_path() -> PathBuf {
+    match env::var_os("XDG_RUNTIME_DIR").filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir).join("todo-rs.sock"),
+        None => env::temp_dir().join(format!("todo-rs-{}.sock", unsafe { libc::getuid() })),
+    }
+}
+
+// Becomes readable when a client connects. None without the socket.
+#[cfg(not(feature = "crossterm"))]
+pub fn wakeup_fd() -> Option<RawFd> {
+    let fd = LISTENER.load(Ordering::Relaxed);
+    if fd >= 0 {
+        Some(fd)
+    } else {
+        None
+    }
+}
+
+impl Control {
+    // By `socket` of the [control] section: the path, `off` or the default one when not set. Some
+    // other app having the socket already is not an error, it's what the message is for.
+    pub fn from_config(config: &Config) -> Result<(Option<Self>, Option<String>), String> {
+        let path = match config.get("control", "socket") {
+            Some("off") => return Ok((None, None)),
+            None | Some("") => default_path(),
+            Some(path) => PathBuf::from(path),
+        };
+        match Self::bind(path.clone()) {
+            Ok(control) => Ok((Some(control), None)),
+            Err(error) if error.kind() == ErrorKind::AddrInUse => Ok((
+                None,
+                Some(format!(
+                    "Another todo-rs has the control socket {}",
+                    path.display()
+                )),
+            )),
+            Err(error) => Err(config.error(
+                "control",
+                "socket",
+                &format!("could not listen on {}: {}", path.display(), error),
+            )),
+        }
+    }
+
+    fn bind(path: PathBuf) -> io::Result<Self> {
+        if path.exists() {
+            // Left behind by an app that didn't get to clean up after itself, like when it was
+            // killed, nobody answers on it
+            if UnixStream::connect(&path).is_ok() {
+                return Err(io::Error::new(ErrorKind::AddrInUse, "the socket is in use"));
+            }
+            fs::remove_file(&path)?;
+        }
+        // The socket is only for the user from the start, a chmod after the bind would leave the
+        // others a moment to connect
+        let umask = unsafe { libc::umask(0o177) };
+        let listener = UnixListener::bind(&path);
+        unsafe { libc::umask(umask) };
+        let listener = listener?;
+        listener.set_nonblocking(true)?;
+        LISTENER.store(listener.as_raw_fd(), Ordering::Relaxed);
+        Ok(Self {
+            listener,
+            path,
+            clients: Vec::new(),
+        })
+    }
+
+    // Some clients are still connected, poll() has to come back for them soon
+    pub fn has_clients(&self) -> bool {
+        !self.clients.is_empty()
+    }
+
+    // The lines the clients sent since the last time, up to MAX_LINES of them. Never blocks, a
+    // client that is slow to say what it wants only holds up itself.
+    pub fn accept(&mut self) -> Vec<Request> {
+        let now = Instant::now();
+        while let Ok((stream, _)) = self.listener.accept() {
+            if stream.set_nonblocking(true).is_ok() {
+                self.clients.push(Client {
+                    stream,
+                    buffer: Vec::new(),
+                    closed: false,
+                    last_read: now,
+                });
+            }
+        }
+        let mut requests = Vec::new();
+        for client in &mut self.clients {
+            client.read(now);
+            while requests.len() < MAX_LINES {
+                let line = match client.line() {
+                    Some(line) => line,
+                    None => break,
+                };
+                if line.is_empty() {
+                    continue;
+                }
+                match client.stream.try_clone() {
+                    Ok(stream) => requests.push(Request { line, stream }),
+                    Err(_) => client.closed = true,
+                }
+            }
+        }
+        self.clients.retain(|client| !client.is_done(now));
+        requests
+    }
+}
+
+impl Drop for Control {
+    fn drop(&mut self) {
+        LISTENER.store(-1, Ordering::Relaxed);
+        let _ = fs::remove_file(&self.path);
+    }
+}
@@ -0,0 +1,400 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, ErrorKind, Read, Write};
+#[cfg(unix)]
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+
+const AGE_MAGIC: &[u8] = b"age-encryption.org/v1";
+const AGE_ARMOR_MAGIC: &[u8] = b"-----BEGIN AGE ENCRYPTED FILE-----";
+const GPG_ARMOR_MAGIC: &[u8] = b"-----BEGIN PGP MESSAGE-----";
+
+/// The tools the files are encrypted with
+#[derive(Clone, Copy, PartialEq)]
+pub enum Kind {
+    /// OpenPGP, by .gpg, .pgp and .asc
+    Gpg,
+    /// <https://age-encryption.org>, by .age
+    Age,
+}
+
+/// How an encrypted file is decrypted and encrypted back. We don't do any cryptography ourselves,
+/// the gpg and age tools do it for us.
+pub enum Cipher {
+    /// Symmetric encryption with a passphrase
+    Gpg {
+        /// Given to gpg on its stdin, never in the arguments
+        passphrase: String,
+        /// Whether the file is ASCII armored, like the .asc ones
+        armor: bool,
+    },
+    /// Encryption to the recipient of an age identity file
+    Age {
+        /// The file with the private key, whose recipient the file is encrypted to
+        identity: PathBuf,
+        /// Whether the file is ASCII armored
+        armor: bool,
+    },
+}
+
+/// Tells the encrypted files by their extension, or by their first bytes if they have none of ours
+pub fn detect(file_path: &str) -> Option<Kind> {
+    match Path::new(file_path)
+        .extension()
+        .and_then(|ext| ext.to_str())
+    {
+        Some("gpg") | Some("pgp") | Some("asc") => return Some(Kind::Gpg),
+        Some("age") => return Some(Kind::Age),
+        _ => {}
+    }
+    let mut head = Vec::new();
+    File::open(file_path)
+        .and_then(|file| {
+            file.take(AGE_ARMOR_MAGIC.len() as u64)
+                .read_to_end(&mut head)
+        })
+        .ok()?;
+    if head.starts_with(AGE_MAGIC) || head.starts_with(AGE_ARMOR_MAGIC) {
+        Some(Kind::Age)
+    } else if head.starts_with(GPG_ARMOR_MAGIC) || session_key_packet(&head) {
+        Some(Kind::Gpg)
+    } else {
+        None
+    }
+}
+
+// The first packet of a passphrase encrypted OpenPGP message is a Symmetric-Key Encrypted Session
+// Key packet (tag 3) and of a key encrypted one a Public-Key Encrypted Session Key packet (tag 1),
+// in either the old or the new packet format. The whole header is checked along with the version
+// of the packet, the first byte alone is also how é and the rest of U+00C0-U+00FF start in UTF-8.
+fn session_key_packet(head: &[u8]) -> bool {
+    let number = |bytes: &[u8]| {
+        bytes
+            .iter()
+            .fold(0usize, |number, byte| number << 8 | *byte as usize)
+    };
+    let (tag, length, body) = match head {
+        // 10TTTTLL, with the length in 1, 2 or 4 bytes by LL
+        [first, rest @ ..] if first & 0xc0 == 0x80 => {
+            let size = match first & 0x03 {
+                0 => 1,
+                1 => 2,
+                2 => 4,
+                // Of an indeterminate length, which is only for the data packets
+                _ => return false,
+            };
+            if rest.len() < size {
+                return false;
+            }
+            ((first >> 2) & 0x0f, number(&rest[..size]), &rest[size..])
+        }
+        // 11TTTTTT, with the length in 1, 2 or 5 bytes by the first one of them
+        [first, second, rest @ ..] if first & 0xc0 == 0xc0 => match (second, rest) {
+            (0..=191, _) => (first & 0x3f, *second as usize, rest),
+            (192..=223, [third, rest @ ..]) => {
+                let length = ((*second as usize - 192) << 8) + *third as usize + 192;
+                (first & 0x3f, length, rest)
+            }
+            (255, [a, b, c, d, rest @ ..]) => (first & 0x3f, number(&[*a, *b, *c, *d]), rest),
+            // Partial lengths are only for the data packets too
+            _ => return false,
+        },
+        _ => return false,
+    };
+    match (tag, body.first()) {
+        (1, Some(3 | 6)) => length >= 4,
+        (3, Some(4..=6)) => length >= 4,
+        _ => false,
+    }
+}
+
+fn armored(file_path: &str, magic: &[u8]) -> bool {
+    let mut head = Vec::new();
+    match File::open(file_path)
+        .and_then(|file| file.take(magic.len() as u64).read_to_end(&mut head))
+    {
+        Ok(_) => head.starts_with(magic),
+        // New files are armored if their extension says so
+        Err(_) => file_path.ends_with(".asc"),
+    }
+}
+
+impl Cipher {
+    /// Gets whatever is needed to open the file, asking the user for the passphrase on the
+    /// terminal. Returns None for the files that are not encrypted.
+    pub fn for_file(file_path: &str, age_identity: Option<&Path>) -> io::Result<Option<Self>> {
+        match detect(file_path) {
+            None => Ok(None),
+            Some(Kind::Gpg) => {
+                let passphrase = ask_passphrase(&format!("Passphrase for {}: ", file_path))?;
+                // A typo in the passphrase of a new file would lock its owner out of it for good
+                if !Path::new(file_path).exists()
+                    && ask_passphrase("Repeat the passphrase: ")? != passphrase
+                {
+                    return Err(io::Error::new(
+                        ErrorKind::InvalidInput,
+                        "the passphrases do not match",
+                    ));
+                }
+                Ok(Some(Cipher::Gpg {
+                    passphrase,
+                    armor: armored(file_path, GPG_ARMOR_MAGIC),
+                }))
+            }
+            Some(Kind::Age) => match age_identity {
+                Some(identity) => Ok(Some(Cipher::Age {
+                    identity: identity.to_path_buf(),
+                    armor: armored(file_path, AGE_ARMOR_MAGIC),
+                })),
+                None => Err(io::Error::new(
+                    ErrorKind::InvalidInput,
+                    format!(
+                        "{} is encrypted with age but no `age_identity` is set in [file]",
+                        file_path
+                    ),
+                )),
+            },
+        }
+    }
+
+    /// The contents of the file from what is on disk
+    pub fn decrypt(&self, data: &[u8]) -> io::Result<Vec<u8>> {
+        match self {
+            Cipher::Gpg { passphrase, .. } => gpg(&["--decrypt"], passphrase, data),
+            Cipher::Age { identity, .. } => {
+                let identity = identity.to_string_lossy();
+                run(
+                    &["age", "--decrypt", "--identity", &identity],
+                    data.to_vec(),
+                )
+            }
+        }
+    }
+
+    /// What goes on disk for the contents of the file, armored if the file was
+    pub fn encrypt(&self, data: &[u8]) -> io::Result<Vec<u8>> {
+        match self {
+            Cipher::Gpg { passphrase, armor } => {
+                let mut args = vec!["--symmetric"];
+                if *armor {
+                    args.push("--armor");
+                }
+                gpg(&args, passphrase, data)
+            }
+            Cipher::Age { identity, armor } => {
+                let identity = identity.to_string_lossy();
+                let mut args = vec!["age", "--encrypt", "--identity", &identity];
+                if *armor {
+                    args.push("--armor");
+                }
+                run(&args, data.to_vec())
+            }
+        }
+    }
+}
+
+// gpg reads the passphrase as the first line of its input and the data as the rest of it, so the
+// passphrase never shows up in the arguments or in a file
+fn gpg(args: &[&str], passphrase: &str, data: &[u8]) -> io::Result<Vec<u8>> {
+    let mut command = vec![
+        "gpg",
+        "--batch",
+        "--yes",
+        "--quiet",
+        "--pinentry-mode",
+        "loopback",
+        "--passphrase-fd",
+        "0",
+        "--output",
+        "-",
+    ];
+    command.extend_from_slice(args);
+    let mut input = Vec::with_capacity(passphrase.len() + 1 + data.len());
+    input.extend_from_slice(passphrase.as_bytes());
+    input.push(b'\n');
+    input.extend_from_slice(data);
+    run(&command, input)
+}
+
+fn run(command: &[&str], input: Vec<u8>) -> io::Result<Vec<u8>> {
+    let mut child = Command::new(command[0])
+        .args(&command[1..])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|error| io::Error::new(error.kind(), format!("{}: {}", command[0], error)))?;
+    // Written from another thread, otherwise a tool that fills up its stdout before reading all of
+    // its stdin would wait for us as we wait for it
+    let mut stdin = child.stdin.take().expect("stdin of the tool is piped");
+    let writer = thread::spawn(move || stdin.write_all(&input));
+    let output = child.wait_with_output()?;
+    writer.join().expect("the writer thread does not panic")?;
+    if output.status.success() {
+        Ok(output.stdout)
+    } else {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        Err(io::Error::other(format!(
+            "{} failed: {}",
+            command[0],
+            stderr.trim()
+        )))
+    }
+}
+
+/// Asks on the terminal rather than on stdin, which may be a pipe. Every typed character shows up
+/// as a `*`, so it's clear that the keys get through without showing the passphrase itself. Ctrl+C
+/// and Ctrl+\ come as the keys too rather than the signals, which would kill the app with the
+/// terminal left without echo, and cancel the question.
+#[cfg(unix)]
+pub fn ask_passphrase(prompt: &str) -> io::Result<String> {
+    let mut tty = OpenOptions::new().read(true).write(true).open("/dev/tty")?;
+    tty.write_all(prompt.as_bytes())?;
+    tty.flush()?;
+
+    let tty_fd = tty.as_raw_fd();
+    let mut termios: libc::termios = unsafe { std::mem::zeroed() };
+    let raw = unsafe { libc::tcgetattr(tty_fd, &mut termios) } == 0;
+    if raw {
+        let mut masked = termios;
+        masked.c_lflag &= !(libc::ECHO | libc::ICANON | libc::ISIG);
+        masked.c_cc[libc::VMIN] = 1;
+        masked.c_cc[libc::VTIME] = 0;
+        unsafe { libc::tcsetattr(tty_fd, libc::TCSANOW, &masked) };
+    }
+    let result = read_masked(&mut &tty, &mut &tty);
+    if raw {
+        unsafe { libc::tcsetattr(tty_fd, libc::TCSANOW, &termios) };
+    }
+    let _ = tty.write_all(b"\n");
+    result
+}
+
+/// Windows has neither /dev/tty nor termios. The console is opened by its name instead and the keys
+/// come from _getwch() of the C runtime, which doesn't echo them.
+#[cfg(windows)]
+pub fn ask_passphrase(prompt: &str) -> io::Result<String> {
+    let mut console = OpenOptions::new().write(true).open("CONOUT$")?;
+    console.write_all(prompt.as_bytes())?;
+    let result = read_masked(&mut ConsoleKeys::default(), &mut console);
+    let _ = console.write_all(b"\r\n");
+    result
+}
+
+// The keys typed into the console as UTF-8
+#[cfg(windows)]
+#[derive(Default)]
+struct ConsoleKeys {
+    pending: Vec<u8>,
+}
+
+#[cfg(windows)]
+impl Read for ConsoleKeys {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        extern "C" {
+            fn _getwch() -> u16;
+        }
+        if self.pending.is_empty() {
+            let mut units = vec![unsafe { _getwch() }];
+            // The characters outside of the BMP come as surrogate pairs
+            if (0xd800..0xdc00).contains(&units[0]) {
+                units.push(unsafe { _getwch() });
+            }
+            for c in char::decode_utf16(units) {
+                let c = c.unwrap_or(char::REPLACEMENT_CHARACTER);
+                self.pending.extend_from_slice(c.to_string().as_bytes());
+            }
+        }
+        let count = buffer.len().min(self.pending.len());
+        buffer[..count].copy_from_slice(&self.pending[..count]);
+        self.pending.drain(..count);
+        Ok(count)
+    }
+}
+
+fn read_masked(keys: &mut impl Read, tty: &mut impl Write) -> io::Result<String> {
+    let mut passphrase = Vec::new();
+    let mut byte = [0; 1];
+    loop {
+        if keys.read(&mut byte)? == 0 {
+            break;
+        }
+        match byte[0] {
+            b'\r' | b'\n' => break,
+            // Ctrl+C and Ctrl+\
+            0x03 | 0x1c => {
+                return Err(io::Error::new(
+                    ErrorKind::Interrupted,
+                    "no passphrase was given",
+                ))
+            }
+            // Ctrl+Z, there's no stopping in the middle of the question
+            0x1a => {}
+            // Backspace and Delete erase the whole last character, however many bytes it takes
+            0x08 | 0x7f => {
+                while let Some(last) = passphrase.pop() {
+                    if last & 0xc0 != 0x80 {
+                        tty.write_all(b"\x08 \x08")?;
+                        break;
+                    }
+                }
+            }
+            byte => {
+                // The continuation bytes of UTF-8 don't start new characters
+                if byte & 0xc0 != 0x80 {
+                    tty.write_all(b"*")?;
+                }
+                passphrase.push(byte);
+            }
+        }
+    }
+    String::from_utf8(passphrase)
+        .map_err(|_| io::Error::new(ErrorKind::InvalidData, "the passphrase is not valid UTF-8"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::env;
+    use std::fs;
+    use std::process;
+
+    #[test]
+    fn a_file_that_starts_with_an_accent_opens_as_plain_text() {
+        let path = env::temp_dir().join(format!("todo-rs-crypt-test-{}-accent", process::id()));
+        fs::write(&path, "Épicerie\n\n- [ ] buy milk\n").unwrap();
+        let file_path = path.to_string_lossy();
+        let kind = detect(&file_path);
+        let cipher = Cipher::for_file(&file_path, None);
+        fs::remove_file(&path).unwrap();
+        assert!(kind.is_none());
+        assert!(matches!(cipher, Ok(None)));
+    }
+
+    #[test]
+    fn the_session_key_packets_are_told_by_their_headers() {
+        // As gpg writes them, in the new and the old format
+        assert!(session_key_packet(&[0xc3, 0x0d, 0x04, 0x09, 0x03, 0x08]));
+        assert!(session_key_packet(&[0x8c, 0x0d, 0x04, 0x09, 0x03, 0x08]));
+        assert!(session_key_packet(&[0x85, 0x01, 0x0c, 0x03, 0x12, 0x34]));
+        assert!(!session_key_packet("Übung".as_bytes()));
+        assert!(!session_key_packet("été".as_bytes()));
+        assert!(!session_key_packet("Á".as_bytes()));
+        assert!(!session_key_packet(&[0xc3]));
+    }
+
+    #[test]
+    fn an_encrypted_file_is_told_without_its_extension() {
+        let cipher = Cipher::Gpg {
+            passphrase: "correct horse".to_string(),
+            armor: false,
+        };
+        let path = env::temp_dir().join(format!("todo-rs-crypt-test-{}-binary", process::id()));
+        fs::write(&path, cipher.encrypt(b"TODO: buy milk\n").unwrap()).unwrap();
+        let kind = detect(&path.to_string_lossy());
+        fs::remove_file(&path).unwrap();
+        assert!(kind == Some(Kind::Gpg));
+    }
+}
@@ -0,0 +1,48 @@
+use crate::{item_meta, item_tags, meta_word, Status};
+use std::borrow::Cow;
+
+// For the spreadsheets, one row per item with the header naming the columns
+const HEADER: &[&str] = &[
+    "status",
+    "title",
+    "tags",
+    "created",
+    "completed",
+    "priority",
+];
+
+// Quoted only if it has to be, with the quotes inside doubled
+fn field(text: &str) -> Cow<'_, str> {
+    if text.contains([',', '"', '\n', '\r']) {
+        Cow::Owned(format!("\"{}\"", text.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(text)
+    }
+}
+
+/// A row per item under a header row, for the spreadsheets
+pub fn export(todos: &[String], dones: &[String]) -> String {
+    let mut out = HEADER.join(",");
+    out.push('\n');
+    for (status, list) in [(Status::Todo, todos), (Status::Done, dones)].iter() {
+        for title in list.iter() {
+            let words: Vec<&str> = title
+                .split(' ')
+                .filter(|word| meta_word(word).is_none())
+                .collect();
+            let tags: Vec<&str> = item_tags(title).collect();
+            let row = [
+                status.name().to_lowercase(),
+                words.join(" "),
+                tags.join(" "),
+                item_meta(title, "created").unwrap_or("").to_string(),
+                item_meta(title, "done").unwrap_or("").to_string(),
+                item_meta(title, "prio").unwrap_or("").to_string(),
+            ];
+            let row: Vec<Cow<str>> = row.iter().map(|text| field(text)).collect();
+            out.push_str(&row.join(","));
+            out.push('\n');
+        }
+    }
+    out
+}
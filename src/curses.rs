@@ -0,0 +1,673 @@
+// The part of the ncurses API the app uses, on top of crossterm. Just enough of it to draw the
+// frames and read the keys the way ncurses does, so the rest of the app can't tell the difference.
+#![allow(non_camel_case_types, non_snake_case, clippy::upper_case_acronyms)]
+
+use crate::render::{PASTE_BEGIN, PASTE_END};
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{
+    self, DisableBracketedPaste, EnableBracketedPaste, Event, KeyCode, KeyEvent, KeyEventKind,
+    KeyModifiers,
+};
+use crossterm::style::SetForegroundColor;
+use crossterm::style::{Attribute, Color, Print, SetAttribute, SetBackgroundColor};
+use crossterm::terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{execute, queue};
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::fs::OpenOptions;
+use std::io::{self, BufWriter, Write};
+use std::time::{Duration, Instant};
+
+pub type chtype = u32;
+pub type attr_t = chtype;
+
+#[derive(Clone, Copy)]
+pub struct WINDOW;
+
+pub const ERR: i32 = -1;
+pub const OK: i32 = 0;
+
+pub const COLOR_BLACK: i16 = 0;
+pub const COLOR_RED: i16 = 1;
+pub const COLOR_GREEN: i16 = 2;
+pub const COLOR_YELLOW: i16 = 3;
+pub const COLOR_BLUE: i16 = 4;
+pub const COLOR_MAGENTA: i16 = 5;
+pub const COLOR_CYAN: i16 = 6;
+pub const COLOR_WHITE: i16 = 7;
+
+// The same bits ncurses uses
+const A_COLOR: attr_t = 0xff << 8;
+const DIM: attr_t = 1 << 20;
+const BOLD: attr_t = 1 << 21;
+
+pub mod constants {
+    pub const KEY_DOWN: i32 = 0o402;
+    pub const KEY_UP: i32 = 0o403;
+    pub const KEY_LEFT: i32 = 0o404;
+    pub const KEY_RIGHT: i32 = 0o405;
+    pub const KEY_HOME: i32 = 0o406;
+    pub const KEY_BACKSPACE: i32 = 0o407;
+    pub const KEY_F0: i32 = 0o410;
+    pub const KEY_DC: i32 = 0o512;
+    pub const KEY_IC: i32 = 0o513;
+    pub const KEY_SF: i32 = 0o520;
+    pub const KEY_SR: i32 = 0o521;
+    pub const KEY_NPAGE: i32 = 0o522;
+    pub const KEY_PPAGE: i32 = 0o523;
+    pub const KEY_BTAB: i32 = 0o541;
+    pub const KEY_END: i32 = 0o550;
+    pub const KEY_RESIZE: i32 = 0o632;
+}
+
+pub use self::constants::*;
+
+pub enum CURSOR_VISIBILITY {
+    CURSOR_INVISIBLE,
+    CURSOR_VISIBLE,
+}
+
+pub enum LcCategory {
+    all,
+}
+
+const ESCAPE: i32 = 27;
+
+#[derive(Clone, PartialEq)]
+struct Cell {
+    // The character with the combining ones that follow it. Empty for the second column of the
+    // wide characters.
+    text: String,
+    attrs: attr_t,
+}
+
+struct Screen {
+    out: Box<dyn Write>,
+    lines: i32,
+    cols: i32,
+    // What the app draws into
+    cells: Vec<Cell>,
+    // What is on the terminal right now. Empty when it has to be redrawn from scratch.
+    shown: Vec<Cell>,
+    y: i32,
+    x: i32,
+    attrs: attr_t,
+    background: attr_t,
+    pairs: HashMap<i16, (i16, i16)>,
+    // The colors redefined by init_color()
+    colors: HashMap<i16, (u8, u8, u8)>,
+    // The keys read from the terminal but not from getch() yet, as ncurses codes and bytes
+    input: VecDeque<i32>,
+    // How long getch() waits for the input, forever if None
+    delay: Option<Duration>,
+    cursor_visible: bool,
+    suspended: bool,
+}
+
+thread_local! {
+    static SCREEN: RefCell<Option<Screen>> = const { RefCell::new(None) };
+}
+
+fn with<R>(f: impl FnOnce(&mut Screen) -> R) -> R {
+    SCREEN.with(|screen| f(screen.borrow_mut().get_or_insert_with(Screen::new)))
+}
+
+// The terminal itself rather than stdout, which is where the lists go with --stdout
+fn open_terminal() -> Box<dyn Write> {
+    let path = if cfg!(windows) { "CONOUT$" } else { "/dev/tty" };
+    match OpenOptions::new().read(true).write(true).open(path) {
+        Ok(file) => Box::new(BufWriter::new(file)),
+        Err(_) => Box::new(io::stdout()),
+    }
+}
+
+fn blank(attrs: attr_t) -> Cell {
+    Cell {
+        text: " ".to_string(),
+        attrs,
+    }
+}
+
+fn pair_of(attrs: attr_t) -> i16 {
+    ((attrs & A_COLOR) >> 8) as i16
+}
+
+impl Screen {
+    fn new() -> Self {
+        let (cols, lines) = terminal::size().unwrap_or((80, 24));
+        let mut screen = Self {
+            out: open_terminal(),
+            lines: 0,
+            cols: 0,
+            cells: Vec::new(),
+            shown: Vec::new(),
+            y: 0,
+            x: 0,
+            attrs: 0,
+            background: 0,
+            pairs: HashMap::new(),
+            colors: HashMap::new(),
+            input: VecDeque::new(),
+            delay: None,
+            cursor_visible: true,
+            suspended: true,
+        };
+        screen.resize(lines as i32, cols as i32);
+        screen
+    }
+
+    fn resize(&mut self, lines: i32, cols: i32) {
+        self.lines = lines;
+        self.cols = cols;
+        self.cells = vec![blank(self.background); (lines * cols) as usize];
+        self.shown.clear();
+    }
+
+    fn index(&self, y: i32, x: i32) -> Option<usize> {
+        if (0..self.lines).contains(&y) && (0..self.cols).contains(&x) {
+            Some((y * self.cols + x) as usize)
+        } else {
+            None
+        }
+    }
+
+    fn resume(&mut self) -> io::Result<()> {
+        terminal::enable_raw_mode()?;
+        execute!(self.out, EnterAlternateScreen)?;
+        if !self.cursor_visible {
+            execute!(self.out, Hide)?;
+        }
+        self.suspended = false;
+        self.shown.clear();
+        Ok(())
+    }
+
+    fn suspend(&mut self) -> io::Result<()> {
+        self.suspended = true;
+        leave(&mut self.out)
+    }
+
+    fn put(&mut self, c: char) {
+        let width = crate::ui::char_width(c) as i32;
+        // The combining characters go on top of the one before them
+        if width == 0 {
+            let mut x = self.x - 1;
+            while let Some(index) = self.index(self.y, x) {
+                if !self.cells[index].text.is_empty() {
+                    self.cells[index].text.push(c);
+                    break;
+                }
+                x -= 1;
+            }
+            return;
+        }
+        let attrs = if pair_of(self.attrs) == 0 {
+            self.attrs | (self.background & A_COLOR)
+        } else {
+            self.attrs
+        };
+        if let (Some(first), Some(last)) = (
+            self.index(self.y, self.x),
+            self.index(self.y, self.x + width - 1),
+        ) {
+            // Half of a wide character is no character at all
+            if self.cells[first].text.is_empty() && first > 0 {
+                self.cells[first - 1] = blank(attrs);
+            }
+            if self
+                .cells
+                .get(last + 1)
+                .is_some_and(|cell| cell.text.is_empty())
+            {
+                self.cells[last + 1] = blank(attrs);
+            }
+            self.cells[first] = Cell {
+                text: c.to_string(),
+                attrs,
+            };
+            for cell in &mut self.cells[first + 1..=last] {
+                *cell = Cell {
+                    text: String::new(),
+                    attrs,
+                };
+            }
+        }
+        self.x += width;
+    }
+
+    fn set_style(&mut self, attrs: attr_t) -> io::Result<()> {
+        let (fg, bg) = self.pairs.get(&pair_of(attrs)).copied().unwrap_or((-1, -1));
+        let (fg, bg) = (self.color(fg), self.color(bg));
+        queue!(self.out, SetAttribute(Attribute::Reset))?;
+        if attrs & BOLD != 0 {
+            queue!(self.out, SetAttribute(Attribute::Bold))?;
+        }
+        if attrs & DIM != 0 {
+            queue!(self.out, SetAttribute(Attribute::Dim))?;
+        }
+        queue!(self.out, SetForegroundColor(fg), SetBackgroundColor(bg))
+    }
+
+    fn color(&self, color: i16) -> Color {
+        match self.colors.get(&color) {
+            Some(&(r, g, b)) => Color::Rgb { r, g, b },
+            None if color < 0 => Color::Reset,
+            None => Color::AnsiValue(color as u8),
+        }
+    }
+
+    fn refresh(&mut self) -> io::Result<()> {
+        if self.suspended {
+            self.resume()?;
+        }
+        if self.shown.len() != self.cells.len() {
+            queue!(
+                self.out,
+                SetAttribute(Attribute::Reset),
+                Clear(ClearType::All)
+            )?;
+            self.shown = vec![blank(0); self.cells.len()];
+        }
+        let mut style = None;
+        // Where the terminal put its cursor after the last character
+        let mut at = None;
+        for index in 0..self.cells.len() {
+            let cell = &self.cells[index];
+            if cell == &self.shown[index] || cell.text.is_empty() {
+                continue;
+            }
+            let (y, x) = (index as i32 / self.cols, index as i32 % self.cols);
+            if at != Some((y, x)) {
+                queue!(self.out, MoveTo(x as u16, y as u16))?;
+            }
+            let attrs = cell.attrs;
+            if style != Some(attrs) {
+                self.set_style(attrs)?;
+                style = Some(attrs);
+            }
+            let cell = &self.cells[index];
+            let width = cell.text.chars().next().map_or(1, crate::ui::char_width) as i32;
+            queue!(self.out, Print(&cell.text))?;
+            at = Some((y, x + width));
+        }
+        self.shown.clone_from(&self.cells);
+        if self.cursor_visible {
+            let (y, x) = (
+                self.y.clamp(0, self.lines - 1),
+                self.x.clamp(0, self.cols - 1),
+            );
+            queue!(self.out, MoveTo(x as u16, y as u16))?;
+        }
+        self.out.flush()
+    }
+
+    fn getch(&mut self) -> i32 {
+        loop {
+            if let Some(key) = self.input.pop_front() {
+                return key;
+            }
+            let ready = match self.delay {
+                Some(delay) => event::poll(delay).unwrap_or(false),
+                None => true,
+            };
+            if !ready {
+                return ERR;
+            }
+            match event::read() {
+                Ok(event) => self.feed(event),
+                Err(_) => return ERR,
+            }
+            if self.input.is_empty() && self.delay.is_some() {
+                return ERR;
+            }
+        }
+    }
+
+    fn feed(&mut self, event: Event) {
+        match event {
+            Event::Key(key) => self.feed_key(key),
+            // Sent the way the terminals send the bracketed pastes, which is what the app expects
+            Event::Paste(text) => {
+                let bytes = [PASTE_BEGIN, text.as_bytes(), PASTE_END].concat();
+                self.input.extend(bytes.iter().map(|byte| *byte as i32));
+            }
+            Event::Resize(cols, lines) => {
+                self.resize(lines as i32, cols as i32);
+                self.input.push_back(KEY_RESIZE);
+            }
+            _ => {}
+        }
+    }
+
+    fn feed_key(&mut self, key: KeyEvent) {
+        // Windows reports the releases of the keys too
+        if key.kind == KeyEventKind::Release {
+            return;
+        }
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        let shift = key.modifiers.contains(KeyModifiers::SHIFT);
+        let code = match key.code {
+            // There is no SIGINT in the raw mode, Ctrl+C is just another key
+            KeyCode::Char('c') if ctrl => {
+                crate::signals::interrupt();
+                return;
+            }
+            KeyCode::Char(c) if ctrl && c.is_ascii_alphabetic() => {
+                (c.to_ascii_lowercase() as u8 - b'a' + 1) as i32
+            }
+            // The bytes of UTF-8, like ncurses gives them
+            KeyCode::Char(c) => {
+                if key.modifiers.contains(KeyModifiers::ALT) {
+                    self.input.push_back(ESCAPE);
+                }
+                let mut buffer = [0; 4];
+                let bytes = c.encode_utf8(&mut buffer).bytes();
+                self.input.extend(bytes.map(|byte| byte as i32));
+                return;
+            }
+            KeyCode::Enter => '\n' as i32,
+            KeyCode::Tab => '\t' as i32,
+            KeyCode::BackTab => KEY_BTAB,
+            KeyCode::Backspace => KEY_BACKSPACE,
+            KeyCode::Esc => ESCAPE,
+            KeyCode::Up if shift => KEY_SR,
+            KeyCode::Down if shift => KEY_SF,
+            KeyCode::Up => KEY_UP,
+            KeyCode::Down => KEY_DOWN,
+            KeyCode::Left => KEY_LEFT,
+            KeyCode::Right => KEY_RIGHT,
+            KeyCode::Home => KEY_HOME,
+            KeyCode::End => KEY_END,
+            KeyCode::PageUp => KEY_PPAGE,
+            KeyCode::PageDown => KEY_NPAGE,
+            KeyCode::Delete => KEY_DC,
+            KeyCode::Insert => KEY_IC,
+            KeyCode::F(n) => KEY_F0 + n as i32,
+            _ => return,
+        };
+        if key.modifiers.contains(KeyModifiers::ALT) {
+            self.input.push_back(ESCAPE);
+        }
+        self.input.push_back(code);
+    }
+}
+
+fn leave(out: &mut impl Write) -> io::Result<()> {
+    execute!(
+        out,
+        SetAttribute(Attribute::Reset),
+        Show,
+        LeaveAlternateScreen
+    )?;
+    terminal::disable_raw_mode()
+}
+
+fn status(result: io::Result<()>) -> i32 {
+    match result {
+        Ok(()) => OK,
+        Err(_) => ERR,
+    }
+}
+
+pub fn setlocale(_category: LcCategory, _locale: &str) -> String {
+    String::new()
+}
+
+pub fn initscr() -> WINDOW {
+    with(|screen| {
+        let _ = screen.resume();
+    });
+    WINDOW
+}
+
+// Safe to call from the panic hook, even if the panic happened in the middle of drawing
+pub fn endwin() -> i32 {
+    SCREEN.with(|screen| match screen.try_borrow_mut() {
+        Ok(mut screen) => match screen.as_mut() {
+            Some(screen) => status(screen.suspend()),
+            None => OK,
+        },
+        Err(_) => status(leave(&mut open_terminal())),
+    })
+}
+
+// crossterm has no modes to save, the raw mode is all there is
+pub fn def_prog_mode() -> i32 {
+    OK
+}
+
+pub fn reset_prog_mode() -> i32 {
+    with(|screen| status(screen.resume()))
+}
+
+pub fn stdscr() -> WINDOW {
+    WINDOW
+}
+
+pub fn noecho() -> i32 {
+    OK
+}
+
+pub fn keypad(_window: WINDOW, _enable: bool) -> i32 {
+    OK
+}
+
+pub fn timeout(delay: i32) {
+    with(|screen| {
+        screen.delay = if delay < 0 {
+            None
+        } else {
+            Some(Duration::from_millis(delay as u64))
+        };
+    })
+}
+
+pub fn curs_set(visibility: CURSOR_VISIBILITY) -> Option<CURSOR_VISIBILITY> {
+    let visible = !matches!(visibility, CURSOR_VISIBILITY::CURSOR_INVISIBLE);
+    with(|screen| {
+        let previous = if screen.cursor_visible {
+            CURSOR_VISIBILITY::CURSOR_VISIBLE
+        } else {
+            CURSOR_VISIBILITY::CURSOR_INVISIBLE
+        };
+        screen.cursor_visible = visible;
+        let _ = if visible {
+            execute!(screen.out, Show)
+        } else {
+            execute!(screen.out, Hide)
+        };
+        Some(previous)
+    })
+}
+
+pub fn start_color() -> i32 {
+    OK
+}
+
+pub fn use_default_colors() -> i32 {
+    OK
+}
+
+pub fn COLORS() -> i32 {
+    256
+}
+
+// Any color can be redefined, since crossterm talks RGB to the terminal
+pub fn can_change_color() -> bool {
+    true
+}
+
+// The components go from 0 to 1000, like in ncurses
+pub fn init_color(color: i16, r: i16, g: i16, b: i16) -> i32 {
+    let scale = |x: i16| (x.clamp(0, 1000) as i32 * 255 / 1000) as u8;
+    with(|screen| screen.colors.insert(color, (scale(r), scale(g), scale(b))));
+    OK
+}
+
+pub fn init_pair(pair: i16, fg: i16, bg: i16) -> i32 {
+    with(|screen| screen.pairs.insert(pair, (fg, bg)));
+    OK
+}
+
+pub fn COLOR_PAIR(pair: i16) -> attr_t {
+    (pair as attr_t) << 8
+}
+
+pub fn A_NORMAL() -> attr_t {
+    0
+}
+
+pub fn A_DIM() -> attr_t {
+    DIM
+}
+
+pub fn A_BOLD() -> attr_t {
+    BOLD
+}
+
+pub fn bkgd(attrs: chtype) -> i32 {
+    with(|screen| screen.background = attrs);
+    OK
+}
+
+pub fn attron(attrs: attr_t) -> i32 {
+    with(|screen| {
+        if pair_of(attrs) != 0 {
+            screen.attrs &= !A_COLOR;
+        }
+        screen.attrs |= attrs;
+    });
+    OK
+}
+
+pub fn attroff(attrs: attr_t) -> i32 {
+    with(|screen| screen.attrs &= !attrs);
+    OK
+}
+
+pub fn getmaxyx(_window: WINDOW, y: &mut i32, x: &mut i32) {
+    with(|screen| {
+        *y = screen.lines;
+        *x = screen.cols;
+    })
+}
+
+pub fn erase() -> i32 {
+    with(|screen| {
+        let cell = blank(screen.background);
+        screen.cells.fill(cell);
+    });
+    OK
+}
+
+pub fn refresh() -> i32 {
+    with(|screen| status(screen.refresh()))
+}
+
+pub fn mv(y: i32, x: i32) -> i32 {
+    with(|screen| {
+        screen.y = y;
+        screen.x = x;
+    });
+    OK
+}
+
+// Whatever doesn't fit on the line is cut off
+pub fn addstr(text: &str) -> i32 {
+    with(|screen| text.chars().for_each(|c| screen.put(c)));
+    OK
+}
+
+pub fn mvaddch(y: i32, x: i32, c: chtype) -> i32 {
+    mv(y, x);
+    with(|screen| screen.put(char::from_u32(c).unwrap_or(' ')));
+    OK
+}
+
+pub fn mvhline(y: i32, x: i32, c: chtype, n: i32) -> i32 {
+    for i in 0..n {
+        mvaddch(y, x + i, c);
+    }
+    OK
+}
+
+pub fn mvvline(y: i32, x: i32, c: chtype, n: i32) -> i32 {
+    for i in 0..n {
+        mvaddch(y + i, x, c);
+    }
+    OK
+}
+
+pub fn ACS_ULCORNER() -> chtype {
+    '┌' as chtype
+}
+
+pub fn ACS_URCORNER() -> chtype {
+    '┐' as chtype
+}
+
+pub fn ACS_LLCORNER() -> chtype {
+    '└' as chtype
+}
+
+pub fn ACS_LRCORNER() -> chtype {
+    '┘' as chtype
+}
+
+pub fn ACS_HLINE() -> chtype {
+    '─' as chtype
+}
+
+pub fn ACS_VLINE() -> chtype {
+    '│' as chtype
+}
+
+pub fn getch() -> i32 {
+    with(|screen| screen.getch())
+}
+
+pub fn ungetch(key: i32) -> i32 {
+    with(|screen| screen.input.push_front(key));
+    OK
+}
+
+// Blocks until there is some input or the deadline passes, whichever comes first. crossterm waits
+// through the signals, so without the deadline it still wakes up every second to let the main loop
+// notice them.
+pub fn wait_for_input(deadline: Option<Instant>) {
+    if with(|screen| !screen.input.is_empty()) {
+        return;
+    }
+    let timeout = match deadline {
+        Some(deadline) => deadline.saturating_duration_since(Instant::now()),
+        None => Duration::from_secs(1),
+    };
+    // An error only wakes the main loop up early, it finds nothing to read and comes back
+    let _ = event::poll(timeout);
+}
+
+// crossterm reports the resizes along with the input, the size is right already
+pub fn resize() {}
+
+// The raw mode and the alternate screen are all there is to give back and take again
+pub fn suspend() {
+    endwin();
+    crate::signals::stop();
+    reset_prog_mode();
+}
+
+pub fn beep() -> i32 {
+    let mut out = open_terminal();
+    let _ = out.write_all(b"\x07");
+    let _ = out.flush();
+    OK
+}
+
+pub fn bracketed_paste(enable: bool) {
+    let mut out = open_terminal();
+    let _ = if enable {
+        execute!(out, EnableBracketedPaste)
+    } else {
+        execute!(out, DisableBracketedPaste)
+    };
+}
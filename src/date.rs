@@ -0,0 +1,193 @@
+use std::fmt;
+
+/// A calendar date without any time zone attached to it. Good enough for due dates and the like.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Date {
+    /// Like 2021
+    pub year: i32,
+    /// 1 is January, 12 is December
+    pub month: u32,
+    /// Of the month, starting from 1
+    pub day: u32,
+}
+
+const MONTH_NAMES: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+
+fn is_leap_year(year: i32) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
+
+/// 28 to 31, with the leap years
+pub fn days_in_month(year: i32, month: u32) -> u32 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+fn local_time(seconds: i64) -> libc::tm {
+    unsafe {
+        let mut tm: libc::tm = std::mem::zeroed();
+        #[cfg(unix)]
+        libc::localtime_r(&(seconds as libc::time_t), &mut tm);
+        #[cfg(windows)]
+        libc::localtime_s(&mut tm, &(seconds as libc::time_t));
+        tm
+    }
+}
+
+// How far the local time zone is ahead of UTC at the moment, in seconds
+#[cfg(unix)]
+fn utc_offset(seconds: i64) -> i64 {
+    local_time(seconds).tm_gmtoff
+}
+
+// The tm of Windows doesn't have the offset in it, so it's the difference between the local time
+// taken as if it was UTC and the actual UTC
+#[cfg(windows)]
+fn utc_offset(seconds: i64) -> i64 {
+    let tm = local_time(seconds);
+    let date = Date {
+        year: tm.tm_year + 1900,
+        month: tm.tm_mon as u32 + 1,
+        day: tm.tm_mday as u32,
+    };
+    let time = i64::from(tm.tm_hour * 3600 + tm.tm_min * 60 + tm.tm_sec);
+    date.to_days() * 86400 + time - seconds
+}
+
+impl Date {
+    /// None for the dates that don't exist, like 2021-02-29
+    pub fn new(year: i32, month: u32, day: u32) -> Option<Self> {
+        if (1..=12).contains(&month) && day >= 1 && day <= days_in_month(year, month) {
+            Some(Self { year, month, day })
+        } else {
+            None
+        }
+    }
+
+    /// YYYY-MM-DD
+    pub fn parse(text: &str) -> Option<Self> {
+        let mut parts = text.splitn(3, '-');
+        let year = parts.next()?;
+        let month = parts.next()?;
+        let day = parts.next()?;
+        if year.len() != 4 || month.len() != 2 || day.len() != 2 {
+            return None;
+        }
+        Self::new(year.parse().ok()?, month.parse().ok()?, day.parse().ok()?)
+    }
+
+    /// In the local time zone
+    pub fn today() -> Self {
+        Self::local(unsafe { libc::time(std::ptr::null_mut()) })
+    }
+
+    /// The date in the local time zone of the seconds since 1970-01-01 UTC
+    pub fn local(seconds: i64) -> Self {
+        let tm = local_time(seconds);
+        Self {
+            year: tm.tm_year + 1900,
+            month: tm.tm_mon as u32 + 1,
+            day: tm.tm_mday as u32,
+        }
+    }
+
+    /// When the day starts in the local time zone, in seconds since 1970-01-01 UTC. The offset of
+    /// the time zone is the one at the midnight of UTC, which is off only on the days the clocks
+    /// change at midnight.
+    pub fn local_midnight(self) -> i64 {
+        let utc = self.to_days() * 86400;
+        utc - utc_offset(utc)
+    }
+
+    // Days since 1970-01-01. See http://howardhinnant.github.io/date_algorithms.html#days_from_civil
+    fn to_days(self) -> i64 {
+        let year = i64::from(self.year) - if self.month <= 2 { 1 } else { 0 };
+        let era = year.div_euclid(400);
+        let year_of_era = year - era * 400;
+        let month = i64::from(self.month);
+        let day_of_year =
+            (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + i64::from(self.day) - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        era * 146097 + day_of_era - 719468
+    }
+
+    // The inverse of Date::to_days()
+    fn from_days(days: i64) -> Self {
+        let days = days + 719468;
+        let era = days.div_euclid(146097);
+        let day_of_era = days - era * 146097;
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let month_index = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+        let month = if month_index < 10 {
+            month_index + 3
+        } else {
+            month_index - 9
+        };
+        let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+        Self {
+            year: year as i32,
+            month: month as u32,
+            day: day as u32,
+        }
+    }
+
+    /// The date in UTC of the seconds since 1970-01-01, along with the seconds since its midnight
+    pub fn from_timestamp(seconds: i64) -> (Self, u32) {
+        (
+            Self::from_days(seconds.div_euclid(86400)),
+            seconds.rem_euclid(86400) as u32,
+        )
+    }
+
+    /// The days can be negative to go back
+    pub fn add_days(self, days: i64) -> Self {
+        Self::from_days(self.to_days() + days)
+    }
+
+    /// Negative if the other date is later
+    pub fn days_since(self, other: Date) -> i64 {
+        self.to_days() - other.to_days()
+    }
+
+    /// 0 is Monday, 6 is Sunday
+    pub fn weekday(self) -> u32 {
+        // 1970-01-01 was a Thursday
+        (self.to_days() + 3).rem_euclid(7) as u32
+    }
+
+    /// The 1st of the same month
+    pub fn first_of_month(self) -> Self {
+        Self { day: 1, ..self }
+    }
+
+    /// In English, like January
+    pub fn month_name(self) -> &'static str {
+        MONTH_NAMES[self.month as usize - 1]
+    }
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
@@ -0,0 +1,59 @@
+use std::io::{self, ErrorKind};
+use std::process::{Child, Command, Stdio};
+
+// Like with the clipboard, every desktop has its own way of showing a notification. We try the
+// command line tools one by one until something works.
+#[cfg(not(windows))]
+fn commands(summary: &str, body: &str) -> Vec<Command> {
+    let mut notify_send = Command::new("notify-send");
+    // The items may start with a dash, which must not be taken for an option
+    notify_send.args(["--app-name=todo-rs", "--", summary, body]);
+    // AppleScript strings are in double quotes with the usual escapes
+    let quote = |text: &str| format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""));
+    let mut osascript = Command::new("osascript");
+    osascript.arg("-e").arg(format!(
+        "display notification {} with title {}",
+        quote(body),
+        quote(summary)
+    ));
+    vec![notify_send, osascript]
+}
+
+// A balloon of the tray, which needs no modules installed. The texts go in the environment rather
+// than in the script, so there is nothing to escape.
+#[cfg(windows)]
+fn commands(summary: &str, body: &str) -> Vec<Command> {
+    let script = "Add-Type -AssemblyName System.Windows.Forms; \
+                  $icon = New-Object System.Windows.Forms.NotifyIcon; \
+                  $icon.Icon = [System.Drawing.SystemIcons]::Information; \
+                  $icon.Visible = $true; \
+                  $icon.ShowBalloonTip(10000, $env:TODO_SUMMARY, $env:TODO_BODY, 'Info'); \
+                  Start-Sleep -Seconds 10; $icon.Dispose()";
+    let mut powershell = Command::new("powershell");
+    powershell
+        .args(["-NoProfile", "-Command", script])
+        .env("TODO_SUMMARY", summary)
+        .env("TODO_BODY", body);
+    vec![powershell]
+}
+
+// Returns the tool that shows the notification. It's the caller's to reap once it's done, a Child
+// that is dropped without try_wait() or wait() stays a zombie until the app exits.
+pub fn notify(summary: &str, body: &str) -> io::Result<Child> {
+    for mut command in commands(summary, body) {
+        let result = command
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn();
+        match result {
+            Ok(child) => return Ok(child),
+            Err(error) if error.kind() == ErrorKind::NotFound => continue,
+            Err(error) => return Err(error),
+        }
+    }
+    Err(io::Error::new(
+        ErrorKind::NotFound,
+        "no notification tool found (tried notify-send, osascript)",
+    ))
+}
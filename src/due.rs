@@ -0,0 +1,107 @@
+use std::collections::HashSet;
+use std::io::ErrorKind;
+use std::process::Child;
+use std::time::{Duration, Instant};
+
+use todo_rs::date::Date;
+use todo_rs::{dedupe_key, item_date};
+
+use crate::config::Config;
+use crate::desktop;
+use crate::notifications::Notifications;
+
+// The due dates are days, once a minute is plenty to notice the midnight
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+// Tells about the TODO items as they become due, the ones due already on the first check. Every
+// item is told about once per due date, so moving the date later brings it up again when it comes.
+pub struct DueCheck {
+    // dedupe_key() and the due date of the items that were told about
+    notified: HashSet<String>,
+    next_check: Instant,
+    // Off by [ui] desktop_notifications or once there turns out to be no way to send them
+    desktop: bool,
+    // The notifications that are still being shown. Nobody waits for them, the notification takes
+    // as long as it takes, but they are reaped on the ticks once they are done.
+    running: Vec<Child>,
+}
+
+impl DueCheck {
+    pub fn new(desktop: bool) -> Self {
+        Self {
+            notified: HashSet::new(),
+            next_check: Instant::now(),
+            desktop,
+            running: Vec::new(),
+        }
+    }
+
+    // For Event::Tick
+    pub fn deadline(&self) -> Instant {
+        self.next_check
+    }
+
+    pub fn check<'a>(
+        &mut self,
+        now: Instant,
+        todos: impl Iterator<Item = &'a String>,
+        notifications: &mut Notifications,
+    ) {
+        self.running
+            .retain_mut(|child| !matches!(child.try_wait(), Ok(Some(_))));
+        if now < self.next_check {
+            return;
+        }
+        self.next_check = now + CHECK_INTERVAL;
+        let today = Date::today();
+        let mut due = Vec::new();
+        for todo in todos {
+            let date = match item_date(todo, "due") {
+                Some(date) if date <= today => date,
+                _ => continue,
+            };
+            if self
+                .notified
+                .insert(format!("{} {}", dedupe_key(todo), date))
+            {
+                due.push(dedupe_key(todo));
+            }
+        }
+        let body = match due.as_slice() {
+            [] => return,
+            [title] => {
+                notifications.warning(format!("Due: {}", title));
+                title.clone()
+            }
+            _ => {
+                let body = format!("{} items are due", due.len());
+                notifications.warning(body.clone());
+                body
+            }
+        };
+        if !self.desktop {
+            return;
+        }
+        match desktop::notify("Due", &body) {
+            Ok(child) => self.running.push(child),
+            // Nothing to send them with, like over ssh, the in-app ones are enough then
+            Err(error) if error.kind() == ErrorKind::NotFound => self.desktop = false,
+            Err(error) => {
+                notifications.error(format!("Could not send a desktop notification: {}", error));
+                self.desktop = false;
+            }
+        }
+    }
+}
+
+pub fn desktop_from_config(config: &Config) -> Result<bool, String> {
+    match config.get("ui", "desktop_notifications") {
+        None | Some("on") => Ok(true),
+        Some("off") => Ok(false),
+        Some(value) => Err(config.error(
+            "ui",
+            "desktop_notifications",
+            &format!("expected `on` or `off` but got `{}`", value),
+        )),
+    }
+}
@@ -0,0 +1,151 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant, SystemTime};
+
+#[cfg(feature = "dbus")]
+use crate::bus::{Bus, Call};
+#[cfg(unix)]
+use crate::control::{Control, Request};
+use crate::macros::Input;
+use crate::signals;
+use crate::terminal::constants;
+
+// How often the file is checked for the changes made by somebody else
+const FILE_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+// crossterm can't wait on the control socket and D-Bus along with the input, so they are checked
+// this often
+#[cfg(all(any(unix, feature = "dbus"), feature = "crossterm"))]
+const CONTROL_CHECK_INTERVAL: Duration = Duration::from_millis(250);
+
+// Everything the main loop reacts to. Each event is dispatched exactly once, to exactly one place.
+pub enum Event {
+    Key(i32),
+    Paste(String),
+    Resize,
+    // One of the timers went off: a notification expired, the focus timer or the autosave is due
+    Tick,
+    // Ctrl+C
+    Signal,
+    // SIGTSTP from somebody else, Ctrl+Z itself comes as a key
+    #[cfg(not(any(windows, feature = "crossterm")))]
+    Suspend,
+    // The file on disk is not what was loaded or saved the last time, carries its new mtime
+    FileChanged(Option<SystemTime>),
+    // A line from the control socket
+    #[cfg(unix)]
+    Control(Request),
+    // A method call of the D-Bus service
+    #[cfg(feature = "dbus")]
+    DBus(Call),
+}
+
+impl From<&Input> for Event {
+    fn from(input: &Input) -> Self {
+        match input {
+            Input::Key(constants::KEY_RESIZE) => Event::Resize,
+            Input::Key(key) => Event::Key(*key),
+            Input::Paste(text) => Event::Paste(text.clone()),
+        }
+    }
+}
+
+pub struct Events {
+    queue: VecDeque<Event>,
+    next_file_check: Instant,
+    #[cfg(unix)]
+    pub control: Option<Control>,
+    #[cfg(feature = "dbus")]
+    pub bus: Option<Bus>,
+}
+
+impl Default for Events {
+    fn default() -> Self {
+        Self {
+            queue: VecDeque::new(),
+            next_file_check: Instant::now() + FILE_CHECK_INTERVAL,
+            #[cfg(unix)]
+            control: None,
+            #[cfg(feature = "dbus")]
+            bus: None,
+        }
+    }
+}
+
+impl Events {
+    pub fn push(&mut self, event: Event) {
+        self.queue.push_back(event);
+    }
+
+    pub fn pop(&mut self) -> Option<Event> {
+        self.queue.pop_front()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    // Queues up whatever happened apart from the input: the signal, the timers, the file, the
+    // control socket and D-Bus. The timer is the earliest of the caller's own deadlines.
+    pub fn poll(
+        &mut self,
+        now: Instant,
+        timer: Option<Instant>,
+        file_path: &str,
+        saved_mtime: Option<SystemTime>,
+    ) {
+        if signals::poll() {
+            self.push(Event::Signal);
+        }
+        #[cfg(not(any(windows, feature = "crossterm")))]
+        if signals::resized() {
+            self.push(Event::Resize);
+        }
+        #[cfg(not(any(windows, feature = "crossterm")))]
+        if signals::suspended() {
+            self.push(Event::Suspend);
+        }
+        if timer.is_some_and(|timer| now >= timer) {
+            self.push(Event::Tick);
+        }
+        if now >= self.next_file_check {
+            self.next_file_check = now + FILE_CHECK_INTERVAL;
+            let mtime = todo_rs::file_mtime(file_path);
+            if mtime != saved_mtime {
+                self.push(Event::FileChanged(mtime));
+            }
+        }
+        #[cfg(unix)]
+        if let Some(control) = &mut self.control {
+            for request in control.accept() {
+                self.queue.push_back(Event::Control(request));
+            }
+        }
+        #[cfg(feature = "dbus")]
+        if let Some(bus) = &self.bus {
+            for call in bus.accept() {
+                self.queue.push_back(Event::DBus(call));
+            }
+        }
+    }
+
+    // When poll() has something to say next, given the same timer
+    pub fn deadline(&self, timer: Option<Instant>) -> Instant {
+        let deadline = match timer {
+            Some(timer) => timer.min(self.next_file_check),
+            None => self.next_file_check,
+        };
+        #[cfg(unix)]
+        if self.control.as_ref().is_some_and(Control::has_clients) {
+            return deadline.min(Instant::now() + crate::control::CLIENT_CHECK_INTERVAL);
+        }
+        #[cfg(all(unix, feature = "crossterm"))]
+        if self.control.is_some() {
+            return deadline.min(Instant::now() + CONTROL_CHECK_INTERVAL);
+        }
+        #[cfg(all(feature = "dbus", feature = "crossterm"))]
+        if self.bus.is_some() {
+            return deadline.min(Instant::now() + CONTROL_CHECK_INTERVAL);
+        }
+        deadline
+    }
+}
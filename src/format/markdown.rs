@@ -0,0 +1,67 @@
+use super::Format;
+use crate::Status;
+
+/// The task lists of GitHub flavored Markdown, so the same file renders nicely on GitHub. Only the
+/// top level items are ours, the nested ones are left alone along with the rest of the document.
+pub struct Markdown;
+
+const LIST_MARKERS: &[char] = &['-', '*', '+'];
+
+impl Format for Markdown {
+    fn parse_item(&self, line: &str) -> Option<(Status, String)> {
+        let rest = line.strip_prefix(LIST_MARKERS)?.strip_prefix(' ')?;
+        let (status, title) = if let Some(title) = rest.strip_prefix("[ ] ") {
+            (Status::Todo, title)
+        } else if let Some(title) = rest
+            .strip_prefix("[x] ")
+            .or_else(|| rest.strip_prefix("[X] "))
+        {
+            (Status::Done, title)
+        } else {
+            return None;
+        };
+        Some((status, title.to_string()))
+    }
+
+    fn is_plain_done(&self, line: &str) -> bool {
+        line.strip_prefix(LIST_MARKERS)
+            .and_then(|rest| rest.strip_prefix(' '))
+            .is_some_and(|rest| rest.starts_with("[x] ") || rest.starts_with("[X] "))
+    }
+
+    fn write_item(&self, status: Status, title: &str) -> String {
+        match status {
+            Status::Todo => format!("- [ ] {}", title),
+            Status::Done => format!("- [x] {}", title),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_what_it_reads() {
+        for line in ["- [ ] buy milk #home", "- [x] call mom done:2021-06-30"] {
+            let (status, title) = Markdown.parse_item(line).unwrap();
+            assert_eq!(Markdown.write_item(status, &title), line);
+        }
+        assert_eq!(
+            Markdown.parse_item("* [X] call mom"),
+            Some((Status::Done, "call mom".to_string()))
+        );
+    }
+
+    #[test]
+    fn leaves_the_rest_of_the_document_alone() {
+        for line in [
+            "  - [ ] nested",
+            "- plain item",
+            "[ ] no marker",
+            "# Heading",
+        ] {
+            assert_eq!(Markdown.parse_item(line), None);
+        }
+    }
+}
@@ -0,0 +1,190 @@
+use crate::Status;
+use std::path::Path;
+use std::sync::OnceLock;
+
+mod markdown;
+mod org;
+mod todo;
+mod todo_txt;
+
+/// How the items are written down in a file, one per line. Whatever lines are not items are kept as
+/// they are, so a format only needs to know about its items.
+pub trait Format {
+    /// The item on the line if there is one, with whatever the format says about it turned into
+    /// the metadata of the title
+    fn parse_item(&self, line: &str) -> Option<(Status, String)>;
+
+    /// Tells the lines that were meant to be items but are not quite from the rest. Returns the item
+    /// they were probably meant to be, if any.
+    fn parse_ill_formed(&self, _line: &str) -> Option<Option<(Status, String)>> {
+        None
+    }
+
+    /// Whether the line right after an item is a part of it, like the planning line under the
+    /// headlines of org. What the line says goes into the metadata of the title.
+    fn parse_continuation(&self, _line: &str, _title: &mut String) -> bool {
+        false
+    }
+
+    /// Whether the items have bodies under them, like the headlines of org. The lines after an item
+    /// go along with it for as long as parse_body takes them, and the rest of the lines stay where
+    /// they are in the file instead of going along with the item after them.
+    fn has_body(&self) -> bool {
+        false
+    }
+
+    /// Whether the line is a part of the body of the item before it, see has_body()
+    fn parse_body(&self, _line: &str) -> bool {
+        false
+    }
+
+    /// Whether parse_item takes the line for a DONE item all on its own, with no continuation or body,
+    /// by a glance much cheaper than parse_item. The DONE items at the end of a file that are all
+    /// like that are left unread until they are needed, see parse_state_lazily(). No is always a
+    /// safe answer, it only gets them read right away.
+    fn is_plain_done(&self, _line: &str) -> bool {
+        false
+    }
+
+    /// The lines of the item, without the last line break
+    fn write_item(&self, status: Status, title: &str) -> String;
+
+    /// The lines of the item that was read from the given lines and changed since, keeping whatever
+    /// the title has no metadata for
+    fn update_item(&self, status: Status, title: &str, _read: &[&str]) -> String {
+        self.write_item(status, title)
+    }
+
+    /// Whether the line gives the format away when the file does not tell what it is by its name.
+    /// Only the items that could hardly be anything else are good for that.
+    fn sniff(&self, line: &str) -> bool {
+        self.parse_item(line).is_some()
+    }
+}
+
+/// The prefix of the TODO items of our own format unless the config says otherwise
+pub const TODO_PREFIX: &str = "TODO: ";
+/// The prefix of the DONE items of our own format unless the config says otherwise
+pub const DONE_PREFIX: &str = "DONE: ";
+
+// Our own format with the prefixes from the config, set before any file is read
+static TODO: OnceLock<todo::Todo> = OnceLock::new();
+
+/// The prefixes of our own format from the config. Only the first call counts, before any file is
+/// read.
+pub fn set_prefixes(todo: String, done: String) {
+    let _ = TODO.set(todo::Todo { todo, done });
+}
+
+// The format of the files that don't tell, like the new ones, from the config
+static DEFAULT: OnceLock<Kind> = OnceLock::new();
+
+/// The format of the files that don't tell, from the config. Only the first call counts.
+pub fn set_default(kind: Kind) {
+    let _ = DEFAULT.set(kind);
+}
+
+// Plenty to tell the format by, the rest of the file may well be a long archive of the DONE items
+const SNIFF_LINES: usize = 1000;
+
+// The extensions of the encrypted files, see crypt::detect(). Whatever is before them tells the
+// format, like todo.md.gpg.
+const CIPHER_EXTENSIONS: &[&str] = &[".gpg", ".pgp", ".asc", ".age"];
+
+/// The formats of the files, see Format for what they have in common
+#[derive(Clone, Copy, PartialEq, Default)]
+pub enum Kind {
+    /// `TODO: title` and `DONE: title`, or the prefixes from the config
+    #[default]
+    Todo,
+    /// `- [ ] title` and `- [x] title`
+    Markdown,
+    /// <http://todotxt.org>, `(A) 2021-06-30 title +project @context`
+    TodoTxt,
+    /// `* TODO heading` and `* DONE heading` of Emacs org-mode
+    Org,
+}
+
+impl Kind {
+    /// Every format there is
+    pub const ALL: &'static [Kind] = &[Kind::Todo, Kind::Markdown, Kind::TodoTxt, Kind::Org];
+
+    /// As the config and the headers of the files have it
+    pub fn name(self) -> &'static str {
+        match self {
+            Kind::Todo => "todo",
+            Kind::Markdown => "markdown",
+            Kind::TodoTxt => "todo.txt",
+            Kind::Org => "org",
+        }
+    }
+
+    /// The other way around of name()
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|kind| kind.name() == name)
+    }
+
+    /// By the name or the extension, if they tell anything
+    pub fn from_path(file_path: &str) -> Option<Self> {
+        let mut name = Path::new(file_path)
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or("");
+        if let Some(stripped) = CIPHER_EXTENSIONS
+            .iter()
+            .find_map(|ext| name.strip_suffix(ext))
+        {
+            name = stripped;
+        }
+        if name.ends_with("todo.txt") || name.ends_with("done.txt") {
+            return Some(Kind::TodoTxt);
+        }
+        match name.rsplit_once('.').map(|(_, ext)| ext) {
+            Some("md") | Some("markdown") => Some(Kind::Markdown),
+            Some("org") => Some(Kind::Org),
+            _ => None,
+        }
+    }
+
+    /// By the header of the file or by the name if they tell, by what is in the file otherwise:
+    /// whichever of the formats finds the most of its items in its first lines. Ours wins the ties,
+    /// and the files without any items are in the format of set_default().
+    pub fn detect(file_path: &str, data: &[u8]) -> Self {
+        if let Some(kind) = crate::header::read(data).and_then(|header| header.format) {
+            return kind;
+        }
+        if let Some(kind) = Self::from_path(file_path) {
+            return kind;
+        }
+        let end = data
+            .iter()
+            .enumerate()
+            .filter(|(_, &byte)| byte == b'\n')
+            .nth(SNIFF_LINES)
+            .map_or(data.len(), |(end, _)| end);
+        let data = String::from_utf8_lossy(&data[..end]);
+        Self::ALL
+            .iter()
+            .copied()
+            .map(|kind| {
+                let format = kind.format();
+                (kind, data.lines().filter(|line| format.sniff(line)).count())
+            })
+            .filter(|(_, count)| *count > 0)
+            .max_by_key(|(kind, count)| (*count, *kind == Kind::Todo))
+            .map_or_else(
+                || DEFAULT.get().copied().unwrap_or_default(),
+                |(kind, _)| kind,
+            )
+    }
+
+    /// How the items of the kind are read and written
+    pub fn format(self) -> &'static dyn Format {
+        match self {
+            Kind::Todo => TODO.get_or_init(todo::Todo::default),
+            Kind::Markdown => &markdown::Markdown,
+            Kind::TodoTxt => &todo_txt::TodoTxt,
+            Kind::Org => &org::Org,
+        }
+    }
+}
@@ -0,0 +1,329 @@
+use super::Format;
+use crate::date::Date;
+use crate::{item_date, item_meta, item_set_meta, Status};
+
+/// The headlines of Emacs org-mode with the TODO or DONE keyword, along with the planning line right
+/// under them:
+///
+/// ```text
+/// ** TODO [#A] title :tag:@context:
+/// DEADLINE: <2021-07-01 Thu> SCHEDULED: <2021-06-30 Wed>
+/// ```
+///
+/// The level becomes level:2 unless it's the top one, the priority prio:A, the tags #tag @context
+/// and the timestamps due:, scheduled: and done: for CLOSED. The times and the repeaters of the
+/// timestamps are kept as long as their dates stay the same. The lines under the headline are its
+/// body and go wherever it goes, the rest of the outline is left alone.
+pub struct Org;
+
+// The keywords of the planning line and the metadata they turn into
+const PLANNING: &[(&str, &str)] = &[
+    ("CLOSED", "done"),
+    ("DEADLINE", "due"),
+    ("SCHEDULED", "scheduled"),
+];
+
+const WEEKDAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+
+fn strip_keyword<'a>(text: &'a str, keyword: &str) -> Option<&'a str> {
+    let rest = text.strip_prefix(keyword)?;
+    if rest.is_empty() || rest.starts_with(' ') {
+        Some(rest.trim_start())
+    } else {
+        None
+    }
+}
+
+fn is_tags(word: &str) -> bool {
+    word.len() > 2 && word.starts_with(':') && word.ends_with(':') && !word.contains("::")
+}
+
+// #tag for org's :tag:, @context stays as it is
+fn is_tag(word: &str) -> bool {
+    word.len() > 1 && word.starts_with(['#', '@'])
+}
+
+// <2021-07-01 Thu> or [2021-07-02 Fri 10:00 +1w], with the timestamp as it is and the rest
+fn parse_timestamp(text: &str) -> Option<(Date, &str, &str)> {
+    let close = match text.chars().next()? {
+        '<' => '>',
+        '[' => ']',
+        _ => return None,
+    };
+    let end = text.find(close)?;
+    let date = Date::parse(text[1..end].split(' ').next()?)?;
+    Some((date, &text[..=end], text[end + 1..].trim_start()))
+}
+
+// The keywords of the planning line with their dates and timestamps, None if it's not one
+fn parse_planning(line: &str) -> Option<Vec<(&'static str, Date, &str)>> {
+    let mut rest = line.trim();
+    let mut timestamps = Vec::new();
+    while !rest.is_empty() {
+        let (keyword, (date, timestamp, after)) = PLANNING.iter().find_map(|&(keyword, _)| {
+            let after = rest.strip_prefix(keyword)?.strip_prefix(':')?;
+            Some((keyword, parse_timestamp(after.trim_start())?))
+        })?;
+        timestamps.push((keyword, date, timestamp));
+        rest = after;
+    }
+    if timestamps.is_empty() {
+        None
+    } else {
+        Some(timestamps)
+    }
+}
+
+fn is_headline(line: &str) -> bool {
+    let rest = line.trim_start_matches('*');
+    rest.len() < line.len() && rest.starts_with(' ')
+}
+
+fn write_timestamp(date: Date, active: bool) -> String {
+    let weekday = WEEKDAYS[date.weekday() as usize];
+    if active {
+        format!("<{} {}>", date, weekday)
+    } else {
+        format!("[{} {}]", date, weekday)
+    }
+}
+
+impl Format for Org {
+    fn parse_item(&self, line: &str) -> Option<(Status, String)> {
+        let rest = line.trim_start_matches('*');
+        let level = line.len() - rest.len();
+        if level == 0 {
+            return None;
+        }
+        let rest = rest.strip_prefix(' ')?;
+        let (status, rest) = if let Some(rest) = strip_keyword(rest, "TODO") {
+            (Status::Todo, rest)
+        } else if let Some(rest) = strip_keyword(rest, "DONE") {
+            (Status::Done, rest)
+        } else {
+            return None;
+        };
+
+        let mut priority = None;
+        let mut rest = rest;
+        if let [b'[', b'#', letter @ b'A'..=b'Z', b']', ..] = rest.as_bytes() {
+            let after = &rest[4..];
+            if after.is_empty() || after.starts_with(' ') {
+                priority = Some(*letter as char);
+                rest = after.trim_start();
+            }
+        }
+        let mut words: Vec<&str> = rest.split(' ').filter(|word| !word.is_empty()).collect();
+        let mut tags = Vec::new();
+        if let Some(last) = words.last().copied().filter(|last| is_tags(last)) {
+            words.pop();
+            for tag in last.split(':').filter(|tag| !tag.is_empty()) {
+                if tag.starts_with('@') {
+                    tags.push(tag.to_string());
+                } else {
+                    tags.push(format!("#{}", tag));
+                }
+            }
+        }
+
+        let mut title = words.join(" ");
+        for tag in tags {
+            if !title.is_empty() {
+                title.push(' ');
+            }
+            title.push_str(&tag);
+        }
+        if level > 1 {
+            item_set_meta(&mut title, "level", Some(&level.to_string()));
+        }
+        if let Some(priority) = priority {
+            item_set_meta(&mut title, "prio", Some(&priority.to_string()));
+        }
+        Some((status, title))
+    }
+
+    fn parse_continuation(&self, line: &str, title: &mut String) -> bool {
+        let timestamps = match parse_planning(line) {
+            Some(timestamps) => timestamps,
+            // Anything else is the body of the entry rather than its planning line
+            None => return false,
+        };
+        for (keyword, date, _) in timestamps {
+            if let Some(&(_, key)) = PLANNING.iter().find(|&&(other, _)| other == keyword) {
+                item_set_meta(title, key, Some(&date.to_string()));
+            }
+        }
+        true
+    }
+
+    fn has_body(&self) -> bool {
+        true
+    }
+
+    // Up to the next headline, whatever its level
+    fn parse_body(&self, line: &str) -> bool {
+        !is_headline(line)
+    }
+
+    fn write_item(&self, status: Status, title: &str) -> String {
+        write(status, title, &[])
+    }
+
+    fn update_item(&self, status: Status, title: &str, read: &[&str]) -> String {
+        let timestamps = read
+            .get(1)
+            .and_then(|line| parse_planning(line))
+            .unwrap_or_default();
+        write(status, title, &timestamps)
+    }
+}
+
+// The item with the timestamps it was read with, for the dates that stayed the same
+fn write(status: Status, title: &str, timestamps: &[(&str, Date, &str)]) -> String {
+    let mut title = title.to_string();
+    let level = item_meta(&title, "level")
+        .and_then(|level| level.parse().ok())
+        .filter(|&level| level > 0)
+        .unwrap_or(1);
+    item_set_meta(&mut title, "level", None);
+    let mut headline = format!("{} {}", "*".repeat(level), status.name());
+
+    let priority = item_meta(&title, "prio")
+        .filter(|prio| prio.len() == 1 && prio.as_bytes()[0].is_ascii_uppercase())
+        .map(|prio| format!("[#{}]", prio));
+    if let Some(priority) = priority {
+        item_set_meta(&mut title, "prio", None);
+        headline.push(' ');
+        headline.push_str(&priority);
+    }
+
+    let mut planning = Vec::new();
+    for &(keyword, key) in PLANNING.iter() {
+        if let Some(date) = item_date(&title, key) {
+            item_set_meta(&mut title, key, None);
+            let read = timestamps
+                .iter()
+                .find(|&&(other, other_date, _)| other == keyword && other_date == date);
+            let timestamp = match read {
+                Some(&(_, _, timestamp)) => timestamp.to_string(),
+                // CLOSED is the only inactive one, it does not show up in the agenda
+                None => write_timestamp(date, keyword != "CLOSED"),
+            };
+            planning.push(format!("{}: {}", keyword, timestamp));
+        }
+    }
+
+    // Only the tags at the very end are org's, the ones in the middle are part of the title
+    let mut words: Vec<&str> = title.split(' ').filter(|word| !word.is_empty()).collect();
+    let tags_start = words
+        .iter()
+        .rposition(|word| !is_tag(word))
+        .map_or(0, |index| index + 1);
+    let tags: Vec<&str> = words
+        .split_off(tags_start)
+        .into_iter()
+        .map(|tag| tag.strip_prefix('#').unwrap_or(tag))
+        .collect();
+    if !words.is_empty() {
+        headline.push(' ');
+        headline.push_str(&words.join(" "));
+    }
+    if !tags.is_empty() {
+        headline.push_str(&format!(" :{}:", tags.join(":")));
+    }
+
+    if !planning.is_empty() {
+        headline.push('\n');
+        headline.push_str(&planning.join(" "));
+    }
+    headline
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_state, write_state, Extras};
+
+    const OUTLINE: &str = "\
+#+TITLE: Projects
+#+STARTUP: overview
+
+* Work
+** TODO [#A] Write the report :work:
+   DEADLINE: <2024-06-10 Mon 17:00> SCHEDULED: <2024-06-03 Mon +1w>
+   :PROPERTIES:
+   :EFFORT:   2:00
+   :END:
+   The numbers are in the spreadsheet.
+** DONE Book the flight
+   CLOSED: [2024-05-30 Thu 09:12]
+** TODO Call the bank
+* Home
+** TODO Fix the sink                                                  :home:
+   Needs a new washer.
+* Notes
+Some notes.
+";
+
+    fn load(data: &str) -> (Vec<String>, Vec<String>, Extras) {
+        let (mut todos, mut dones, mut extras) = (Vec::new(), Vec::new(), Extras::default());
+        parse_state(&mut todos, &mut dones, &mut extras, data.as_bytes(), &Org);
+        (todos, dones, extras)
+    }
+
+    fn save(todos: &[String], dones: &[String], extras: &Extras) -> String {
+        let mut out = Vec::new();
+        write_state(&mut out, todos, dones, extras, &Org).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn keeps_the_outline_as_it_is() {
+        let (todos, dones, extras) = load(OUTLINE);
+        assert_eq!(
+            todos,
+            [
+                "Write the report #work level:2 prio:A due:2024-06-10 scheduled:2024-06-03",
+                "Call the bank level:2",
+                "Fix the sink #home level:2",
+            ]
+        );
+        assert_eq!(dones, ["Book the flight level:2 done:2024-05-30"]);
+        assert_eq!(save(&todos, &dones, &extras), OUTLINE);
+    }
+
+    #[test]
+    fn keeps_the_timestamps_of_the_changed_items() {
+        let (mut todos, dones, extras) = load(OUTLINE);
+        todos[0] = todos[0].replace("the report", "the final report");
+        let saved = save(&todos, &dones, &extras);
+        assert!(saved.contains(
+            "** TODO [#A] Write the final report :work:\n\
+             DEADLINE: <2024-06-10 Mon 17:00> SCHEDULED: <2024-06-03 Mon +1w>\n   :PROPERTIES:"
+        ));
+        todos[0] = todos[0].replace("due:2024-06-10", "due:2024-06-11");
+        let saved = save(&todos, &dones, &extras);
+        assert!(saved.contains("DEADLINE: <2024-06-11 Tue> SCHEDULED: <2024-06-03 Mon +1w>\n"));
+    }
+
+    #[test]
+    fn keeps_the_items_under_their_headlines() {
+        let (mut todos, mut dones, extras) = load(OUTLINE);
+        // Marked as DONE
+        let mut done = todos.remove(1);
+        item_set_meta(&mut done, "done", Some("2024-06-01"));
+        dones.push(done);
+        // Added after the last one
+        todos.push("Buy a plunger level:2".to_string());
+        let expected = OUTLINE
+            .replace(
+                "** TODO Call the bank\n",
+                "** DONE Call the bank\nCLOSED: [2024-06-01 Sat]\n",
+            )
+            .replace(
+                "   Needs a new washer.\n",
+                "   Needs a new washer.\n** TODO Buy a plunger\n",
+            );
+        assert_eq!(save(&todos, &dones, &extras), expected);
+    }
+}
@@ -0,0 +1,194 @@
+use super::{Format, DONE_PREFIX, TODO_PREFIX};
+use crate::{meta_word, Status};
+
+/// The format of the app itself, `TODO: title` and `DONE: title`. The prefixes can be anything else
+/// the config says, like `[ ] ` and `[x] `, to match the files written by hand some other way.
+pub struct Todo {
+    pub todo: String,
+    pub done: String,
+}
+
+impl Default for Todo {
+    fn default() -> Self {
+        Self {
+            todo: TODO_PREFIX.to_string(),
+            done: DONE_PREFIX.to_string(),
+        }
+    }
+}
+
+// The metadata of the item goes after the bar, so the title reads on its own:
+//
+//   TODO: title | due:2024-06-01 prio:A id:ab12
+//
+// Only the keys the app sets itself go there. The other words that look like key:value, like
+// 10:30, 16:9 or mailto:someone, stay in the title where they were.
+const META_SEPARATOR: &str = " | ";
+
+const OWN_KEYS: &[&str] = &[
+    "due",
+    "done",
+    "created",
+    "scheduled",
+    "prio",
+    "id",
+    "remind",
+    "src",
+    "level",
+    "uid",
+    "uuid",
+    "issue",
+    "project",
+];
+
+fn is_own_meta(word: &str) -> bool {
+    meta_word(word).is_some_and(|(key, _)| OWN_KEYS.contains(&key))
+}
+
+// The title with the metadata after the bar joined back to it. Whatever follows the last bar is
+// a part of the title unless it's all our metadata.
+fn join_meta(title: &str) -> String {
+    match title.rsplit_once(META_SEPARATOR) {
+        Some(("", meta)) if is_meta(meta) => meta.to_string(),
+        Some((text, meta)) if is_meta(meta) => format!("{} {}", text, meta),
+        _ => title.to_string(),
+    }
+}
+
+fn is_meta(words: &str) -> bool {
+    !words.is_empty() && words.split(' ').all(is_own_meta)
+}
+
+// The word of the prefixes like `TODO: `, which the ill-formed lines are recognized by. The
+// prefixes that are not words have nothing like that.
+fn keyword(prefix: &str) -> Option<&str> {
+    let word = prefix.trim_end().strip_suffix(':')?;
+    if !word.is_empty() && word.chars().all(char::is_alphabetic) {
+        Some(word)
+    } else {
+        None
+    }
+}
+
+impl Todo {
+    fn prefix(&self, status: Status) -> &str {
+        match status {
+            Status::Todo => &self.todo,
+            Status::Done => &self.done,
+        }
+    }
+}
+
+impl Format for Todo {
+    fn parse_item(&self, line: &str) -> Option<(Status, String)> {
+        let todo_item = line
+            .strip_prefix(self.todo.as_str())
+            .map(|title| (Status::Todo, title));
+        let done_item = line
+            .strip_prefix(self.done.as_str())
+            .map(|title| (Status::Done, title));
+        todo_item
+            .or(done_item)
+            .map(|(status, title)| (status, join_meta(title)))
+    }
+
+    // Like `todo: title`, `TODO:title`, `TODO title` or an indented item
+    fn parse_ill_formed(&self, line: &str) -> Option<Option<(Status, String)>> {
+        let trimmed = line.trim_start();
+        let word_end = trimmed
+            .find(|c: char| c == ':' || c.is_whitespace())
+            .unwrap_or(trimmed.len());
+        let (word, rest) = trimmed.split_at(word_end);
+        let status = [Status::Todo, Status::Done]
+            .iter()
+            .copied()
+            .find(|&status| {
+                match keyword(self.prefix(status)) {
+                    Some(keyword) if word == keyword => true,
+                    // Lowercase words at the start of a line are likely just text, unless there is a
+                    // colon
+                    Some(keyword) => word.eq_ignore_ascii_case(keyword) && rest.starts_with(':'),
+                    None => false,
+                }
+            })?;
+        let title = rest.trim_start_matches(':').trim();
+        if title.is_empty() {
+            Some(None)
+        } else {
+            Some(Some((status, title.to_string())))
+        }
+    }
+
+    fn is_plain_done(&self, line: &str) -> bool {
+        line.starts_with(self.done.as_str()) && !line.starts_with(self.todo.as_str())
+    }
+
+    fn write_item(&self, status: Status, title: &str) -> String {
+        let (meta, words): (Vec<&str>, Vec<&str>) =
+            title.split(' ').partition(|word| is_own_meta(word));
+        if meta.is_empty() {
+            format!("{}{}", self.prefix(status), title)
+        } else {
+            format!(
+                "{}{}{}{}",
+                self.prefix(status),
+                words.join(" "),
+                META_SEPARATOR,
+                meta.join(" ")
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(line: &str) -> String {
+        let format = Todo::default();
+        let (status, title) = format.parse_item(line).unwrap();
+        format.write_item(status, &title)
+    }
+
+    #[test]
+    fn keeps_the_words_that_only_look_like_metadata() {
+        for line in [
+            "TODO: meet at 10:30 in room 4",
+            "TODO: read ch 3:4 today",
+            "DONE: crop to 16:9",
+            "TODO: write to mailto:someone@example.com",
+            "TODO: ratio | 16:9",
+            "TODO: see https://example.com/a:b",
+        ] {
+            assert_eq!(round_trip(line), line);
+        }
+    }
+
+    #[test]
+    fn moves_the_own_metadata_after_the_bar() {
+        let format = Todo::default();
+        assert_eq!(
+            format.write_item(Status::Todo, "meet at 10:30 due:2024-06-01 #work prio:A"),
+            "TODO: meet at 10:30 #work | due:2024-06-01 prio:A"
+        );
+        let line = "DONE: buy milk #home | done:2024-06-02 created:2024-06-01";
+        assert_eq!(
+            format.parse_item(line),
+            Some((
+                Status::Done,
+                "buy milk #home done:2024-06-02 created:2024-06-01".to_string()
+            ))
+        );
+        assert_eq!(round_trip(line), line);
+    }
+
+    #[test]
+    fn keeps_the_metadata_only_items() {
+        let format = Todo::default();
+        let line = format.write_item(Status::Todo, "due:2024-06-01");
+        assert_eq!(
+            format.parse_item(&line),
+            Some((Status::Todo, "due:2024-06-01".to_string()))
+        );
+    }
+}
@@ -0,0 +1,158 @@
+use super::Format;
+use crate::date::Date;
+use crate::{item_date, item_meta, item_set_meta, Status};
+
+/// <http://todotxt.org>. Every line is an item there:
+///
+/// ```text
+/// (A) 2021-06-30 title +project @context key:value
+/// x 2021-07-02 2021-06-30 title +project @context key:value
+/// ```
+///
+/// The priority becomes prio:A, the creation date created: and the completion date done:, the way
+/// the items of the app carry them. The projects and the contexts are tags already.
+pub struct TodoTxt;
+
+fn parse_priority(word: &str) -> Option<char> {
+    match word.as_bytes() {
+        [b'(', priority @ b'A'..=b'Z', b')'] => Some(*priority as char),
+        _ => None,
+    }
+}
+
+// Takes the date off the start of the words if it's there
+fn take_date(words: &mut &[&str]) -> Option<Date> {
+    let date = Date::parse(words.first()?)?;
+    *words = &words[1..];
+    Some(date)
+}
+
+impl Format for TodoTxt {
+    fn parse_item(&self, line: &str) -> Option<(Status, String)> {
+        if line.trim().is_empty() {
+            return None;
+        }
+        let all: Vec<&str> = line.split(' ').collect();
+        let mut words = all.as_slice();
+        let mut meta = Vec::new();
+        let status = if words.first() == Some(&"x") {
+            words = &words[1..];
+            // The creation date can only be there if the completion date is
+            if let Some(done) = take_date(&mut words) {
+                meta.push(("done", done));
+                if let Some(created) = take_date(&mut words) {
+                    meta.push(("created", created));
+                }
+            }
+            Status::Done
+        } else {
+            Status::Todo
+        };
+        let mut priority = None;
+        if status == Status::Todo {
+            priority = words.first().and_then(|word| parse_priority(word));
+            if priority.is_some() {
+                words = &words[1..];
+            }
+            if let Some(created) = take_date(&mut words) {
+                meta.push(("created", created));
+            }
+        }
+
+        let mut title = words.join(" ");
+        if let Some(priority) = priority {
+            item_set_meta(&mut title, "prio", Some(&priority.to_string()));
+        }
+        for (key, date) in meta {
+            item_set_meta(&mut title, key, Some(&date.to_string()));
+        }
+        Some((status, title))
+    }
+
+    // Every line is an item of todo.txt, but only a few of them look like it
+    fn sniff(&self, line: &str) -> bool {
+        let mut words = line.split(' ');
+        match words.next() {
+            Some("x") => words.next().and_then(Date::parse).is_some(),
+            Some(word) => parse_priority(word).is_some(),
+            None => false,
+        }
+    }
+
+    fn is_plain_done(&self, line: &str) -> bool {
+        line.starts_with("x ")
+    }
+
+    fn write_item(&self, status: Status, title: &str) -> String {
+        let mut title = title.to_string();
+        let mut line = Vec::new();
+        let created = item_date(&title, "created");
+        match status {
+            Status::Todo => {
+                let priority = item_meta(&title, "prio")
+                    .filter(|prio| prio.len() == 1 && prio.as_bytes()[0].is_ascii_uppercase())
+                    .map(|prio| format!("({})", prio));
+                if let Some(priority) = priority {
+                    item_set_meta(&mut title, "prio", None);
+                    line.push(priority);
+                }
+                if let Some(created) = created {
+                    item_set_meta(&mut title, "created", None);
+                    line.push(created.to_string());
+                }
+            }
+            Status::Done => {
+                line.push("x".to_string());
+                // Without the completion date a lone creation date would be taken for one
+                if let Some(done) = item_date(&title, "done") {
+                    item_set_meta(&mut title, "done", None);
+                    line.push(done.to_string());
+                    if let Some(created) = created {
+                        item_set_meta(&mut title, "created", None);
+                        line.push(created.to_string());
+                    }
+                }
+            }
+        }
+        line.push(title);
+        line.join(" ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_what_it_reads() {
+        for line in [
+            "(A) 2021-06-30 call mom +family @phone key:value",
+            "2021-06-30 buy milk",
+            "buy milk",
+            "x 2021-07-02 2021-06-30 call mom +family",
+            "x 2021-07-02 buy milk",
+            "x buy milk",
+        ] {
+            let (status, title) = TodoTxt.parse_item(line).unwrap();
+            assert_eq!(TodoTxt.write_item(status, &title), line);
+        }
+    }
+
+    #[test]
+    fn turns_the_dates_and_the_priority_into_metadata() {
+        assert_eq!(
+            TodoTxt.parse_item("(B) 2021-06-30 buy milk"),
+            Some((
+                Status::Todo,
+                "buy milk prio:B created:2021-06-30".to_string()
+            ))
+        );
+        assert_eq!(
+            TodoTxt.parse_item("x 2021-07-02 2021-06-30 buy milk"),
+            Some((
+                Status::Done,
+                "buy milk done:2021-07-02 created:2021-06-30".to_string()
+            ))
+        );
+    }
+}
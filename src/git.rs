@@ -0,0 +1,215 @@
+use std::collections::HashSet;
+use std::fs;
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use todo_rs::{dedupe_key, plain_title};
+
+use crate::config::Config;
+
+// Which of the files are committed after each save, by `autocommit` of the [git] section
+enum Files {
+    None,
+    All,
+    // The canonical paths, so any path to the same file counts
+    Only(Vec<PathBuf>),
+}
+
+// Commits the file after it's saved, if it's one of the files and it lives in a git repository.
+// Nothing is pushed, that's up to the user or the on_save hook.
+pub struct AutoCommit {
+    files: Files,
+    // What the autosaves changed in each file since its last commit. git is too slow to run every
+    // few seconds, so they go into the commit of the next save by hand, or the one on quitting.
+    pending: Vec<(String, Vec<String>)>,
+}
+
+impl Default for AutoCommit {
+    fn default() -> Self {
+        Self {
+            files: Files::None,
+            pending: Vec::new(),
+        }
+    }
+}
+
+impl AutoCommit {
+    pub fn from_config(config: &Config) -> Result<Self, String> {
+        let files = match config.get("git", "autocommit") {
+            None | Some("") | Some("off") => Files::None,
+            Some("on") => Files::All,
+            Some(value) => Files::Only(
+                value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|path| !path.is_empty())
+                    .map(|path| {
+                        fs::canonicalize(path).map_err(|error| {
+                            let message = format!("could not find `{}`: {}", path, error);
+                            config.error("git", "autocommit", &message)
+                        })
+                    })
+                    .collect::<Result<_, _>>()?,
+            ),
+        };
+        Ok(Self {
+            files,
+            pending: Vec::new(),
+        })
+    }
+
+    // The changes of an autosave, for the next commit of the file
+    pub fn defer(&mut self, file_path: &str, changes: Vec<String>) {
+        if matches!(self.files, Files::None) {
+            return;
+        }
+        match self.pending.iter_mut().find(|(path, _)| path == file_path) {
+            Some((_, pending)) => pending.extend(changes),
+            None => self.pending.push((file_path.to_string(), changes)),
+        }
+    }
+
+    // Commits the changes along with those of the autosaves before. They wait for the next time
+    // if git fails.
+    pub fn commit_changes(&mut self, file_path: &str, changes: Vec<String>) -> Result<(), String> {
+        let mut all = match self.pending.iter().position(|(path, _)| path == file_path) {
+            Some(index) => self.pending.remove(index).1,
+            None => Vec::new(),
+        };
+        all.extend(changes);
+        let result = self.commit(file_path, &describe(&all));
+        if result.is_err() {
+            self.defer(file_path, all);
+        }
+        result
+    }
+
+    // Commits whatever the autosaves left, the errors start with the paths of their files
+    pub fn flush(&mut self) -> Vec<String> {
+        let mut errors = Vec::new();
+        for (file_path, changes) in mem::take(&mut self.pending) {
+            if let Err(error) = self.commit_changes(&file_path, changes) {
+                errors.push(format!("{}: {}", file_path, error));
+            }
+        }
+        errors
+    }
+
+    fn wants(&self, path: &Path) -> bool {
+        match &self.files {
+            Files::None => false,
+            Files::All => true,
+            Files::Only(paths) => fs::canonicalize(path).is_ok_and(|path| paths.contains(&path)),
+        }
+    }
+
+    // Nothing happens if the file is not one of the files, not in a repository or the same as in
+    // the last commit
+    pub fn commit(&self, file_path: &str, message: &str) -> Result<(), String> {
+        let path = Path::new(file_path);
+        if !self.wants(path) {
+            return Ok(());
+        }
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        let name = path.file_name().unwrap_or(path.as_os_str());
+        if git(dir, &["rev-parse", "--is-inside-work-tree"]).is_err() {
+            return Ok(());
+        }
+        let mut add = Command::new("git");
+        add.current_dir(dir).args(["add", "--"]).arg(name);
+        run(add)?;
+        let mut diff = Command::new("git");
+        diff.current_dir(dir)
+            .args(["diff", "--cached", "--quiet", "--"])
+            .arg(name);
+        if run(diff).is_ok() {
+            return Ok(());
+        }
+        let mut commit = Command::new("git");
+        commit
+            .current_dir(dir)
+            .args(["commit", "--quiet", "-m", message, "--"])
+            .arg(name);
+        run(commit)
+    }
+}
+
+fn git(dir: &Path, args: &[&str]) -> Result<(), String> {
+    let mut command = Command::new("git");
+    command.current_dir(dir).args(args);
+    run(command)
+}
+
+// The error is what git said about it, or how it failed to start
+fn run(mut command: Command) -> Result<(), String> {
+    let output = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .output()
+        .map_err(|error| format!("could not run git: {}", error))?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        Err(stderr.lines().next().unwrap_or("git failed").to_string())
+    }
+}
+
+// What happened to the items since the last save, e.g. "done: buy milk". The items are told apart
+// by their titles without the metadata, so marking one done is not mistaken for a new one.
+pub fn message(saved: (&[String], &[String]), lists: (&[String], &[String])) -> String {
+    describe(&changes(saved, lists))
+}
+
+// The lines of message(), one for each item
+pub fn changes(saved: (&[String], &[String]), lists: (&[String], &[String])) -> Vec<String> {
+    let keys = |list: &[String]| list.iter().map(|item| dedupe_key(item)).collect::<Vec<_>>();
+    let (todo_keys, done_keys) = (keys(lists.0), keys(lists.1));
+    let (saved_todo_keys, saved_done_keys) = (keys(saved.0), keys(saved.1));
+    let set = |keys: &[String]| keys.iter().cloned().collect::<HashSet<_>>();
+    let (todos, dones) = (set(&todo_keys), set(&done_keys));
+    let (saved_todos, saved_dones) = (set(&saved_todo_keys), set(&saved_done_keys));
+
+    let mut changes = Vec::new();
+    for (item, key) in lists.1.iter().zip(&done_keys) {
+        if !saved_dones.contains(key) {
+            changes.push(format!("done: {}", plain_title(item)));
+        }
+    }
+    for (item, key) in lists.0.iter().zip(&todo_keys) {
+        if saved_dones.contains(key) && !saved_todos.contains(key) {
+            changes.push(format!("undone: {}", plain_title(item)));
+        } else if !saved_todos.contains(key) {
+            changes.push(format!("add: {}", plain_title(item)));
+        }
+    }
+    for (item, key) in saved
+        .0
+        .iter()
+        .chain(saved.1)
+        .zip(saved_todo_keys.iter().chain(&saved_done_keys))
+    {
+        if !todos.contains(key) && !dones.contains(key) {
+            changes.push(format!("delete: {}", plain_title(item)));
+        }
+    }
+    changes
+}
+
+fn describe(changes: &[String]) -> String {
+    match changes {
+        [] => "update".to_string(),
+        [change] => change.clone(),
+        [first, rest @ ..] => format!(
+            "{} and {} more\n\n{}",
+            first,
+            rest.len(),
+            changes.join("\n")
+        ),
+    }
+}
@@ -0,0 +1,282 @@
+use std::env;
+
+use todo_rs::json::{self, Value};
+use todo_rs::{item_meta, item_set_meta, item_tags, meta_word, Status};
+
+use crate::config::Config;
+use crate::http;
+use crate::sync::{self, Service, State, Synced};
+
+// The open issues of a GitHub repository as the TODO items and the closed ones as the DONE items.
+// The items keep the number of their issue in issue:, the new TODO items open new issues and moving
+// an item to the other list closes or reopens its issue. Whatever changes while offline waits for
+// the next sync, which tells it apart by the state of the last one, kept in <file>.github:
+//
+//     [github]
+//     repo = "tsoding/todo-rs"
+//     # The variable with the token, GITHUB_TOKEN by default
+//     token_env = "GITHUB_TOKEN"
+//     # Of GitHub Enterprise, https://api.github.com by default
+//     api = "https://github.example.com/api/v3"
+//
+// The pull requests are left out, and so are the issues of the items deleted here. Deleting an
+// item doesn't touch its issue, there is no deleting the issues on GitHub anyway.
+#[derive(Clone)]
+pub struct GitHub {
+    api: String,
+    // owner/name
+    repo: String,
+    token_env: String,
+}
+
+const API: &str = "https://api.github.com";
+
+// As many as GitHub gives in a single page
+const PER_PAGE: usize = 100;
+
+// An issue as an item
+struct Issue {
+    number: String,
+    status: Status,
+    title: String,
+}
+
+fn text<'a>(value: &'a Value, key: &str) -> Option<&'a str> {
+    match value.get(key)? {
+        Value::String(text) => Some(text),
+        _ => None,
+    }
+}
+
+fn issue(value: &Value) -> Option<Issue> {
+    if value.get("pull_request").is_some() {
+        return None;
+    }
+    let number = match value.get("number")? {
+        Value::Number(number) => (*number as u64).to_string(),
+        _ => return None,
+    };
+    // The metadata of the items is theirs, whatever looks like it in the title of an issue is not
+    let mut title = text(value, "title")?
+        .split_whitespace()
+        .filter(|word| meta_word(word).is_none())
+        .collect::<Vec<_>>()
+        .join(" ");
+    if let Some(Value::Array(labels)) = value.get("labels") {
+        for label in labels.iter().filter_map(|label| text(label, "name")) {
+            let tag = label.split_whitespace().collect::<Vec<_>>().join("-");
+            if !tag.is_empty() && !item_tags(&title).any(|other| other[1..] == tag) {
+                title.push_str(&format!(" #{}", tag));
+            }
+        }
+    }
+    let status = match text(value, "state")? {
+        "closed" => Status::Done,
+        _ => Status::Todo,
+    };
+    if status == Status::Done {
+        let closed = text(value, "closed_at").and_then(|date| date.get(..10));
+        sync::set_status(&mut title, Status::Done, closed);
+    }
+    item_set_meta(&mut title, "issue", Some(&number));
+    Some(Issue {
+        number,
+        status,
+        title,
+    })
+}
+
+impl GitHub {
+    pub fn from_config(config: &Config) -> Result<Option<Self>, String> {
+        let repo = match config.get("github", "repo").filter(|repo| !repo.is_empty()) {
+            Some(repo) => repo,
+            None if config.section("github").is_empty() => return Ok(None),
+            None => return Err(config.error("github", "repo", "the repository is missing")),
+        };
+        let valid = |part: &str| {
+            !part.is_empty()
+                && part
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+        };
+        match repo.split_once('/') {
+            Some((owner, name)) if valid(owner) && valid(name) => {}
+            _ => {
+                let message = format!("expected the repository as owner/name but got `{}`", repo);
+                return Err(config.error("github", "repo", &message));
+            }
+        }
+        let token_env = config
+            .get("github", "token_env")
+            .filter(|name| !name.is_empty())
+            .unwrap_or("GITHUB_TOKEN");
+        let api = config
+            .get("github", "api")
+            .filter(|api| !api.is_empty())
+            .unwrap_or(API);
+        Ok(Some(Self {
+            api: api.trim_end_matches('/').to_string(),
+            repo: repo.to_string(),
+            token_env: token_env.to_string(),
+        }))
+    }
+
+    fn request(
+        &self,
+        token: &str,
+        method: &str,
+        path: &str,
+        body: Option<&Value>,
+    ) -> Result<Value, String> {
+        let url = format!("{}/repos/{}/{}", self.api, self.repo, path);
+        let authorization = format!("Authorization: Bearer {}", token);
+        let headers = [
+            "Accept: application/vnd.github+json",
+            "X-GitHub-Api-Version: 2022-11-28",
+            "Content-Type: application/json",
+            &authorization,
+        ];
+        let body = body.map(|body| body.to_string());
+        let response = http::request(method, &url, &headers, None, body.as_deref())?;
+        json::parse(&response).map_err(|error| format!("{} {}: {}", method, url, error))
+    }
+
+    fn fetch(&self, token: &str) -> Result<Vec<Issue>, String> {
+        let mut issues = Vec::new();
+        for page in 1.. {
+            let path = format!("issues?state=all&per_page={}&page={}", PER_PAGE, page);
+            let values = match self.request(token, "GET", &path, None)? {
+                Value::Array(values) => values,
+                _ => return Err(format!("expected the issues of {}", self.repo)),
+            };
+            issues.extend(values.iter().filter_map(issue));
+            if values.len() < PER_PAGE {
+                break;
+            }
+        }
+        Ok(issues)
+    }
+
+    fn set_state(&self, token: &str, number: &str, status: Status) -> Result<(), String> {
+        let state = match status {
+            Status::Todo => "open",
+            Status::Done => "closed",
+        };
+        let body = Value::Object(vec![(
+            "state".to_string(),
+            Value::String(state.to_string()),
+        )]);
+        self.request(token, "PATCH", &format!("issues/{}", number), Some(&body))
+            .map(|_| ())
+    }
+
+    fn reconcile(
+        &self,
+        token: &str,
+        base: &State,
+        issues: &[Issue],
+        todos: &mut Vec<String>,
+        dones: &mut Vec<String>,
+        synced: &mut Synced,
+    ) -> Result<(), String> {
+        for issue in issues.iter() {
+            let number = Some(issue.number.as_str());
+            let local = todos
+                .iter()
+                .position(|item| item_meta(item, "issue") == number)
+                .map(|index| (Status::Todo, index))
+                .or_else(|| {
+                    dones
+                        .iter()
+                        .position(|item| item_meta(item, "issue") == number)
+                        .map(|index| (Status::Done, index))
+                });
+            let entry = (issue.status, String::new());
+            match local {
+                Some((status, _)) if status == issue.status => {
+                    synced.state.insert(issue.number.clone(), entry);
+                }
+                // It was the same on both sides the last time, so GitHub is the one that changed
+                Some((status, index))
+                    if base.get(&issue.number).map(|(base, _)| *base) == Some(status) =>
+                {
+                    let (from, to) = match status {
+                        Status::Todo => (&mut *todos, &mut *dones),
+                        Status::Done => (&mut *dones, &mut *todos),
+                    };
+                    let mut item = from.remove(index);
+                    sync::set_status(&mut item, issue.status, item_meta(&issue.title, "done"));
+                    to.push(item);
+                    synced.pulled += 1;
+                    synced.state.insert(issue.number.clone(), entry);
+                }
+                // Either ours changed or both did, ours wins then
+                Some((status, _)) => {
+                    self.set_state(token, &issue.number, status)?;
+                    synced.pushed += 1;
+                    synced
+                        .state
+                        .insert(issue.number.clone(), (status, String::new()));
+                }
+                // Deleted here, so it stays out of the lists
+                None if base.contains_key(&issue.number) => {
+                    synced.state.insert(issue.number.clone(), entry);
+                }
+                None => {
+                    match issue.status {
+                        Status::Todo => todos.push(issue.title.clone()),
+                        Status::Done => dones.push(issue.title.clone()),
+                    }
+                    synced.pulled += 1;
+                    synced.state.insert(issue.number.clone(), entry);
+                }
+            }
+        }
+
+        // The new TODO items open new issues. The ones with an issue: that GitHub doesn't know are
+        // left alone, they may be from another repository.
+        for todo in todos.iter_mut() {
+            if item_meta(todo, "issue").is_some() {
+                continue;
+            }
+            let title: Vec<&str> = todo
+                .split(' ')
+                .filter(|word| !word.is_empty() && meta_word(word).is_none())
+                .collect();
+            let body = Value::Object(vec![("title".to_string(), Value::String(title.join(" ")))]);
+            let number = match self
+                .request(token, "POST", "issues", Some(&body))?
+                .get("number")
+            {
+                Some(Value::Number(number)) => (*number as u64).to_string(),
+                _ => return Err(format!("GitHub did not say the number of `{}`", todo)),
+            };
+            item_set_meta(todo, "issue", Some(&number));
+            synced.pushed += 1;
+            synced.state.insert(number, (Status::Todo, String::new()));
+        }
+        Ok(())
+    }
+}
+
+impl Service for GitHub {
+    const NAME: &'static str = "github";
+
+    fn sync(
+        &self,
+        file_path: &str,
+        todos: &mut Vec<String>,
+        dones: &mut Vec<String>,
+    ) -> Result<Synced, String> {
+        let token = env::var(&self.token_env)
+            .ok()
+            .filter(|token| !token.is_empty())
+            .ok_or_else(|| format!("${} has no GitHub token", self.token_env))?;
+        let base = sync::load_state(file_path, Self::NAME);
+        let issues = self.fetch(&token)?;
+        let mut synced = Synced::new(Self::NAME);
+        let result = self.reconcile(&token, &base, &issues, todos, dones, &mut synced);
+        synced.finish(base, result);
+        Ok(synced)
+    }
+}
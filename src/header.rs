@@ -0,0 +1,95 @@
+use crate::format::Kind;
+
+// Whatever may come before `todo-rs:` so the header is a comment in the format of the file, or at
+// least doesn't show in its rendering like in markdown
+const COMMENT_MARKERS: &[&str] = &["<!--", "//", "#", ";", "%"];
+
+/// The layouts of the panels of the app
+pub const LAYOUTS: &[&str] = &["split", "stacked", "single"];
+
+/// The settings a file carries on its first line for everybody who opens it, over the ones of the
+/// config:
+///
+/// ```text
+/// # todo-rs: format=markdown layout=stacked
+/// <!-- todo-rs: format=markdown columns=Backlog,Shipped -->
+/// ```
+///
+/// The line stays in the file as it is, and is never an item.
+#[derive(Default)]
+pub struct Header {
+    /// The format of the file, over the one its name and its contents tell
+    pub format: Option<Kind>,
+    /// One of LAYOUTS
+    pub layout: Option<&'static str>,
+    /// The names of the TODO and the DONE panels, there are no other columns
+    pub columns: Option<[String; 2]>,
+    /// The settings that could not be made sense of, for the warnings. A file is shared with the
+    /// other versions of the app, so they are not errors.
+    pub problems: Vec<String>,
+}
+
+/// None if the line is not a header
+pub fn parse(line: &str) -> Option<Header> {
+    let mut line = line.trim();
+    for marker in COMMENT_MARKERS {
+        if let Some(rest) = line.strip_prefix(marker) {
+            line = rest.trim_start_matches(marker).trim_start();
+            break;
+        }
+    }
+    let settings = line.strip_prefix("todo-rs:")?;
+    let settings = settings.trim_end();
+    let settings = settings.strip_suffix("-->").unwrap_or(settings);
+    let mut header = Header::default();
+    for setting in settings.split_whitespace() {
+        let (key, value) = setting.split_once('=').unwrap_or((setting, ""));
+        match key {
+            "format" => match Kind::from_name(value) {
+                Some(kind) => header.format = Some(kind),
+                None => header.problems.push(format!("unknown format `{}`", value)),
+            },
+            "layout" => match LAYOUTS.iter().find(|&&layout| layout == value) {
+                Some(layout) => header.layout = Some(layout),
+                None => header.problems.push(format!("unknown layout `{}`", value)),
+            },
+            "columns" => match value.split(',').map(str::trim).collect::<Vec<_>>()[..] {
+                [todo, done] if !todo.is_empty() && !done.is_empty() => {
+                    header.columns = Some([todo.to_string(), done.to_string()])
+                }
+                _ => header.problems.push(format!(
+                    "`columns` takes the names of the TODO and the DONE panels, not `{}`",
+                    value
+                )),
+            },
+            _ => header.problems.push(format!("unknown setting `{}`", key)),
+        }
+    }
+    Some(header)
+}
+
+/// The header on the first line of the file, if there is one
+pub fn read(data: &[u8]) -> Option<Header> {
+    let line = data.split(|&byte| byte == b'\n').next()?;
+    parse(std::str::from_utf8(line).ok()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_the_columns() {
+        let header = parse("<!-- todo-rs: format=markdown columns=Backlog,Shipped -->").unwrap();
+        assert!(matches!(header.format, Some(Kind::Markdown)));
+        assert_eq!(
+            header.columns,
+            Some(["Backlog".to_string(), "Shipped".to_string()])
+        );
+        assert!(header.problems.is_empty());
+
+        let header = parse("# todo-rs: columns=Backlog,Doing,Done").unwrap();
+        assert_eq!(header.columns, None);
+        assert_eq!(header.problems.len(), 1);
+    }
+}
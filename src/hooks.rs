@@ -0,0 +1,112 @@
+use std::io::{self, Write};
+use std::process::{Child, Command, Stdio};
+
+use crate::config::Config;
+
+// What happens to the items that a command can be hooked on, by the keys of the [hooks] section
+#[derive(Clone, Copy, PartialEq)]
+pub enum Hook {
+    // A new TODO item
+    Add,
+    // A TODO item moved to DONE
+    Done,
+    // A DONE item deleted for good
+    Delete,
+    // The file is written
+    Save,
+}
+
+impl Hook {
+    const ALL: [Hook; 4] = [Hook::Add, Hook::Done, Hook::Delete, Hook::Save];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Hook::Add => "on_add",
+            Hook::Done => "on_done",
+            Hook::Delete => "on_delete",
+            Hook::Save => "on_save",
+        }
+    }
+}
+
+// The commands are run by the shell, so they can be pipelines and use the variables: $TODO_HOOK is
+// the name of the hook, $TODO_FILE the file and $TODO_ITEM the item, which comes on stdin as well.
+// Nobody waits for them but the exit of the app, and their output is thrown away, since the screen
+// belongs to the app.
+#[derive(Default)]
+pub struct Hooks {
+    commands: Vec<(Hook, String)>,
+    running: Vec<Child>,
+}
+
+impl Hooks {
+    pub fn from_config(config: &Config) -> Result<Self, String> {
+        let mut hooks = Self::default();
+        for (key, command) in config.section("hooks") {
+            let hook = Hook::ALL
+                .iter()
+                .find(|hook| hook.name() == key)
+                .ok_or_else(|| {
+                    let names: Vec<&str> = Hook::ALL.iter().map(|hook| hook.name()).collect();
+                    let message = format!(
+                        "unknown hook `{}`, expected one of {}",
+                        key,
+                        names.join(", ")
+                    );
+                    config.error("hooks", key, &message)
+                })?;
+            // An empty string turns the hook off
+            if !command.is_empty() {
+                hooks.commands.push((*hook, command.to_string()));
+            }
+        }
+        Ok(hooks)
+    }
+
+    pub fn run(&mut self, hook: Hook, file_path: &str, item: &str) -> io::Result<()> {
+        // The ones that are done already are not zombies for longer than they have to be
+        self.running
+            .retain_mut(|child| !matches!(child.try_wait(), Ok(Some(_))));
+        let command = match self.commands.iter().find(|(other, _)| *other == hook) {
+            Some((_, command)) => command,
+            None => return Ok(()),
+        };
+        let mut child = shell(command)
+            .env("TODO_HOOK", hook.name())
+            .env("TODO_FILE", file_path)
+            .env("TODO_ITEM", item)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()?;
+        // The command doesn't have to read it, so a closed pipe is fine. Dropping stdin closes it,
+        // otherwise the command could wait for more input forever.
+        if let Some(mut stdin) = child.stdin.take() {
+            let _ = writeln!(stdin, "{}", item);
+        }
+        self.running.push(child);
+        Ok(())
+    }
+}
+
+impl Drop for Hooks {
+    fn drop(&mut self) {
+        for child in self.running.iter_mut() {
+            let _ = child.wait();
+        }
+    }
+}
+
+#[cfg(unix)]
+pub fn shell(command: &str) -> Command {
+    let mut shell = Command::new("sh");
+    shell.arg("-c").arg(command);
+    shell
+}
+
+#[cfg(windows)]
+pub fn shell(command: &str) -> Command {
+    let mut shell = Command::new("cmd");
+    shell.arg("/C").arg(command);
+    shell
+}
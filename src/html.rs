@@ -0,0 +1,114 @@
+use crate::date::Date;
+use crate::{item_date, item_tags, meta_word, Status};
+
+// A page to share the lists with the people who would never run a terminal app. Everything it
+// needs is in it, so it can be sent around as a single file.
+
+const STYLE: &str = "\
+body { font-family: sans-serif; max-width: 48em; margin: 2em auto; padding: 0 1em; color: #222; }
+h1 { font-size: 1.5em; }
+h2 { font-size: 1.2em; border-bottom: 1px solid #ddd; padding-bottom: .2em; }
+ul { list-style: none; padding: 0; }
+li { display: flex; align-items: baseline; gap: .5em; padding: .3em 0; }
+.box { flex: none; width: .9em; height: .9em; border: 2px solid #888; border-radius: 3px; }
+.done .box { background: #4a4; border-color: #4a4; }
+.done .box::after { content: '\\2713'; color: #fff; font-size: .8em; display: block; text-align: center; line-height: .9em; }
+.done .title { color: #888; text-decoration: line-through; }
+.tag { background: #eef; color: #336; border-radius: 3px; padding: 0 .3em; font-size: .85em; }
+.date { color: #666; font-size: .85em; }
+.overdue { color: #c22; }
+form { display: flex; gap: .5em; }
+form input[type=text] { flex: auto; }
+";
+
+fn escape(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => result.push_str("&amp;"),
+            '<' => result.push_str("&lt;"),
+            '>' => result.push_str("&gt;"),
+            '"' => result.push_str("&quot;"),
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+fn item(out: &mut String, status: Status, title: &str, today: Date) {
+    let words: Vec<&str> = title
+        .split(' ')
+        .filter(|word| meta_word(word).is_none())
+        .collect();
+    let class = match status {
+        Status::Todo => "todo",
+        Status::Done => "done",
+    };
+    out.push_str(&format!(
+        "<li class=\"{}\"><span class=\"box\"></span>",
+        class
+    ));
+    out.push_str(&format!(
+        "<span class=\"title\">{}</span>",
+        escape(&words.join(" "))
+    ));
+    for tag in item_tags(title) {
+        out.push_str(&format!("<span class=\"tag\">{}</span>", escape(tag)));
+    }
+    match status {
+        Status::Todo => {
+            if let Some(due) = item_date(title, "due") {
+                let class = if due < today { "date overdue" } else { "date" };
+                out.push_str(&format!("<span class=\"{}\">due {}</span>", class, due));
+            }
+        }
+        Status::Done => {
+            if let Some(done) = item_date(title, "done") {
+                out.push_str(&format!("<span class=\"date\">done {}</span>", done));
+            }
+        }
+    }
+    out.push_str("</li>\n");
+}
+
+/// A page of the lists that needs nothing else to show, under the title
+pub fn export(title: &str, todos: &[String], dones: &[String]) -> String {
+    page(title, todos, dones, None)
+}
+
+/// export() with a form that posts the title of a new TODO item to the action, for `todo-rs serve`
+pub fn page(title: &str, todos: &[String], dones: &[String], add_action: Option<&str>) -> String {
+    let today = Date::today();
+    let mut out = String::new();
+    out.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+    out.push_str(&format!("<title>{}</title>\n", escape(title)));
+    out.push_str(&format!("<style>\n{}</style>\n</head>\n<body>\n", STYLE));
+    out.push_str(&format!("<h1>{}</h1>\n", escape(title)));
+    out.push_str(&format!(
+        "<p>{} to do, {} done as of {}</p>\n",
+        todos.len(),
+        dones.len(),
+        today
+    ));
+    if let Some(action) = add_action {
+        out.push_str(&format!(
+            "<form method=\"post\" action=\"{}\">\
+             <input type=\"text\" name=\"title\" placeholder=\"New TODO item\" required>\
+             <button>Add</button></form>\n",
+            escape(action)
+        ));
+    }
+    for (status, list) in [(Status::Todo, todos), (Status::Done, dones)].iter() {
+        out.push_str(&format!(
+            "<h2>{} ({})</h2>\n<ul>\n",
+            status.name(),
+            list.len()
+        ));
+        for title in list.iter() {
+            item(&mut out, *status, title, today);
+        }
+        out.push_str("</ul>\n");
+    }
+    out.push_str("</body>\n</html>\n");
+    out
+}
@@ -0,0 +1,68 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+// HTTP by curl, which is everywhere already and saves us a TLS stack. It gets the whole request on
+// stdin as its config, so the passwords and the tokens never show up in the command line of a
+// process.
+
+// The value in double quotes the way curl reads it from its config
+fn quote(value: &str) -> String {
+    let mut result = String::with_capacity(value.len() + 2);
+    result.push('"');
+    for c in value.chars() {
+        match c {
+            '\\' => result.push_str("\\\\"),
+            '"' => result.push_str("\\\""),
+            '\r' => result.push_str("\\r"),
+            '\n' => result.push_str("\\n"),
+            '\t' => result.push_str("\\t"),
+            c => result.push(c),
+        }
+    }
+    result.push('"');
+    result
+}
+
+// The body of the response. The error statuses are errors, with what curl has to say about them.
+pub fn request(
+    method: &str,
+    url: &str,
+    headers: &[&str],
+    // user:password
+    user: Option<&str>,
+    body: Option<&str>,
+) -> Result<String, String> {
+    let mut config = format!("url = {}\nrequest = {}\n", quote(url), quote(method));
+    for header in headers {
+        config.push_str(&format!("header = {}\n", quote(header)));
+    }
+    if let Some(user) = user {
+        config.push_str(&format!("user = {}\n", quote(user)));
+    }
+    if let Some(body) = body {
+        config.push_str(&format!("data-binary = {}\n", quote(body)));
+    }
+    let mut child = Command::new("curl")
+        .args(["--silent", "--show-error", "--fail", "--config", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|error| format!("could not run curl: {}", error))?;
+    // Dropping stdin closes it, so curl knows the config is over
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(config.as_bytes())
+            .map_err(|error| format!("could not run curl: {}", error))?;
+    }
+    let output = child
+        .wait_with_output()
+        .map_err(|error| format!("could not run curl: {}", error))?;
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    } else {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let error = stderr.lines().next().unwrap_or("curl failed");
+        Err(format!("{} {}: {}", method, url, error))
+    }
+}
@@ -0,0 +1,347 @@
+use crate::date::Date;
+use crate::{item_date, item_meta, item_set_meta, item_tags, meta_word, Status};
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// The lists as the to-dos of RFC 5545, so they show up in the calendar apps and can be imported
+// into a CalDAV server. Reading them back takes only what the items can keep of them: the summary,
+// the status, the dates, the priority and the categories. The calendars have way more to say about
+// a to-do than that.
+
+// 20210701T000000Z
+fn date_time(date: Date, seconds: u32) -> String {
+    format!(
+        "{:04}{:02}{:02}T{:02}{:02}{:02}Z",
+        date.year,
+        date.month,
+        date.day,
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+// 20210701
+fn date_value(date: Date) -> String {
+    format!("{:04}{:02}{:02}", date.year, date.month, date.day)
+}
+
+fn escape(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' | ';' | ',' => {
+                result.push('\\');
+                result.push(c);
+            }
+            '\n' => result.push_str("\\n"),
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+// The lines are at most 75 bytes long, the rest goes on the next lines starting with a space.
+// Never in the middle of a character though.
+fn push_line(out: &mut String, line: &str) {
+    let mut limit = 75;
+    let mut rest = line;
+    while rest.len() > limit {
+        let mut end = limit;
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+        out.push_str(&rest[..end]);
+        out.push_str("\r\n ");
+        rest = &rest[end..];
+        // The space takes one of the bytes of the next line
+        limit = 74;
+    }
+    out.push_str(rest);
+    out.push_str("\r\n");
+}
+
+// FNV-1a, the UID of an item has to stay the same from one export to the other for the calendars
+// to update their copies instead of adding new ones
+fn hash(text: &str) -> u64 {
+    text.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+    })
+}
+
+// A..I of prio: go to 1..9, which is how the calendars rank them too. A number is taken as it is.
+fn priority(title: &str) -> Option<u32> {
+    let prio = item_meta(title, "prio")?;
+    match prio.as_bytes() {
+        [letter @ b'A'..=b'I'] => Some(u32::from(letter - b'A') + 1),
+        _ => prio.parse().ok().filter(|prio| (1..=9).contains(prio)),
+    }
+}
+
+fn summary(title: &str) -> String {
+    let words: Vec<&str> = title
+        .split(' ')
+        .filter(|word| meta_word(word).is_none())
+        .collect();
+    words.join(" ")
+}
+
+fn stamp() -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs() as i64);
+    let (today, seconds) = Date::from_timestamp(now);
+    date_time(today, seconds)
+}
+
+fn push_vtodo(out: &mut String, status: Status, title: &str, uid: &str, stamp: &str) {
+    push_line(out, "BEGIN:VTODO");
+    push_line(out, &format!("UID:{}", uid));
+    push_line(out, &format!("DTSTAMP:{}", stamp));
+    push_line(out, &format!("SUMMARY:{}", escape(&summary(title))));
+    match status {
+        Status::Todo => push_line(out, "STATUS:NEEDS-ACTION"),
+        Status::Done => push_line(out, "STATUS:COMPLETED"),
+    }
+    if let Some(created) = item_date(title, "created") {
+        push_line(out, &format!("CREATED:{}", date_time(created, 0)));
+    }
+    if let Some(due) = item_date(title, "due") {
+        push_line(out, &format!("DUE;VALUE=DATE:{}", date_value(due)));
+    }
+    if let Some(done) = item_date(title, "done") {
+        push_line(out, &format!("COMPLETED:{}", date_time(done, 0)));
+    }
+    if let Some(priority) = priority(title) {
+        push_line(out, &format!("PRIORITY:{}", priority));
+    }
+    let categories: Vec<String> = item_tags(title).map(|tag| escape(&tag[1..])).collect();
+    if !categories.is_empty() {
+        push_line(out, &format!("CATEGORIES:{}", categories.join(",")));
+    }
+    push_line(out, "END:VTODO");
+}
+
+fn push_header(out: &mut String) {
+    push_line(out, "BEGIN:VCALENDAR");
+    push_line(out, "VERSION:2.0");
+    push_line(out, "PRODID:-//tsoding//todo-rs//EN");
+}
+
+/// A calendar of all the items as VTODOs, for the calendar apps
+pub fn export(todos: &[String], dones: &[String]) -> String {
+    let stamp = stamp();
+    let mut out = String::new();
+    push_header(&mut out);
+    // The items with the same title are told apart by how many of them came before
+    let mut seen: HashMap<u64, usize> = HashMap::new();
+    for (status, list) in [(Status::Todo, todos), (Status::Done, dones)].iter() {
+        for title in list.iter() {
+            let hash = hash(&summary(title));
+            let count = seen.entry(hash).or_default();
+            *count += 1;
+            // The ones that came from a calendar keep the UID they had there
+            let uid = match item_meta(title, "uid") {
+                Some(uid) => uid.to_string(),
+                None => format!("{:016x}-{}@todo-rs", hash, count),
+            };
+            push_vtodo(&mut out, *status, title, &uid, &stamp);
+        }
+    }
+    push_line(&mut out, "END:VCALENDAR");
+    out
+}
+
+/// A calendar of the single item, the way CalDAV stores them. The UID is the uid: of the item.
+pub fn calendar(status: Status, title: &str, uid: &str) -> String {
+    let mut out = String::new();
+    push_header(&mut out);
+    push_vtodo(&mut out, status, title, uid, &stamp());
+    push_line(&mut out, "END:VCALENDAR");
+    out
+}
+
+fn unescape(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match (c, chars.clone().next()) {
+            ('\\', Some('n' | 'N')) => {
+                chars.next();
+                result.push(' ');
+            }
+            ('\\', Some(next @ ('\\' | ';' | ','))) => {
+                chars.next();
+                result.push(next);
+            }
+            // The items are single lines
+            ('\r' | '\n' | '\t', _) => result.push(' '),
+            (c, _) => result.push(c),
+        }
+    }
+    result
+}
+
+// 20210701, 20210701T120000 or 20210701T120000Z, only the day is kept
+fn parse_date(value: &str) -> Option<Date> {
+    let digits = value.get(..8)?;
+    if !digits.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+    Date::new(
+        digits[..4].parse().ok()?,
+        digits[4..6].parse().ok()?,
+        digits[6..].parse().ok()?,
+    )
+}
+
+// The properties of a VTODO as the metadata and the tags of the item. The UID goes to uid:.
+fn item(properties: &[(String, String)]) -> Option<(Status, String)> {
+    let property = |name: &str| {
+        properties
+            .iter()
+            .find(|(other, _)| other == name)
+            .map(|(_, value)| value.as_str())
+    };
+    let uid = property("UID").filter(|uid| !uid.is_empty() && !uid.contains(' '))?;
+    let mut title = unescape(property("SUMMARY")?)
+        .split_whitespace()
+        .filter(|word| meta_word(word).is_none())
+        .collect::<Vec<_>>()
+        .join(" ");
+    if title.is_empty() {
+        return None;
+    }
+    let status = match property("STATUS") {
+        Some("COMPLETED") => Status::Done,
+        Some(_) => Status::Todo,
+        None if property("COMPLETED").is_some() => Status::Done,
+        None => Status::Todo,
+    };
+    for category in properties
+        .iter()
+        .filter(|(name, _)| name == "CATEGORIES")
+        .flat_map(|(_, value)| value.split(','))
+    {
+        let tag = unescape(category)
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join("-");
+        if !tag.is_empty() && !item_tags(&title).any(|other| other[1..] == tag) {
+            title.push_str(&format!(" #{}", tag));
+        }
+    }
+    if let Some(priority) = property("PRIORITY").and_then(|value| value.parse::<u8>().ok()) {
+        if (1..=9).contains(&priority) {
+            let letter = char::from(b'A' + priority - 1).to_string();
+            item_set_meta(&mut title, "prio", Some(&letter));
+        }
+    }
+    for (key, name) in [
+        ("created", "CREATED"),
+        ("due", "DUE"),
+        ("done", "COMPLETED"),
+    ] {
+        if let Some(date) = property(name).and_then(parse_date) {
+            item_set_meta(&mut title, key, Some(&date.to_string()));
+        }
+    }
+    if status == Status::Todo {
+        item_set_meta(&mut title, "done", None);
+    }
+    item_set_meta(&mut title, "uid", Some(uid));
+    Some((status, title))
+}
+
+/// The VTODOs of the calendar that have a UID and a summary, as items
+pub fn import(source: &str) -> Vec<(Status, String)> {
+    // The long lines are folded, the rest of them goes on the next lines starting with a space
+    let mut lines: Vec<String> = Vec::new();
+    for line in source.split('\n') {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        match line.strip_prefix([' ', '\t']) {
+            Some(rest) if !lines.is_empty() => lines.last_mut().unwrap().push_str(rest),
+            _ => lines.push(line.to_string()),
+        }
+    }
+    let mut items = Vec::new();
+    // The properties of the VTODO, while inside of one
+    let mut vtodo: Option<Vec<(String, String)>> = None;
+    // How deep into the components inside the VTODO, like VALARM, whose properties are not its own
+    let mut depth = 0;
+    for line in lines.iter() {
+        let (name, value) = match line.split_once(':') {
+            Some((name, value)) => (name.split(';').next().unwrap_or(name), value),
+            None => continue,
+        };
+        let name = name.to_ascii_uppercase();
+        match (name.as_str(), vtodo.as_mut()) {
+            ("BEGIN", None) if value.eq_ignore_ascii_case("VTODO") => vtodo = Some(Vec::new()),
+            ("BEGIN", Some(_)) => depth += 1,
+            ("END", Some(_)) if depth > 0 => depth -= 1,
+            ("END", Some(properties)) => {
+                items.extend(item(properties));
+                vtodo = None;
+            }
+            (_, Some(properties)) if depth == 0 => {
+                properties.push((name.clone(), value.to_string()))
+            }
+            _ => {}
+        }
+    }
+    items
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn imports_what_it_exports() {
+        let todos = [
+            "write the report, finally; \\o/ #work prio:B created:2021-06-01 due:2021-07-01"
+                .to_string(),
+            "buy milk".to_string(),
+            "buy milk".to_string(),
+        ];
+        let dones = ["call mom done:2021-06-30".to_string()];
+        let items = import(&export(&todos, &dones));
+        let expected = [
+            (Status::Todo, &todos[0]),
+            (Status::Todo, &todos[1]),
+            (Status::Todo, &todos[2]),
+            (Status::Done, &dones[0]),
+        ];
+        assert_eq!(items.len(), expected.len());
+        for ((status, title), (expected_status, expected_title)) in items.iter().zip(expected) {
+            let mut without_uid = title.clone();
+            item_set_meta(&mut without_uid, "uid", None);
+            assert_eq!((status, &without_uid), (&expected_status, expected_title));
+        }
+        // The same items get the same UIDs, and the ones with the same title different ones
+        let uids: Vec<&str> = items
+            .iter()
+            .map(|(_, title)| item_meta(title, "uid").unwrap())
+            .collect();
+        assert_ne!(uids[1], uids[2]);
+        let (todos, dones): (Vec<_>, Vec<_>) = items
+            .iter()
+            .partition(|(status, _)| *status == Status::Todo);
+        let titles = |items: Vec<&(Status, String)>| {
+            items
+                .into_iter()
+                .map(|(_, title)| title.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(import(&export(&titles(todos), &titles(dones))), items);
+    }
+
+    #[test]
+    fn folds_the_long_lines() {
+        let title = "a very long title ".repeat(10).trim().to_string();
+        let exported = export(std::slice::from_ref(&title), &[]);
+        assert!(exported.split("\r\n").all(|line| line.len() <= 75));
+        let (_, imported) = &import(&exported)[0];
+        assert!(imported.starts_with(&format!("{} uid:", title)));
+    }
+}
@@ -0,0 +1,159 @@
+use std::borrow::Cow;
+use std::mem;
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock};
+
+use crate::Tail;
+
+// Every list that is or might be changed gets a revision nobody else has, 0 is for the empty ones
+static REVISIONS: AtomicUsize = AtomicUsize::new(1);
+
+fn next_revision() -> usize {
+    REVISIONS.fetch_add(1, Ordering::Relaxed)
+}
+
+/// The items of a list that tell when they might have changed. Getting to them mutably gives them a
+/// new revision, so the lists with the same revision are the same and need no comparing.
+///
+/// The DONE items at the end of the file may be left in it, see parse_state_lazily(). The frames
+/// only read the ones they show, and the rest are read the first time anything needs all of them.
+#[derive(Default, Clone)]
+pub struct Items {
+    // All of them when there is no tail, the ones before it otherwise
+    items: Vec<String>,
+    tail: Option<Arc<Tail>>,
+    // Along with the tail, once they are needed
+    all: OnceLock<Vec<String>>,
+    revision: usize,
+}
+
+impl Items {
+    /// The items followed by the unread ones of the tail, if any
+    pub fn with_tail(items: Vec<String>, tail: Option<Arc<Tail>>) -> Self {
+        Self {
+            items,
+            tail,
+            all: OnceLock::new(),
+            revision: next_revision(),
+        }
+    }
+
+    /// The same for the lists that are the same, see Items
+    pub fn revision(&self) -> usize {
+        self.revision
+    }
+
+    /// Along with the unread items of the tail, without reading them
+    pub fn len(&self) -> usize {
+        self.items.len() + self.tail.as_ref().map_or(0, |tail| tail.len())
+    }
+
+    /// Without reading the tail either
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The title of the item without reading the rest of the tail
+    pub fn title(&self, index: usize) -> Option<Cow<'_, str>> {
+        if let Some(all) = self.all.get() {
+            return all.get(index).map(|title| Cow::Borrowed(title.as_str()));
+        }
+        match (self.items.get(index), &self.tail) {
+            (Some(title), _) => Some(Cow::Borrowed(title)),
+            (None, Some(tail)) => {
+                let index = index - self.items.len();
+                (index < tail.len()).then(|| Cow::Owned(tail.title(index)))
+            }
+            (None, None) => None,
+        }
+    }
+
+    /// Whether the tail is read, if there is one
+    pub fn is_read(&self) -> bool {
+        self.tail.is_none() || self.all.get().is_some()
+    }
+}
+
+impl From<Vec<String>> for Items {
+    fn from(items: Vec<String>) -> Self {
+        Self::with_tail(items, None)
+    }
+}
+
+impl Deref for Items {
+    type Target = Vec<String>;
+
+    fn deref(&self) -> &Vec<String> {
+        match &self.tail {
+            Some(tail) => self.all.get_or_init(|| {
+                let mut all = self.items.clone();
+                all.extend(tail.titles());
+                all
+            }),
+            None => &self.items,
+        }
+    }
+}
+
+impl DerefMut for Items {
+    fn deref_mut(&mut self) -> &mut Vec<String> {
+        self.revision = next_revision();
+        if let Some(tail) = self.tail.take() {
+            match self.all.take() {
+                Some(all) => self.items = all,
+                None => self.items.extend(tail.titles()),
+            }
+        }
+        &mut self.items
+    }
+}
+
+impl IntoIterator for Items {
+    type Item = String;
+    type IntoIter = std::vec::IntoIter<String>;
+
+    fn into_iter(mut self) -> Self::IntoIter {
+        mem::take(&mut *self).into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Items {
+    type Item = &'a String;
+    type IntoIter = std::slice::Iter<'a, String>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl PartialEq for Items {
+    fn eq(&self, other: &Self) -> bool {
+        self.revision == other.revision || **self == **other
+    }
+}
+
+/// Whether the lists differ from the saved ones, as of the revisions they were last compared at
+#[derive(Default)]
+pub struct Unsaved {
+    revisions: [usize; 4],
+    unsaved: bool,
+}
+
+impl Unsaved {
+    /// Whether the TODO and the DONE lists differ from the saved ones. Only compares them when their
+    /// revisions changed since the last time.
+    pub fn check(&mut self, lists: [&Items; 2], saved: [&Items; 2]) -> bool {
+        let revisions = [
+            lists[0].revision,
+            lists[1].revision,
+            saved[0].revision,
+            saved[1].revision,
+        ];
+        if revisions != self.revisions {
+            self.revisions = revisions;
+            self.unsaved = lists != saved;
+        }
+        self.unsaved
+    }
+}
@@ -0,0 +1,385 @@
+use crate::{item_set_meta, item_tags, meta_word, Status};
+use std::fmt::{self, Write};
+
+/// Just enough of JSON to exchange the lists with scripts and other tools. The objects keep the
+/// order of their keys, so whatever we write comes out the way we wrote it.
+pub enum Value {
+    /// `null`
+    Null,
+    /// `true` or `false`
+    Bool(bool),
+    /// All the numbers are floats, like in JavaScript
+    Number(f64),
+    /// Unescaped
+    String(String),
+    /// `[...]`
+    Array(Vec<Value>),
+    /// `{...}` with the keys in the order they came in
+    Object(Vec<(String, Value)>),
+}
+
+impl Value {
+    /// The value of the key of an object, None for a missing key or anything but an object
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Object(entries) => entries
+                .iter()
+                .find(|(entry_key, _)| entry_key == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    // Numbers and booleans are as good as strings for the metadata
+    fn as_text(&self) -> Option<String> {
+        match self {
+            Value::Bool(value) => Some(value.to_string()),
+            Value::Number(value) => Some(value.to_string()),
+            Value::String(value) => Some(value.clone()),
+            _ => None,
+        }
+    }
+
+    fn write(&self, out: &mut String, indent: usize) -> fmt::Result {
+        match self {
+            Value::Null => out.write_str("null"),
+            Value::Bool(value) => write!(out, "{}", value),
+            Value::Number(value) => write!(out, "{}", value),
+            Value::String(value) => write_string(out, value),
+            Value::Array(values) if values.is_empty() => out.write_str("[]"),
+            Value::Array(values) => {
+                out.write_str("[\n")?;
+                for (index, value) in values.iter().enumerate() {
+                    write!(out, "{:1$}", "", indent + 2)?;
+                    value.write(out, indent + 2)?;
+                    out.write_str(if index + 1 < values.len() {
+                        ",\n"
+                    } else {
+                        "\n"
+                    })?;
+                }
+                write!(out, "{:1$}]", "", indent)
+            }
+            Value::Object(entries) if entries.is_empty() => out.write_str("{}"),
+            Value::Object(entries) => {
+                out.write_str("{\n")?;
+                for (index, (key, value)) in entries.iter().enumerate() {
+                    write!(out, "{:1$}", "", indent + 2)?;
+                    write_string(out, key)?;
+                    out.write_str(": ")?;
+                    value.write(out, indent + 2)?;
+                    out.write_str(if index + 1 < entries.len() {
+                        ",\n"
+                    } else {
+                        "\n"
+                    })?;
+                }
+                write!(out, "{:1$}}}", "", indent)
+            }
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut out = String::new();
+        self.write(&mut out, 0)?;
+        f.write_str(&out)
+    }
+}
+
+fn write_string(out: &mut String, value: &str) -> fmt::Result {
+    out.write_char('"')?;
+    for c in value.chars() {
+        match c {
+            '"' => out.write_str("\\\"")?,
+            '\\' => out.write_str("\\\\")?,
+            '\n' => out.write_str("\\n")?,
+            '\r' => out.write_str("\\r")?,
+            '\t' => out.write_str("\\t")?,
+            c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32)?,
+            c => out.write_char(c)?,
+        }
+    }
+    out.write_char('"')
+}
+
+struct Parser<'a> {
+    source: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, message: &str) -> String {
+        let line = self.source[..self.pos].matches('\n').count() + 1;
+        format!("line {}: {}", line, message)
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = &self.source[self.pos..];
+        self.pos += rest.len() - rest.trim_start_matches([' ', '\t', '\n', '\r']).len();
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.source[self.pos..].chars().next()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        self.skip_whitespace();
+        match self.next() {
+            Some(c) if c == expected => Ok(()),
+            _ => Err(self.error(&format!("expected `{}`", expected))),
+        }
+    }
+
+    fn keyword(&mut self, keyword: &str, value: Value) -> Result<Value, String> {
+        if self.source[self.pos..].starts_with(keyword) {
+            self.pos += keyword.len();
+            Ok(value)
+        } else {
+            Err(self.error("unexpected character"))
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        self.skip_whitespace();
+        match self.peek() {
+            None => Err(self.error("unexpected end of the document")),
+            Some('n') => self.keyword("null", Value::Null),
+            Some('t') => self.keyword("true", Value::Bool(true)),
+            Some('f') => self.keyword("false", Value::Bool(false)),
+            Some('"') => self.string().map(Value::String),
+            Some('[') => {
+                self.next();
+                let mut values = Vec::new();
+                self.skip_whitespace();
+                if self.peek() == Some(']') {
+                    self.next();
+                    return Ok(Value::Array(values));
+                }
+                loop {
+                    values.push(self.value()?);
+                    self.skip_whitespace();
+                    match self.next() {
+                        Some(',') => {}
+                        Some(']') => return Ok(Value::Array(values)),
+                        _ => return Err(self.error("expected `,` or `]`")),
+                    }
+                }
+            }
+            Some('{') => {
+                self.next();
+                let mut entries = Vec::new();
+                self.skip_whitespace();
+                if self.peek() == Some('}') {
+                    self.next();
+                    return Ok(Value::Object(entries));
+                }
+                loop {
+                    self.skip_whitespace();
+                    let key = self.string()?;
+                    self.expect(':')?;
+                    entries.push((key, self.value()?));
+                    self.skip_whitespace();
+                    match self.next() {
+                        Some(',') => {}
+                        Some('}') => return Ok(Value::Object(entries)),
+                        _ => return Err(self.error("expected `,` or `}`")),
+                    }
+                }
+            }
+            Some(_) => {
+                let rest = &self.source[self.pos..];
+                let len = rest
+                    .find(|c: char| !(c.is_ascii_digit() || "+-.eE".contains(c)))
+                    .unwrap_or(rest.len());
+                let number = rest[..len]
+                    .parse()
+                    .map_err(|_| self.error("unexpected character"))?;
+                self.pos += len;
+                Ok(Value::Number(number))
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        if self.next() != Some('"') {
+            return Err(self.error("expected a string"));
+        }
+        let mut result = String::new();
+        loop {
+            match self.next() {
+                None => return Err(self.error("unterminated string")),
+                Some('"') => return Ok(result),
+                Some('\\') => match self.next() {
+                    Some('n') => result.push('\n'),
+                    Some('r') => result.push('\r'),
+                    Some('t') => result.push('\t'),
+                    Some('b') => result.push('\u{8}'),
+                    Some('f') => result.push('\u{c}'),
+                    Some('u') => {
+                        let code = self.code_unit()?;
+                        // The characters outside of the BMP come as surrogate pairs
+                        let code = if (0xd800..0xdc00).contains(&code) {
+                            if !self.source[self.pos..].starts_with("\\u") {
+                                return Err(self.error("unpaired surrogate"));
+                            }
+                            self.pos += 2;
+                            let low = self.code_unit()?;
+                            0x10000 + ((code - 0xd800) << 10) + (low.wrapping_sub(0xdc00) & 0x3ff)
+                        } else {
+                            code
+                        };
+                        result.push(
+                            char::from_u32(code)
+                                .ok_or_else(|| self.error("invalid unicode escape"))?,
+                        );
+                    }
+                    Some(c @ ('"' | '\\' | '/')) => result.push(c),
+                    _ => return Err(self.error("invalid escape")),
+                },
+                Some(c) => result.push(c),
+            }
+        }
+    }
+
+    fn code_unit(&mut self) -> Result<u32, String> {
+        let hex = self
+            .source
+            .get(self.pos..self.pos + 4)
+            .ok_or_else(|| self.error("invalid unicode escape"))?;
+        let code =
+            u32::from_str_radix(hex, 16).map_err(|_| self.error("invalid unicode escape"))?;
+        self.pos += 4;
+        Ok(code)
+    }
+}
+
+/// The whole of the source as a single value, with the error saying where it went wrong
+pub fn parse(source: &str) -> Result<Value, String> {
+    let mut parser = Parser { source, pos: 0 };
+    let value = parser.value()?;
+    parser.skip_whitespace();
+    if parser.pos < source.len() {
+        return Err(parser.error("unexpected data after the document"));
+    }
+    Ok(value)
+}
+
+/// {"status": "todo", "title": "buy milk #home", "tags": ["#home"], "meta": {"due": "2021-07-01"}}.
+/// The title is without the metadata, it's in "meta" instead.
+pub fn item(status: Status, title: &str) -> Vec<(String, Value)> {
+    let words: Vec<&str> = title
+        .split(' ')
+        .filter(|word| meta_word(word).is_none())
+        .collect();
+    let tags = item_tags(title)
+        .map(|tag| Value::String(tag.to_string()))
+        .collect();
+    let meta = title
+        .split(' ')
+        .filter_map(meta_word)
+        .map(|(key, value)| (key.to_string(), Value::String(value.to_string())))
+        .collect();
+    vec![
+        (
+            "status".to_string(),
+            Value::String(status.name().to_lowercase()),
+        ),
+        ("title".to_string(), Value::String(words.join(" "))),
+        ("tags".to_string(), Value::Array(tags)),
+        ("meta".to_string(), Value::Object(meta)),
+    ]
+}
+
+/// {"items": [...]} with the items as item() has them
+pub fn export(todos: &[String], dones: &[String]) -> String {
+    let mut items = Vec::new();
+    for (status, list) in [(Status::Todo, todos), (Status::Done, dones)].iter() {
+        for title in list.iter() {
+            items.push(Value::Object(item(*status, title)));
+        }
+    }
+    let document = Value::Object(vec![("items".to_string(), Value::Array(items))]);
+    format!("{}\n", document)
+}
+
+/// The items of a document written by export(). Only the title is required, the tags are a part
+/// of it and are not looked at.
+pub fn import(source: &str) -> Result<Vec<(Status, String)>, String> {
+    let document = parse(source)?;
+    let items = match document.get("items") {
+        Some(Value::Array(items)) => items,
+        _ => return Err("expected an object with the `items` array".to_string()),
+    };
+    let mut result = Vec::new();
+    for (index, item) in items.iter().enumerate() {
+        let error = |message: &str| format!("item {}: {}", index + 1, message);
+        let status = match item.get("status") {
+            None => Status::Todo,
+            Some(Value::String(status)) if status.eq_ignore_ascii_case("todo") => Status::Todo,
+            Some(Value::String(status)) if status.eq_ignore_ascii_case("done") => Status::Done,
+            Some(_) => return Err(error("`status` is neither \"todo\" nor \"done\"")),
+        };
+        let mut title = match item.get("title") {
+            Some(Value::String(title)) => title.split_whitespace().collect::<Vec<_>>().join(" "),
+            _ => return Err(error("no `title` string")),
+        };
+        match item.get("meta") {
+            None => {}
+            Some(Value::Object(meta)) => {
+                for (key, value) in meta.iter() {
+                    let value = value
+                        .as_text()
+                        .filter(|value| !value.is_empty() && !value.contains(char::is_whitespace))
+                        .ok_or_else(|| error(&format!("the value of `{}` is not a word", key)))?;
+                    if meta_word(&format!("{}:{}", key, value)).is_none() {
+                        return Err(error(&format!("`{}` can't be a metadata key", key)));
+                    }
+                    item_set_meta(&mut title, key, Some(&value));
+                }
+            }
+            Some(_) => return Err(error("`meta` is not an object")),
+        }
+        result.push((status, title));
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn imports_what_it_exports() {
+        let todos = [
+            "buy milk #home due:2021-07-01".to_string(),
+            "say \"hi\" to the ünïcode \\ folks\tnow".to_string(),
+        ];
+        let dones = ["call mom @phone done:2021-06-30 created:2021-06-29".to_string()];
+        assert_eq!(
+            import(&export(&todos, &dones)),
+            Ok(vec![
+                (Status::Todo, todos[0].clone()),
+                (
+                    Status::Todo,
+                    "say \"hi\" to the ünïcode \\ folks now".to_string()
+                ),
+                (Status::Done, dones[0].clone()),
+            ])
+        );
+    }
+
+    #[test]
+    fn parses_what_it_writes() {
+        let source = r#"{"a": [1, -2.5, true, null], "b": {"c": "d\n\u00e9"}}"#;
+        let written = parse(source).unwrap().to_string();
+        assert_eq!(parse(&written).unwrap().to_string(), written);
+    }
+}
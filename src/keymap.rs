@@ -0,0 +1,360 @@
+use crate::action::{Action, BINDINGS, ESCAPE};
+use crate::config::Config;
+use crate::terminal::constants;
+use std::cmp;
+
+// Maps the sequences of keys onto the actions. Starts with the default BINDINGS, then the preset
+// and then the [keys] section of the config, each replacing the keys of the actions it sets:
+//
+//     [keys]
+//     preset = "emacs"  # One of the PRESETS
+//     down = "n, Down"  # Either n or the down arrow
+//     first = "g g"     # g twice
+//     quit = ""         # Not bound to anything at all
+pub struct Keymap {
+    bindings: Vec<(Vec<i32>, Action)>,
+    // The keys that run a `:` command line rather than an action
+    commands: Vec<(Vec<i32>, String)>,
+    // The keys of the sequence typed so far
+    pending: Vec<i32>,
+    // The digits typed before the sequence, e.g. 10 in 10j
+    count: Option<usize>,
+}
+
+// Big enough for any list a human would keep, small enough to not hang on a typo
+const MAX_COUNT: usize = 9999;
+
+pub enum Feed {
+    // The action and how many times to do it, if the count was typed
+    Action(Action, Option<usize>),
+    // The `:` command line bound to the keys
+    Command(String),
+    // The key continues a sequence, more keys are needed to tell the action
+    Pending,
+    // Nothing is bound to the key
+    Unbound,
+}
+
+// The presets are written the same way as the [keys] section of the config
+pub const PRESETS: &[(&str, &[(&str, &str)])] = &[
+    ("default", &[]),
+    (
+        "vim",
+        &[
+            ("first", "g g"),
+            ("delete", "d d"),
+            ("copy", "y y"),
+            ("switch_panel", "Tab, Ctrl+W Ctrl+W"),
+            ("quit", "Z Z, Q"),
+        ],
+    ),
+    (
+        "emacs",
+        &[
+            ("up", "Ctrl+P, Up"),
+            ("down", "Ctrl+N, Down"),
+            ("drag_up", "Shift+Up"),
+            ("drag_down", "Shift+Down"),
+            ("first", "Home"),
+            ("last", "End"),
+            ("insert", "Ctrl+O"),
+            ("delete", "Ctrl+K"),
+            ("copy", "Ctrl+W"),
+            ("paste", "Ctrl+Y"),
+            ("switch_panel", "Tab, Ctrl+X o"),
+            ("command", "Ctrl+X Ctrl+E, :"),
+        ],
+    ),
+    (
+        "arrows",
+        &[
+            ("up", "Up"),
+            ("down", "Down"),
+            ("drag_up", "Shift+Up"),
+            ("drag_down", "Shift+Down"),
+            ("first", "Home"),
+            ("last", "End"),
+            ("insert", "Insert"),
+            ("delete", "Delete"),
+            ("switch_panel", "Tab, Left, Right"),
+        ],
+    ),
+];
+
+const NAMED_KEYS: &[(&str, i32)] = &[
+    ("Enter", '\n' as i32),
+    ("Tab", '\t' as i32),
+    ("Space", ' ' as i32),
+    ("Esc", ESCAPE),
+    ("Backspace", constants::KEY_BACKSPACE),
+    ("Insert", constants::KEY_IC),
+    ("Delete", constants::KEY_DC),
+    ("Up", constants::KEY_UP),
+    ("Down", constants::KEY_DOWN),
+    ("Shift+Up", constants::KEY_SR),
+    ("Shift+Down", constants::KEY_SF),
+    ("Left", constants::KEY_LEFT),
+    ("Right", constants::KEY_RIGHT),
+    ("Home", constants::KEY_HOME),
+    ("End", constants::KEY_END),
+    ("PageUp", constants::KEY_PPAGE),
+    ("PageDown", constants::KEY_NPAGE),
+];
+
+pub fn key_name(key: i32) -> String {
+    if let Some((name, _)) = NAMED_KEYS.iter().find(|(_, named)| *named == key) {
+        return name.to_string();
+    }
+    match key {
+        1..=26 => format!("Ctrl+{}", (b'A' + key as u8 - 1) as char),
+        33..=126 => (key as u8 as char).to_string(),
+        _ => format!("<{}>", key),
+    }
+}
+
+// The inverse of key_name()
+fn parse_key(name: &str) -> Option<i32> {
+    if let Some((_, key)) = NAMED_KEYS.iter().find(|(named, _)| *named == name) {
+        return Some(*key);
+    }
+    let mut chars = name.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c @ '!'..='~'), None) => Some(c as i32),
+        _ => match name.strip_prefix("Ctrl+") {
+            Some(letter) if letter.len() == 1 => {
+                let letter = letter.to_ascii_uppercase().as_bytes()[0];
+                if letter.is_ascii_uppercase() {
+                    Some((letter - b'A' + 1) as i32)
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        },
+    }
+}
+
+fn sequence_name(keys: &[i32]) -> String {
+    let names: Vec<String> = keys.iter().map(|key| key_name(*key)).collect();
+    names.join(" ")
+}
+
+// Sequences that are prefixes of one another can't be told apart as they are typed
+fn conflict(a: &[i32], b: &[i32]) -> bool {
+    a.starts_with(b) || b.starts_with(a)
+}
+
+// Parses the keys of an action like "k, Up" or "g g". On failure returns the name of the unknown key.
+fn parse_sequences(value: &str) -> Result<Vec<Vec<i32>>, &str> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|sequence| !sequence.is_empty())
+        .map(|sequence| {
+            sequence
+                .split_whitespace()
+                .map(|key| parse_key(key).ok_or(key))
+                .collect()
+        })
+        .collect()
+}
+
+// Puts the layer of bindings over the bindings. The actions of the layer lose their old keys and
+// the old keys that get in the way of the layer's keys are dropped.
+fn apply_layer(bindings: &mut Vec<(Vec<i32>, Action)>, mut layer: Vec<(Vec<i32>, Action)>) {
+    let kept: Vec<(Vec<i32>, Action)> = bindings
+        .drain(..)
+        .filter(|(keys, action)| {
+            !layer
+                .iter()
+                .any(|(other, other_action)| other_action == action || conflict(keys, other))
+        })
+        .collect();
+    layer.extend(kept);
+    *bindings = layer;
+}
+
+fn preset(name: &str) -> Option<Vec<(Vec<i32>, Action)>> {
+    let (_, entries) = PRESETS.iter().find(|(preset, _)| *preset == name)?;
+    let mut layer = Vec::new();
+    for (action, value) in entries.iter() {
+        let action = Action::from_name(action).expect("Unknown action in a keymap preset");
+        let sequences = parse_sequences(value).expect("Unknown key in a keymap preset");
+        layer.extend(sequences.into_iter().map(|keys| (keys, action)));
+    }
+    Some(layer)
+}
+
+impl Keymap {
+    // The preset from the command line, if any, takes over the one from the config
+    pub fn from_config(config: &Config, preset_name: Option<&str>) -> Result<Self, String> {
+        let mut bindings: Vec<(Vec<i32>, Action)> = BINDINGS
+            .iter()
+            .map(|(key, action)| (vec![*key], *action))
+            .collect();
+
+        if let Some(name) = preset_name.or_else(|| config.get("keys", "preset")) {
+            let layer = preset(name).ok_or_else(|| {
+                let names: Vec<&str> = PRESETS.iter().map(|(name, _)| *name).collect();
+                let message = format!(
+                    "unknown keymap preset `{}`, expected one of {}",
+                    name,
+                    names.join(", ")
+                );
+                match preset_name {
+                    Some(_) => format!("ERROR: {}", message),
+                    None => config.error("keys", "preset", &message),
+                }
+            })?;
+            apply_layer(&mut bindings, layer);
+        }
+
+        let mut layer: Vec<(Vec<i32>, Action)> = Vec::new();
+        let mut configured: Vec<Action> = Vec::new();
+        for (name, value) in config.section("keys") {
+            if name == "preset" {
+                continue;
+            }
+            let action = Action::from_name(name)
+                .ok_or_else(|| config.error("keys", name, &format!("unknown action `{}`", name)))?;
+            configured.push(action);
+            let sequences = parse_sequences(value)
+                .map_err(|key| config.error("keys", name, &format!("unknown key `{}`", key)))?;
+            for keys in sequences {
+                if let Some((_, other)) = layer.iter().find(|(other, _)| conflict(&keys, other)) {
+                    return Err(config.error(
+                        "keys",
+                        name,
+                        &format!(
+                            "`{}` conflicts with the keys of `{}`",
+                            sequence_name(&keys),
+                            other.name()
+                        ),
+                    ));
+                }
+                layer.push((keys, action));
+            }
+        }
+        // Unbinding an action is a layer with the action and no keys
+        let unbound: Vec<Action> = configured
+            .into_iter()
+            .filter(|action| !layer.iter().any(|(_, bound)| bound == action))
+            .collect();
+        bindings.retain(|(_, action)| !unbound.contains(action));
+        apply_layer(&mut bindings, layer);
+
+        Ok(Self {
+            bindings,
+            commands: Vec::new(),
+            pending: Vec::new(),
+            count: None,
+        })
+    }
+
+    pub fn feed(&mut self, key: i32) -> Feed {
+        // Digits before the sequence are its count. 0 can't start a count, so it can still be bound.
+        let digit = match key {
+            48..=57 if self.pending.is_empty() => Some((key - 48) as usize),
+            _ => None,
+        };
+        match (digit, self.count) {
+            (Some(digit), Some(count)) => {
+                self.count = Some(cmp::min(count * 10 + digit, MAX_COUNT));
+                return Feed::Pending;
+            }
+            (Some(digit), None) if digit > 0 => {
+                self.count = Some(digit);
+                return Feed::Pending;
+            }
+            _ => {}
+        }
+
+        self.pending.push(key);
+        let pending = &self.pending;
+        if let Some((_, action)) = self.bindings.iter().find(|(keys, _)| keys == pending) {
+            let action = *action;
+            self.pending.clear();
+            return Feed::Action(action, self.count.take());
+        }
+        if let Some((_, command)) = self.commands.iter().find(|(keys, _)| keys == pending) {
+            let command = command.clone();
+            self.pending.clear();
+            self.count = None;
+            return Feed::Command(command);
+        }
+        let mut sequences = self
+            .bindings
+            .iter()
+            .map(|(keys, _)| keys)
+            .chain(self.commands.iter().map(|(keys, _)| keys));
+        if sequences.any(|keys| keys.starts_with(pending)) {
+            return Feed::Pending;
+        }
+        // The key broke the sequence. The sequence is dropped, but the key may still mean
+        // something on its own.
+        let broken = self.pending.len() > 1;
+        self.pending.clear();
+        if broken {
+            self.feed(key)
+        } else {
+            self.count = None;
+            Feed::Unbound
+        }
+    }
+
+    // Binds the keys to a `:` command line, taking them away from whatever had them before
+    #[cfg(feature = "scripting")]
+    pub fn bind_command(&mut self, keys: &str, command: &str) -> Result<(), String> {
+        let sequences = parse_sequences(keys).map_err(|key| format!("unknown key `{}`", key))?;
+        for keys in sequences {
+            self.bindings.retain(|(other, _)| !conflict(&keys, other));
+            self.commands.retain(|(other, _)| !conflict(&keys, other));
+            self.commands.push((keys, command.to_string()));
+        }
+        Ok(())
+    }
+
+    // The command lines bound to the keys, with the names of the keys
+    pub fn commands(&self) -> Vec<(String, &str)> {
+        self.commands
+            .iter()
+            .map(|(keys, command)| (sequence_name(keys), command.as_str()))
+            .collect()
+    }
+
+    // What's been typed of the current sequence so far, count included
+    pub fn pending(&self) -> String {
+        match self.count {
+            Some(count) if self.pending.is_empty() => count.to_string(),
+            Some(count) => format!("{} {}", count, sequence_name(&self.pending)),
+            None => sequence_name(&self.pending),
+        }
+    }
+
+    pub fn lookup(&self, keys: &[i32]) -> Option<Action> {
+        self.bindings
+            .iter()
+            .find(|(bound, _)| bound == keys)
+            .map(|(_, action)| *action)
+    }
+
+    // All the keys bound to the action, e.g. "p, Ctrl+V"
+    pub fn action_keys(&self, action: Action) -> String {
+        let keys: Vec<String> = self
+            .bindings
+            .iter()
+            .filter(|(_, bound)| *bound == action)
+            .map(|(keys, _)| sequence_name(keys))
+            .collect();
+        keys.join(", ")
+    }
+
+    // Every action that is bound to anything
+    pub fn actions(&self) -> Vec<Action> {
+        Action::ALL
+            .iter()
+            .filter(|action| self.bindings.iter().any(|(_, bound)| bound == *action))
+            .copied()
+            .collect()
+    }
+}
@@ -0,0 +1,1259 @@
+//! The todo engine of todo-rs: the items, their metadata and the files they live in.
+//!
+//! An item is its title, with whatever else is known about it kept right in the title as
+//! `key:value` words and `#tags`. A file is two lists of them, the TODO and the DONE items, and
+//! whatever else there was in the file, which is written back as it was.
+//!
+//! ```no_run
+//! use todo_rs::{format::Kind, load_state, save_state, Extras};
+//!
+//! let (mut todos, mut dones, mut extras) = (Vec::new(), Vec::new(), Extras::default());
+//! let format = Kind::from_path("TODO").unwrap_or_default().format();
+//! load_state(&mut todos, &mut dones, &mut extras, "TODO", None, format).unwrap();
+//! todos.push("buy milk #home".to_string());
+//! save_state(&todos, &dones, &extras, "TODO", 0, None, format).unwrap();
+//! ```
+
+#![warn(missing_docs)]
+
+use std::borrow::Cow;
+use std::cmp;
+use std::collections::{BTreeSet, HashMap};
+use std::fs::{self, File};
+use std::io::{self, ErrorKind, Write};
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+/// Encrypting and decrypting the files with gpg and age
+pub mod crypt;
+/// The lists as CSV
+pub mod csv;
+/// The dates of the metadata
+pub mod date;
+/// The formats of the files
+pub mod format;
+/// The settings on the first line of a file
+pub mod header;
+/// The lists as an HTML page
+pub mod html;
+/// The lists as iCalendar to-dos
+pub mod ics;
+/// The lists that know when they changed
+pub mod items;
+/// The lists as JSON, and a JSON parser to read them back
+pub mod json;
+/// The lock that keeps the other instances of the app off the file
+pub mod lock;
+/// What the lists add up to
+pub mod stats;
+/// Importing from and exporting to Taskwarrior
+pub mod taskwarrior;
+
+use crypt::Cipher;
+use date::Date;
+use format::Format;
+
+/// The formats an export can be in besides the formats of the files, named like their extensions
+pub const EXPORT_FORMATS: &[&str] = &["json", "ics", "csv", "taskwarrior", "html"];
+
+/// Which of the two lists the item is in
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Status {
+    /// Still to be done
+    Todo,
+    /// Done already
+    Done,
+}
+
+impl Status {
+    /// The other list
+    pub fn toggle(&self) -> Self {
+        match self {
+            Status::Todo => Status::Done,
+            Status::Done => Status::Todo,
+        }
+    }
+
+    /// The name of the list, like the headers of the panels have it
+    pub fn name(&self) -> &'static str {
+        match self {
+            Status::Todo => "TODO",
+            Status::Done => "DONE",
+        }
+    }
+}
+
+/// The `#tags`, `@contexts` and `+projects` of the title
+pub fn item_tags(title: &str) -> impl Iterator<Item = &str> {
+    title
+        .split(' ')
+        .filter(|word| word.starts_with(['#', '@', '+']) && word.len() > 1)
+}
+
+/// The http:// and https:// links of the title, without the punctuation around them like in
+/// `(see https://example.com).`
+pub fn item_urls(title: &str) -> impl Iterator<Item = &str> {
+    title.split(' ').filter_map(|word| {
+        let start = word.find("https://").or_else(|| word.find("http://"))?;
+        let url = word[start..]
+            .trim_end_matches(['.', ',', ';', ':', '!', '?', ')', ']', '>', '"', '\'']);
+        Some(url).filter(|url| !url.ends_with("//"))
+    })
+}
+
+/// Metadata lives right in the title as key:value words, e.g. due:2021-07-01
+pub fn item_meta<'a>(title: &'a str, key: &str) -> Option<&'a str> {
+    title
+        .split(' ')
+        .find_map(|word| word.strip_prefix(key)?.strip_prefix(':'))
+}
+
+/// Replaces the value of the key or removes it altogether if the value is None
+pub fn item_set_meta(title: &mut String, key: &str, value: Option<&str>) {
+    let mut words: Vec<String> = title
+        .split(' ')
+        .filter(|word| item_meta(word, key).is_none())
+        .map(|word| word.to_string())
+        .collect();
+    if let Some(value) = value {
+        words.push(format!("{}:{}", key, value));
+    }
+    *title = words.join(" ");
+}
+
+/// The key and the value of a metadata word. Not the links though, `https://example.com` has a colon
+/// in it as well.
+pub fn meta_word(word: &str) -> Option<(&str, &str)> {
+    let (key, value) = word.split_once(':')?;
+    let is_key = !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '_' || c == '-');
+    if is_key && !value.is_empty() && !value.starts_with('/') {
+        Some((key, value))
+    } else {
+        None
+    }
+}
+
+/// The date in the value of the metadata key, if there is one and it's a date
+pub fn item_date(title: &str, key: &str) -> Option<Date> {
+    item_meta(title, key).and_then(Date::parse)
+}
+
+/// Whether the item is due or was done on the day
+pub fn item_on_day(title: &str, day: Date) -> bool {
+    item_date(title, "due") == Some(day) || item_date(title, "done") == Some(day)
+}
+
+/// What the lists are narrowed down to. The items that don't match are hidden.
+#[derive(Default)]
+pub struct Filter {
+    /// Due or done on the day
+    pub day: Option<Date>,
+    /// Containing the text
+    pub text: Option<String>,
+}
+
+impl Filter {
+    /// Whether the item is to be seen
+    pub fn shows(&self, title: &str) -> bool {
+        self.day.is_none_or(|day| item_on_day(title, day))
+            && self
+                .text
+                .as_ref()
+                .is_none_or(|text| title.contains(text.as_str()))
+    }
+
+    /// Whether the filter shows everything
+    pub fn is_empty(&self) -> bool {
+        self.day.is_none() && self.text.is_none()
+    }
+
+    /// For the status bar
+    pub fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(day) = self.day {
+            parts.push(day.to_string());
+        }
+        if let Some(text) = &self.text {
+            parts.push(format!("\"{}\"", text));
+        }
+        parts.join(" ")
+    }
+}
+
+/// How many TODO items are due and how many items were done on each day
+pub fn day_counts(todos: &[String], dones: &[String]) -> HashMap<Date, (usize, usize)> {
+    let mut counts: HashMap<Date, (usize, usize)> = HashMap::new();
+    for due in todos.iter().filter_map(|todo| item_date(todo, "due")) {
+        counts.entry(due).or_default().0 += 1;
+    }
+    for done in dones.iter().filter_map(|done| item_date(done, "done")) {
+        counts.entry(done).or_default().1 += 1;
+    }
+    counts
+}
+
+/// All the tags of the lists, sorted and without duplicates
+pub fn list_tags(todos: &[String], dones: &[String]) -> Vec<String> {
+    let tags: BTreeSet<&str> = todos
+        .iter()
+        .chain(dones.iter())
+        .flat_map(|title| item_tags(title))
+        .collect();
+    tags.into_iter().map(|tag| tag.to_string()).collect()
+}
+
+// The count arguments below come from the count prefix of the key, e.g. 10j, and the cursors are
+// the indices of the current items. The items are moved as far as the count says or as far as the
+// list goes, whichever is closer.
+
+/// Moves the current item up along with the cursor
+pub fn list_drag_up(list: &mut [String], list_curr: &mut usize, count: usize) {
+    let count = cmp::min(count, *list_curr);
+    if count > 0 {
+        list[*list_curr - count..=*list_curr].rotate_right(1);
+        *list_curr -= count;
+    }
+}
+
+/// Moves the current item down along with the cursor
+pub fn list_drag_down(list: &mut [String], list_curr: &mut usize, count: usize) {
+    if *list_curr + 1 < list.len() {
+        let count = cmp::min(count, list.len() - 1 - *list_curr);
+        list[*list_curr..=*list_curr + count].rotate_left(1);
+        *list_curr += count;
+    }
+}
+
+/// Moves the cursor up
+pub fn list_up(list_curr: &mut usize, count: usize) {
+    *list_curr = list_curr.saturating_sub(count);
+}
+
+/// Moves the cursor down
+pub fn list_down(list: &[String], list_curr: &mut usize, count: usize) {
+    if *list_curr + 1 < list.len() {
+        *list_curr = cmp::min(list_curr.saturating_add(count), list.len() - 1);
+    }
+}
+
+/// The number is 1-based, the way the line numbers show it
+pub fn list_goto(list: &[String], list_curr: &mut usize, number: usize) {
+    if !list.is_empty() {
+        *list_curr = cmp::min(number.saturating_sub(1), list.len() - 1);
+    }
+}
+
+/// Moves the cursor to the first item
+pub fn list_first(list_curr: &mut usize) {
+    if *list_curr > 0 {
+        *list_curr = 0;
+    }
+}
+
+/// Moves the cursor to the last item
+pub fn list_last(list: &[String], list_curr: &mut usize) {
+    if !list.is_empty() {
+        *list_curr = list.len() - 1;
+    }
+}
+
+/// Moves the current item of the source list to the end of the destination list, returning
+/// whether there was an item to move
+pub fn list_transfer(
+    list_dst: &mut Vec<String>,
+    list_src: &mut Vec<String>,
+    list_src_curr: &mut usize,
+) -> bool {
+    if *list_src_curr < list_src.len() {
+        list_dst.push(list_src.remove(*list_src_curr));
+        if *list_src_curr >= list_src.len() && !list_src.is_empty() {
+            *list_src_curr = list_src.len() - 1;
+        }
+        true
+    } else {
+        false
+    }
+}
+
+/// Moves the cursor off the item hidden by the filter, in the direction it was moving from prev
+pub fn list_snap(list: &[String], list_curr: &mut usize, prev: usize, filter: &Filter) {
+    let shown = |index: usize| filter.shows(&list[index]);
+    if *list_curr >= list.len() || shown(*list_curr) {
+        return;
+    }
+    let forward = (*list_curr + 1..list.len()).find(|&index| shown(index));
+    let backward = (0..*list_curr).rev().find(|&index| shown(index));
+    let next = if *list_curr < prev {
+        backward.or(forward)
+    } else {
+        forward.or(backward)
+    };
+    if let Some(next) = next {
+        *list_curr = next;
+    }
+}
+
+/// By the title or by the value of the metadata key. The items without the key go last.
+pub fn list_sort(list: &mut [String], key: Option<&str>) {
+    match key {
+        None => list.sort_by_key(|title| title.to_lowercase()),
+        Some(key) => list.sort_by(|a, b| match (item_meta(a, key), item_meta(b, key)) {
+            // Numbers sort as numbers, so prio:10 goes after prio:9
+            (Some(a), Some(b)) => match (a.parse::<f64>(), b.parse::<f64>()) {
+                (Ok(a), Ok(b)) => a.total_cmp(&b),
+                _ => a.cmp(b),
+            },
+            (Some(_), None) => cmp::Ordering::Less,
+            (None, Some(_)) => cmp::Ordering::Greater,
+            (None, None) => cmp::Ordering::Equal,
+        }),
+    }
+}
+
+/// Removes the current item, keeping the cursor within the list
+pub fn list_delete(list: &mut Vec<String>, list_curr: &mut usize) {
+    if *list_curr < list.len() {
+        list.remove(*list_curr);
+        if *list_curr >= list.len() && !list.is_empty() {
+            *list_curr = list.len() - 1;
+        }
+    }
+}
+
+/// The lines of a file that are not items, like blank lines, `# comments` or anything in other
+/// formats, along with where the items were among them. They are written back verbatim where they
+/// were, so hand edited files survive saving.
+#[derive(Default, Clone)]
+pub struct Extras {
+    // The file as it was read, in its order
+    segments: Vec<Segment>,
+    /// The lines that were meant to be items but are not quite, waiting for the user to decide
+    /// what to do with them. They are among the lines above until then.
+    pub problems: Vec<Problem>,
+    /// The git merge conflicts in the file, waiting to be resolved. Their lines are among the
+    /// lines above until then, so they are written back as they are.
+    pub conflicts: Vec<Conflict>,
+    /// Some of the lines were taken out since the file was saved
+    pub changed: bool,
+    // The DONE items at the end of the file that were left unread, see parse_state_lazily(). Their
+    // segments go after the ones above once they are needed.
+    tail: Option<Arc<Tail>>,
+}
+
+#[derive(Clone)]
+enum Segment {
+    // An item as it was read, with where it was in its list and its lines, which are written back
+    // as they are while the item stays the same
+    Item {
+        status: Status,
+        index: usize,
+        title: String,
+        lines: Vec<Vec<u8>>,
+    },
+    // The lines between the items, and the segment of the item they go along with wherever it
+    // moves, if any
+    Lines {
+        lines: Vec<Line>,
+        item: Option<usize>,
+    },
+}
+
+#[derive(Clone)]
+struct Line {
+    // Counting from 1, like Problem::line
+    number: usize,
+    // As they are in the file, they are not necessarily UTF-8
+    bytes: Vec<u8>,
+}
+
+/// A git merge conflict in the file, with the items of both of its sides
+#[derive(Clone)]
+pub struct Conflict {
+    /// Of the `<<<<<<<` marker, counting from 1
+    pub line: usize,
+    /// What git says the sides are, like HEAD and the name of the merged branch
+    pub labels: [String; 2],
+    /// The items of our side
+    pub ours: Vec<(Status, String)>,
+    /// The items of their side
+    pub theirs: Vec<(Status, String)>,
+    /// All of the lines from marker to marker
+    pub lines: Vec<String>,
+}
+
+const CONFLICT_OURS: &str = "<<<<<<<";
+
+const CONFLICT_BASE: &str = "|||||||";
+
+const CONFLICT_SEPARATOR: &str = "=======";
+
+const CONFLICT_THEIRS: &str = ">>>>>>>";
+
+/// A line that was meant to be an item but is not quite
+#[derive(Clone)]
+pub struct Problem {
+    /// Counting from 1, like the editors do
+    pub line: usize,
+    /// The line as it is
+    pub text: String,
+    /// What is wrong with it
+    pub reason: &'static str,
+    /// The item the line was probably meant to be
+    pub fix: Option<(Status, String)>,
+}
+
+// Where an item goes among the segments of the file when it's written: right at the item segment
+// it was read from, right before the one of the next item read from the file or after the item
+// before it in its list, in the order of the lists.
+type Key = (usize, usize, usize);
+
+enum Unit {
+    // Of the lists, with the item segment it was read from if it was
+    Item(Status, usize, Option<usize>),
+    // The segment of the lines that don't go along with any of the items
+    Lines(usize),
+}
+
+impl Extras {
+    fn items(&self) -> impl Iterator<Item = (usize, Status, usize, &str)> {
+        self.segments
+            .iter()
+            .enumerate()
+            .filter_map(|(segment, item)| match item {
+                Segment::Item {
+                    status,
+                    index,
+                    title,
+                    ..
+                } => Some((segment, *status, *index, title.as_str())),
+                Segment::Lines { .. } => None,
+            })
+    }
+
+    // The item segment each of the items of the lists was read from, None for the new ones. The
+    // same title in the same list comes first, the closest one if there are several. Then the
+    // renamed items by their place among the ones already found, and then the items moved to the
+    // other list by their titles without the metadata.
+    fn anchors(&self, lists: [&[String]; 2]) -> [Vec<Option<usize>>; 2] {
+        let mut anchors = [vec![None; lists[0].len()], vec![None; lists[1].len()]];
+        let mut found = vec![None; self.segments.len()];
+        for (list_index, list) in lists.iter().enumerate() {
+            let mut by_title: HashMap<&str, Vec<usize>> = HashMap::new();
+            for (index, title) in list.iter().enumerate() {
+                by_title.entry(title.as_str()).or_default().push(index);
+            }
+            for (segment, status, original, title) in self.items() {
+                if status as usize != list_index {
+                    continue;
+                }
+                let candidates = match by_title.get_mut(title) {
+                    Some(candidates) if !candidates.is_empty() => candidates,
+                    _ => continue,
+                };
+                let closest = (0..candidates.len())
+                    .min_by_key(|&candidate| candidates[candidate].abs_diff(original))
+                    .unwrap_or_default();
+                let index = candidates.swap_remove(closest);
+                anchors[list_index][index] = Some(segment);
+                found[segment] = Some(index);
+            }
+        }
+
+        for (list_index, list) in lists.iter().enumerate() {
+            // The current index of the last item found and the ones not found since
+            let mut previous: Option<usize> = None;
+            let mut missing = Vec::new();
+            let segments = self
+                .items()
+                .filter(|(_, status, _, _)| *status as usize == list_index)
+                .map(|(segment, _, _, _)| Some(segment))
+                .chain(Some(None));
+            for segment in segments {
+                let next = match segment {
+                    Some(segment) => match found[segment] {
+                        Some(index) => index,
+                        None => {
+                            missing.push(segment);
+                            continue;
+                        }
+                    },
+                    None => list.len(),
+                };
+                let start = previous.map_or(0, |previous| previous + 1);
+                if start < next {
+                    let renamed: Vec<usize> = (start..next)
+                        .filter(|&index| anchors[list_index][index].is_none())
+                        .collect();
+                    for (index, segment) in renamed.into_iter().zip(missing.iter()) {
+                        anchors[list_index][index] = Some(*segment);
+                        found[*segment] = Some(index);
+                    }
+                }
+                missing.clear();
+                previous = Some(next);
+            }
+        }
+
+        if self
+            .items()
+            .any(|(segment, _, _, _)| found[segment].is_none())
+        {
+            let mut by_key: [HashMap<String, Vec<usize>>; 2] = Default::default();
+            for (list_index, list) in lists.iter().enumerate() {
+                for (index, title) in list.iter().enumerate().rev() {
+                    if anchors[list_index][index].is_none() {
+                        by_key[list_index]
+                            .entry(dedupe_key(title))
+                            .or_default()
+                            .push(index);
+                    }
+                }
+            }
+            for (segment, status, _, title) in self.items() {
+                if found[segment].is_some() {
+                    continue;
+                }
+                let other = status.toggle() as usize;
+                if let Some(index) = by_key[other]
+                    .get_mut(&dedupe_key(title))
+                    .and_then(|indices| indices.pop())
+                {
+                    anchors[other][index] = Some(segment);
+                }
+            }
+        }
+        anchors
+    }
+
+    // The order of the file for the lists as they are now: the items and the lines that don't go
+    // along with any item, each with its key. The items keep the order of their lists, except for
+    // the ones moved from the other list which stay where they were, like the items marked as DONE.
+    // The lines of the items that are gone stay where they were too.
+    fn order(&self, lists: [&[String]; 2]) -> Vec<Unit> {
+        let anchors = self.anchors(lists);
+        let mut units = Vec::new();
+        let mut last_todo: Option<Key> = None;
+        for status in [Status::Todo, Status::Done] {
+            let anchors = &anchors[status as usize];
+            let first = anchors.iter().flatten().min().copied();
+            // Where the items before the first one read from the file go
+            let start = |index: usize| -> Key {
+                match (first, status, last_todo) {
+                    (Some(first), _, _) => (2 * first, status as usize, index),
+                    (None, Status::Done, Some(last)) => (last.0, last.1, last.2 + 1 + index),
+                    (None, _, _) => (usize::MAX, status as usize, index),
+                }
+            };
+            let mut last: Option<Key> = None;
+            for (index, anchor) in anchors.iter().enumerate() {
+                let read = anchor.map(|segment| (2 * segment + 1, 0, 0));
+                let moved = anchor.is_some_and(|segment| {
+                    matches!(self.segments[segment], Segment::Item { status: other, .. } if other != status)
+                });
+                if let (Some(read), true) = (read, moved) {
+                    units.push((read, Unit::Item(status, index, *anchor)));
+                    continue;
+                }
+                let key = match (read, last) {
+                    (Some(read), Some(last)) if read > last => read,
+                    (Some(read), None) => read,
+                    (_, Some(last)) => (last.0, last.1, last.2 + 1),
+                    (None, None) => start(index),
+                };
+                units.push((key, Unit::Item(status, index, *anchor)));
+                last = Some(key);
+            }
+            if status == Status::Todo {
+                last_todo = last;
+            }
+        }
+        let mut anchored = vec![false; self.segments.len()];
+        for segment in anchors.iter().flatten().flatten() {
+            anchored[*segment] = true;
+        }
+        for (segment, extra) in self.segments.iter().enumerate() {
+            if let Segment::Lines { item, .. } = extra {
+                if !item.is_some_and(|item| anchored[item]) {
+                    units.push(((2 * segment + 1, 0, 0), Unit::Lines(segment)));
+                }
+            }
+        }
+        units.sort_by_key(|(key, _)| *key);
+        units.into_iter().map(|(_, unit)| unit).collect()
+    }
+
+    // The lines that go along with the item segment, on the side of the segment
+    fn bound_lines(&self, item: usize, segment: Option<usize>) -> &[Line] {
+        match segment.and_then(|segment| self.segments.get(segment)) {
+            Some(Segment::Lines { lines, item: bound }) if *bound == Some(item) => lines,
+            _ => &[],
+        }
+    }
+
+    /// Removes the line, returning the place of the item it went before
+    pub fn take_line(&mut self, number: usize) -> Option<(Status, usize)> {
+        let segment = self.segments.iter_mut().position(|segment| match segment {
+            Segment::Lines { lines, .. } => {
+                match lines.iter().position(|line| line.number == number) {
+                    Some(index) => {
+                        lines.remove(index);
+                        true
+                    }
+                    None => false,
+                }
+            }
+            Segment::Item { .. } => false,
+        })?;
+        self.changed = true;
+        self.segments[segment..]
+            .iter()
+            .find_map(|segment| match segment {
+                Segment::Item { status, index, .. } => Some((*status, *index)),
+                Segment::Lines { .. } => None,
+            })
+            .or_else(|| self.tail.as_ref().map(|tail| (Status::Done, tail.index)))
+    }
+
+    /// The DONE items at the end of the file that were left unread, if any
+    pub fn tail(&self) -> Option<&Arc<Tail>> {
+        self.tail.as_ref()
+    }
+
+    // With the segments of the tail, for writing the file
+    fn read_tail(&self) -> Cow<'_, Extras> {
+        let tail = match &self.tail {
+            Some(tail) => tail,
+            None => return Cow::Borrowed(self),
+        };
+        let mut extras = self.clone();
+        extras.tail = None;
+        for index in 0..tail.len() {
+            extras.segments.push(Segment::Item {
+                status: Status::Done,
+                index: tail.index + index,
+                title: tail.title(index),
+                lines: vec![tail.line(index).to_vec()],
+            });
+        }
+        Cow::Owned(extras)
+    }
+}
+
+/// The DONE items at the end of a file, left in it until they are needed. Only where their lines
+/// are is known of them, so a file with tens of thousands of them in its archive opens as fast as
+/// any other.
+pub struct Tail {
+    // The lines from the first one of the tail, without the line breaks
+    data: Vec<u8>,
+    // Where each of the lines ends in data
+    ends: Vec<usize>,
+    // Of the first item in the DONE list
+    index: usize,
+    format: format::Kind,
+}
+
+impl Tail {
+    /// How many items there are
+    pub fn len(&self) -> usize {
+        self.ends.len()
+    }
+
+    /// Whether there are none, which there always are
+    pub fn is_empty(&self) -> bool {
+        self.ends.is_empty()
+    }
+
+    fn line(&self, index: usize) -> &[u8] {
+        let start = match index.checked_sub(1) {
+            Some(previous) => self.ends[previous] + 1,
+            None => 0,
+        };
+        let line = &self.data[start..self.ends[index]];
+        line.strip_suffix(b"\r").unwrap_or(line)
+    }
+
+    /// The title of the item, counting from the first one of the tail
+    pub fn title(&self, index: usize) -> String {
+        let line = String::from_utf8_lossy(self.line(index));
+        let item = self.format.format().parse_item(&line);
+        item.map(|(_, title)| title).unwrap_or_default()
+    }
+
+    /// The titles of all of the items
+    pub fn titles(&self) -> Vec<String> {
+        (0..self.len()).map(|index| self.title(index)).collect()
+    }
+}
+
+/// Reads the items of the file into the lists and the rest of its lines into extras
+pub fn load_state(
+    todos: &mut Vec<String>,
+    dones: &mut Vec<String>,
+    extras: &mut Extras,
+    file_path: &str,
+    cipher: Option<&Cipher>,
+    format: &dyn Format,
+) -> io::Result<()> {
+    let data = read_state(file_path, cipher)?;
+    parse_state(todos, dones, extras, &data, format);
+    Ok(())
+}
+
+/// What is in the file, decrypted if it's encrypted
+pub fn read_state(file_path: &str, cipher: Option<&Cipher>) -> io::Result<Vec<u8>> {
+    let data = fs::read(file_path)?;
+    match cipher {
+        Some(cipher) => cipher.decrypt(&data),
+        None => Ok(data),
+    }
+}
+
+/// Never fails, whatever is not an item is kept as it is and the lines that look broken are
+/// reported in extras.problems
+pub fn parse_state(
+    todos: &mut Vec<String>,
+    dones: &mut Vec<String>,
+    extras: &mut Extras,
+    data: &[u8],
+    format: &dyn Format,
+) {
+    let mut lines: Vec<Line> = Vec::new();
+    // The conflict being read and which of its sides, 0 for ours, 1 for theirs and None for the
+    // common ancestor of the diff3 style conflicts which nobody needs
+    let mut conflict: Option<(Conflict, Option<usize>)> = None;
+    // The segment of the item on the previous line, the lines right after an item may be a part of
+    // it
+    let mut previous: Option<usize> = None;
+    let data = data.strip_suffix(b"\n").unwrap_or(data);
+    for (index, line) in data.split(|&byte| byte == b'\n').enumerate() {
+        let bytes = line.strip_suffix(b"\r").unwrap_or(line).to_vec();
+        let number = index + 1;
+        let continues = previous.take();
+        let line = match std::str::from_utf8(&bytes) {
+            Ok(line) => line.to_string(),
+            Err(_) => {
+                extras.problems.push(Problem {
+                    line: number,
+                    text: String::from_utf8_lossy(&bytes).into_owned(),
+                    reason: "the line is not valid UTF-8",
+                    fix: None,
+                });
+                lines.push(Line { number, bytes });
+                continue;
+            }
+        };
+        // Not an item in any of the formats, todo.txt would take it for one
+        if index == 0 && header::parse(&line).is_some() {
+            lines.push(Line { number, bytes });
+            continue;
+        }
+        if let Some(label) = line.strip_prefix(CONFLICT_OURS) {
+            if conflict.is_none() {
+                let conflict_start = Conflict {
+                    line: number,
+                    labels: [label.trim().to_string(), String::new()],
+                    ours: Vec::new(),
+                    theirs: Vec::new(),
+                    lines: vec![line.clone()],
+                };
+                conflict = Some((conflict_start, Some(0)));
+                lines.push(Line { number, bytes });
+                continue;
+            }
+        }
+        if let Some((mut current, side)) = conflict.take() {
+            current.lines.push(line.clone());
+            if let Some(label) = line.strip_prefix(CONFLICT_THEIRS) {
+                current.labels[1] = label.trim().to_string();
+                extras.conflicts.push(current);
+            } else {
+                let side = if line.starts_with(CONFLICT_BASE) {
+                    None
+                } else if line.starts_with(CONFLICT_SEPARATOR) {
+                    Some(1)
+                } else {
+                    if let Some(side) = side {
+                        let items = if side == 0 {
+                            &mut current.ours
+                        } else {
+                            &mut current.theirs
+                        };
+                        if let Some(item) = format.parse_item(&line) {
+                            items.push(item);
+                        } else if let Some((_, title)) = items.last_mut() {
+                            format.parse_continuation(&line, title);
+                        }
+                    }
+                    side
+                };
+                conflict = Some((current, side));
+            }
+            lines.push(Line { number, bytes });
+            continue;
+        }
+        if let Some(segment) = continues {
+            if let Segment::Item {
+                status,
+                index,
+                title: read,
+                lines: read_lines,
+            } = &mut extras.segments[segment]
+            {
+                let list = match status {
+                    Status::Todo => &mut *todos,
+                    Status::Done => &mut *dones,
+                };
+                let title = &mut list[*index];
+                if format.parse_continuation(&line, title) {
+                    // The items are found by their whole titles when the file is written
+                    *read = title.clone();
+                    read_lines.push(bytes);
+                    previous = Some(segment);
+                    continue;
+                }
+            }
+        }
+        let (status, title) = match format.parse_item(&line) {
+            Some(item) => item,
+            None => {
+                if let Some(fix) = format.parse_ill_formed(&line) {
+                    extras.problems.push(Problem {
+                        line: number,
+                        text: line.clone(),
+                        reason: "ill-formed item line",
+                        fix,
+                    });
+                }
+                lines.push(Line { number, bytes });
+                continue;
+            }
+        };
+        let list = match status {
+            Status::Todo => &mut *todos,
+            Status::Done => &mut *dones,
+        };
+        push_lines(extras, mem::take(&mut lines), true, format);
+        previous = Some(extras.segments.len());
+        extras.segments.push(Segment::Item {
+            status,
+            index: list.len(),
+            title: title.clone(),
+            lines: vec![bytes],
+        });
+        list.push(title);
+    }
+    push_lines(extras, lines, false, format);
+}
+
+/// Like parse_state(), only the DONE items at the end of the file are left unread until they are
+/// needed, when they are all alone on their lines. The DONE list gets the ones before them, and
+/// extras.tail() tells where the rest are.
+pub fn parse_state_lazily(
+    todos: &mut Vec<String>,
+    dones: &mut Vec<String>,
+    extras: &mut Extras,
+    data: &[u8],
+    format: format::Kind,
+) {
+    let data = data.strip_suffix(b"\n").unwrap_or(data);
+    // Where the tail starts, right after a line break, since the first line may be the header
+    let mut start = None;
+    // Of the lines of the tail, from the last one
+    let mut ends = Vec::new();
+    let mut end = data.len();
+    while let Some(newline) = data[..end].iter().rposition(|&byte| byte == b'\n') {
+        let line = &data[newline + 1..end];
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        if !std::str::from_utf8(line).is_ok_and(|line| format.format().is_plain_done(line)) {
+            break;
+        }
+        ends.push(end);
+        start = Some(newline + 1);
+        end = newline;
+    }
+    // The tail may well be a side of a conflict, which is only known by reading all of it
+    let start = start.filter(|&start| {
+        !data[..start]
+            .split(|&byte| byte == b'\n')
+            .any(|line| line.starts_with(CONFLICT_OURS.as_bytes()))
+    });
+    let start = match start {
+        Some(start) => start,
+        None => return parse_state(todos, dones, extras, data, format.format()),
+    };
+    parse_state(todos, dones, extras, &data[..start], format.format());
+    // The lines right before the tail go along with its first item, like they would if it was read
+    let first = extras.segments.len();
+    if let Some(Segment::Lines { item, .. }) = extras.segments.last_mut() {
+        if !format.format().has_body() {
+            *item = item.or(Some(first));
+        }
+    }
+    extras.tail = Some(Arc::new(Tail {
+        data: data[start..].to_vec(),
+        ends: ends.iter().rev().map(|end| end - start).collect(),
+        index: dones.len(),
+        format,
+    }));
+}
+
+// The lines between the items go along with the next one, or with the previous one as its body in
+// the formats that have them
+fn push_lines(extras: &mut Extras, mut lines: Vec<Line>, next: bool, format: &dyn Format) {
+    let mut item = None;
+    if format.has_body() {
+        // The last segment is always an item, if any, the lines are pushed right before the items
+        if let Some(previous) = extras.segments.len().checked_sub(1) {
+            let body = lines
+                .iter()
+                .position(|line| !format.parse_body(&String::from_utf8_lossy(&line.bytes)))
+                .unwrap_or(lines.len());
+            let rest = lines.split_off(body);
+            if !lines.is_empty() {
+                extras.segments.push(Segment::Lines {
+                    lines,
+                    item: Some(previous),
+                });
+            }
+            lines = rest;
+        }
+    } else if next {
+        item = Some(extras.segments.len() + 1);
+    }
+    if !lines.is_empty() {
+        extras.segments.push(Segment::Lines { lines, item });
+    }
+}
+
+fn write_line(out: &mut impl Write, bytes: &[u8]) -> io::Result<()> {
+    out.write_all(bytes)?;
+    out.write_all(b"\n")
+}
+
+/// Writes the lists in the format, with the lines of extras where they were
+pub fn write_state(
+    out: &mut impl Write,
+    todos: &[String],
+    dones: &[String],
+    extras: &Extras,
+    format: &dyn Format,
+) -> io::Result<()> {
+    let extras = &*extras.read_tail();
+    let lists = [todos, dones];
+    for unit in extras.order(lists) {
+        match unit {
+            Unit::Item(status, index, anchor) => {
+                let (before, after) = match anchor {
+                    Some(item) => (
+                        extras.bound_lines(item, item.checked_sub(1)),
+                        extras.bound_lines(item, Some(item + 1)),
+                    ),
+                    None => (&[][..], &[][..]),
+                };
+                for line in before {
+                    write_line(out, &line.bytes)?;
+                }
+                let item = &lists[status as usize][index];
+                match anchor.map(|anchor| &extras.segments[anchor]) {
+                    Some(Segment::Item {
+                        status: read_status,
+                        title,
+                        lines,
+                        ..
+                    }) => {
+                        if *read_status == status && title == item {
+                            for line in lines {
+                                write_line(out, line)?;
+                            }
+                        } else {
+                            let read: Vec<Cow<str>> = lines
+                                .iter()
+                                .map(|line| String::from_utf8_lossy(line))
+                                .collect();
+                            let read: Vec<&str> = read.iter().map(|line| line.as_ref()).collect();
+                            writeln!(out, "{}", format.update_item(status, item, &read))?;
+                        }
+                    }
+                    _ => writeln!(out, "{}", format.write_item(status, item))?,
+                }
+                for line in after {
+                    write_line(out, &line.bytes)?;
+                }
+            }
+            Unit::Lines(segment) => {
+                if let Segment::Lines { lines, .. } = &extras.segments[segment] {
+                    for line in lines {
+                        write_line(out, &line.bytes)?;
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// When the file was last modified, to tell when somebody else changes it
+pub fn file_mtime(file_path: &str) -> Option<SystemTime> {
+    fs::metadata(file_path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+fn backup_path(path: &Path, number: usize) -> PathBuf {
+    let mut backup = path.as_os_str().to_owned();
+    backup.push(format!(".bak.{}", number));
+    PathBuf::from(backup)
+}
+
+// Copies the file to <file>.bak.1, moving the older backups one number up and dropping the ones
+// past the count
+fn rotate_backups(path: &Path, count: usize) -> io::Result<()> {
+    if count == 0 {
+        return Ok(());
+    }
+    for number in (1..count).rev() {
+        match fs::rename(backup_path(path, number), backup_path(path, number + 1)) {
+            Err(error) if error.kind() != ErrorKind::NotFound => {
+                return Err(io::Error::new(
+                    error.kind(),
+                    format!("could not rotate the backups: {}", error),
+                ))
+            }
+            _ => {}
+        }
+    }
+    fs::copy(path, backup_path(path, 1))?;
+    Ok(())
+}
+
+/// The state is written next to the file and renamed over it, so whatever happens in the middle of
+/// saving the file is either the old one or the new one but never half written
+pub fn save_state(
+    todos: &[String],
+    dones: &[String],
+    extras: &Extras,
+    file_path: &str,
+    backups: usize,
+    cipher: Option<&Cipher>,
+    format: &dyn Format,
+) -> io::Result<()> {
+    // Saving through a symlink updates the file it points to rather than replacing the link
+    let target = fs::canonicalize(file_path).unwrap_or_else(|_| PathBuf::from(file_path));
+    let mut tmp_path = target.clone().into_os_string();
+    tmp_path.push(".tmp");
+
+    let mut data = Vec::new();
+    write_state(&mut data, todos, dones, extras, format)?;
+    // Only the encrypted state ever gets to the disk, even in the temporary file
+    if let Some(cipher) = cipher {
+        data = cipher.encrypt(&data)?;
+    }
+    let result = write_synced(Path::new(&tmp_path), &target, &data)
+        .and_then(|()| {
+            if target.exists() {
+                rotate_backups(&target, backups)?;
+            }
+            Ok(())
+        })
+        .and_then(|()| fs::rename(&tmp_path, &target));
+    if let Err(error) = result {
+        // Whatever made it to the disk is of no use to anybody, and the original is still intact
+        let _ = fs::remove_file(&tmp_path);
+        return Err(error);
+    }
+    // The rename itself only survives a power loss once the directory is synced. Not every system
+    // lets us open a directory, and the file is already in place anyway, so this is best effort.
+    if let Some(dir) = target.parent() {
+        let _ = File::open(dir).and_then(|dir| dir.sync_all());
+    }
+    Ok(())
+}
+
+// Writes the data all the way to the disk, with the permissions of the original file if there is one
+fn write_synced(path: &Path, original: &Path, data: &[u8]) -> io::Result<()> {
+    let mut file = File::create(path)?;
+    file.write_all(data)?;
+    if let Ok(metadata) = fs::metadata(original) {
+        file.set_permissions(metadata.permissions())?;
+    }
+    file.sync_all()
+}
+
+/// The lists of the file in one of EXPORT_FORMATS, None for the formats of the files
+pub fn export_document(
+    format: &str,
+    file_path: &str,
+    todos: &[String],
+    dones: &[String],
+) -> Option<String> {
+    match format {
+        "html" => {
+            let name = Path::new(file_path)
+                .file_name()
+                .map_or(Cow::Borrowed(file_path), |name| name.to_string_lossy());
+            Some(html::export(&name, todos, dones))
+        }
+        "json" => Some(json::export(todos, dones)),
+        "ics" => Some(ics::export(todos, dones)),
+        "csv" => Some(csv::export(todos, dones)),
+        "taskwarrior" => Some(taskwarrior::export(todos, dones)),
+        _ => None,
+    }
+}
+
+/// The item without its metadata, the way it reads to people, e.g. in the commit messages
+pub fn plain_title(item: &str) -> String {
+    item.split(' ')
+        .filter(|word| !word.is_empty() && meta_word(word).is_none())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// The title without the metadata and the case, to tell the same items apart from the new ones
+pub fn dedupe_key(title: &str) -> String {
+    plain_title(title).to_lowercase()
+}
+
+/// A TODO item for every line that has anything on it. The list markers and the empty checkboxes
+/// of the notes go away, so `- [ ] buy milk` is just `buy milk`.
+pub fn import_lines(source: &str) -> Vec<(Status, String)> {
+    let today = Date::today().to_string();
+    source
+        .lines()
+        .filter_map(|line| {
+            let line = line.trim();
+            let line = line
+                .strip_prefix(&['-', '*', '+'][..])
+                .filter(|rest| rest.starts_with(' '))
+                .unwrap_or(line)
+                .trim_start();
+            let line = line.strip_prefix("[ ] ").unwrap_or(line);
+            let mut title = line.split_whitespace().collect::<Vec<_>>().join(" ");
+            if title.is_empty() {
+                return None;
+            }
+            if item_meta(&title, "created").is_none() {
+                item_set_meta(&mut title, "created", Some(&today));
+            }
+            Some((Status::Todo, title))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use format::Kind;
+
+    fn load(data: &str) -> (Vec<String>, Vec<String>, Extras) {
+        let (mut todos, mut dones, mut extras) = (Vec::new(), Vec::new(), Extras::default());
+        let format = Kind::Todo.format();
+        parse_state(&mut todos, &mut dones, &mut extras, data.as_bytes(), format);
+        (todos, dones, extras)
+    }
+
+    fn save(todos: &[String], dones: &[String], extras: &Extras) -> String {
+        let mut out = Vec::new();
+        write_state(&mut out, todos, dones, extras, Kind::Todo.format()).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn keeps_the_file_as_it_is() {
+        let data = "# work\nTODO: a\nDONE: b\n\n# home\nTODO: c\n# the end\n";
+        let (todos, dones, extras) = load(data);
+        assert_eq!(save(&todos, &dones, &extras), data);
+    }
+
+    #[test]
+    fn moves_the_lines_along_with_their_items() {
+        let data = "# work\nTODO: a\n# home\nTODO: b\nTODO: c\n";
+        let (mut todos, dones, extras) = load(data);
+        todos.swap(0, 1);
+        todos.push("d".to_string());
+        assert_eq!(
+            save(&todos, &dones, &extras),
+            "# home\nTODO: b\n# work\nTODO: a\nTODO: c\nTODO: d\n"
+        );
+        // The lines of the items that are gone stay where they were
+        todos.remove(1);
+        assert_eq!(
+            save(&todos, &dones, &extras),
+            "# work\n# home\nTODO: b\nTODO: c\nTODO: d\n"
+        );
+    }
+
+    #[test]
+    fn keeps_the_bytes_of_the_unchanged_lines() {
+        let data = b"# caf\xe9\nTODO: a due:2024-06-01\nTODO: b\n";
+        let (mut todos, mut dones, mut extras) = (Vec::new(), Vec::new(), Extras::default());
+        parse_state(
+            &mut todos,
+            &mut dones,
+            &mut extras,
+            data,
+            Kind::Todo.format(),
+        );
+        assert_eq!(extras.problems.len(), 1);
+        todos[1] = "c".to_string();
+        let mut out = Vec::new();
+        write_state(&mut out, &todos, &dones, &extras, Kind::Todo.format()).unwrap();
+        assert_eq!(out, b"# caf\xe9\nTODO: a due:2024-06-01\nTODO: c\n");
+    }
+
+    #[test]
+    fn leaves_the_done_items_at_the_end_unread() {
+        let data =
+            "# work\nTODO: a\nDONE: b\n\n# archive\nDONE: c\nDONE: d | done:2024-06-01\nDONE:  e\n";
+        let (mut todos, mut dones, mut extras) = (Vec::new(), Vec::new(), Extras::default());
+        parse_state_lazily(
+            &mut todos,
+            &mut dones,
+            &mut extras,
+            data.as_bytes(),
+            Kind::Todo,
+        );
+        assert_eq!(dones, ["b"]);
+        dones.extend(extras.tail().unwrap().titles());
+        let (_, mut read, read_extras) = load(data);
+        assert_eq!(dones, read);
+        assert_eq!(save(&todos, &dones, &extras), data);
+        // The archive goes along with the first item of the tail, like it does when it's read
+        dones.rotate_left(1);
+        read.rotate_left(1);
+        assert_eq!(
+            save(&todos, &dones, &extras),
+            save(&todos, &read, &read_extras)
+        );
+
+        // Whatever is in a conflict is read, the DONE items may be on one of its sides
+        let data = "<<<<<<< HEAD\nTODO: a\n=======\nTODO: b\n>>>>>>> other\nDONE: c\n";
+        let (mut todos, mut dones, mut extras) = (Vec::new(), Vec::new(), Extras::default());
+        parse_state_lazily(
+            &mut todos,
+            &mut dones,
+            &mut extras,
+            data.as_bytes(),
+            Kind::Todo,
+        );
+        assert!(extras.tail().is_none());
+        assert_eq!(dones, ["c"]);
+    }
+
+    #[test]
+    fn takes_the_line_by_its_number() {
+        let data = "TODO: a\nTODO a\nTODO: b\nTODO a\nTODO: c\n";
+        let (todos, dones, mut extras) = load(data);
+        assert_eq!(extras.problems.len(), 2);
+        assert_eq!(extras.take_line(4), Some((Status::Todo, 2)));
+        assert_eq!(
+            save(&todos, &dones, &extras),
+            "TODO: a\nTODO a\nTODO: b\nTODO: c\n"
+        );
+    }
+}
@@ -0,0 +1,85 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+#[cfg(unix)]
+use std::os::unix::io::AsRawFd;
+use std::path::PathBuf;
+use std::process;
+
+/// An advisory lock that keeps the other instances of the app from opening the same file. The file
+/// itself is replaced on every save (see save_state()), so the lock is taken on `<file>.lock` next to
+/// it instead, which lives as long as the lock is held. It says the process id of the instance.
+pub struct FileLock {
+    path: PathBuf,
+    // Closing the file releases the lock
+    _file: File,
+}
+
+fn lock_path(file_path: &str) -> PathBuf {
+    let mut path = PathBuf::from(file_path).into_os_string();
+    path.push(".lock");
+    PathBuf::from(path)
+}
+
+/// None if another instance holds the lock already, see owner() for which one
+pub fn acquire(file_path: &str) -> io::Result<Option<FileLock>> {
+    let path = lock_path(file_path);
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(&path)?;
+    #[cfg(unix)]
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } != 0 {
+        let error = io::Error::last_os_error();
+        return match error.raw_os_error() {
+            Some(libc::EWOULDBLOCK) => Ok(None),
+            _ => Err(error),
+        };
+    }
+    // There is no flock() on Windows, std locks the files with LockFileEx() there
+    #[cfg(not(unix))]
+    match file.try_lock() {
+        Ok(()) => {}
+        Err(fs::TryLockError::WouldBlock) => return Ok(None),
+        Err(fs::TryLockError::Error(error)) => return Err(error),
+    }
+    // Only for the messages, the lock is all that matters
+    let _ = file
+        .set_len(0)
+        .and_then(|()| write!(file, "{}", process::id()));
+    Ok(Some(FileLock { path, _file: file }))
+}
+
+/// The process id of the instance that holds the lock of the file, if it can be told. Windows
+/// keeps the others from reading a locked file.
+pub fn owner(file_path: &str) -> Option<u32> {
+    fs::read_to_string(lock_path(file_path))
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::env;
+
+    #[test]
+    fn the_lock_tells_its_owner_to_the_other_instances() {
+        let path = env::temp_dir().join(format!("todo-rs-lock-test-{}", process::id()));
+        let path = path.to_string_lossy().into_owned();
+        let file_lock = acquire(&path).unwrap().unwrap();
+        assert_eq!(owner(&path), Some(process::id()));
+        assert!(acquire(&path).unwrap().is_none());
+        drop(file_lock);
+        assert_eq!(owner(&path), None);
+    }
+}
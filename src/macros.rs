@@ -0,0 +1,78 @@
+use std::collections::{HashMap, VecDeque};
+
+// A single unit of the user's input. Macros are recorded and replayed in these, so everything that
+// reacts to the input has to take it from the main loop rather than reading the terminal itself.
+#[derive(Clone)]
+pub enum Input {
+    Key(i32),
+    Paste(String),
+}
+
+// Replaying a macro that replays itself would never end otherwise
+const MAX_QUEUED: usize = 100_000;
+
+#[derive(Default)]
+pub struct Macros {
+    registers: HashMap<char, Vec<Input>>,
+    recording: Option<(char, Vec<Input>)>,
+    // The register replayed the last time, for @@
+    last: Option<char>,
+    // The inputs waiting to be replayed
+    queue: VecDeque<Input>,
+}
+
+// Registers are named by the lowercase letters and the digits, @ stands for the last replayed one
+pub fn register(key: i32) -> Option<char> {
+    match key {
+        48..=57 | 97..=122 | 64 => Some(key as u8 as char),
+        _ => None,
+    }
+}
+
+impl Macros {
+    pub fn start(&mut self, register: char) {
+        self.recording = Some((register, Vec::new()));
+    }
+
+    // Returns the register the macro was recorded into
+    pub fn stop(&mut self) -> Option<char> {
+        let (register, inputs) = self.recording.take()?;
+        self.registers.insert(register, inputs);
+        Some(register)
+    }
+
+    pub fn recording(&self) -> Option<char> {
+        self.recording.as_ref().map(|(register, _)| *register)
+    }
+
+    pub fn record(&mut self, input: &Input) {
+        if let Some((_, inputs)) = self.recording.as_mut() {
+            inputs.push(input.clone());
+        }
+    }
+
+    // Queues the macro in the register to be replayed count times. Returns the actual register.
+    pub fn replay(&mut self, register: char, count: usize) -> Result<char, String> {
+        let register = match register {
+            '@' => self.last.ok_or("No macro was replayed yet")?,
+            register => register,
+        };
+        let inputs = self
+            .registers
+            .get(&register)
+            .ok_or_else(|| format!("Register @{} is empty", register))?;
+        if self.queue.len() + inputs.len().saturating_mul(count) > MAX_QUEUED {
+            self.queue.clear();
+            return Err(format!("Macro @{} is too long to replay", register));
+        }
+        for _ in 0..count {
+            self.queue.extend(inputs.iter().cloned());
+        }
+        self.last = Some(register);
+        Ok(register)
+    }
+
+    pub fn pop(&mut self) -> Option<Input> {
+        self.queue.pop_front()
+    }
+}
@@ -1,567 +1,156 @@
-use ncurses::*;
-use std::cmp;
 use std::env;
-use std::fs::File;
-use std::io::{self, BufRead, ErrorKind, Write};
-use std::ops::{Add, Mul};
+use std::io::{self, IsTerminal};
 use std::process;
 
-mod ctrlc;
+// The app on top of the engine of the library. None of it is a part of the library, so the
+// engine is all that the other projects get. main() only parses the command line and runs the
+// loop.
+
+/// The actions of the keys
+mod action;
+/// The state of the app and what the actions and the commands do to it
+mod app;
+/// The commands that work on the file without the interactive app
+mod batch;
+/// Opening the links
+mod browser;
+/// The org.tsoding.TodoRs service on the D-Bus session bus
+#[cfg(feature = "dbus")]
+mod bus;
+/// Syncing with a CalDAV calendar
+mod caldav;
+/// The command line
+mod cli;
+/// The system clipboard
+mod clipboard;
+/// The `:` commands
+mod command;
+/// The shell completions of the command line
+mod completions;
+/// The config file
+mod config;
+/// The control socket for the other programs
+#[cfg(unix)]
+mod control;
+/// The crossterm backend in place of ncurses
+#[cfg(any(windows, feature = "crossterm"))]
+mod curses;
+/// The desktop notifications
+mod desktop;
+/// The items coming due
+mod due;
+/// What the main loop waits for
+mod event;
+/// Committing the file to git
+mod git;
+/// Syncing with the issues of a GitHub repository
+mod github;
+/// The commands run on the changes to the items
+mod hooks;
+/// Just enough HTTP for the syncs
+mod http;
+/// The keys and their actions
+mod keymap;
+/// Recording and replaying the keys
+mod macros;
+/// The messages that come and go on the banner
+mod notifications;
+/// The lists printed to the terminal
+mod print;
+/// The unsaved changes written out when the app crashes
+mod recovery;
+/// The reminders of the items
+mod remind;
+/// Where the frames are drawn
+mod render;
+/// Collecting the TODO comments of the source code
+mod scan;
+/// The `:` commands and the key bindings from a rhai script
+#[cfg(feature = "scripting")]
+mod script;
+/// `todo-rs serve`
+mod serve;
+/// What is kept from one run of the app to the next
+mod session;
+/// Reading the config and the files before the app starts
+mod setup;
+/// Ctrl+C and the other signals
+mod signals;
+/// The one line summary of the lists for the status bars
+mod status;
+/// What the syncs have in common
+mod sync;
+/// The terminal backend
+mod terminal;
+/// The theme of the terminal
+mod theme;
+/// The interactive app on the terminal
+mod tui;
+/// The widgets the frames are made of
+mod ui;
+/// What the user sees of the lists
+mod view;
+
+use cli::Args;
+use setup::Setup;
 
-const REGULAR_PAIR: i16 = 0;
-const HIGHLIGHT_PAIR: i16 = 1;
-
-#[derive(Default, Copy, Clone)]
-struct Vec2 {
-    x: i32,
-    y: i32,
-}
-
-impl Add for Vec2 {
-    type Output = Vec2;
-
-    fn add(self, rhs: Vec2) -> Vec2 {
-        Vec2 {
-            x: self.x + rhs.x,
-            y: self.y + rhs.y,
-        }
-    }
-}
-
-impl Mul for Vec2 {
-    type Output = Vec2;
-
-    fn mul(self, rhs: Vec2) -> Vec2 {
-        Vec2 {
-            x: self.x * rhs.x,
-            y: self.y * rhs.y,
-        }
-    }
-}
-
-impl Vec2 {
-    fn new(x: i32, y: i32) -> Self {
-        Self { x, y }
-    }
-}
-
-enum LayoutKind {
-    Vert,
-    Horz,
-}
-
-struct Layout {
-    kind: LayoutKind,
-    pos: Vec2,
-    size: Vec2,
-}
-
-impl Layout {
-    fn available_pos(&self) -> Vec2 {
-        use LayoutKind::*;
-        match self.kind {
-            Horz => self.pos + self.size * Vec2::new(1, 0),
-            Vert => self.pos + self.size * Vec2::new(0, 1),
-        }
-    }
-
-    fn add_widget(&mut self, size: Vec2) {
-        use LayoutKind::*;
-        match self.kind {
-            Horz => {
-                self.size.x += size.x;
-                self.size.y = cmp::max(self.size.y, size.y);
-            }
-            Vert => {
-                self.size.x = cmp::max(self.size.x, size.x);
-                self.size.y += size.y;
-            }
-        }
-    }
-}
-
-#[derive(Default)]
-struct Ui {
-    layouts: Vec<Layout>,
-    key: Option<i32>,
-}
-
-impl Ui {
-    fn begin(&mut self, pos: Vec2, kind: LayoutKind) {
-        assert!(self.layouts.is_empty());
-        self.layouts.push(Layout {
-            kind,
-            pos,
-            size: Vec2::new(0, 0),
-        })
-    }
-
-    fn begin_layout(&mut self, kind: LayoutKind) {
-        let layout = self
-            .layouts
-            .last()
-            .expect("Can't create a layout outside of Ui::begin() and Ui::end()");
-        let pos = layout.available_pos();
-        self.layouts.push(Layout {
-            kind,
-            pos,
-            size: Vec2::new(0, 0),
-        });
-    }
-
-    fn end_layout(&mut self) {
-        let layout = self
-            .layouts
-            .pop()
-            .expect("Unbalanced Ui::begin_layout() and Ui::end_layout() calls.");
-        self.layouts
-            .last_mut()
-            .expect("Unbalanced Ui::begin_layout() and Ui::end_layout() calls.")
-            .add_widget(layout.size);
-    }
-
-    fn label_fixed_width(&mut self, text: &str, width: i32, pair: i16) {
-        // TODO(#17): Ui::label_fixed_width() does not elide the text when width < text.len()
-        let layout = self
-            .layouts
-            .last_mut()
-            .expect("Trying to render label outside of any layout");
-        let pos = layout.available_pos();
-
-        mv(pos.y, pos.x);
-        attron(COLOR_PAIR(pair));
-        addstr(text);
-        attroff(COLOR_PAIR(pair));
-
-        layout.add_widget(Vec2::new(width, 1));
-    }
-
-    // TODO(#26): Ui::edit_field does not scroll according to the cursor
-    fn edit_field(&mut self, buffer: &mut String, cursor: &mut usize, width: i32) {
-        let layout = self
-            .layouts
-            .last_mut()
-            .expect("Trying to render edit field outside of any layout");
-        let pos = layout.available_pos();
-
-        if *cursor > buffer.len() {
-            *cursor = buffer.len();
-        }
-
-        if let Some(key) = self.key.take() {
-            match key {
-                32..=126 => {
-                    if *cursor >= buffer.len() {
-                        buffer.push(key as u8 as char);
-                    } else {
-                        buffer.insert(*cursor, key as u8 as char);
-                    }
-                    *cursor += 1;
-                }
-                constants::KEY_LEFT => {
-                    if *cursor > 0 {
-                        *cursor -= 1
-                    }
-                }
-                constants::KEY_RIGHT => {
-                    if *cursor < buffer.len() {
-                        *cursor += 1;
-                    }
-                }
-                constants::KEY_BACKSPACE => {
-                    if *cursor > 0 {
-                        *cursor -= 1;
-                        if *cursor < buffer.len() {
-                            buffer.remove(*cursor);
-                        }
-                    }
-                }
-                constants::KEY_DC => {
-                    if *cursor < buffer.len() {
-                        buffer.remove(*cursor);
-                    }
-                }
-                _ => {
-                    self.key = Some(key);
-                }
-            }
-        }
-
-        // Buffer
-        {
-            mv(pos.y, pos.x);
-            attron(COLOR_PAIR(REGULAR_PAIR));
-            addstr(buffer);
-            attroff(COLOR_PAIR(REGULAR_PAIR));
-            layout.add_widget(Vec2::new(width, 1));
-        }
-
-        // Cursor
-        {
-            mv(pos.y, pos.x + *cursor as i32);
-            attron(COLOR_PAIR(HIGHLIGHT_PAIR));
-            addstr(buffer.get(*cursor..=*cursor).unwrap_or(" "));
-            attroff(COLOR_PAIR(HIGHLIGHT_PAIR));
-        }
-    }
-
-    #[allow(dead_code)]
-    fn label(&mut self, text: &str, pair: i16) {
-        self.label_fixed_width(text, text.len() as i32, pair);
-    }
-
-    fn end(&mut self) {
-        self.layouts
-            .pop()
-            .expect("Unbalanced Ui::begin() and Ui::end() calls.");
-    }
-}
-
-#[derive(Debug, PartialEq)]
-enum Status {
-    Todo,
-    Done,
-}
-
-impl Status {
-    fn toggle(&self) -> Self {
-        match self {
-            Status::Todo => Status::Done,
-            Status::Done => Status::Todo,
-        }
-    }
-}
-
-fn parse_item(line: &str) -> Option<(Status, &str)> {
-    let todo_item = line
-        .strip_prefix("TODO: ")
-        .map(|title| (Status::Todo, title));
-    let done_item = line
-        .strip_prefix("DONE: ")
-        .map(|title| (Status::Done, title));
-    todo_item.or(done_item)
-}
-
-fn list_drag_up(list: &mut [String], list_curr: &mut usize) {
-    if *list_curr > 0 {
-        list.swap(*list_curr, *list_curr - 1);
-        *list_curr -= 1;
-    }
-}
-
-fn list_drag_down(list: &mut [String], list_curr: &mut usize) {
-    if *list_curr + 1 < list.len() {
-        list.swap(*list_curr, *list_curr + 1);
-        *list_curr += 1;
-    }
-}
-
-fn list_up(list_curr: &mut usize) {
-    if *list_curr > 0 {
-        *list_curr -= 1;
-    }
-}
-
-fn list_down(list: &[String], list_curr: &mut usize) {
-    if *list_curr + 1 < list.len() {
-        *list_curr += 1;
-    }
-}
-
-fn list_first(list_curr: &mut usize) {
-    if *list_curr > 0 {
-        *list_curr = 0;
-    }
-}
-
-fn list_last(list: &[String], list_curr: &mut usize) {
-    if !list.is_empty() {
-        *list_curr = list.len() - 1;
-    }
-}
-
-fn list_transfer(
-    list_dst: &mut Vec<String>,
-    list_src: &mut Vec<String>,
-    list_src_curr: &mut usize,
-) {
-    if *list_src_curr < list_src.len() {
-        list_dst.push(list_src.remove(*list_src_curr));
-        if *list_src_curr >= list_src.len() && !list_src.is_empty() {
-            *list_src_curr = list_src.len() - 1;
+fn main() {
+    let args = match cli::parse(env::args().skip(1)) {
+        Ok(cli::Mode::Run(args)) => args,
+        Ok(cli::Mode::Help) => {
+            print!("{}", cli::help());
+            return;
         }
-    }
-}
-
-fn list_delete(list: &mut Vec<String>, list_curr: &mut usize) {
-    if *list_curr < list.len() {
-        list.remove(*list_curr);
-        if *list_curr >= list.len() && !list.is_empty() {
-            *list_curr = list.len() - 1;
+        Ok(cli::Mode::Version) => {
+            println!("{}", cli::version());
+            return;
         }
-    }
-}
-
-fn load_state(todos: &mut Vec<String>, dones: &mut Vec<String>, file_path: &str) -> io::Result<()> {
-    let file = File::open(file_path)?;
-    for (index, line) in io::BufReader::new(file).lines().enumerate() {
-        match parse_item(&line?) {
-            Some((Status::Todo, title)) => todos.push(title.to_string()),
-            Some((Status::Done, title)) => dones.push(title.to_string()),
-            None => {
-                eprintln!("{}:{}: ERROR: ill-formed item line", file_path, index + 1);
-                process::exit(1);
-            }
+        Ok(cli::Mode::Completions(shell)) => {
+            print!("{}", completions::script(shell));
+            return;
         }
-    }
-    Ok(())
-}
-
-fn save_state(todos: &[String], dones: &[String], file_path: &str) {
-    let mut file = File::create(file_path).unwrap();
-    for todo in todos.iter() {
-        writeln!(file, "TODO: {}", todo).unwrap();
-    }
-    for done in dones.iter() {
-        writeln!(file, "DONE: {}", done).unwrap();
-    }
-}
-
-// TODO(#2): add new items to TODO
-// TODO(#3): delete items
-// TODO(#4): edit the items
-// TODO(#5): keep track of date when the item was DONE
-// TODO(#6): undo system
-// TODO(#12): save the state on SIGINT
-
-fn main() {
-    ctrlc::init();
-
-    let mut args = env::args();
-    args.next().unwrap();
-
-    let file_path = match args.next() {
-        Some(file_path) => file_path,
-        None => {
-            eprintln!("Usage: todo-rs <file-path>");
-            eprintln!("ERROR: file path is not provided");
-            process::exit(1);
-        }
-    };
-
-    let mut todos = Vec::<String>::new();
-    let mut todo_curr: usize = 0;
-    let mut dones = Vec::<String>::new();
-    let mut done_curr: usize = 0;
-
-    let mut notification: String;
-
-    match load_state(&mut todos, &mut dones, &file_path) {
-        Ok(()) => notification = format!("Loaded file {}", file_path),
         Err(error) => {
-            if error.kind() == ErrorKind::NotFound {
-                notification = format!("New file {}", file_path)
-            } else {
-                panic!(
-                    "Could not load state from file `{}`: {:?}",
-                    file_path, error
-                );
-            }
+            eprintln!("ERROR: {}", error);
+            eprintln!("Run `todo-rs --help` for the usage");
+            process::exit(1);
         }
     };
+    if let Err(error) = run(args) {
+        eprintln!("{}", error);
+        process::exit(1);
+    }
+}
 
-    initscr();
-    noecho();
-    keypad(stdscr(), true);
-    timeout(16); // running in 60 FPS for better gaming experience
-    curs_set(CURSOR_VISIBILITY::CURSOR_INVISIBLE);
-
-    start_color();
-    init_pair(REGULAR_PAIR, COLOR_WHITE, COLOR_BLACK);
-    init_pair(HIGHLIGHT_PAIR, COLOR_BLACK, COLOR_WHITE);
-
-    let mut quit = false;
-    let mut panel = Status::Todo;
-    let mut editing = false;
-    let mut editing_cursor = 0;
-
-    let mut ui = Ui::default();
-    while !quit && !ctrlc::poll() {
-        erase();
-
-        let mut x = 0;
-        let mut y = 0;
-        getmaxyx(stdscr(), &mut y, &mut x);
-
-        ui.begin(Vec2::new(0, 0), LayoutKind::Vert);
-        {
-            ui.label_fixed_width(&notification, x, REGULAR_PAIR);
-            ui.label_fixed_width("", x, REGULAR_PAIR);
-
-            ui.begin_layout(LayoutKind::Horz);
+// Whatever stops the app from starting ends up here, see Setup::load()
+fn run(mut args: Args) -> Result<(), String> {
+    let setup = loop {
+        match Setup::load(args.clone()) {
+            Ok(setup) => break setup,
+            // Only somebody at the terminal can be asked, the batch commands and the scripts that
+            // run the app just fail on a locked file instead of waiting for an answer
+            Err(setup::Error::Locked(locked))
+                if args.batch.is_none() && io::stdin().is_terminal() =>
             {
-                ui.begin_layout(LayoutKind::Vert);
+                eprint!("{}. Open it read-only? [y/N] ", locked);
+                let mut answer = String::new();
+                if io::stdin().read_line(&mut answer).is_err()
+                    || !answer.trim().eq_ignore_ascii_case("y")
                 {
-                    if panel == Status::Todo {
-                        ui.label_fixed_width("TODO", x / 2, HIGHLIGHT_PAIR);
-                        // TODO(#27): the item lists don't have a scroll area
-                        for (index, todo) in todos.iter_mut().enumerate() {
-                            if index == todo_curr {
-                                if editing {
-                                    ui.edit_field(todo, &mut editing_cursor, x / 2);
-
-                                    if let Some('\n') = ui.key.take().map(|x| x as u8 as char) {
-                                        editing = false;
-                                    }
-                                } else {
-                                    ui.label_fixed_width(
-                                        &format!("- [ ] {}", todo),
-                                        x / 2,
-                                        HIGHLIGHT_PAIR,
-                                    );
-                                    if let Some('r') = ui.key.map(|x| x as u8 as char) {
-                                        editing = true;
-                                        editing_cursor = todo.len();
-                                        ui.key = None;
-                                    }
-                                }
-                            } else {
-                                ui.label_fixed_width(
-                                    &format!("- [ ] {}", todo),
-                                    x / 2,
-                                    REGULAR_PAIR,
-                                );
-                            }
-                        }
-
-                        if let Some(key) = ui.key.take() {
-                            match key as u8 as char {
-                                'K' => list_drag_up(&mut todos, &mut todo_curr),
-                                'J' => list_drag_down(&mut todos, &mut todo_curr),
-                                'i' => {
-                                    todos.insert(todo_curr, String::new());
-                                    editing_cursor = 0;
-                                    editing = true;
-                                    notification.push_str("What needs to be done?");
-                                }
-                                'd' => {
-                                    notification.push_str(
-                                        "Can't remove items from TODO. Mark it as DONE first.",
-                                    );
-                                }
-                                'k' => list_up(&mut todo_curr),
-                                'j' => list_down(&todos, &mut todo_curr),
-                                'g' => list_first(&mut todo_curr),
-                                'G' => list_last(&todos, &mut todo_curr),
-                                '\n' => {
-                                    list_transfer(&mut dones, &mut todos, &mut todo_curr);
-                                    notification.push_str("DONE!")
-                                }
-                                '\t' => {
-                                    panel = panel.toggle();
-                                }
-                                _ => {
-                                    ui.key = Some(key);
-                                }
-                            }
-                        }
-                    } else {
-                        ui.label_fixed_width("TODO", x / 2, REGULAR_PAIR);
-                        for todo in todos.iter() {
-                            ui.label_fixed_width(&format!("- [ ] {}", todo), x / 2, REGULAR_PAIR);
-                        }
-                    }
+                    process::exit(1);
                 }
-                ui.end_layout();
-
-                ui.begin_layout(LayoutKind::Vert);
-                {
-                    if panel == Status::Done {
-                        ui.label_fixed_width("DONE", x / 2, HIGHLIGHT_PAIR);
-                        for (index, done) in dones.iter_mut().enumerate() {
-                            if index == done_curr {
-                                if editing {
-                                    ui.edit_field(done, &mut editing_cursor, x / 2);
-
-                                    if let Some('\n') = ui.key.take().map(|x| x as u8 as char) {
-                                        editing = false;
-                                    }
-                                } else {
-                                    ui.label_fixed_width(
-                                        &format!("- [x] {}", done),
-                                        x / 2,
-                                        HIGHLIGHT_PAIR,
-                                    );
-                                    if let Some('r') = ui.key.map(|x| x as u8 as char) {
-                                        editing = true;
-                                        editing_cursor = done.len();
-                                        ui.key = None;
-                                    }
-                                }
-                            } else {
-                                ui.label_fixed_width(
-                                    &format!("- [x] {}", done),
-                                    x / 2,
-                                    REGULAR_PAIR,
-                                );
-                            }
-                        }
-
-                        if let Some(key) = ui.key.take() {
-                            match key as u8 as char {
-                                'K' => list_drag_up(&mut dones, &mut done_curr),
-                                'J' => list_drag_down(&mut dones, &mut done_curr),
-                                'k' => list_up(&mut done_curr),
-                                'j' => list_down(&dones, &mut done_curr),
-                                'g' => list_first(&mut done_curr),
-                                'G' => list_last(&dones, &mut done_curr),
-                                'i' => {
-                                    notification.push_str(
-                                        "Can't insert new DONE items. Only TODO is allowed.",
-                                    );
-                                }
-                                'd' => {
-                                    list_delete(&mut dones, &mut done_curr);
-                                    notification.push_str("Into The Abyss!");
-                                }
-                                '\n' => {
-                                    list_transfer(&mut todos, &mut dones, &mut done_curr);
-                                    notification.push_str("No, not done yet...")
-                                }
-                                '\t' => {
-                                    panel = panel.toggle();
-                                }
-                                _ => ui.key = Some(key),
-                            }
-                        }
-                    } else {
-                        ui.label_fixed_width("DONE", x / 2, REGULAR_PAIR);
-                        for done in dones.iter() {
-                            ui.label_fixed_width(&format!("- [x] {}", done), x / 2, REGULAR_PAIR);
-                        }
-                    }
-                }
-                ui.end_layout();
+                args.read_only_files.push(locked.file_path);
             }
-            ui.end_layout();
-        }
-        ui.end();
-
-        if let Some('q') = ui.key.take().map(|x| x as u8 as char) {
-            quit = true;
-        }
-
-        refresh();
-
-        let key = getch();
-        if key != ERR {
-            notification.clear();
-            ui.key = Some(key);
+            Err(error) => return Err(error.to_string()),
         }
+    };
+    let Some(mut tui) = setup.run()? else {
+        return Ok(());
+    };
+    while tui.running() {
+        tui.step();
     }
-
-    endwin();
-
-    save_state(&todos, &dones, &file_path);
-    println!("Saved state to {}", file_path);
+    if !tui.finish() {
+        process::exit(1);
+    }
+    Ok(())
 }
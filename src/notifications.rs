@@ -0,0 +1,85 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Level {
+    Info,
+    Warning,
+    Error,
+    // Stays up for a while, or until it's snoozed
+    Reminder,
+}
+
+impl Level {
+    // The more serious the message the longer it stays so the user has a chance to actually read it
+    fn duration(&self) -> Duration {
+        match self {
+            Level::Info => Duration::from_secs(3),
+            Level::Warning => Duration::from_secs(5),
+            Level::Error => Duration::from_secs(8),
+            Level::Reminder => Duration::from_secs(60),
+        }
+    }
+}
+
+pub struct Notification {
+    pub text: String,
+    pub level: Level,
+    expires: Instant,
+}
+
+#[derive(Default)]
+pub struct Notifications {
+    queue: VecDeque<Notification>,
+}
+
+impl Notifications {
+    pub fn push(&mut self, level: Level, text: impl Into<String>) {
+        self.queue.push_back(Notification {
+            text: text.into(),
+            level,
+            expires: Instant::now() + level.duration(),
+        });
+    }
+
+    pub fn info(&mut self, text: impl Into<String>) {
+        self.push(Level::Info, text);
+    }
+
+    pub fn warning(&mut self, text: impl Into<String>) {
+        self.push(Level::Warning, text);
+    }
+
+    pub fn error(&mut self, text: impl Into<String>) {
+        self.push(Level::Error, text);
+    }
+
+    pub fn dismiss(&mut self, level: Level) {
+        self.queue
+            .retain(|notification| notification.level != level);
+    }
+
+    // Returns true if anything expired
+    pub fn expire(&mut self, now: Instant) -> bool {
+        let len = self.queue.len();
+        self.queue.retain(|notification| notification.expires > now);
+        self.queue.len() != len
+    }
+
+    // When the next notification is going to expire, if there is any
+    pub fn next_expiry(&self) -> Option<Instant> {
+        self.queue
+            .iter()
+            .map(|notification| notification.expires)
+            .min()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    // From the newest to the oldest
+    pub fn iter(&self) -> impl Iterator<Item = &Notification> {
+        self.queue.iter().rev()
+    }
+}
@@ -0,0 +1,125 @@
+use crate::ui::{elide, text_width};
+use std::env;
+#[cfg(unix)]
+use std::mem;
+use todo_rs::date::Date;
+use todo_rs::{item_date, Status};
+
+// The board for a glance without the app taking the terminal over: both lists next to each other
+// with their counts, the way the app shows them. The colors are ANSI escapes and only go to a
+// terminal.
+
+const BOLD: &str = "\x1b[1m";
+const DIM: &str = "\x1b[2m";
+const RED: &str = "\x1b[31m";
+const RESET: &str = "\x1b[0m";
+
+// Any narrower and the lists go one after the other instead
+const MIN_COLUMN_WIDTH: usize = 30;
+const GAP: usize = 2;
+
+struct Cell {
+    text: String,
+    style: &'static str,
+}
+
+fn column(status: Status, items: &[String], width: usize, today: Date) -> Vec<Cell> {
+    let mut cells = vec![
+        Cell {
+            text: format!("{} ({})", status.name(), items.len()),
+            style: BOLD,
+        },
+        Cell {
+            text: "-".repeat(width),
+            style: DIM,
+        },
+    ];
+    for item in items.iter() {
+        let (marker, style) = match status {
+            Status::Todo if item_date(item, "due").is_some_and(|due| due < today) => ("[ ]", RED),
+            Status::Todo => ("[ ]", ""),
+            Status::Done => ("[x]", DIM),
+        };
+        cells.push(Cell {
+            text: elide(&format!("- {} {}", marker, item), width as i32).into_owned(),
+            style,
+        });
+    }
+    if items.is_empty() {
+        cells.push(Cell {
+            text: "nothing here".to_string(),
+            style: DIM,
+        });
+    }
+    cells
+}
+
+fn paint(cell: &Cell, color: bool) -> String {
+    if color && !cell.style.is_empty() {
+        format!("{}{}{}", cell.style, cell.text, RESET)
+    } else {
+        cell.text.clone()
+    }
+}
+
+pub fn render(todos: &[String], dones: &[String], width: usize, color: bool) -> String {
+    let today = Date::today();
+    let mut output = String::new();
+    if width >= 2 * MIN_COLUMN_WIDTH + GAP {
+        let width = (width - GAP) / 2;
+        let left = column(Status::Todo, todos, width, today);
+        let right = column(Status::Done, dones, width, today);
+        for row in 0..left.len().max(right.len()) {
+            match left.get(row) {
+                Some(cell) => {
+                    output.push_str(&paint(cell, color));
+                    output.push_str(&" ".repeat(width - text_width(&cell.text) + GAP));
+                }
+                None => output.push_str(&" ".repeat(width + GAP)),
+            }
+            if let Some(cell) = right.get(row) {
+                output.push_str(&paint(cell, color));
+            }
+            // No trailing spaces where the right column is over
+            let trimmed = output.trim_end_matches(' ').len();
+            output.truncate(trimmed);
+            output.push('\n');
+        }
+    } else {
+        let width = width.max(1);
+        let todos = column(Status::Todo, todos, width, today);
+        let dones = column(Status::Done, dones, width, today);
+        for (index, cells) in [todos, dones].iter().enumerate() {
+            if index > 0 {
+                output.push('\n');
+            }
+            for cell in cells.iter() {
+                output.push_str(&paint(cell, color));
+                output.push('\n');
+            }
+        }
+    }
+    output
+}
+
+// The width of the terminal on stdout, otherwise $COLUMNS or 80 like the terminals start with
+pub fn terminal_width() -> usize {
+    #[cfg(unix)]
+    {
+        let mut size: libc::winsize = unsafe { mem::zeroed() };
+        if unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) } == 0
+            && size.ws_col > 0
+        {
+            return size.ws_col as usize;
+        }
+    }
+    #[cfg(not(unix))]
+    if let Ok((columns, _)) = crossterm::terminal::size() {
+        return columns as usize;
+    }
+    env::var("COLUMNS")
+        .ok()
+        .and_then(|columns| columns.parse().ok())
+        .filter(|&columns| columns > 0)
+        .unwrap_or(80)
+}
@@ -0,0 +1,178 @@
+use crate::terminal;
+use std::fs::File;
+use std::io::{self, Write};
+use std::panic;
+use std::sync::{Mutex, TryLockError};
+use todo_rs::items::Items;
+use todo_rs::{format, Extras};
+
+// A copy of the lists of a file as of the last frame. The panic hook has no other way to get to
+// them, since they live on the stack of main() which is being unwound at that point.
+struct Snapshot {
+    file_path: String,
+    // The recovered file is written the way the file itself is, the lines that are not items
+    // included
+    format: format::Kind,
+    todos: Items,
+    dones: Items,
+    extras: Extras,
+    // Of what the copies were made of, they are made again only once it changes
+    version: Version,
+}
+
+impl Snapshot {
+    fn write(&self, out: &mut impl Write) -> io::Result<()> {
+        let format = self.format.format();
+        todo_rs::write_state(out, &self.todos, &self.dones, &self.extras, format)
+    }
+}
+
+// The revisions of the lists and what tells the changes of the extras apart. The extras only ever
+// lose their lines, so the numbers of what is left to resolve go down whenever they change.
+#[derive(PartialEq)]
+struct Version {
+    revisions: [usize; 2],
+    extras: (bool, usize, usize),
+}
+
+impl Version {
+    fn new(todos: &Items, dones: &Items, extras: &Extras) -> Self {
+        Self {
+            revisions: [todos.revision(), dones.revision()],
+            extras: (
+                extras.changed,
+                extras.problems.len(),
+                extras.conflicts.len(),
+            ),
+        }
+    }
+}
+
+// One for every file that has been open
+static SNAPSHOTS: Mutex<Vec<Snapshot>> = Mutex::new(Vec::new());
+
+fn recovered_path(file_path: &str) -> String {
+    format!("{}.recovered", file_path)
+}
+
+// On panic restores the terminal, prints the panic message where the user can actually see it and
+// writes the last known state of the lists to recovered_path() next to the original files
+pub fn install() {
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        terminal::endwin();
+        terminal::bracketed_paste(false);
+
+        default_hook(info);
+        dump();
+    }));
+}
+
+// Writes the last known state of the lists to recovered_path() next to the original files. Also
+// the last resort for when they can't be saved the normal way.
+pub fn dump() {
+    // try_lock() because the panic might have happened while the main thread was holding it
+    let snapshots = match SNAPSHOTS.try_lock() {
+        Ok(snapshots) => snapshots,
+        Err(TryLockError::Poisoned(error)) => error.into_inner(),
+        Err(TryLockError::WouldBlock) => return,
+    };
+    for snapshot in snapshots.iter() {
+        let path = recovered_path(&snapshot.file_path);
+        let result = File::create(&path).and_then(|mut file| snapshot.write(&mut file));
+        match result {
+            Ok(()) => eprintln!("Saved the unsaved state to {}", path),
+            Err(error) => eprintln!("Could not save the unsaved state to {}: {}", path, error),
+        }
+    }
+}
+
+// Must be called every time the lists of the file change, so the hook has something fresh to save.
+// Nothing is copied unless they did change, so it's fine to call it on every frame.
+pub fn update(
+    file_path: &str,
+    format: format::Kind,
+    todos: &Items,
+    dones: &Items,
+    extras: &Extras,
+) {
+    let version = Version::new(todos, dones, extras);
+    // The lock can't be poisoned by anything but a panic in here, and the data is still fine then
+    let mut snapshots = SNAPSHOTS.lock().unwrap_or_else(|error| error.into_inner());
+    match snapshots
+        .iter_mut()
+        .find(|snapshot| snapshot.file_path == file_path)
+    {
+        Some(snapshot) if snapshot.version == version && snapshot.format == format => {}
+        Some(snapshot) => {
+            snapshot.format = format;
+            snapshot.todos = todos.clone();
+            snapshot.dones = dones.clone();
+            snapshot.extras = extras.clone();
+            snapshot.version = version;
+        }
+        None => snapshots.push(Snapshot {
+            file_path: file_path.to_string(),
+            format,
+            todos: todos.clone(),
+            dones: dones.clone(),
+            extras: extras.clone(),
+            version,
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_recovered_file_is_in_the_format_of_the_file() {
+        let data = "# Groceries\n\n- [ ] buy milk\n- [x] buy bread\n";
+        let format = format::Kind::Markdown;
+        let (mut todos, mut dones, mut extras) = (Vec::new(), Vec::new(), Extras::default());
+        todo_rs::parse_state(
+            &mut todos,
+            &mut dones,
+            &mut extras,
+            data.as_bytes(),
+            format.format(),
+        );
+        let (todos, dones) = (Items::from(todos), Items::from(dones));
+        update("recovery-test.md", format, &todos, &dones, &extras);
+
+        let snapshots = SNAPSHOTS.lock().unwrap();
+        let snapshot = snapshots
+            .iter()
+            .find(|snapshot| snapshot.file_path == "recovery-test.md")
+            .unwrap();
+        let mut out = Vec::new();
+        snapshot.write(&mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), data);
+    }
+
+    #[test]
+    fn the_snapshot_follows_the_changes() {
+        let snapshot = |todos: &Items| {
+            update(
+                "recovery-test.todo",
+                format::Kind::default(),
+                todos,
+                &Items::default(),
+                &Extras::default(),
+            );
+            let snapshots = SNAPSHOTS.lock().unwrap();
+            let snapshot = snapshots
+                .iter()
+                .find(|snapshot| snapshot.file_path == "recovery-test.todo")
+                .unwrap();
+            let mut out = Vec::new();
+            snapshot.write(&mut out).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        let mut todos = Items::from(vec!["buy milk".to_string()]);
+        assert_eq!(snapshot(&todos), "TODO: buy milk\n");
+        todos.push("call mom".to_string());
+        assert_eq!(snapshot(&todos), "TODO: buy milk\nTODO: call mom\n");
+    }
+}
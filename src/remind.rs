@@ -0,0 +1,130 @@
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use todo_rs::date::Date;
+use todo_rs::{dedupe_key, item_meta, item_set_meta};
+
+use crate::config::Config;
+use crate::notifications::{Level, Notifications};
+
+const MINUTES_PER_DAY: u32 = 24 * 60;
+
+// For :snooze without the minutes
+pub const SNOOZE_MINUTES: u32 = 10;
+
+// The TODO items with remind:HH:MM come up on the banner when the clock gets there, every day until
+// they are done or the reminder is removed. The reminders missed while the app was closed come up
+// when it starts, the ones of the day so far.
+#[derive(Default)]
+pub struct Reminders {
+    // The minute of the day of the last check, the reminders up to it went off already
+    checked: Option<u32>,
+    // dedupe_key() of the items that went off last, for :snooze
+    fired: Vec<String>,
+    // Ring the terminal bell too, by [ui] reminder_bell
+    pub bell: bool,
+}
+
+// The seconds since the local midnight
+fn seconds_of_day() -> u32 {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs() as i64);
+    (now - Date::local(now).local_midnight()).clamp(0, 86399) as u32
+}
+
+// HH:MM in minutes since the midnight
+fn parse_time(text: &str) -> Option<u32> {
+    let (hours, minutes) = text.split_once(':')?;
+    if hours.is_empty() || hours.len() > 2 || minutes.len() != 2 {
+        return None;
+    }
+    let hours: u32 = hours.parse().ok()?;
+    let minutes: u32 = minutes.parse().ok()?;
+    if hours < 24 && minutes < 60 {
+        Some(hours * 60 + minutes)
+    } else {
+        None
+    }
+}
+
+fn format_time(minutes: u32) -> String {
+    format!("{:02}:{:02}", minutes / 60, minutes % 60)
+}
+
+impl Reminders {
+    pub fn from_config(config: &Config) -> Result<Self, String> {
+        let bell = match config.get("ui", "reminder_bell") {
+            None | Some("off") => false,
+            Some("on") => true,
+            Some(value) => {
+                return Err(config.error(
+                    "ui",
+                    "reminder_bell",
+                    &format!("expected `on` or `off` but got `{}`", value),
+                ))
+            }
+        };
+        Ok(Self {
+            checked: None,
+            fired: Vec::new(),
+            bell,
+        })
+    }
+
+    // The start of the next minute, for Event::Tick
+    pub fn deadline(&self) -> Instant {
+        Instant::now() + Duration::from_secs(u64::from(60 - seconds_of_day() % 60))
+    }
+
+    // Puts the reminders that are due on the banner. Returns true if there were any.
+    pub fn check<'a>(
+        &mut self,
+        todos: impl Iterator<Item = &'a String>,
+        notifications: &mut Notifications,
+    ) -> bool {
+        let now = seconds_of_day() / 60;
+        // Past the midnight the rest of the yesterday goes first
+        let went_off = |time: u32| match self.checked {
+            None => time <= now,
+            Some(checked) if checked <= now => checked < time && time <= now,
+            Some(checked) => checked < time || time <= now,
+        };
+        let fired: Vec<&String> = todos
+            .filter(|todo| {
+                item_meta(todo, "remind")
+                    .and_then(parse_time)
+                    .is_some_and(went_off)
+            })
+            .collect();
+        self.checked = Some(now);
+        if fired.is_empty() {
+            return false;
+        }
+        for todo in fired.iter() {
+            notifications.push(Level::Reminder, format!("Reminder: {}", dedupe_key(todo)));
+        }
+        self.fired = fired.into_iter().map(|todo| dedupe_key(todo)).collect();
+        true
+    }
+
+    // Moves the reminders that went off last to the given minutes from now. Returns how many of
+    // them are still among the items.
+    pub fn snooze<'a>(
+        &mut self,
+        todos: impl Iterator<Item = &'a mut String>,
+        minutes: u32,
+        notifications: &mut Notifications,
+    ) -> usize {
+        let time = format_time((seconds_of_day() / 60 + minutes) % MINUTES_PER_DAY);
+        let mut snoozed = 0;
+        for todo in todos {
+            if item_meta(todo, "remind").is_some() && self.fired.contains(&dedupe_key(todo)) {
+                item_set_meta(todo, "remind", Some(&time));
+                snoozed += 1;
+            }
+        }
+        self.fired.clear();
+        notifications.dismiss(Level::Reminder);
+        snoozed
+    }
+}
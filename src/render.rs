@@ -0,0 +1,164 @@
+use crate::action::ESCAPE;
+use crate::macros::Input;
+use crate::terminal::*;
+use crate::ui::Vec2;
+use std::time::Instant;
+
+// Where the Ui draws and where the input comes from. TerminalRenderer is the terminal the app runs
+// in. Anything else that implements these few calls can take its place, the Ui and the App don't
+// know the difference.
+pub trait Renderer {
+    // The columns and the rows there are to draw on
+    fn size(&self) -> Vec2;
+    // Blanks the whole screen for the next frame
+    fn clear(&mut self);
+    // Where the next put() starts
+    fn move_to(&mut self, pos: Vec2);
+    // Draws the text at the cursor and moves the cursor past it. The attributes are a COLOR_PAIR()
+    // with A_BOLD() and the like on top.
+    fn put(&mut self, text: &str, attrs: attr_t);
+    // A box around the area of the given size
+    fn border(&mut self, pos: Vec2, size: Vec2, attrs: attr_t);
+    // Shows everything drawn since the last clear()
+    fn present(&mut self);
+    // The next key or paste, if there is any. Never blocks.
+    fn read_input(&mut self) -> Option<Input>;
+    // Blocks until there may be some input or the deadline passes, whichever comes first. Without
+    // the deadline it may wait indefinitely.
+    fn wait_for_input(&mut self, deadline: Option<Instant>);
+    // The bell of the terminal, for the reminders
+    fn beep(&mut self);
+    // The terminal changed its size, for the backends that don't find out about it themselves
+    fn resize(&mut self);
+    // Gives the terminal back to the shell and stops the app until it's continued, then takes the
+    // terminal again and redraws everything
+    fn suspend(&mut self);
+}
+
+// Terminals in the bracketed paste mode wrap the pasted text into these sequences
+pub const PASTE_BEGIN: &[u8] = b"\x1b[200~";
+pub const PASTE_END: &[u8] = b"\x1b[201~";
+// How long to wait for the next byte of a bracketed paste before counting it as a stall
+const PASTE_STALL_MS: i32 = 16;
+
+// The ncurses API of terminal.rs, whichever backend provides it
+pub struct TerminalRenderer;
+
+impl Renderer for TerminalRenderer {
+    fn size(&self) -> Vec2 {
+        let mut x = 0;
+        let mut y = 0;
+        getmaxyx(stdscr(), &mut y, &mut x);
+        Vec2::new(x, y)
+    }
+
+    fn clear(&mut self) {
+        erase();
+    }
+
+    fn move_to(&mut self, pos: Vec2) {
+        mv(pos.y, pos.x);
+    }
+
+    fn put(&mut self, text: &str, attrs: attr_t) {
+        attron(attrs);
+        addstr(text);
+        attroff(attrs);
+    }
+
+    fn border(&mut self, pos: Vec2, size: Vec2, attrs: attr_t) {
+        let right = pos.x + size.x - 1;
+        let bottom = pos.y + size.y - 1;
+        attron(attrs);
+        mvaddch(pos.y, pos.x, ACS_ULCORNER());
+        mvaddch(pos.y, right, ACS_URCORNER());
+        mvaddch(bottom, pos.x, ACS_LLCORNER());
+        mvaddch(bottom, right, ACS_LRCORNER());
+        mvhline(pos.y, pos.x + 1, ACS_HLINE(), size.x - 2);
+        mvhline(bottom, pos.x + 1, ACS_HLINE(), size.x - 2);
+        mvvline(pos.y + 1, pos.x, ACS_VLINE(), size.y - 2);
+        mvvline(pos.y + 1, right, ACS_VLINE(), size.y - 2);
+        attroff(attrs);
+    }
+
+    fn present(&mut self) {
+        refresh();
+    }
+
+    // Tells the bracketed pastes apart from the keys
+    fn read_input(&mut self) -> Option<Input> {
+        match getch() {
+            ERR => None,
+            ESCAPE => Some(match read_paste() {
+                Some(text) => Input::Paste(text),
+                None => Input::Key(ESCAPE),
+            }),
+            key => Some(Input::Key(key)),
+        }
+    }
+
+    fn wait_for_input(&mut self, deadline: Option<Instant>) {
+        wait_for_input(deadline);
+    }
+
+    fn beep(&mut self) {
+        beep();
+    }
+
+    fn resize(&mut self) {
+        resize();
+    }
+
+    fn suspend(&mut self) {
+        suspend();
+    }
+}
+
+// Must be called right after getch() returned ESCAPE. Returns None if ESCAPE does not start a
+// bracketed paste, leaving the input as it was.
+fn read_paste() -> Option<String> {
+    // The rest of the paste may still be on its way, so wait for it a little
+    timeout(PASTE_STALL_MS);
+    let text = read_paste_sequence();
+    timeout(0);
+    text
+}
+
+fn read_paste_sequence() -> Option<String> {
+    let mut begin = vec![ESCAPE as u8];
+    while begin.len() < PASTE_BEGIN.len() && PASTE_BEGIN.starts_with(&begin) {
+        match getch() {
+            ERR => break,
+            key @ 0..=255 => begin.push(key as u8),
+            key => {
+                ungetch(key);
+                break;
+            }
+        }
+    }
+
+    if begin != PASTE_BEGIN {
+        for byte in begin[1..].iter().rev() {
+            ungetch(*byte as i32);
+        }
+        return None;
+    }
+
+    // Give up on the paste if the terminal stops sending it halfway for about a second
+    const MAX_STALLS: i32 = 1000 / PASTE_STALL_MS;
+    let mut text = Vec::new();
+    let mut stalls = 0;
+    while !text.ends_with(PASTE_END) && stalls < MAX_STALLS {
+        match getch() {
+            ERR => stalls += 1,
+            key => {
+                stalls = 0;
+                text.push(key as u8);
+            }
+        }
+    }
+    if text.ends_with(PASTE_END) {
+        text.truncate(text.len() - PASTE_END.len());
+    }
+    Some(String::from_utf8_lossy(&text).into_owned())
+}
@@ -0,0 +1,261 @@
+use std::fs;
+use std::mem;
+use std::path::Path;
+
+use todo_rs::{dedupe_key, item_meta, item_set_meta, Status};
+
+use crate::sync;
+
+// The directories that are never worth scanning, along with the hidden ones like .git
+const SKIPPED_DIRS: &[&str] = &["target", "node_modules"];
+
+// Whatever may come right before TODO in a comment of most of the languages
+const COMMENT_MARKERS: &[&str] = &["//", "/*", "*", "#", "--", ";", "<!--"];
+
+// A TODO comment of the source tree
+pub struct Found {
+    // Relative to the current directory, like the directory that was scanned
+    pub path: String,
+    pub line: usize,
+    pub text: String,
+}
+
+impl Found {
+    fn location(&self) -> String {
+        format!("{}:{}", escape(&self.path), self.line)
+    }
+}
+
+// src:<path>:<line> is a word of the item, so the whitespace in the path is escaped the way the URLs
+// do it, along with the percent signs themselves: `my notes/a.rs` becomes `my%20notes/a.rs`
+fn escape(path: &str) -> String {
+    let mut escaped = String::new();
+    for c in path.chars() {
+        if c == '%' || c.is_whitespace() {
+            for byte in c.to_string().bytes() {
+                escaped.push_str(&format!("%{:02X}", byte));
+            }
+        } else {
+            escaped.push(c);
+        }
+    }
+    escaped
+}
+
+fn unescape(path: &str) -> String {
+    let mut bytes = Vec::new();
+    let mut rest = path.as_bytes();
+    while let Some((&byte, after)) = rest.split_first() {
+        let hex = after
+            .get(..2)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match hex {
+            Some(decoded) if byte == b'%' => {
+                bytes.push(decoded);
+                rest = &after[2..];
+            }
+            _ => {
+                bytes.push(byte);
+                rest = after;
+            }
+        }
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+// The text of `// TODO: text`, `# TODO(#12): text` and the like. TODO without the colon may as well
+// be a word of the comment, so it doesn't count.
+fn comment_todo(line: &str) -> Option<&str> {
+    let mut searched = 0;
+    while let Some(found) = line[searched..].find("TODO") {
+        let start = searched + found;
+        searched = start + "TODO".len();
+        let before = line[..start].trim_end();
+        if !COMMENT_MARKERS
+            .iter()
+            .any(|marker| before.ends_with(marker))
+        {
+            continue;
+        }
+        let mut rest = &line[searched..];
+        if rest.starts_with('(') {
+            match rest.find(')') {
+                Some(end) => rest = &rest[end + 1..],
+                None => continue,
+            }
+        }
+        let text = match rest.strip_prefix(':') {
+            Some(text) => text,
+            None => continue,
+        };
+        let text = text.trim();
+        let text = text.strip_suffix("*/").unwrap_or(text);
+        let text = text.strip_suffix("-->").unwrap_or(text).trim();
+        if !text.is_empty() {
+            return Some(text);
+        }
+    }
+    None
+}
+
+fn walk(
+    dir: &Path,
+    skipped: &Path,
+    found: &mut Vec<Found>,
+    files: &mut usize,
+) -> Result<(), String> {
+    let entries = fs::read_dir(dir).map_err(|error| format!("{}: {}", dir.display(), error))?;
+    let mut entries: Vec<_> = entries.filter_map(Result::ok).collect();
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        let path = entry.path();
+        // The symlinks are left alone, they may well loop
+        let file_type = match entry.file_type() {
+            Ok(file_type) => file_type,
+            Err(_) => continue,
+        };
+        if file_type.is_dir() {
+            if !name.starts_with('.') && !SKIPPED_DIRS.contains(&name.as_ref()) {
+                walk(&path, skipped, found, files)?;
+            }
+            continue;
+        }
+        if !file_type.is_file() || fs::canonicalize(&path).is_ok_and(|path| path == skipped) {
+            continue;
+        }
+        // The binaries and whatever else is not text
+        let source = match fs::read_to_string(&path) {
+            Ok(source) => source,
+            Err(_) => continue,
+        };
+        *files += 1;
+        let path = path.to_string_lossy();
+        let path = path.strip_prefix("./").unwrap_or(&path);
+        for (index, line) in source.lines().enumerate() {
+            if let Some(text) = comment_todo(line) {
+                // The tabs would not survive the file
+                let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+                found.push(Found {
+                    path: path.to_string(),
+                    line: index + 1,
+                    text,
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+// The TODO comments of the files under the directory but the file of the lists, and how many files
+// there were
+pub fn scan(dir: &str, file_path: &str) -> Result<(Vec<Found>, usize), String> {
+    // The metadata can't start with a slash, that's what tells it apart from the links
+    if Path::new(dir).has_root() {
+        return Err(format!(
+            "expected the directory relative to the current one but got `{}`",
+            dir
+        ));
+    }
+    let skipped = fs::canonicalize(file_path).unwrap_or_default();
+    let mut found = Vec::new();
+    let mut files = 0;
+    walk(Path::new(dir), &skipped, &mut found, &mut files)?;
+    Ok((found, files))
+}
+
+// What merge() did
+#[derive(Default)]
+pub struct Merged {
+    pub added: Vec<String>,
+    pub updated: usize,
+    pub done: Vec<String>,
+}
+
+// The items of the comments keep where they are in src:<path>:<line>. The comment that is an item
+// already moves its src: along with the line, the new ones are added, and the items of the comments
+// that are gone from the directory are done.
+pub fn merge(
+    dir: &str,
+    found: &[Found],
+    todos: &mut Vec<String>,
+    dones: &mut Vec<String>,
+) -> Merged {
+    // The paths of the comments don't start with ./, and everything is under the current directory
+    let mut dir = dir;
+    while let Some(rest) = dir.strip_prefix("./") {
+        dir = rest;
+    }
+    let dir = Path::new(if dir == "." { "" } else { dir });
+    let source_path = |item: &str| {
+        let location = item_meta(item, "src")?;
+        Some(unescape(
+            location.rsplit_once(':').map_or(location, |(path, _)| path),
+        ))
+    };
+    let mut merged = Merged::default();
+    // The items that are among the comments, the rest of the directory's are gone
+    let mut seen = vec![false; todos.len()];
+    for comment in found {
+        let key = dedupe_key(&comment.text);
+        let index = todos.iter().enumerate().position(|(index, item)| {
+            !seen.get(index).copied().unwrap_or(true)
+                && source_path(item).as_deref() == Some(comment.path.as_str())
+                && dedupe_key(item) == key
+        });
+        match index {
+            Some(index) => {
+                seen[index] = true;
+                if item_meta(&todos[index], "src") != Some(comment.location().as_str()) {
+                    item_set_meta(&mut todos[index], "src", Some(&comment.location()));
+                    merged.updated += 1;
+                }
+            }
+            None => {
+                let mut item = comment.text.clone();
+                item_set_meta(&mut item, "src", Some(&comment.location()));
+                merged.added.push(item.clone());
+                todos.push(item);
+            }
+        }
+    }
+    for (index, mut item) in mem::take(todos).into_iter().enumerate() {
+        let gone = !seen.get(index).copied().unwrap_or(true)
+            && source_path(&item).is_some_and(|path| Path::new(&path).starts_with(dir));
+        if gone {
+            sync::set_status(&mut item, Status::Done, None);
+            merged.done.push(item.clone());
+            dones.push(item);
+        } else {
+            todos.push(item);
+        }
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_paths_with_spaces_are_merged_once() {
+        let found = [Found {
+            path: "my notes/100% done.rs".to_string(),
+            line: 3,
+            text: "handle the errors".to_string(),
+        }];
+        let (mut todos, mut dones) = (Vec::new(), Vec::new());
+        merge(".", &found, &mut todos, &mut dones);
+        assert_eq!(
+            todos,
+            ["handle the errors src:my%20notes/100%25%20done.rs:3"]
+        );
+
+        let merged = merge(".", &found, &mut todos, &mut dones);
+        assert!(merged.added.is_empty());
+        assert_eq!(todos.len(), 1);
+        assert!(dones.is_empty());
+    }
+}
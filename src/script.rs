@@ -0,0 +1,222 @@
+use std::cell::RefCell;
+use std::cmp;
+use std::mem;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+use rhai::{Array, CallFnOptions, Dynamic, Engine, EvalAltResult, Scope, AST};
+use todo_rs::date::Date;
+use todo_rs::{item_meta, item_set_meta, item_tags};
+
+use crate::app::App;
+use crate::config::Config;
+use crate::keymap::Keymap;
+
+// A script that never ends is stopped rather than hanging the app
+const MAX_OPERATIONS: u64 = 10_000_000;
+
+// What the script sees of the app. Its commands work on copies of the lists, which take the place
+// of the real ones only once the command is done, so a command that fails changes nothing.
+#[derive(Default)]
+struct Shared {
+    todos: Vec<String>,
+    dones: Vec<String>,
+    changed: bool,
+    // For the notifications, from notify() and print()
+    messages: Vec<String>,
+    // The keys and the command lines from bind(), only while the script is loaded
+    bindings: Vec<(String, String)>,
+}
+
+type Result<T> = std::result::Result<T, Box<EvalAltResult>>;
+
+// The functions of the script are the `:` commands, called with the words after the name as
+// strings. The statements outside of them run once when the app starts, to bind() the keys.
+pub struct Script {
+    engine: Engine,
+    ast: AST,
+    // The variables of the script that live from one command to another
+    scope: Scope<'static>,
+    shared: Rc<RefCell<Shared>>,
+}
+
+// The items are single lines of text, that's all the script has to keep in mind
+fn items(array: Array) -> Result<Vec<String>> {
+    array
+        .into_iter()
+        .map(|item| {
+            let item = item
+                .into_string()
+                .map_err(|kind| format!("the items are strings, not {}", kind))?;
+            let item = item.trim().to_string();
+            if item.is_empty() || item.contains(['\n', '\r']) {
+                Err(format!("`{}` is not a single line of text", item).into())
+            } else {
+                Ok(item)
+            }
+        })
+        .collect()
+}
+
+fn date(text: &str) -> Result<Date> {
+    Date::parse(text).ok_or_else(|| format!("`{}` is not a YYYY-MM-DD date", text).into())
+}
+
+fn engine(shared: &Rc<RefCell<Shared>>) -> Engine {
+    let mut engine = Engine::new();
+    engine.set_max_operations(MAX_OPERATIONS);
+
+    let lists = Rc::clone(shared);
+    engine.register_fn("todos", move || -> Array {
+        lists
+            .borrow()
+            .todos
+            .iter()
+            .cloned()
+            .map(Dynamic::from)
+            .collect()
+    });
+    let lists = Rc::clone(shared);
+    engine.register_fn("dones", move || -> Array {
+        lists
+            .borrow()
+            .dones
+            .iter()
+            .cloned()
+            .map(Dynamic::from)
+            .collect()
+    });
+    let lists = Rc::clone(shared);
+    engine.register_fn("set_todos", move |todos: Array| -> Result<()> {
+        let mut lists = lists.borrow_mut();
+        lists.todos = items(todos)?;
+        lists.changed = true;
+        Ok(())
+    });
+    let lists = Rc::clone(shared);
+    engine.register_fn("set_dones", move |dones: Array| -> Result<()> {
+        let mut lists = lists.borrow_mut();
+        lists.dones = items(dones)?;
+        lists.changed = true;
+        Ok(())
+    });
+
+    engine.register_fn("meta", |item: &str, key: &str| -> String {
+        item_meta(item, key).unwrap_or_default().to_string()
+    });
+    // An empty value removes the key
+    engine.register_fn("set_meta", |item: &str, key: &str, value: &str| -> String {
+        let mut item = item.to_string();
+        item_set_meta(
+            &mut item,
+            key,
+            Some(value).filter(|value| !value.is_empty()),
+        );
+        item
+    });
+    engine.register_fn("tags", |item: &str| -> Array {
+        item_tags(item)
+            .map(|tag| Dynamic::from(tag.to_string()))
+            .collect()
+    });
+    engine.register_fn("today", || Date::today().to_string());
+    engine.register_fn("days_between", |from: &str, to: &str| -> Result<i64> {
+        Ok(date(to)?.days_since(date(from)?))
+    });
+
+    let messages = Rc::clone(shared);
+    engine.register_fn("notify", move |text: &str| {
+        messages.borrow_mut().messages.push(text.to_string())
+    });
+    let messages = Rc::clone(shared);
+    engine.on_print(move |text| messages.borrow_mut().messages.push(text.to_string()));
+    let bindings = Rc::clone(shared);
+    engine.register_fn("bind", move |keys: &str, command: &str| {
+        let binding = (keys.to_string(), command.to_string());
+        bindings.borrow_mut().bindings.push(binding)
+    });
+    engine
+}
+
+// The path from [script] of the config, init.rhai next to the config otherwise
+fn script_path(config: &Config) -> Option<PathBuf> {
+    match config.get("script", "path") {
+        Some("") => None,
+        Some(path) => Some(PathBuf::from(path)),
+        None => Some(config.dir()?.join("init.rhai")).filter(|path| path.exists()),
+    }
+}
+
+impl Script {
+    // No script is not an error, unless the config asks for one that isn't there
+    pub fn load(config: &Config, keymap: &mut Keymap) -> std::result::Result<Option<Self>, String> {
+        let path = match script_path(config) {
+            Some(path) => path,
+            None => return Ok(None),
+        };
+        let error = |error: &dyn std::fmt::Display| format!("{}: ERROR: {}", path.display(), error);
+        let shared = Rc::new(RefCell::new(Shared::default()));
+        let engine = engine(&shared);
+        let ast = engine
+            .compile_file(path.clone())
+            .map_err(|message| error(&message))?;
+        let mut scope = Scope::new();
+        engine
+            .run_ast_with_scope(&mut scope, &ast)
+            .map_err(|message| error(&message))?;
+        for (keys, command) in mem::take(&mut shared.borrow_mut().bindings) {
+            keymap
+                .bind_command(&keys, &command)
+                .map_err(|message| error(&format!("bind(\"{}\"): {}", keys, message)))?;
+        }
+        Ok(Some(Self {
+            engine,
+            ast,
+            scope,
+            shared,
+        }))
+    }
+
+    pub fn defines(&self, name: &str) -> bool {
+        self.ast
+            .iter_functions()
+            .any(|function| function.name == name)
+    }
+
+    pub fn call(&mut self, app: &mut App, name: &str, args: &[String]) {
+        {
+            let mut shared = self.shared.borrow_mut();
+            shared.todos = app.board().todos.to_vec();
+            shared.dones = app.board().dones.to_vec();
+            shared.changed = false;
+        }
+        let args: Vec<Dynamic> = args.iter().cloned().map(Dynamic::from).collect();
+        // The statements outside of the functions ran when the script was loaded, once is enough
+        let options = CallFnOptions::new().eval_ast(false);
+        let result = self.engine.call_fn_with_options::<Dynamic>(
+            options,
+            &mut self.scope,
+            &self.ast,
+            name,
+            args,
+        );
+        let shared = mem::take(&mut *self.shared.borrow_mut());
+        for message in shared.messages {
+            app.notifications.info(message);
+        }
+        match result {
+            Err(error) => app
+                .notifications
+                .error(format!(":{} failed: {}", name, error)),
+            Ok(_) if !shared.changed => {}
+            Ok(_) if app.board().read_only => app.read_only_error(),
+            Ok(_) => {
+                let board = app.board_mut();
+                board.todo_curr = cmp::min(board.todo_curr, shared.todos.len().saturating_sub(1));
+                board.done_curr = cmp::min(board.done_curr, shared.dones.len().saturating_sub(1));
+                board.todos = shared.todos.into();
+                board.dones = shared.dones.into();
+            }
+        }
+    }
+}
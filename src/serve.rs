@@ -0,0 +1,274 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::io::{self, BufRead, BufReader, ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+// `todo-rs serve` without --port and --bind, only this machine can see the lists
+pub const DEFAULT_PORT: u16 = 8080;
+pub const DEFAULT_ADDRESS: &str = "127.0.0.1";
+
+// Nobody types a TODO item this long, and a client that is this slow is not a browser
+const MAX_BODY: usize = 64 * 1024;
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+// The request line and each of the headers, and how many headers there may be. The browsers send
+// much less.
+const MAX_LINE: usize = 8 * 1024;
+const MAX_HEADERS: usize = 100;
+
+// How long accept() waits for a client, Ctrl+C is noticed in between
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+// The lists over HTTP for a phone or another computer, one request at a time:
+//
+//     GET  /            the page of html.rs, with the form for the new items given the token
+//     GET  /items.json  the document of --export-json
+//     POST /items       title=<title>, with --allow-add and the token only
+//
+// It's plain HTTP, for this machine or the home network and not for the internet.
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    // What comes after the `?`
+    pub query: String,
+    pub body: String,
+    // Of `Authorization: Bearer <token>`
+    pub bearer: Option<String>,
+    stream: TcpStream,
+}
+
+// The flags of `todo-rs serve`
+#[derive(Clone)]
+pub struct Options {
+    pub address: String,
+    pub port: u16,
+    pub allow_add: bool,
+    // By --token, made up at the start when not given
+    pub token: Option<String>,
+}
+
+// Nobody can guess it, RandomState is seeded by the OS
+pub fn new_token() -> String {
+    let half = || {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u128(
+            SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .map_or(0, |time| time.as_nanos()),
+        );
+        hasher.finish()
+    };
+    format!("{:016x}{:016x}", half(), half())
+}
+
+impl Request {
+    // In the query string, the form or the Authorization header, whichever the client likes. The
+    // other sites can't read the page for it, so they can't post in the name of the browser.
+    pub fn has_token(&self, token: &str) -> bool {
+        let given = form_value(&self.query, "token")
+            .or_else(|| form_value(&self.body, "token"))
+            .or_else(|| self.bearer.clone());
+        given.is_some_and(|given| same(given.as_bytes(), token.as_bytes()))
+    }
+}
+
+pub struct Response {
+    pub status: &'static str,
+    pub content_type: &'static str,
+    pub body: String,
+    // For the redirects
+    pub location: Option<String>,
+}
+
+impl Response {
+    pub fn new(status: &'static str, content_type: &'static str, body: String) -> Self {
+        Self {
+            status,
+            content_type,
+            body,
+            location: None,
+        }
+    }
+
+    pub fn text(status: &'static str, body: &str) -> Self {
+        Self::new(status, "text/plain; charset=utf-8", format!("{}\n", body))
+    }
+}
+
+impl Request {
+    // The client that went away is its own business
+    pub fn respond(mut self, response: Response) {
+        let mut head = format!(
+            "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+            response.status,
+            response.content_type,
+            response.body.len()
+        );
+        if let Some(location) = response.location {
+            head.push_str(&format!("Location: {}\r\n", location));
+        }
+        head.push_str("\r\n");
+        let _ = self.stream.write_all(head.as_bytes());
+        if self.method != "HEAD" {
+            let _ = self.stream.write_all(response.body.as_bytes());
+        }
+    }
+}
+
+pub fn listen(address: &str, port: u16) -> Result<TcpListener, String> {
+    TcpListener::bind((address, port))
+        .and_then(|listener| listener.set_nonblocking(true).map(|()| listener))
+        .map_err(|error| format!("could not listen on {} port {}: {}", address, port, error))
+}
+
+// Takes as long for any wrong token, so the time doesn't tell how much of it was right
+fn same(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+// A line of the head, no longer than MAX_LINE. Some(0) at the end of the stream.
+fn read_line(
+    reader: &mut BufReader<TcpStream>,
+    line: &mut String,
+) -> Result<usize, Option<String>> {
+    line.clear();
+    let read = reader
+        .by_ref()
+        .take(MAX_LINE as u64)
+        .read_line(line)
+        .map_err(|_| None)?;
+    if read == MAX_LINE && !line.ends_with('\n') {
+        return Err(Some("the line is too long".to_string()));
+    }
+    Ok(read)
+}
+
+// The next request that makes sense, or None if nobody came for a while. The broken ones are
+// answered right here.
+pub fn accept(listener: &TcpListener) -> io::Result<Option<Request>> {
+    loop {
+        let stream = match listener.accept() {
+            Ok((stream, _)) => stream,
+            Err(error) if error.kind() == ErrorKind::WouldBlock => {
+                thread::sleep(POLL_INTERVAL);
+                return Ok(None);
+            }
+            Err(error) => return Err(error),
+        };
+        match read_request(stream) {
+            Ok(request) => return Ok(Some(request)),
+            Err(Some((mut stream, error))) => {
+                let body = format!("{}\n", error);
+                let _ = write!(
+                    stream,
+                    "HTTP/1.1 400 Bad Request\r\nContent-Type: text/plain; charset=utf-8\r\n\
+                     Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+            }
+            Err(None) => {}
+        }
+    }
+}
+
+// None for the clients that are gone already
+fn read_request(stream: TcpStream) -> Result<Request, Option<(TcpStream, String)>> {
+    stream
+        .set_nonblocking(false)
+        .and_then(|()| stream.set_read_timeout(Some(TIMEOUT)))
+        .map_err(|_| None)?;
+    let mut reader = BufReader::new(stream.try_clone().map_err(|_| None)?);
+    let mut line = String::new();
+    if let Err(error) = read_line(&mut reader, &mut line) {
+        return Err(error.map(|error| (stream, error)));
+    }
+    let mut words = line.split_whitespace();
+    let (method, path) = match (words.next(), words.next()) {
+        (Some(method), Some(path)) => (method.to_string(), path.to_string()),
+        _ => return Err(Some((stream, "expected an HTTP request".to_string()))),
+    };
+    let mut length = 0;
+    let mut bearer = None;
+    let mut headers = 0;
+    loop {
+        match read_line(&mut reader, &mut line) {
+            Ok(0) => return Err(None),
+            Ok(_) => {}
+            Err(error) => return Err(error.map(|error| (stream, error))),
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        headers += 1;
+        if headers > MAX_HEADERS {
+            return Err(Some((stream, "too many headers".to_string())));
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("authorization") {
+                bearer = value
+                    .trim()
+                    .strip_prefix("Bearer ")
+                    .map(|token| token.trim().to_string());
+            } else if name.eq_ignore_ascii_case("content-length") {
+                length = match value.trim().parse() {
+                    Ok(length) if length <= MAX_BODY => length,
+                    Ok(_) => return Err(Some((stream, "the body is too long".to_string()))),
+                    Err(_) => return Err(Some((stream, "bad Content-Length".to_string()))),
+                };
+            }
+        }
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).map_err(|_| None)?;
+    let (path, query) = path.split_once('?').unwrap_or((&path, ""));
+    Ok(Request {
+        method,
+        path: path.to_string(),
+        query: query.to_string(),
+        body: String::from_utf8_lossy(&body).into_owned(),
+        bearer,
+        stream,
+    })
+}
+
+// The value of the key in an application/x-www-form-urlencoded body, like the one of the form
+pub fn form_value(body: &str, key: &str) -> Option<String> {
+    body.split('&').find_map(|pair| {
+        let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+        if decode(name) == key {
+            Some(decode(value))
+        } else {
+            None
+        }
+    })
+}
+
+fn decode(text: &str) -> String {
+    let mut bytes = Vec::with_capacity(text.len());
+    let mut rest = text.as_bytes();
+    while let Some((&byte, after)) = rest.split_first() {
+        rest = after;
+        match byte {
+            b'+' => bytes.push(b' '),
+            b'%' => {
+                let hex = rest
+                    .get(..2)
+                    .and_then(|hex| std::str::from_utf8(hex).ok())
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+                match hex {
+                    Some(decoded) => {
+                        bytes.push(decoded);
+                        rest = &rest[2..];
+                    }
+                    None => bytes.push(b'%'),
+                }
+            }
+            byte => bytes.push(byte),
+        }
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
@@ -0,0 +1,40 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+// What the app remembers between the runs of how it was left, as `key = value` lines like the
+// config has. Only the split between the panels for now, which `<` and `>` change.
+
+// $XDG_STATE_HOME/todo-rs/session
+pub fn path() -> Option<PathBuf> {
+    let state_home = env::var_os("XDG_STATE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| {
+            env::var_os("HOME").map(|home| PathBuf::from(home).join(".local").join("state"))
+        })
+        // Windows
+        .or_else(|| env::var_os("LOCALAPPDATA").map(PathBuf::from))?;
+    Some(state_home.join("todo-rs").join("session"))
+}
+
+// Nothing saved yet is no split, and so is a session nobody can make sense of anymore
+pub fn load_split(path: &Path) -> Option<i32> {
+    let source = fs::read_to_string(path).ok()?;
+    source.lines().find_map(|line| {
+        let (key, value) = line.split_once('=')?;
+        if key.trim() == "split" {
+            value.trim().parse().ok()
+        } else {
+            None
+        }
+    })
+}
+
+pub fn save_split(path: &Path, split: i32) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, format!("split = {}\n", split))
+}
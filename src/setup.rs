@@ -0,0 +1,611 @@
+use std::env;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, ErrorKind, Read};
+#[cfg(unix)]
+use std::os::unix::io::{AsRawFd, FromRawFd};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::app::*;
+use crate::batch::run_batch;
+#[cfg(feature = "dbus")]
+use crate::bus;
+use crate::caldav::CalDav;
+use crate::cli::{Args, Batch, STDIN_PATH};
+use crate::config::Config;
+#[cfg(unix)]
+use crate::control;
+use crate::due::{self, DueCheck};
+use crate::event::Events;
+use crate::git::AutoCommit;
+use crate::github::GitHub;
+use crate::hooks::Hooks;
+use crate::keymap::Keymap;
+use crate::remind::Reminders;
+use crate::render::TerminalRenderer;
+#[cfg(feature = "scripting")]
+use crate::script;
+use crate::terminal::*;
+use crate::theme::*;
+use crate::tui::*;
+use crate::ui::*;
+use crate::view::*;
+use crate::{recovery, session, signals};
+use todo_rs::crypt::Cipher;
+use todo_rs::lock::FileLock;
+use todo_rs::*;
+
+pub fn split_from_config(config: &Config) -> Result<i32, String> {
+    match config.get("ui", "split") {
+        None => Ok(DEFAULT_SPLIT),
+        Some(value) => match value.parse::<i32>() {
+            Ok(split) if (MIN_SPLIT..=MAX_SPLIT).contains(&split) => Ok(split),
+            _ => Err(config.error(
+                "ui",
+                "split",
+                &format!(
+                    "expected a percentage between {} and {} but got `{}`",
+                    MIN_SPLIT, MAX_SPLIT, value
+                ),
+            )),
+        },
+    }
+}
+
+// Seconds of no changes before the unsaved changes are saved, off when not set or 0
+pub fn autosave_from_config(config: &Config) -> Result<Option<Duration>, String> {
+    match config.get("file", "autosave") {
+        None | Some("off") => Ok(None),
+        Some(value) => match value.parse::<u64>() {
+            Ok(0) => Ok(None),
+            Ok(secs) => Ok(Some(Duration::from_secs(secs))),
+            Err(_) => Err(config.error(
+                "file",
+                "autosave",
+                &format!(
+                    "expected the number of seconds or `off` but got `{}`",
+                    value
+                ),
+            )),
+        },
+    }
+}
+
+// How many of the previous versions of the file are kept as <file>.bak.N, none by default
+pub const MAX_BACKUPS: usize = 100;
+
+// The age identity to decrypt the .age files with and to encrypt them back to
+pub fn age_identity_from_config(config: &Config) -> Option<PathBuf> {
+    config
+        .get("file", "age_identity")
+        .filter(|path| !path.is_empty())
+        .map(PathBuf::from)
+}
+
+// The line prefixes of the items in our own format, `TODO: ` and `DONE: ` by default
+pub fn prefixes_from_config(config: &Config) -> Result<Option<(String, String)>, String> {
+    let todo = config.get("format", "todo");
+    let done = config.get("format", "done");
+    if todo.is_none() && done.is_none() {
+        return Ok(None);
+    }
+    let todo = todo.unwrap_or(format::TODO_PREFIX);
+    let done = done.unwrap_or(format::DONE_PREFIX);
+    for (key, prefix) in [("todo", todo), ("done", done)].iter() {
+        if prefix.trim().is_empty() {
+            return Err(config.error("format", key, "the prefix can't be empty"));
+        }
+    }
+    // Otherwise some of the items of one list would be read as the items of the other
+    if todo.starts_with(done) || done.starts_with(todo) {
+        return Err(config.error(
+            "format",
+            "done",
+            &format!(
+                "the prefixes `{}` and `{}` can't start the same way",
+                todo, done
+            ),
+        ));
+    }
+    Ok(Some((todo.to_string(), done.to_string())))
+}
+
+pub fn backups_from_config(config: &Config) -> Result<usize, String> {
+    match config.get("file", "backups") {
+        None => Ok(0),
+        Some(value) => match value.parse::<usize>() {
+            Ok(backups) if backups <= MAX_BACKUPS => Ok(backups),
+            _ => Err(config.error(
+                "file",
+                "backups",
+                &format!(
+                    "expected a number of backups between 0 and {} but got `{}`",
+                    MAX_BACKUPS, value
+                ),
+            )),
+        },
+    }
+}
+
+// The file is open in another instance of the app already, by the process if it can be told
+pub struct Locked {
+    pub file_path: String,
+    pub owner: Option<u32>,
+}
+
+impl fmt::Display for Locked {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} is already open in another instance of todo-rs",
+            self.file_path
+        )?;
+        match self.owner {
+            Some(pid) => write!(f, " (pid {})", pid),
+            None => Ok(()),
+        }
+    }
+}
+
+// Why Setup::load() failed. A locked file is told apart from the rest, so that main() can ask
+// whether to open it read-only when there is somebody at the terminal to ask.
+pub enum Error {
+    Locked(Locked),
+    Other(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Locked(locked) => write!(f, "ERROR: {}", locked),
+            Error::Other(message) => f.write_str(message),
+        }
+    }
+}
+
+impl From<String> for Error {
+    fn from(message: String) -> Self {
+        Error::Other(message)
+    }
+}
+
+// Locks the file against the other instances. Returns the lock and whether the file is read-only.
+pub fn lock(file_path: &str) -> Result<(Option<FileLock>, bool), Locked> {
+    match lock::acquire(file_path) {
+        Ok(Some(file_lock)) => Ok((Some(file_lock), false)),
+        Ok(None) => Err(Locked {
+            file_path: file_path.to_string(),
+            owner: lock::owner(file_path),
+        }),
+        // Not being able to lock, e.g. on a read-only file system, should not stop anybody from
+        // looking at their list
+        Err(error) => {
+            eprintln!("WARNING: could not lock {}: {}", file_path, error);
+            Ok((None, false))
+        }
+    }
+}
+
+// The names of the per-project lists, in the order of preference
+pub const PROJECT_FILES: &[&str] = &["TODO", ".todo"];
+
+// The closest project list up from the current directory, the way git looks for .git
+pub fn project_file_path() -> Option<String> {
+    let mut dir = env::current_dir().ok()?;
+    loop {
+        for name in PROJECT_FILES {
+            let path = dir.join(name);
+            if path.is_file() {
+                return path.into_os_string().into_string().ok();
+            }
+        }
+        if !dir.pop() {
+            return None;
+        }
+    }
+}
+
+// The personal list for when no file is given: $TODO_RS_FILE, `path` of the [file] section or
+// $XDG_DATA_HOME/todo-rs/TODO
+pub fn default_file_path(config: &Config) -> Result<String, String> {
+    let path = env::var_os("TODO_RS_FILE")
+        .filter(|path| !path.is_empty())
+        .or_else(|| {
+            config
+                .get("file", "path")
+                .filter(|path| !path.is_empty())
+                .map(Into::into)
+        });
+    let path = match path {
+        Some(path) => PathBuf::from(path),
+        None => env::var_os("XDG_DATA_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| {
+                env::var_os("HOME").map(|home| PathBuf::from(home).join(".local").join("share"))
+            })
+            // Windows
+            .or_else(|| env::var_os("APPDATA").map(PathBuf::from))
+            .ok_or("ERROR: file path is not provided and neither $TODO_RS_FILE nor $HOME is set")?
+            .join("todo-rs")
+            .join("TODO"),
+    };
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)
+            .map_err(|error| format!("ERROR: could not create {}: {}", dir.display(), error))?;
+    }
+    path.into_os_string().into_string().map_err(|path| {
+        format!(
+            "ERROR: {} is not valid UTF-8",
+            PathBuf::from(path).display()
+        )
+    })
+}
+
+// Points the descriptor at the terminal, so ncurses can talk to the user even when stdin or
+// stdout is a pipe
+#[cfg(unix)]
+pub fn attach_to_tty(fd: i32) -> io::Result<()> {
+    let tty = fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open("/dev/tty")?;
+    if unsafe { libc::dup2(tty.as_raw_fd(), fd) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+// The terminal takes the place of stdin once the items are read from it
+#[cfg(unix)]
+pub fn attach_stdin_to_tty() -> io::Result<()> {
+    attach_to_tty(libc::STDIN_FILENO)
+}
+
+// The console is read directly by crossterm, whatever stdin is
+#[cfg(not(unix))]
+pub fn attach_stdin_to_tty() -> io::Result<()> {
+    Ok(())
+}
+
+// The original stdout for the lists to go to on quit, while the terminal takes its place
+#[cfg(unix)]
+pub fn take_stdout() -> io::Result<File> {
+    let fd = unsafe { libc::dup(libc::STDOUT_FILENO) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let stdout = unsafe { File::from_raw_fd(fd) };
+    attach_to_tty(libc::STDOUT_FILENO)?;
+    Ok(stdout)
+}
+
+// crossterm draws on the console directly, so stdout is left as it is
+#[cfg(not(unix))]
+pub fn take_stdout() -> io::Result<File> {
+    use std::os::windows::io::AsHandle;
+    Ok(File::from(io::stdout().as_handle().try_clone_to_owned()?))
+}
+
+// Everything the app needs from the command line, the config and the files before it can start
+pub struct Setup {
+    pub state: App,
+    // For the control socket
+    #[cfg(unix)]
+    pub config: Config,
+    pub keymap: Keymap,
+    pub theme: Theme,
+    pub backups: usize,
+    pub autosave: Option<Duration>,
+    pub desktop_notifications: bool,
+    pub batch: Option<Batch>,
+    // The lists go to the original stdout on quit, while ncurses gets the terminal in its place
+    pub stdout_file: Option<File>,
+}
+
+impl Setup {
+    // Reads the config and the files. The errors are what main() prints before it exits, as they are.
+    pub fn load(args: Args) -> Result<Self, Error> {
+        let Args {
+            mut file_paths,
+            keymap: keymap_preset,
+            theme: theme_preset,
+            layout: layout_flag,
+            no_autosave,
+            format: format_flag,
+            read_only: all_read_only,
+            read_only_files,
+            to_stdout,
+            batch,
+        } = args;
+        let config = Config::load()?;
+        if file_paths.is_empty() {
+            file_paths.push(project_file_path().map_or_else(|| default_file_path(&config), Ok)?);
+        }
+        let theme = Theme::from_config(&config, theme_preset.as_deref())?;
+        let line_numbers = LineNumbers::from_config(&config)?;
+        let keymap = Keymap::from_config(&config, keymap_preset.as_deref())?;
+        let session_path = session::path();
+        // The one the panels were left with the last time goes over the config
+        let split = session_path
+            .as_deref()
+            .and_then(session::load_split)
+            .filter(|split| (MIN_SPLIT..=MAX_SPLIT).contains(split));
+        let split = split_from_config(&config).map(|default| split.unwrap_or(default))?;
+        let panel_layout = PanelLayout::from_config(&config)?;
+        let desktop_notifications = due::desktop_from_config(&config)?;
+        let autosave = autosave_from_config(&config)?.filter(|_| !no_autosave);
+        let backups = backups_from_config(&config)?;
+
+        let hooks = Hooks::from_config(&config)?;
+        let autocommit = AutoCommit::from_config(&config)?;
+        let caldav = CalDav::from_config(&config)?;
+        let github = GitHub::from_config(&config)?;
+        let reminders = Reminders::from_config(&config)?;
+
+        let age_identity = age_identity_from_config(&config);
+        if let Some((todo, done)) = prefixes_from_config(&config)? {
+            format::set_prefixes(todo, done);
+        }
+        if let Some(name) = config.get("format", "default") {
+            let kind = format::Kind::from_name(name).ok_or_else(|| {
+                let names: Vec<&str> = format::Kind::ALL.iter().map(|kind| kind.name()).collect();
+                let message = format!(
+                    "unknown format `{}`, expected one of {}",
+                    name,
+                    names.join(", ")
+                );
+                config.error("format", "default", &message)
+            })?;
+            format::set_default(kind);
+        }
+
+        let mut state = App::new(line_numbers, split);
+        state.session_path = session_path;
+        // --layout goes over the headers of the files, which go over the config
+        state.panel_layout = layout_flag
+            .and_then(PanelLayout::from_name)
+            .unwrap_or(panel_layout);
+        state.layout_flag = layout_flag.is_some();
+        state.age_identity = age_identity;
+        state.hooks = hooks;
+        state.autocommit = autocommit;
+        state.caldav = caldav;
+        state.github = github;
+        state.reminders = reminders;
+        #[cfg(feature = "scripting")]
+        let keymap = {
+            let mut keymap = keymap;
+            state.script = script::Script::load(&config, &mut keymap)?;
+            keymap
+        };
+        state.open_cipher = open_cipher;
+        state.open_url = open_url;
+        state.all_read_only = all_read_only;
+
+        // All the files are locked before anything is taken from the terminal, so that main() can
+        // load them again after asking about the locked one
+        let mut file_locks = Vec::new();
+        for file_path in &file_paths {
+            // The file is never written to in these modes, so there is nothing to lock
+            let file_lock = if file_path == STDIN_PATH
+                || to_stdout
+                || all_read_only
+                || matches!(&batch, Some(batch) if !batch.writes())
+            {
+                (None, all_read_only)
+            } else if read_only_files.contains(file_path) {
+                (None, true)
+            } else if !writable(file_path) {
+                let message = format!("{} is not writable, opened it read-only", file_path);
+                state.notifications.warning(message);
+                (None, true)
+            } else {
+                lock(file_path).map_err(Error::Locked)?
+            };
+            file_locks.push(file_lock);
+        }
+
+        let stdout_file = if to_stdout && batch.is_none() {
+            let file = take_stdout().map_err(|error| {
+                format!(
+                    "ERROR: could not open the terminal in place of stdout: {}",
+                    error
+                )
+            })?;
+            Some(file)
+        } else {
+            None
+        };
+        state.to_stdout = stdout_file.is_some();
+
+        for (file_path, (file_lock, read_only)) in file_paths.into_iter().zip(file_locks) {
+            if file_path == STDIN_PATH {
+                let mut data = Vec::new();
+                let mut todos = Vec::new();
+                let mut dones = Vec::new();
+                let mut extras = Extras::default();
+                io::stdin()
+                    .read_to_end(&mut data)
+                    .map_err(|error| format!("ERROR: could not read stdin: {}", error))?;
+                let format = format_flag.unwrap_or_else(|| format::Kind::detect(&file_path, &data));
+                parse_state(&mut todos, &mut dones, &mut extras, &data, format.format());
+                // Only the interactive app needs the terminal in place of stdin
+                if batch.is_none() {
+                    attach_stdin_to_tty().map_err(|error| {
+                        format!("ERROR: could not open the terminal: {}", error)
+                    })?;
+                }
+                state.notifications.info(format!(
+                    "Loaded {} items from stdin",
+                    todos.len() + dones.len()
+                ));
+                let header = file_header(&file_path, &data, &mut state.notifications);
+                let layout = header.layout.and_then(PanelLayout::from_name);
+                if let Some(layout) = layout.filter(|_| !state.layout_flag) {
+                    state.panel_layout = layout;
+                }
+                // There is no file to save stdin back to, the lists only ever go to stdout
+                let mut board = Board::new(file_path, None, None, todos, dones, extras);
+                board.format = format;
+                board.columns = header.columns;
+                board.detached = true;
+                board.read_only = all_read_only;
+                state.boards.push(board);
+                continue;
+            }
+            let cipher = Cipher::for_file(&file_path, state.age_identity.as_deref())
+                .map_err(|error| format!("ERROR: could not open {}: {}", file_path, error))?;
+            let data = read_state(&file_path, cipher.as_ref());
+            let format = format_flag.unwrap_or_else(|| {
+                format::Kind::detect(&file_path, data.as_deref().unwrap_or_default())
+            });
+            let mut todos = Vec::new();
+            let mut dones = Vec::new();
+            let mut extras = Extras::default();
+            let mut columns = None;
+            match data {
+                Ok(data) => {
+                    parse_state_lazily(&mut todos, &mut dones, &mut extras, &data, format);
+                    state
+                        .notifications
+                        .info(format!("Loaded file {}", file_path));
+                    let header = file_header(&file_path, &data, &mut state.notifications);
+                    let layout = header.layout.and_then(PanelLayout::from_name);
+                    columns = header.columns;
+                    // The first of the files is the one on the screen
+                    if let Some(layout) =
+                        layout.filter(|_| state.boards.is_empty() && !state.layout_flag)
+                    {
+                        state.panel_layout = layout;
+                    }
+                }
+                Err(error) if error.kind() == ErrorKind::NotFound => {
+                    state.notifications.info(format!("New file {}", file_path))
+                }
+                Err(error) => {
+                    return Err(format!("ERROR: could not load {}: {}", file_path, error).into());
+                }
+            };
+            let mut board = Board::new(file_path, file_lock, cipher, todos, dones, extras);
+            board.read_only = read_only;
+            board.detached = to_stdout;
+            board.format = format;
+            board.columns = columns;
+            state.boards.push(board);
+        }
+
+        Ok(Self {
+            state,
+            #[cfg(unix)]
+            config,
+            keymap,
+            theme,
+            backups,
+            autosave,
+            desktop_notifications,
+            batch,
+            stdout_file,
+        })
+    }
+
+    // Runs the batch command of the command line if there is one, there is nothing else to do
+    // then. Otherwise takes the terminal over for the interactive app.
+    pub fn run(self) -> Result<Option<Tui>, String> {
+        let Setup {
+            mut state,
+            #[cfg(unix)]
+            config,
+            keymap,
+            theme,
+            backups,
+            autosave,
+            desktop_notifications,
+            batch,
+            stdout_file,
+        } = self;
+        if let Some(batch) = batch {
+            run_batch(
+                batch,
+                &mut state.boards[0],
+                &mut state.hooks,
+                &state.autocommit,
+                state.caldav.as_ref(),
+                state.github.as_ref(),
+                backups,
+            )
+            .map_err(|error| format!("ERROR: {}", error))?;
+            return Ok(None);
+        }
+
+        // Only the interactive app and `todo-rs serve` outlive a Ctrl+C to save the changes, the
+        // batch commands are ended right away by it. Without the handlers the signals end the app
+        // without saving, which is no reason not to run.
+        if let Err(error) = signals::init() {
+            eprintln!("WARNING: could not handle the signals: {}", error);
+        }
+
+        // Only the interactive app listens, the batch commands are done before anybody could ask
+        #[cfg(unix)]
+        let control = {
+            let (control, message) = control::Control::from_config(&config)?;
+            if let Some(message) = message {
+                state.notifications.info(message);
+            }
+            control
+        };
+
+        recovery::install();
+        // The recovered state is written in plain text, which is not what an encrypted file is for
+        let board = state.board();
+        if board.cipher.is_none() {
+            recovery::update(
+                &board.file_path,
+                board.format,
+                &board.todos,
+                &board.dones,
+                &board.extras,
+            );
+        }
+
+        // Needed for ncurses to render anything outside of ASCII, ELLIPSIS for instance
+        setlocale(LcCategory::all, "");
+        initscr();
+        noecho();
+        keypad(stdscr(), true);
+        // Never block in getch(), the main loop waits for the input in wait_for_input() instead
+        timeout(0);
+        curs_set(CURSOR_VISIBILITY::CURSOR_INVISIBLE);
+        #[cfg(unix)]
+        disable_special_chars();
+        #[cfg(not(any(windows, feature = "crossterm")))]
+        if let Err(error) = signals::catch_suspend() {
+            let message = format!("Ctrl+Z would leave the terminal broken: {}", error);
+            state.notifications.warning(message);
+        }
+        bracketed_paste(true);
+
+        start_color();
+        theme.init();
+
+        let mut events = Events::default();
+        #[cfg(unix)]
+        {
+            events.control = control;
+        }
+        #[cfg(feature = "dbus")]
+        {
+            let (bus, message) = bus::Bus::connect(&state.board().todos, &state.board().dones);
+            if let Some(message) = message {
+                state.notifications.info(message);
+            }
+            events.bus = bus;
+        }
+
+        let ui = Ui::new(Box::new(TerminalRenderer));
+        let mut session = Session::new(state, ui, keymap, backups);
+        session.due = DueCheck::new(desktop_notifications);
+        Ok(Some(Tui::new(session, events, autosave, stdout_file)))
+    }
+}
@@ -0,0 +1,227 @@
+use std::io;
+#[cfg(not(any(windows, feature = "crossterm")))]
+use std::os::unix::io::RawFd;
+#[cfg(unix)]
+use std::sync::atomic::AtomicI32;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// The signals the main loop cares about, latched by the handlers until it gets to them. Windows
+// has the console control events in their place. The crossterm backend gets Ctrl+C as a key in the
+// raw mode and passes it on to interrupt(), and reports the resizes itself.
+
+// We are just trying to flip a bunch of bits in a single-threaded environment with no plans of
+// making it multi-threaded. No need to make it overcomplicated. Just a few atomic bools with
+// relaxed ordering should be enough.
+static CTRLC: AtomicBool = AtomicBool::new(false);
+#[cfg(not(any(windows, feature = "crossterm")))]
+static RESIZED: AtomicBool = AtomicBool::new(false);
+#[cfg(not(any(windows, feature = "crossterm")))]
+static SUSPENDED: AtomicBool = AtomicBool::new(false);
+
+// Self-pipe for waking up the main loop while it's blocked waiting for input. The handlers write a
+// byte into it, the main loop includes the read end into the set of the descriptors it waits on.
+#[cfg(unix)]
+static PIPE_READ: AtomicI32 = AtomicI32::new(-1);
+#[cfg(unix)]
+static PIPE_WRITE: AtomicI32 = AtomicI32::new(-1);
+
+#[cfg(unix)]
+fn wake_up() {
+    let fd = PIPE_WRITE.load(Ordering::Relaxed);
+    if fd >= 0 {
+        // write(2) is async-signal-safe. If the pipe is full there is already enough to wake up on.
+        unsafe {
+            libc::write(fd, b"\0".as_ptr() as *const libc::c_void, 1);
+        }
+    }
+}
+
+#[cfg(unix)]
+extern "C" fn on_quit(_signum: i32) {
+    CTRLC.store(true, Ordering::Relaxed);
+    wake_up();
+}
+
+#[cfg(not(any(windows, feature = "crossterm")))]
+extern "C" fn on_resize(_signum: i32) {
+    RESIZED.store(true, Ordering::Relaxed);
+    wake_up();
+}
+
+#[cfg(not(any(windows, feature = "crossterm")))]
+extern "C" fn on_suspend(_signum: i32) {
+    SUSPENDED.store(true, Ordering::Relaxed);
+    wake_up();
+}
+
+// SA_RESTART keeps the reads and the writes of the app going through the signals, the poll(2) of
+// the main loop is woken up by the pipe either way
+#[cfg(unix)]
+unsafe fn install(signum: i32, handler: libc::sighandler_t) -> io::Result<()> {
+    let mut action: libc::sigaction = std::mem::zeroed();
+    action.sa_sigaction = handler;
+    action.sa_flags = libc::SA_RESTART;
+    libc::sigemptyset(&mut action.sa_mask);
+    // sigaction(2) only fails when the signal is invalid or can't be caught, which takes a really
+    // weird UNIX. The app still works without the handler, the signal just does its default.
+    if libc::sigaction(signum, &action, std::ptr::null_mut()) != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn handler(callback: extern "C" fn(i32)) -> libc::sighandler_t {
+    callback as *const () as libc::sighandler_t
+}
+
+#[cfg(windows)]
+const CTRL_CLOSE_EVENT: u32 = 2;
+
+// Ctrl+C and Ctrl+Break in `todo-rs serve` (the interactive app reads them as keys in the raw
+// mode), the console window being closed, the user logging off and the system shutting down end
+// the app the same way SIGINT, SIGHUP and SIGTERM do on UNIX. The handler is only there while the
+// interactive app or serve runs, see init(), the other commands get the default one that ends the
+// process right away.
+#[cfg(windows)]
+extern "system" fn on_console_event(event: u32) -> i32 {
+    let pending = CTRLC.swap(true, Ordering::Relaxed);
+    // Windows ends the process as soon as the handler of the last three returns, so it waits for
+    // the main loop to save the changes and exit, which ends this thread too. Windows only gives it
+    // a few seconds.
+    if event >= CTRL_CLOSE_EVENT {
+        loop {
+            std::thread::sleep(std::time::Duration::from_millis(100));
+        }
+    }
+    // A second Ctrl+C before the app got to the first one is left to the default handler, which
+    // ends the process, in case the app is stuck. Otherwise it's handled and the process goes on.
+    if pending {
+        0
+    } else {
+        1
+    }
+}
+
+// Only for the interactive app and serve, which check poll() until they are done. Anything else
+// would never find out about the signals, so it keeps the default handling.
+#[cfg(windows)]
+pub fn init() -> io::Result<()> {
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn SetConsoleCtrlHandler(handler: Option<extern "system" fn(u32) -> i32>, add: i32) -> i32;
+    }
+    unsafe {
+        // Only fails without a console, where there are no events to handle either
+        SetConsoleCtrlHandler(Some(on_console_event), 1);
+    }
+    Ok(())
+}
+
+// Installs all the handlers it can, the error is the first one that failed. Like on Windows, only
+// for the interactive app and serve.
+#[cfg(unix)]
+pub fn init() -> io::Result<()> {
+    unsafe {
+        let mut fds = [-1; 2];
+        if libc::pipe(fds.as_mut_ptr()) == 0 {
+            for fd in &fds {
+                libc::fcntl(
+                    *fd,
+                    libc::F_SETFL,
+                    libc::fcntl(*fd, libc::F_GETFL) | libc::O_NONBLOCK,
+                );
+                libc::fcntl(*fd, libc::F_SETFD, libc::FD_CLOEXEC);
+            }
+            PIPE_READ.store(fds[0], Ordering::Relaxed);
+            PIPE_WRITE.store(fds[1], Ordering::Relaxed);
+        }
+
+        // SIGTERM (e.g. system shutdown) and SIGHUP (the terminal is closed) end the app the same
+        // way Ctrl+C does, saving the changes on the way out.
+        let mut result = Ok(());
+        for signum in &[libc::SIGINT, libc::SIGTERM, libc::SIGHUP] {
+            result = result.and(install(*signum, handler(on_quit)));
+        }
+        // ncurses leaves SIGWINCH to the app that handles it, so the app resizes the screen itself
+        // instead of waiting for a KEY_RESIZE that not every terminal gets
+        #[cfg(not(feature = "crossterm"))]
+        let result = result.and(install(libc::SIGWINCH, handler(on_resize)));
+        result
+    }
+}
+
+// SIGTSTP is left to the main loop once the screen is taken over, stopping right away would leave
+// the terminal in the raw mode. The batch modes stop the way they always do.
+#[cfg(not(any(windows, feature = "crossterm")))]
+pub fn catch_suspend() -> io::Result<()> {
+    unsafe { install(libc::SIGTSTP, handler(on_suspend)) }
+}
+
+// Does what SIGINT does, for the backends that read Ctrl+C as a key
+#[cfg(any(windows, feature = "crossterm"))]
+pub fn interrupt() {
+    CTRLC.store(true, Ordering::Relaxed);
+}
+
+#[cfg(unix)]
+fn drain() {
+    let fd = PIPE_READ.load(Ordering::Relaxed);
+    if fd >= 0 {
+        let mut buffer = [0u8; 64];
+        while unsafe { libc::read(fd, buffer.as_mut_ptr() as *mut libc::c_void, buffer.len()) } > 0
+        {
+        }
+    }
+}
+
+// Whether Ctrl+C or the like came since the last time
+pub fn poll() -> bool {
+    #[cfg(unix)]
+    drain();
+    CTRLC.swap(false, Ordering::Relaxed)
+}
+
+// Whether the terminal was resized since the last time
+#[cfg(not(any(windows, feature = "crossterm")))]
+pub fn resized() -> bool {
+    drain();
+    RESIZED.swap(false, Ordering::Relaxed)
+}
+
+// Whether the app was asked to stop since the last time
+#[cfg(not(any(windows, feature = "crossterm")))]
+pub fn suspended() -> bool {
+    drain();
+    SUSPENDED.swap(false, Ordering::Relaxed)
+}
+
+// Stops the app like SIGTSTP does by default and returns once it's continued with SIGCONT, e.g. by
+// `fg`. The terminal is expected to be given back to the shell already.
+#[cfg(unix)]
+pub fn stop() {
+    // SIGTSTP does its default either way if the handler can't be changed
+    unsafe {
+        let _ = install(libc::SIGTSTP, libc::SIG_DFL);
+        libc::raise(libc::SIGTSTP);
+    }
+    // Told about when the app started already, if it fails
+    #[cfg(not(feature = "crossterm"))]
+    let _ = catch_suspend();
+}
+
+// There is no job control on Windows
+#[cfg(not(unix))]
+pub fn stop() {}
+
+// Becomes readable when a signal arrives. None if the pipe could not be created, in which case the
+// signal still interrupts whatever blocking call the main loop is in, just not as reliably.
+#[cfg(not(any(windows, feature = "crossterm")))]
+pub fn wakeup_fd() -> Option<RawFd> {
+    let fd = PIPE_READ.load(Ordering::Relaxed);
+    if fd >= 0 {
+        Some(fd)
+    } else {
+        None
+    }
+}
@@ -0,0 +1,178 @@
+use crate::date::Date;
+use crate::json;
+use crate::{day_counts, item_date, item_tags};
+use std::collections::HashMap;
+
+/// What the lists add up to. Everything here comes from the metadata of the items, so it's only as
+/// good as the metadata is.
+pub struct Stats {
+    /// How many TODO items there are
+    pub todos: usize,
+    /// How many DONE items there are
+    pub dones: usize,
+    /// The TODO items due before today
+    pub overdue: usize,
+    /// Over the last STATS_DAYS days, the oldest first
+    pub done_per_day: Vec<(Date, usize)>,
+    /// From created: to done:, of the items that have both
+    pub average_days_to_done: Option<i64>,
+    /// The busiest first
+    pub tags: Vec<(String, usize)>,
+}
+
+/// The days done_per_day goes back
+pub const STATS_DAYS: i64 = 14;
+
+impl Stats {
+    /// Of the lists as of the day
+    pub fn new(todos: &[String], dones: &[String], today: Date) -> Self {
+        let overdue = todos
+            .iter()
+            .filter_map(|todo| item_date(todo, "due"))
+            .filter(|due| *due < today)
+            .count();
+
+        let counts = day_counts(todos, dones);
+        let done_per_day = (0..STATS_DAYS)
+            .rev()
+            .map(|ago| today.add_days(-ago))
+            .map(|day| (day, counts.get(&day).map(|(_, done)| *done).unwrap_or(0)))
+            .collect();
+
+        let durations: Vec<i64> = dones
+            .iter()
+            .filter_map(|done| {
+                Some(item_date(done, "done")?.days_since(item_date(done, "created")?))
+            })
+            .collect();
+        let average_days_to_done =
+            (durations.iter().sum::<i64>()).checked_div(durations.len() as i64);
+
+        let mut tags: HashMap<&str, usize> = HashMap::new();
+        for title in todos.iter().chain(dones.iter()) {
+            for tag in item_tags(title) {
+                *tags.entry(tag).or_default() += 1;
+            }
+        }
+        let mut tags: Vec<(String, usize)> = tags
+            .into_iter()
+            .map(|(tag, count)| (tag.to_string(), count))
+            .collect();
+        tags.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
+        Self {
+            todos: todos.len(),
+            dones: dones.len(),
+            overdue,
+            done_per_day,
+            average_days_to_done,
+            tags,
+        }
+    }
+
+    /// For `todo-rs stats --json`
+    pub fn to_json(&self) -> json::Value {
+        use json::Value;
+        let number = |number: usize| Value::Number(number as f64);
+        let done_per_day = self
+            .done_per_day
+            .iter()
+            .map(|(day, done)| (day.to_string(), number(*done)))
+            .collect();
+        let tags = self
+            .tags
+            .iter()
+            .map(|(tag, count)| (tag.clone(), number(*count)))
+            .collect();
+        Value::Object(vec![
+            ("todo".to_string(), number(self.todos)),
+            ("done".to_string(), number(self.dones)),
+            ("overdue".to_string(), number(self.overdue)),
+            ("done_per_day".to_string(), Value::Object(done_per_day)),
+            (
+                "average_days_to_done".to_string(),
+                self.average_days_to_done
+                    .map_or(Value::Null, |days| Value::Number(days as f64)),
+            ),
+            ("tags".to_string(), Value::Object(tags)),
+        ])
+    }
+
+    /// As text, with the given number of the busiest tags
+    pub fn lines(&self, top_tags: usize) -> Vec<String> {
+        const BAR_WIDTH: usize = 30;
+
+        let mut lines = vec![
+            format!(
+                "TODO: {}   DONE: {}   Overdue: {}",
+                self.todos, self.dones, self.overdue
+            ),
+            String::new(),
+            format!("Done per day over the last {} days", STATS_DAYS),
+        ];
+        let max = self
+            .done_per_day
+            .iter()
+            .map(|(_, done)| *done)
+            .max()
+            .unwrap_or(0);
+        for (day, done) in self.done_per_day.iter() {
+            let bar = (done * BAR_WIDTH).checked_div(max).unwrap_or(0);
+            lines.push(format!(
+                "{:02}-{:02} {:<width$} {}",
+                day.month,
+                day.day,
+                "#".repeat(bar),
+                done,
+                width = BAR_WIDTH
+            ));
+        }
+        lines.push(String::new());
+
+        lines.push(match self.average_days_to_done {
+            Some(average) => format!("Average time to done: {} days", average),
+            None => "Average time to done: unknown".to_string(),
+        });
+        lines.push(String::new());
+
+        lines.push("Busiest tags".to_string());
+        if self.tags.is_empty() {
+            lines.push("none".to_string());
+        }
+        for (tag, count) in self.tags.iter().take(top_tags) {
+            lines.push(format!("{:>5}  {}", count, tag));
+        }
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn adds_up_the_metadata() {
+        let today = Date::new(2021, 7, 10).unwrap();
+        let todos = [
+            "pay rent #home due:2021-07-01".to_string(),
+            "fix sink #home due:2021-07-20".to_string(),
+        ];
+        let dones = [
+            "call mom #family created:2021-07-05 done:2021-07-09".to_string(),
+            "buy milk #home created:2021-07-09 done:2021-07-09".to_string(),
+        ];
+        let stats = Stats::new(&todos, &dones, today);
+        assert_eq!((stats.todos, stats.dones, stats.overdue), (2, 2, 1));
+        assert_eq!(stats.done_per_day.len(), STATS_DAYS as usize);
+        assert_eq!(stats.done_per_day.last(), Some(&(today, 0)));
+        assert_eq!(
+            stats.done_per_day[stats.done_per_day.len() - 2],
+            (today.add_days(-1), 2)
+        );
+        assert_eq!(stats.average_days_to_done, Some(2));
+        assert_eq!(
+            stats.tags,
+            [("#home".to_string(), 3), ("#family".to_string(), 1)]
+        );
+    }
+}
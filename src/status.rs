@@ -0,0 +1,86 @@
+use todo_rs::date::Date;
+use todo_rs::{item_date, meta_word};
+
+// What `todo-rs status` prints without --format
+pub const DEFAULT_FORMAT: &str = "{todo} todo, {due} due";
+
+const PLACEHOLDERS: &[&str] = &[
+    "todo", "done", "total", "due", "overdue", "next", "next_due",
+];
+
+// A single line about the lists for the status bars and the prompts, e.g. "3 todo, 1 due". The
+// placeholders in the braces are replaced with:
+//
+//     {todo}      the number of the TODO items
+//     {done}      the number of the DONE items
+//     {total}     both of them together
+//     {due}       the TODO items due today or earlier
+//     {overdue}   the TODO items due before today
+//     {next}      the title of the TODO item due the soonest, without the metadata
+//     {next_due}  its due date
+//
+// {{ and }} are the braces themselves.
+pub fn render(
+    format: &str,
+    todos: &[String],
+    dones: &[String],
+    today: Date,
+) -> Result<String, String> {
+    let due: Vec<(Date, &String)> = todos
+        .iter()
+        .filter_map(|todo| Some((item_date(todo, "due")?, todo)))
+        .collect();
+    // The first of them in the list if there are several on the same day
+    let next = due.iter().min_by_key(|(date, _)| *date);
+    let value = |name: &str| match name {
+        "todo" => todos.len().to_string(),
+        "done" => dones.len().to_string(),
+        "total" => (todos.len() + dones.len()).to_string(),
+        "due" => due
+            .iter()
+            .filter(|(date, _)| *date <= today)
+            .count()
+            .to_string(),
+        "overdue" => due
+            .iter()
+            .filter(|(date, _)| *date < today)
+            .count()
+            .to_string(),
+        "next" => next.map_or_else(String::new, |(_, todo)| {
+            todo.split(' ')
+                .filter(|word| !word.is_empty() && meta_word(word).is_none())
+                .collect::<Vec<_>>()
+                .join(" ")
+        }),
+        "next_due" => next.map_or_else(String::new, |(date, _)| date.to_string()),
+        _ => unreachable!("the placeholder {{{}}} is not handled", name),
+    };
+
+    let mut line = String::new();
+    let mut rest = format;
+    while let Some(start) = rest.find(['{', '}']) {
+        line.push_str(&rest[..start]);
+        rest = &rest[start..];
+        if let Some(after) = rest.strip_prefix("{{").or_else(|| rest.strip_prefix("}}")) {
+            line.push_str(&rest[..1]);
+            rest = after;
+            continue;
+        }
+        let end = match rest.find('}') {
+            Some(end) if rest.starts_with('{') => end,
+            _ => return Err(format!("unmatched brace in the format `{}`", format)),
+        };
+        let name = &rest[1..end];
+        if !PLACEHOLDERS.contains(&name) {
+            return Err(format!(
+                "unknown placeholder `{{{}}}`, expected one of {{{}}}",
+                name,
+                PLACEHOLDERS.join("}, {")
+            ));
+        }
+        line.push_str(&value(name));
+        rest = &rest[end + 1..];
+    }
+    line.push_str(rest);
+    Ok(line)
+}
@@ -0,0 +1,115 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+
+use todo_rs::date::Date;
+use todo_rs::{item_set_meta, Status};
+
+// The servers the lists are synced with. The items are matched with theirs by an id the items keep
+// in their metadata, and whose side changed since the last sync is told by the status the item had
+// then, which is kept next to the file in <file>.<name>.
+pub trait Service {
+    // Of the config section and the extension of the state
+    const NAME: &'static str;
+
+    // Fails if nothing could be synced at all, otherwise stops at the first request that fails
+    fn sync(
+        &self,
+        file_path: &str,
+        todos: &mut Vec<String>,
+        dones: &mut Vec<String>,
+    ) -> Result<Synced, String>;
+}
+
+// How the items looked at the end of the last sync by their ids: the status and whatever else the
+// service needs to remember about them, like where they live on the server
+pub type State = HashMap<String, (Status, String)>;
+
+// What the sync did. The state is saved once the lists are, otherwise the next sync would take the
+// unsaved changes for the changes made on the server.
+pub struct Synced {
+    name: &'static str,
+    pub pushed: usize,
+    pub pulled: usize,
+    pub deleted: usize,
+    // The request that failed. What was done before it stays done on both sides, and the next sync
+    // takes it from there.
+    pub error: Option<String>,
+    pub state: State,
+}
+
+impl Synced {
+    pub fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            pushed: 0,
+            pulled: 0,
+            deleted: 0,
+            error: None,
+            state: State::new(),
+        }
+    }
+
+    // The items that were not reached before the error are as they were
+    pub fn finish(&mut self, base: State, result: Result<(), String>) {
+        if let Err(error) = result {
+            for (id, entry) in base {
+                self.state.entry(id).or_insert(entry);
+            }
+            self.error = Some(error);
+        }
+    }
+
+    pub fn save(&self, file_path: &str) -> io::Result<()> {
+        let mut ids: Vec<&String> = self.state.keys().collect();
+        ids.sort();
+        let mut out = String::new();
+        for id in ids {
+            let (status, extra) = &self.state[id];
+            out.push_str(&format!("{}\t{}\t{}\n", id, status.name(), extra));
+        }
+        fs::write(state_path(file_path, self.name), out)
+    }
+
+    pub fn describe(&self) -> String {
+        let counts = format!(
+            "{} pushed, {} pulled, {} deleted",
+            self.pushed, self.pulled, self.deleted
+        );
+        match &self.error {
+            Some(error) => format!("Could not sync ({} before that): {}", counts, error),
+            None => format!("Synced: {}", counts),
+        }
+    }
+}
+
+fn state_path(file_path: &str, name: &str) -> String {
+    format!("{}.{}", file_path, name)
+}
+
+// Nothing synced yet is an empty state, as is a state nobody can make sense of anymore
+pub fn load_state(file_path: &str, name: &str) -> State {
+    let source = fs::read_to_string(state_path(file_path, name)).unwrap_or_default();
+    source
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.splitn(3, '\t');
+            let id = fields.next()?;
+            let status = match fields.next()? {
+                "TODO" => Status::Todo,
+                "DONE" => Status::Done,
+                _ => return None,
+            };
+            Some((id.to_string(), (status, fields.next()?.to_string())))
+        })
+        .collect()
+}
+
+// Moves the item to the list of the status, dated like the transfers are
+pub fn set_status(title: &mut String, status: Status, date: Option<&str>) {
+    let today = Date::today().to_string();
+    match status {
+        Status::Done => item_set_meta(title, "done", Some(date.unwrap_or(&today))),
+        Status::Todo => item_set_meta(title, "done", None),
+    }
+}
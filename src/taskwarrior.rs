@@ -0,0 +1,227 @@
+use crate::date::Date;
+use crate::json::{self, Value};
+use crate::{item_date, item_meta, item_set_meta, item_tags, meta_word, Status};
+
+// The JSON of `task export` and `task import` of Taskwarrior, see
+// https://taskwarrior.org/docs/design/task. The tags of a task become #tags, its UUID, project
+// and priority the uuid:, project: and prio: metadata, and the dates created:, due: and done:.
+
+// H, M and L of Taskwarrior are A, B and C of prio:
+const PRIORITIES: &[(&str, &str)] = &[("H", "A"), ("M", "B"), ("L", "C")];
+
+// 20210701T120000Z to the local date of the moment
+fn parse_timestamp(text: &str) -> Option<Date> {
+    let number = |range: std::ops::Range<usize>| -> Option<u32> { text.get(range)?.parse().ok() };
+    if text.len() != 16 || text.as_bytes()[8] != b'T' || !text.ends_with('Z') {
+        return None;
+    }
+    let date = Date::new(number(0..4)? as i32, number(4..6)?, number(6..8)?)?;
+    let days = date.days_since(Date::new(1970, 1, 1)?);
+    let seconds = number(9..11)? * 3600 + number(11..13)? * 60 + number(13..15)?;
+    Some(Date::local(days * 86400 + i64::from(seconds)))
+}
+
+// The local midnight of the date
+fn write_timestamp(date: Date) -> String {
+    let (date, seconds) = Date::from_timestamp(date.local_midnight());
+    format!(
+        "{:04}{:02}{:02}T{:02}{:02}{:02}Z",
+        date.year,
+        date.month,
+        date.day,
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+// FNV-1a with the seed mixed in
+fn hash(seed: u64, text: &str) -> u64 {
+    text.bytes().fold(0xcbf29ce484222325 ^ seed, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+    })
+}
+
+// The items that never were in Taskwarrior get a UUID made of their title and of how many items
+// with the same title came before them, so exporting them again updates the same tasks instead of
+// adding new ones
+fn made_up_uuid(title: &str, count: usize) -> String {
+    let text = format!("{}\n{}", title, count);
+    let high = hash(1, &text);
+    let low = hash(2, &text);
+    format!(
+        "{:08x}-{:04x}-4{:03x}-{:04x}-{:012x}",
+        high >> 32,
+        (high >> 16) & 0xffff,
+        high & 0xfff,
+        0x8000 | (low >> 48) & 0x3fff,
+        low & 0xffff_ffff_ffff
+    )
+}
+
+fn string(text: &str) -> Value {
+    Value::String(text.to_string())
+}
+
+/// The items as `task import` takes them
+pub fn export(todos: &[String], dones: &[String]) -> String {
+    let mut tasks = Vec::new();
+    let mut made_up: Vec<String> = Vec::new();
+    for (status, list) in [(Status::Todo, todos), (Status::Done, dones)].iter() {
+        for title in list.iter() {
+            let description: Vec<&str> = title
+                .split(' ')
+                .filter(|word| meta_word(word).is_none() && !word.starts_with('#'))
+                .collect();
+            let description = description.join(" ");
+            let uuid = item_meta(title, "uuid")
+                .map(str::to_string)
+                .unwrap_or_else(|| {
+                    let count = made_up
+                        .iter()
+                        .filter(|other| **other == description)
+                        .count();
+                    made_up.push(description.clone());
+                    made_up_uuid(&description, count)
+                });
+
+            let mut task = vec![
+                ("uuid".to_string(), string(&uuid)),
+                ("description".to_string(), string(&description)),
+            ];
+            let status = match status {
+                Status::Todo => "pending",
+                Status::Done => "completed",
+            };
+            task.push(("status".to_string(), string(status)));
+            for (key, field) in [("created", "entry"), ("due", "due"), ("done", "end")].iter() {
+                if let Some(date) = item_date(title, key) {
+                    task.push((field.to_string(), string(&write_timestamp(date))));
+                }
+            }
+            if let Some(project) = item_meta(title, "project") {
+                task.push(("project".to_string(), string(project)));
+            }
+            let priority = item_meta(title, "prio").and_then(|prio| {
+                PRIORITIES
+                    .iter()
+                    .find(|(_, ours)| *ours == prio)
+                    .map(|(theirs, _)| *theirs)
+            });
+            if let Some(priority) = priority {
+                task.push(("priority".to_string(), string(priority)));
+            }
+            let tags: Vec<Value> = item_tags(title)
+                .filter_map(|tag| tag.strip_prefix('#'))
+                .map(string)
+                .collect();
+            if !tags.is_empty() {
+                task.push(("tags".to_string(), Value::Array(tags)));
+            }
+            tasks.push(Value::Object(task));
+        }
+    }
+    format!("{}\n", Value::Array(tasks))
+}
+
+/// The deleted tasks and the templates of the recurring ones are skipped, the rest become items
+pub fn import(source: &str) -> Result<Vec<(Status, String)>, String> {
+    let tasks = match json::parse(source)? {
+        Value::Array(tasks) => tasks,
+        _ => return Err("expected the array of the tasks of `task export`".to_string()),
+    };
+    let mut items = Vec::new();
+    for (index, task) in tasks.iter().enumerate() {
+        let error = |message: &str| format!("task {}: {}", index + 1, message);
+        let text = |key: &str| match task.get(key) {
+            Some(Value::String(text)) => Some(text.as_str()),
+            _ => None,
+        };
+        let status = match text("status") {
+            Some("pending") | Some("waiting") => Status::Todo,
+            Some("completed") => Status::Done,
+            Some("deleted") | Some("recurring") => continue,
+            _ => return Err(error("unknown `status`")),
+        };
+        let mut title = text("description")
+            .ok_or_else(|| error("no `description`"))?
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ");
+        if let Some(Value::Array(tags)) = task.get("tags") {
+            for tag in tags.iter() {
+                if let Value::String(tag) = tag {
+                    title.push_str(&format!(" #{}", tag));
+                }
+            }
+        }
+        for (field, key) in [("entry", "created"), ("due", "due"), ("end", "done")].iter() {
+            if let Some(timestamp) = text(field) {
+                let date = parse_timestamp(timestamp)
+                    .ok_or_else(|| error(&format!("ill-formed `{}`", field)))?;
+                item_set_meta(&mut title, key, Some(&date.to_string()));
+            }
+        }
+        if let Some(project) = text("project").filter(|project| !project.contains(' ')) {
+            item_set_meta(&mut title, "project", Some(project));
+        }
+        let priority = text("priority").and_then(|priority| {
+            PRIORITIES
+                .iter()
+                .find(|(theirs, _)| *theirs == priority)
+                .map(|(_, ours)| *ours)
+        });
+        if let Some(priority) = priority {
+            item_set_meta(&mut title, "prio", Some(priority));
+        }
+        if let Some(uuid) = text("uuid") {
+            item_set_meta(&mut title, "uuid", Some(uuid));
+        }
+        items.push((status, title));
+    }
+    Ok(items)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn imports_what_it_exports() {
+        let todos = [
+            "write the report #work created:2021-06-01 due:2021-07-01 project:job prio:A"
+                .to_string(),
+            "buy milk".to_string(),
+            "buy milk".to_string(),
+        ];
+        let dones = ["call mom done:2021-06-30".to_string()];
+        let items = import(&export(&todos, &dones)).unwrap();
+        let expected = [
+            (Status::Todo, &todos[0]),
+            (Status::Todo, &todos[1]),
+            (Status::Todo, &todos[2]),
+            (Status::Done, &dones[0]),
+        ];
+        assert_eq!(items.len(), expected.len());
+        for ((status, title), (expected_status, expected_title)) in items.iter().zip(expected) {
+            let mut without_uuid = title.clone();
+            item_set_meta(&mut without_uuid, "uuid", None);
+            assert_eq!((status, &without_uuid), (&expected_status, expected_title));
+        }
+        assert_ne!(
+            item_meta(&items[1].1, "uuid"),
+            item_meta(&items[2].1, "uuid")
+        );
+
+        // The UUIDs stay the same from one export to the other
+        let titles: Vec<String> = items.iter().map(|(_, title)| title.clone()).collect();
+        let again = import(&export(&titles[..3], &titles[3..])).unwrap();
+        assert_eq!(again, items);
+    }
+
+    #[test]
+    fn keeps_the_dates_across_the_time_zones() {
+        let date = Date::new(2021, 7, 1).unwrap();
+        assert_eq!(parse_timestamp(&write_timestamp(date)), Some(date));
+    }
+}
@@ -0,0 +1,81 @@
+// The terminal backend. The app is written against the ncurses API, which it gets from ncurses
+// itself on unix. Windows has no ncurses, so there, and anywhere with the crossterm feature, the same
+// API comes from curses.rs on top of crossterm instead.
+#[cfg(not(any(windows, feature = "crossterm")))]
+pub use self::ncurses_backend::*;
+#[cfg(any(windows, feature = "crossterm"))]
+pub use crate::curses::*;
+
+#[cfg(not(any(windows, feature = "crossterm")))]
+mod ncurses_backend {
+    use crate::{control, signals};
+    pub use ncurses::*;
+    use std::io::{self, Write};
+    use std::mem;
+    use std::time::Instant;
+
+    // Blocks until there is something on stdin, a signal arrives, a client connects to the control
+    // socket, a D-Bus call comes or the deadline passes, whichever comes first. Without the deadline
+    // it's going to wait for the input indefinitely.
+    pub fn wait_for_input(deadline: Option<Instant>) {
+        let mut fds = vec![libc::pollfd {
+            fd: libc::STDIN_FILENO,
+            events: libc::POLLIN,
+            revents: 0,
+        }];
+        let wakeups = signals::wakeup_fd().into_iter().chain(control::wakeup_fd());
+        #[cfg(feature = "dbus")]
+        let wakeups = wakeups.chain(crate::bus::wakeup_fd());
+        for fd in wakeups {
+            fds.push(libc::pollfd {
+                fd,
+                events: libc::POLLIN,
+                revents: 0,
+            });
+        }
+        let timeout_ms = match deadline {
+            // Rounding up, so we don't wake up right before the deadline and spin until it passes
+            Some(deadline) => {
+                let left = deadline.saturating_duration_since(Instant::now());
+                (left.as_micros() as u64)
+                    .div_ceil(1000)
+                    .min(i32::MAX as u64) as i32
+            }
+            None => -1,
+        };
+        // EINTR is fine, the signals come through the pipe of signals.rs as well
+        unsafe {
+            libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, timeout_ms);
+        }
+    }
+
+    // Takes the new size of the terminal after SIGWINCH. ncurses does the same when it handles the
+    // signal itself, which it doesn't with the handler of signals.rs in place.
+    pub fn resize() {
+        let mut size: libc::winsize = unsafe { mem::zeroed() };
+        if unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) } == 0
+            && size.ws_row > 0
+            && size.ws_col > 0
+        {
+            resizeterm(size.ws_row as i32, size.ws_col as i32);
+        }
+    }
+
+    pub fn suspend() {
+        endwin();
+        bracketed_paste(false);
+        signals::stop();
+        // ncurses repaints the whole screen on the first refresh after endwin()
+        reset_prog_mode();
+        resize();
+        refresh();
+        bracketed_paste(true);
+    }
+
+    // While it's on, the pastes come wrapped in PASTE_BEGIN and PASTE_END, so they can be told apart
+    // from the keys
+    pub fn bracketed_paste(enable: bool) {
+        print!("{}", if enable { "\x1b[?2004h" } else { "\x1b[?2004l" });
+        let _ = io::stdout().flush();
+    }
+}
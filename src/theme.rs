@@ -0,0 +1,260 @@
+use crate::config::Config;
+use crate::terminal::*;
+
+pub const REGULAR_PAIR: i16 = 1;
+pub const HIGHLIGHT_PAIR: i16 = 2;
+pub const HEADER_PAIR: i16 = 3;
+pub const NOTIFICATION_PAIR: i16 = 4;
+pub const DONE_PAIR: i16 = 5;
+pub const WARNING_PAIR: i16 = 6;
+pub const ERROR_PAIR: i16 = 7;
+
+// Tells ncurses to use whatever the terminal has by default
+const COLOR_DEFAULT: i16 = -1;
+
+#[derive(Clone, Copy)]
+enum Color {
+    Index(i16),
+    Rgb(u8, u8, u8),
+}
+
+#[derive(Clone, Copy)]
+struct Colors {
+    fg: Color,
+    bg: Color,
+}
+
+const fn colors(fg: i16, bg: i16) -> Colors {
+    Colors {
+        fg: Color::Index(fg),
+        bg: Color::Index(bg),
+    }
+}
+
+// The palette of xterm-256color which pretty much every modern terminal follows
+fn palette_rgb(index: i16) -> (u8, u8, u8) {
+    const BASIC: [(u8, u8, u8); 16] = [
+        (0, 0, 0),
+        (205, 0, 0),
+        (0, 205, 0),
+        (205, 205, 0),
+        (0, 0, 238),
+        (205, 0, 205),
+        (0, 205, 205),
+        (229, 229, 229),
+        (127, 127, 127),
+        (255, 0, 0),
+        (0, 255, 0),
+        (255, 255, 0),
+        (92, 92, 255),
+        (255, 0, 255),
+        (0, 255, 255),
+        (255, 255, 255),
+    ];
+    match index {
+        0..=15 => BASIC[index as usize],
+        16..=231 => {
+            let level = |x: i16| if x == 0 { 0 } else { (55 + x * 40) as u8 };
+            let index = index - 16;
+            (level(index / 36), level(index / 6 % 6), level(index % 6))
+        }
+        _ => {
+            let gray = (8 + (index - 232) * 10) as u8;
+            (gray, gray, gray)
+        }
+    }
+}
+
+fn nearest_color((r, g, b): (u8, u8, u8), colors_count: i32) -> i16 {
+    let distance = |index: &i16| {
+        let (pr, pg, pb) = palette_rgb(*index);
+        let d = |x: u8, y: u8| (x as i32 - y as i32).pow(2);
+        d(r, pr) + d(g, pg) + d(b, pb)
+    };
+    (0..colors_count.clamp(8, 256) as i16)
+        .min_by_key(distance)
+        .unwrap_or(COLOR_WHITE)
+}
+
+// Turns colors of the theme into something the current terminal can actually display
+struct ColorAllocator {
+    colors_count: i32,
+    // Slots of the palette we redefine for the RGB colors, going down from the end of the palette,
+    // which is the part of it that is used the least
+    next_slot: i32,
+}
+
+impl ColorAllocator {
+    fn new() -> Self {
+        Self {
+            colors_count: COLORS(),
+            next_slot: COLORS() - 1,
+        }
+    }
+
+    fn resolve(&mut self, color: Color) -> i16 {
+        match color {
+            Color::Index(index) if (index as i32) < self.colors_count => index,
+            Color::Index(index) => nearest_color(palette_rgb(index), self.colors_count),
+            Color::Rgb(r, g, b) => {
+                if can_change_color() && self.next_slot >= 16 {
+                    let slot = self.next_slot as i16;
+                    self.next_slot -= 1;
+                    let scale = |x: u8| (x as i32 * 1000 / 255) as i16;
+                    init_color(slot, scale(r), scale(g), scale(b));
+                    slot
+                } else {
+                    nearest_color((r, g, b), self.colors_count)
+                }
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Theme {
+    regular: Colors,
+    highlight: Colors,
+    header: Colors,
+    notification: Colors,
+    warning: Colors,
+    error: Colors,
+    done: Colors,
+}
+
+const PRESETS: &[(&str, Theme)] = &[
+    (
+        "dark",
+        Theme {
+            regular: colors(COLOR_WHITE, COLOR_BLACK),
+            highlight: colors(COLOR_BLACK, COLOR_WHITE),
+            header: colors(COLOR_WHITE, COLOR_BLACK),
+            notification: colors(COLOR_WHITE, COLOR_BLACK),
+            warning: colors(COLOR_YELLOW, COLOR_BLACK),
+            error: colors(COLOR_RED, COLOR_BLACK),
+            done: colors(COLOR_WHITE, COLOR_BLACK),
+        },
+    ),
+    (
+        "light",
+        Theme {
+            regular: colors(COLOR_BLACK, COLOR_WHITE),
+            highlight: colors(COLOR_WHITE, COLOR_BLUE),
+            header: colors(COLOR_BLUE, COLOR_WHITE),
+            notification: colors(COLOR_BLUE, COLOR_WHITE),
+            warning: colors(COLOR_MAGENTA, COLOR_WHITE),
+            error: colors(COLOR_RED, COLOR_WHITE),
+            done: colors(COLOR_BLACK, COLOR_WHITE),
+        },
+    ),
+];
+
+const COLOR_NAMES: &[(&str, i16)] = &[
+    ("default", COLOR_DEFAULT),
+    ("black", COLOR_BLACK),
+    ("red", COLOR_RED),
+    ("green", COLOR_GREEN),
+    ("yellow", COLOR_YELLOW),
+    ("blue", COLOR_BLUE),
+    ("magenta", COLOR_MAGENTA),
+    ("cyan", COLOR_CYAN),
+    ("white", COLOR_WHITE),
+];
+
+// A color is either a name from COLOR_NAMES, an index in the 256 colors palette or `#rrggbb`
+fn parse_color(text: &str) -> Option<Color> {
+    if let Some(hex) = text.strip_prefix('#') {
+        if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+        let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+        return Some(Color::Rgb(channel(0)?, channel(2)?, channel(4)?));
+    }
+    if let Ok(index) = text.parse::<i16>() {
+        return if (0..256).contains(&index) {
+            Some(Color::Index(index))
+        } else {
+            None
+        };
+    }
+    COLOR_NAMES
+        .iter()
+        .find(|(name, _)| *name == text)
+        .map(|(_, color)| Color::Index(*color))
+}
+
+// Colors are written as `"<fg> on <bg>"`, e.g. `"white on black"`
+fn parse_colors(text: &str) -> Option<Colors> {
+    let (fg, bg) = text.split_once(" on ")?;
+    Some(Colors {
+        fg: parse_color(fg.trim())?,
+        bg: parse_color(bg.trim())?,
+    })
+}
+
+pub fn preset_names() -> Vec<&'static str> {
+    PRESETS.iter().map(|(name, _)| *name).collect()
+}
+
+impl Theme {
+    // Everything comes from the `[theme]` section of the config: `preset` picks one of the
+    // PRESETS and the rest of the keys override its separate colors. The preset from the command
+    // line, if any, takes over the one from the config.
+    pub fn from_config(config: &Config, preset_name: Option<&str>) -> Result<Self, String> {
+        let preset = preset_name
+            .or_else(|| config.get("theme", "preset"))
+            .unwrap_or("dark");
+        let mut theme = match PRESETS.iter().find(|(name, _)| *name == preset) {
+            Some((_, theme)) => *theme,
+            None => {
+                let message = format!("unknown theme preset `{}`", preset);
+                return Err(match preset_name {
+                    Some(_) => format!("ERROR: {}", message),
+                    None => config.error("theme", "preset", &message),
+                });
+            }
+        };
+
+        for (key, colors) in [
+            ("regular", &mut theme.regular),
+            ("highlight", &mut theme.highlight),
+            ("header", &mut theme.header),
+            ("notification", &mut theme.notification),
+            ("warning", &mut theme.warning),
+            ("error", &mut theme.error),
+            ("done", &mut theme.done),
+        ] {
+            if let Some(text) = config.get("theme", key) {
+                *colors = parse_colors(text).ok_or_else(|| {
+                    config.error(
+                        "theme",
+                        key,
+                        &format!("expected `\"<color> on <color>\"` but got `{}`", text),
+                    )
+                })?;
+            }
+        }
+
+        Ok(theme)
+    }
+
+    // Must be called after start_color()
+    pub fn init(&self) {
+        use_default_colors();
+        let mut allocator = ColorAllocator::new();
+        for (pair, colors) in [
+            (REGULAR_PAIR, self.regular),
+            (HIGHLIGHT_PAIR, self.highlight),
+            (HEADER_PAIR, self.header),
+            (NOTIFICATION_PAIR, self.notification),
+            (WARNING_PAIR, self.warning),
+            (ERROR_PAIR, self.error),
+            (DONE_PAIR, self.done),
+        ] {
+            let fg = allocator.resolve(colors.fg);
+            let bg = allocator.resolve(colors.bg);
+            init_pair(pair, fg, bg);
+        }
+        bkgd(COLOR_PAIR(REGULAR_PAIR));
+    }
+}
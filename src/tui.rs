@@ -0,0 +1,909 @@
+use std::cmp;
+#[cfg(unix)]
+use std::ffi::CStr;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::mem;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use crate::action::*;
+use crate::app::*;
+use crate::due::DueCheck;
+use crate::event::{Event, Events};
+use crate::keymap::{Feed, Keymap};
+use crate::macros::{self, Input, Macros};
+use crate::notifications::{Level, Notifications};
+use crate::render::Renderer;
+use crate::terminal::*;
+use crate::theme::*;
+use crate::ui::*;
+use crate::view::*;
+use crate::{browser, clipboard, recovery};
+use todo_rs::crypt::{self, Cipher};
+use todo_rs::date::Date;
+use todo_rs::stats::Stats;
+use todo_rs::*;
+
+// What to show in place of an empty list, so a new user is not left staring at a blank screen
+pub fn empty_hints(status: Status, other_len: usize, keymap: &Keymap) -> Vec<String> {
+    let hint =
+        |action: Action, what: &str| format!("Press {} to {}", keymap.action_keys(action), what);
+    match status {
+        Status::Todo if other_len > 0 => vec![
+            "All done!".to_string(),
+            String::new(),
+            hint(Action::Insert, "add a new task"),
+        ],
+        Status::Todo => vec![
+            "Nothing to do yet".to_string(),
+            String::new(),
+            hint(Action::Insert, "add your first task"),
+            hint(Action::Paste, "paste tasks from the clipboard"),
+            hint(Action::SwitchPanel, "switch between the panels"),
+            hint(Action::Help, "see all the keys"),
+        ],
+        Status::Done => vec![
+            "Nothing is done yet".to_string(),
+            String::new(),
+            hint(Action::Transfer, "mark the current TODO as done"),
+        ],
+    }
+}
+
+pub fn stats_lines(todos: &[String], dones: &[String], today: Date) -> Vec<String> {
+    let mut lines = vec!["Statistics".to_string(), String::new()];
+    lines.extend(Stats::new(todos, dones, today).lines(5));
+    lines.push(String::new());
+    lines.push("Press any key to close".to_string());
+    lines
+}
+
+pub fn help_lines(keymap: &Keymap) -> Vec<String> {
+    let mut lines = vec!["Keys".to_string(), String::new()];
+    for action in keymap.actions() {
+        lines.push(format!(
+            "{:>12}  {}",
+            keymap.action_keys(action),
+            action.description()
+        ));
+    }
+    for (keys, command) in keymap.commands() {
+        lines.push(format!("{:>12}  :{}", keys, command));
+    }
+    lines.push(String::new());
+    lines.push("Press any key to close".to_string());
+    lines
+}
+
+// The combining characters we use for the strike-through don't make any sense outside of UTF-8
+#[cfg(unix)]
+pub fn utf8_locale() -> bool {
+    let codeset = unsafe { CStr::from_ptr(libc::nl_langinfo(libc::CODESET)) };
+    let codeset = codeset.to_string_lossy().to_ascii_lowercase();
+    codeset == "utf-8" || codeset == "utf8"
+}
+
+// The consoles of Windows talk UTF-16 to crossterm, which turns it into UTF-8 for us
+#[cfg(not(unix))]
+pub fn utf8_locale() -> bool {
+    true
+}
+
+// Reads the next input from the renderer if there is any. Ctrl+V pastes from the clipboard.
+pub fn read_input(renderer: &mut dyn Renderer, notifications: &mut Notifications) -> Option<Input> {
+    match renderer.read_input() {
+        Some(Input::Key(CTRL_V)) => Some(match clipboard::paste() {
+            Ok(text) => Input::Paste(text),
+            Err(error) => {
+                notifications.error(format!("Could not paste: {}", error));
+                Input::Key(CTRL_V)
+            }
+        }),
+        input => input,
+    }
+}
+
+// By default the terminal handles Ctrl+Z (send SIGTSTP) and Ctrl+V (quote the next key) itself, but
+// we need them as regular keys for undo and clipboard pasting. Ctrl+Z suspends the app from the
+// lists instead, see Renderer::suspend().
+#[cfg(unix)]
+pub fn disable_special_chars() {
+    unsafe {
+        let mut termios: libc::termios = std::mem::zeroed();
+        if libc::tcgetattr(libc::STDIN_FILENO, &mut termios) == 0 {
+            termios.c_cc[libc::VSUSP] = libc::_POSIX_VDISABLE;
+            termios.c_cc[libc::VLNEXT] = libc::_POSIX_VDISABLE;
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &termios);
+        }
+    }
+    // So ncurses restores our tweaked mode instead of its own one whenever it resets the terminal
+    def_prog_mode();
+}
+
+// App::open_cipher for the ncurses frontend. gpg asks for the passphrase on the terminal itself, so
+// ncurses has to get out of its way first.
+pub fn open_cipher(file_path: &str, age_identity: Option<&Path>) -> io::Result<Option<Cipher>> {
+    if crypt::detect(file_path) != Some(crypt::Kind::Gpg) {
+        return Cipher::for_file(file_path, age_identity);
+    }
+    def_prog_mode();
+    endwin();
+    let cipher = Cipher::for_file(file_path, age_identity);
+    reset_prog_mode();
+    refresh();
+    cipher
+}
+
+// App::open_url for the ncurses frontend, the link may open in a browser in the terminal
+pub fn open_url(url: &str) -> io::Result<()> {
+    def_prog_mode();
+    endwin();
+    let result = browser::open(url);
+    reset_prog_mode();
+    refresh();
+    result
+}
+
+// Everything the interactive app keeps from one frame to the next, on top of the App itself.
+// The main loop feeds it the events and renders the frames, and so can anything else that has a
+// Renderer.
+pub struct Session {
+    pub state: App,
+    pub ui: Ui,
+    pub keymap: Keymap,
+    pub macros: Macros,
+    // Recording and replaying a macro needs a register after the key
+    pub awaiting_register: Option<(Action, Option<usize>)>,
+    pub due: DueCheck,
+    pub backups: usize,
+    pub done_style: Style,
+    pub done_highlight_style: Style,
+    // Something has changed since the last frame
+    pub redraw: bool,
+    // What is printed once the app is closed
+    pub quit_message: Option<String>,
+    // There was a Ctrl+C, or the terminal is gone
+    pub interrupted: bool,
+}
+
+impl Session {
+    pub fn new(state: App, ui: Ui, keymap: Keymap, backups: usize) -> Self {
+        let done_style = Style {
+            pair: DONE_PAIR,
+            attrs: A_DIM(),
+            strike: utf8_locale(),
+        };
+        Self {
+            state,
+            ui,
+            keymap,
+            macros: Macros::default(),
+            awaiting_register: None,
+            due: DueCheck::new(false),
+            backups,
+            done_style,
+            done_highlight_style: Style {
+                strike: done_style.strike,
+                ..Style::from(HIGHLIGHT_PAIR)
+            },
+            redraw: true,
+            quit_message: None,
+            interrupted: false,
+        }
+    }
+
+    // The earliest of the timers, for Event::Tick
+    pub fn timer(&self, autosave_at: Option<Instant>) -> Option<Instant> {
+        [
+            self.state.notifications.next_expiry(),
+            // When the focus timer needs to be redrawn
+            self.state
+                .focus
+                .as_ref()
+                .and_then(|current| current.next_tick(Instant::now())),
+            Some(self.due.deadline()),
+            Some(self.state.reminders.deadline()),
+            autosave_at,
+        ]
+        .iter()
+        .flatten()
+        .min()
+        .copied()
+    }
+
+    pub fn dispatch(&mut self, event: Event) {
+        let backups = self.backups;
+        let Session {
+            state,
+            ui,
+            keymap,
+            macros,
+            awaiting_register,
+            due,
+            redraw,
+            interrupted,
+            ..
+        } = self;
+        *redraw = true;
+        match event {
+            Event::Signal => *interrupted = true,
+            // Both SIGWINCH and KEY_RESIZE may come for the same resize, which is harmless
+            Event::Resize => {
+                ui.renderer.resize();
+                state.layout(ui.renderer.size());
+            }
+            #[cfg(not(any(windows, feature = "crossterm")))]
+            Event::Suspend => ui.renderer.suspend(),
+            Event::Tick => {
+                let now = Instant::now();
+                state.notifications.expire(now);
+                // The other boards have their items due too
+                let todos = state.boards.iter().flat_map(|board| &board.todos);
+                due.check(now, todos.clone(), &mut state.notifications);
+                if state.reminders.check(todos, &mut state.notifications) && state.reminders.bell {
+                    ui.renderer.beep();
+                }
+            }
+            #[cfg(unix)]
+            Event::Control(mut request) => state.control(&mut request),
+            #[cfg(feature = "dbus")]
+            Event::DBus(call) => state.dbus(call),
+            // Somebody else, like a text editor, may change the file while it's open
+            Event::FileChanged(_) if state.editing || state.confirmation.is_some() => {
+                // Comes up again on the next check, once the user is done
+            }
+            Event::FileChanged(mtime) => {
+                match mtime {
+                    // Removing the file is not a reason to lose anything, it's saved again on
+                    // exit
+                    None => state
+                        .notifications
+                        .warning(format!("{} was removed on disk", state.board().file_path)),
+                    Some(_) if state.modified() => state.confirmation = Some(Confirmation::Reload),
+                    Some(_) => state.reload(),
+                }
+                // Whatever the user says, they are not asked again until the next change
+                state.board_mut().saved_mtime = mtime;
+            }
+            Event::Key(_) | Event::Paste(_) if state.help || state.stats => {
+                state.help = false;
+                state.stats = false;
+            }
+            // The text fields take the pastes as they are
+            Event::Paste(text) if state.editing || state.command_line.is_some() => {
+                state.handle_raw(Event::Paste(text), keymap, backups)
+            }
+            Event::Paste(_)
+                if state.confirmation.is_some()
+                    || state.resolver.is_some()
+                    || state.calendar.is_some()
+                    || state.links.is_some()
+                    || state.focus.is_some() => {}
+            Event::Paste(text) => state.paste(&text),
+            Event::Key(key) => {
+                // The text fields and the modal screens want the keys as they are
+                let raw = state.editing
+                    || state.confirmation.is_some()
+                    || state.resolver.is_some()
+                    || state.calendar.is_some()
+                    || state.links.is_some()
+                    || state.focus.is_some()
+                    || state.command_line.is_some();
+                if raw {
+                    state.handle_raw(Event::Key(key), keymap, backups);
+                } else if let Some((action, count)) = awaiting_register.take() {
+                    match (action, macros::register(key)) {
+                        (_, None) => state.notifications.warning("Registers are a-z and 0-9"),
+                        (Action::Record, Some('@')) => {
+                            state.notifications.warning("Can't record into @")
+                        }
+                        (Action::Record, Some(register)) => {
+                            macros.start(register);
+                            state.notifications.info(format!("Recording @{}", register));
+                        }
+                        (_, Some(register)) => {
+                            if let Err(error) = macros.replay(register, count.unwrap_or(1)) {
+                                state.notifications.error(error);
+                            }
+                        }
+                    }
+                } else {
+                    match keymap.feed(key) {
+                        Feed::Action(Action::Record, _) if macros.recording().is_some() => {
+                            if let Some(register) = macros.stop() {
+                                state.notifications.info(format!("Recorded @{}", register));
+                            }
+                        }
+                        Feed::Action(action @ (Action::Record | Action::Replay), count) => {
+                            *awaiting_register = Some((action, count));
+                        }
+                        Feed::Action(Action::Repeat, count) => state.repeat(count),
+                        #[cfg(unix)]
+                        Feed::Action(Action::Suspend, _) => ui.renderer.suspend(),
+                        // There is no job control to give the terminal to the shell with, tearing
+                        // the screen down would only make it blink
+                        #[cfg(not(unix))]
+                        Feed::Action(Action::Suspend, _) => state
+                            .notifications
+                            .warning("Suspending is not supported on this system"),
+                        Feed::Action(action, count) => state.handle(action, count),
+                        Feed::Command(line) => state.run_line(&line, backups),
+                        Feed::Pending | Feed::Unbound => {}
+                    }
+                }
+            }
+        }
+    }
+
+    // Whatever follows from the events dispatched since the last frame, before the frame shows
+    // them. The cursors are where they were before the input moved them, for list_snap().
+    pub fn update(&mut self, prev_todo_curr: usize, prev_done_curr: usize) {
+        let backups = self.backups;
+        let Session {
+            state,
+            quit_message,
+            ..
+        } = self;
+
+        // Nothing to focus on anymore, back to the board
+        if state.focus.is_some() && state.board().todo_curr >= state.board().todos.len() {
+            state.focus = None;
+        }
+
+        // Nothing is hidden without a filter, and the DONE items left in the file stay there
+        if !state.filter.is_empty() {
+            let board = &mut state.boards[state.board_index];
+            list_snap(
+                &board.todos,
+                &mut board.todo_curr,
+                prev_todo_curr,
+                &state.filter,
+            );
+            list_snap(
+                &board.dones,
+                &mut board.done_curr,
+                prev_done_curr,
+                &state.filter,
+            );
+        }
+
+        // The merge conflicts and the ill-formed lines of the file are brought up once nothing
+        // else is going on
+        let idle = !state.board().read_only
+            && state.confirmation.is_none()
+            && state.resolver.is_none()
+            && state.calendar.is_none()
+            && state.links.is_none()
+            && state.focus.is_none()
+            && state.command_line.is_none()
+            && !state.editing
+            && !state.help
+            && !state.stats;
+        if idle {
+            if let Some(conflict) = state.board().extras.conflicts.first() {
+                state.resolver = Some(Resolver::new(conflict));
+            } else if !state.board().extras.problems.is_empty() {
+                state.confirmation = Some(Confirmation::Problem);
+            }
+        }
+
+        let board = state.board();
+        if board.cipher.is_none() {
+            recovery::update(
+                &board.file_path,
+                board.format,
+                &board.todos,
+                &board.dones,
+                &board.extras,
+            );
+        }
+
+        // Quitting saves everything first, and if that fails the app stays open rather than
+        // losing the changes
+        if state.quit && !state.discard {
+            let unsaved = state.unsaved().join(", ");
+            match state.save_all(backups, false) {
+                Ok(()) if !unsaved.is_empty() => {
+                    *quit_message = Some(format!("Saved state to {}", unsaved))
+                }
+                Ok(()) => {}
+                Err(error) => {
+                    state.quit = false;
+                    state.save_error = error;
+                    state.confirmation = Some(Confirmation::RetrySave { quit: true });
+                }
+            }
+        }
+    }
+
+    // Only draws what there is, all the input is handled by Session::dispatch()
+    pub fn frame(&mut self) {
+        let (done_style, done_highlight_style) = (self.done_style, self.done_highlight_style);
+        let Session {
+            state,
+            ui,
+            keymap,
+            macros,
+            redraw,
+            ..
+        } = self;
+        *redraw = false;
+
+        ui.renderer.clear();
+
+        let Vec2 { x, y } = ui.renderer.size();
+
+        if let Some(current) = &state.focus {
+            if let Some(todo) = state.board().todos.get(state.board().todo_curr) {
+                let mut lines = wrap(todo, cmp::max(x * 2 / 3, 1));
+                if current.timer {
+                    lines.push(String::new());
+                    lines.push(current.elapsed(Instant::now()));
+                }
+                ui.begin(Vec2::new(0, 0), LayoutKind::Vert);
+                ui.label_centered(
+                    &lines,
+                    Vec2::new(x, y - 1),
+                    Style {
+                        attrs: A_BOLD(),
+                        ..Style::from(REGULAR_PAIR)
+                    },
+                );
+                ui.status_bar(" FOCUS", "Esc back | t timer ", y - 1, x);
+                ui.end();
+            }
+            ui.renderer.present();
+            return;
+        }
+
+        let Panels {
+            details_height,
+            panels_kind,
+            todo,
+            done,
+        } = state.layout(Vec2::new(x, y));
+
+        let modified = state.modified();
+
+        ui.begin(Vec2::new(0, 0), LayoutKind::Vert);
+        {
+            if let Some(current) = state.confirmation {
+                let question = current.question(state);
+                ui.dialog(&question, Vec2::new(x, y));
+            } else if let Some(resolver) = &state.resolver {
+                ui.resolver(
+                    &state.board().extras.conflicts[0],
+                    resolver,
+                    Vec2::new(x, y),
+                );
+            } else if let Some(cursor) = state.calendar {
+                let counts = day_counts(&state.board().todos, &state.board().dones);
+                ui.calendar(cursor, &counts, Vec2::new(x, y));
+            } else if let Some((urls, curr)) = &state.links {
+                ui.picker("Open the link", urls, *curr, Vec2::new(x, y));
+            }
+
+            ui.begin_layout(LayoutKind::Horz);
+            {
+                if state.notifications.is_empty() {
+                    ui.label_fixed_width("", x, NOTIFICATION_PAIR);
+                }
+                for notification in state.notifications.iter() {
+                    let pair = match notification.level {
+                        Level::Info => NOTIFICATION_PAIR,
+                        Level::Warning | Level::Reminder => WARNING_PAIR,
+                        Level::Error => ERROR_PAIR,
+                    };
+                    ui.label(&format!("{}  ", notification.text), pair);
+                }
+            }
+            ui.end_layout();
+            let board = state.board();
+            ui.gauge(board.dones.len(), board.todos.len() + board.dones.len(), x);
+
+            ui.begin_layout(panels_kind);
+            {
+                let todo_styles = (Style::from(REGULAR_PAIR), Style::from(HIGHLIGHT_PAIR));
+                state.panel(ui, Status::Todo, &todo, keymap, todo_styles);
+                if state.done_collapsed {
+                    ui.label_fixed_width(
+                        &format!(
+                            "{} ({})",
+                            state.panel_name(Status::Done),
+                            state.board().dones.len()
+                        ),
+                        done.outer.x,
+                        HEADER_PAIR,
+                    );
+                } else {
+                    let done_styles = (done_style, done_highlight_style);
+                    state.panel(ui, Status::Done, &done, keymap, done_styles);
+                }
+            }
+            ui.end_layout();
+
+            if details_height > 0 {
+                let current = state.current();
+                ui.begin_frame(
+                    Vec2::new(x, details_height),
+                    "DETAILS",
+                    state.panel_name(state.panel),
+                    HEADER_PAIR,
+                );
+                {
+                    let lines = match current {
+                        Some(title) => item_details(title, x - 2),
+                        None => vec!["No item selected".to_string()],
+                    };
+                    for line in lines.iter().take(cmp::max(details_height - 2, 0) as usize) {
+                        ui.label_fixed_width(line, x - 2, REGULAR_PAIR);
+                    }
+                }
+                ui.end_layout();
+            }
+
+            // The command line takes the place of the status bar while it's open
+            if let Some((line, field)) = state.command_line.as_mut() {
+                ui.begin_layout(LayoutKind::Horz);
+                {
+                    ui.label(":", REGULAR_PAIR);
+                    ui.edit_field(line, field, x - 1);
+                }
+                ui.end_layout();
+            } else {
+                ui.status_bar(
+                    &format!(
+                        " {}{}{}{}{}",
+                        state.board().file_path,
+                        if modified { "*" } else { "" },
+                        if state.boards.len() > 1 {
+                            format!(" ({}/{})", state.board_index + 1, state.boards.len())
+                        } else {
+                            String::new()
+                        },
+                        match (state.to_stdout, state.board().read_only) {
+                            (_, true) => " [RO]",
+                            (true, _) if state.board_index == 0 => " [stdout]",
+                            _ => "",
+                        },
+                        if state.filter.is_empty() {
+                            String::new()
+                        } else {
+                            format!(" | {}", state.filter.describe())
+                        }
+                    ),
+                    &format!(
+                        "{}{}{} todo / {} done | {} | {} ",
+                        match macros.recording() {
+                            Some(register) => format!("recording @{} | ", register),
+                            None => String::new(),
+                        },
+                        match keymap.pending().as_str() {
+                            "" => String::new(),
+                            pending => format!("{} | ", pending),
+                        },
+                        state.board().todos.len(),
+                        state.board().dones.len(),
+                        state.panel_name(state.panel),
+                        if state.editing { "EDIT" } else { "NORMAL" }
+                    ),
+                    y - 1,
+                    x,
+                );
+            }
+
+            if state.help {
+                ui.popup_centered(help_lines(keymap), Vec2::new(x, y));
+            }
+            if state.stats {
+                ui.popup_centered(
+                    stats_lines(&state.board().todos, &state.board().dones, Date::today()),
+                    Vec2::new(x, y),
+                );
+            }
+        }
+        ui.end();
+
+        ui.renderer.present();
+    }
+}
+
+// The interactive app on the terminal, the Session with the events from the terminal and from the
+// rest of the world. main() runs the loop.
+pub struct Tui {
+    pub session: Session,
+    pub events: Events,
+    pub autosave: Option<Duration>,
+    // When the unsaved changes are going to be saved if autosave is on
+    pub autosave_at: Option<Instant>,
+    // The lists go there on quit, see --stdout
+    pub stdout_file: Option<File>,
+}
+
+impl Tui {
+    pub fn new(
+        session: Session,
+        events: Events,
+        autosave: Option<Duration>,
+        stdout_file: Option<File>,
+    ) -> Self {
+        Self {
+            session,
+            events,
+            autosave,
+            autosave_at: None,
+            stdout_file,
+        }
+    }
+
+    // Until the user quits, or there is a Ctrl+C or no terminal left
+    pub fn running(&self) -> bool {
+        !self.session.state.quit && !self.session.interrupted
+    }
+
+    // Takes the input and the events there are, and renders a frame if anything has changed. Waits
+    // for the next input when there is nothing to do.
+    pub fn step(&mut self) {
+        let Tui {
+            session,
+            events,
+            autosave,
+            autosave_at,
+            ..
+        } = self;
+        let backups = session.backups;
+        // Where the cursors were before the input moved them, for list_snap()
+        let board = session.state.board();
+        let (prev_todo_curr, prev_done_curr) = (board.todo_curr, board.done_curr);
+        // Whatever is left in the queue from the last frame goes first, the input waits for it
+        let (input, replayed) = match session.macros.pop() {
+            _ if !events.is_empty() => (None, false),
+            Some(input) => (Some(input), true),
+            None => (
+                read_input(&mut *session.ui.renderer, &mut session.state.notifications),
+                false,
+            ),
+        };
+        // Only what the user actually typed goes into the macro, not the keys that start and
+        // stop the recording and not what other macros replay
+        let recorded = !replayed && session.macros.recording().is_some();
+        if let Some(input) = &input {
+            events.push(Event::from(input));
+        }
+        let timer = session.timer(*autosave_at);
+        let state = &session.state;
+        events.poll(
+            Instant::now(),
+            timer,
+            &state.board().file_path,
+            state.board().saved_mtime,
+        );
+
+        // The keys for the text fields and the modal screens included, so whatever comes after
+        // them sees what they did
+        while let Some(event) = events.pop() {
+            session.dispatch(event);
+        }
+        // There may be no terminal left to ask anything on, it's all saved in Tui::finish()
+        if session.interrupted {
+            return;
+        }
+        if recorded && session.macros.recording().is_some() {
+            if let Some(input) = &input {
+                session.macros.record(input);
+            }
+        }
+
+        let now = Instant::now();
+        let state = &mut session.state;
+        if let Some(delay) = *autosave {
+            // Every input puts the autosave off, so it doesn't happen in the middle of typing
+            if state.unsaved().is_empty() {
+                *autosave_at = None;
+            } else if input.is_some() || autosave_at.is_none() {
+                *autosave_at = Some(now + delay);
+            } else if autosave_at.is_some_and(|at| now >= at) {
+                if state.editing || state.confirmation.is_some() {
+                    // Not in the middle of something, it can wait
+                    *autosave_at = Some(now + delay);
+                } else {
+                    *autosave_at = match state.save_all(backups, true) {
+                        Ok(()) => None,
+                        Err(error) => {
+                            let message = format!("{}. The changes are NOT saved", error);
+                            state.notifications.error(message);
+                            Some(now + delay)
+                        }
+                    };
+                    session.redraw = true;
+                }
+            }
+        }
+        if !session.redraw {
+            if input.is_none() && events.is_empty() {
+                let deadline = events.deadline(session.timer(*autosave_at));
+                session.ui.renderer.wait_for_input(Some(deadline));
+            }
+            return;
+        }
+        // Nothing changes the lists without a redraw
+        #[cfg(feature = "dbus")]
+        if let Some(bus) = &mut events.bus {
+            bus.changed(&session.state.board().todos, &session.state.board().dones);
+        }
+        session.update(prev_todo_curr, prev_done_curr);
+        session.frame();
+    }
+
+    // Saves what is left to save and gives the terminal back. Returns false if something didn't
+    // make it, for the exit status.
+    pub fn finish(self) -> bool {
+        let Tui {
+            session,
+            stdout_file,
+            ..
+        } = self;
+        let backups = session.backups;
+        let Session {
+            mut state,
+            quit_message,
+            ..
+        } = session;
+
+        // Saving goes first, the terminal may be gone already if the app is quitting because it
+        // was closed (SIGHUP). Nothing is printed then, which is fine, since there is nobody to
+        // read it.
+        let unsaved = state.unsaved().join(", ");
+        let mut recover = false;
+        let message = if let Some(message) = quit_message {
+            message
+        } else if unsaved.is_empty() && stdout_file.is_some() {
+            "Wrote the lists to stdout".to_string()
+        } else if unsaved.is_empty() {
+            "Nothing to save".to_string()
+        } else if !state.discard {
+            // Nobody is there to ask whether to try again, so the state goes where the crashes
+            // put it
+            match state.save_all(backups, false) {
+                Ok(()) => format!("Saved state to {}", unsaved),
+                Err(error) => {
+                    recover = true;
+                    format!("ERROR: {}", error)
+                }
+            }
+        } else {
+            format!("Discarded the changes to {}", unsaved)
+        };
+        // The files have what the autosaves saved, whatever happens to the rest
+        let commit_errors = state.autocommit.flush();
+        // The file given on the command line, discarding its changes on :q! like it would for a
+        // file
+        let stdout_result = stdout_file.map(|file| {
+            let board = mem::take(&mut state.boards[0]);
+            let (todos, dones) = if !state.discard {
+                (&board.todos, &board.dones)
+            } else {
+                (&board.saved_todos, &board.saved_dones)
+            };
+            let mut out = BufWriter::new(file);
+            let format = board.format.format();
+            write_state(&mut out, todos, dones, &board.extras, format).and_then(|()| out.flush())
+        });
+        // Releases the locks too
+        drop(state);
+
+        endwin();
+        bracketed_paste(false);
+        let mut stdout = io::stdout();
+        let _ = writeln!(stdout, "{}", message);
+        for error in commit_errors {
+            let _ = writeln!(stdout, "ERROR: could not commit {}", error);
+        }
+        if recover {
+            recovery::dump();
+            return false;
+        }
+        if let Some(Err(error)) = stdout_result {
+            let _ = writeln!(
+                stdout,
+                "ERROR: could not write the lists to stdout: {}",
+                error
+            );
+            return false;
+        }
+        true
+    }
+}
+
+impl App {
+    // One of the lists in its frame, the item being edited in the edit field. The styles are for the
+    // items and for the current one.
+    pub fn panel(
+        &mut self,
+        ui: &mut Ui,
+        status: Status,
+        panel: &Panel,
+        keymap: &Keymap,
+        (style, highlight_style): (Style, Style),
+    ) {
+        let tags = if self.panel == status {
+            self.completion_tags()
+        } else {
+            Vec::new()
+        };
+        let board = &self.boards[self.board_index];
+        let (items, curr, scroll, others) = match status {
+            Status::Todo => (
+                &board.todos,
+                board.todo_curr,
+                board.todo_scroll,
+                &board.dones,
+            ),
+            Status::Done => (
+                &board.dones,
+                board.done_curr,
+                board.done_scroll,
+                &board.todos,
+            ),
+        };
+        let current = self.panel == status;
+        ui.begin_frame(
+            panel.size,
+            self.panel_name(status),
+            &list_position(curr, panel.end - scroll, items.len()),
+            if current { HIGHLIGHT_PAIR } else { HEADER_PAIR },
+        );
+        ui.frame_scrollbar(scroll, panel.end - scroll, items.len());
+        {
+            let checkbox = match status {
+                Status::Todo => "- [ ]",
+                Status::Done => "- [x]",
+            };
+            // Nothing at all of the panel that the single panel layout leaves out
+            let shown = if panel.size.x > 0 { panel.end } else { 0 };
+            let filter = &self.filter;
+            // Only the items on the screen are read, the DONE items may be left in the file
+            for (index, item) in (scroll..shown)
+                .filter_map(|index| Some((index, items.title(index)?)))
+                .filter(|(_, item)| filter.shows(item))
+            {
+                ui.begin_layout(LayoutKind::Horz);
+                {
+                    ui.label_fixed_width(
+                        &line_number(self.line_numbers, index, curr, panel.gutter),
+                        panel.gutter,
+                        gutter_style(index == curr),
+                    );
+                    if current && index == curr && self.editing {
+                        ui.tag_completion(&item, &self.edit, &tags);
+                        ui.edit_field(&item, &mut self.edit, panel.width);
+                    } else {
+                        ui.item_label(
+                            &format!("{} {}", checkbox, item),
+                            panel.width,
+                            if current && index == curr {
+                                highlight_style
+                            } else {
+                                style
+                            },
+                            self.word_wrap,
+                        );
+                    }
+                }
+                ui.end_layout();
+            }
+
+            if items.is_empty() && panel.size.x > 0 {
+                ui.placeholder(
+                    &empty_hints(status, others.len(), keymap),
+                    Vec2::new(panel.outer.x - 2, panel.list_height),
+                );
+            }
+        }
+        ui.end_layout();
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,1087 @@
+// Drives the Session the way the main loop does, only with the keys typed by the tests and the
+// frames rendered into a grid of characters instead of the terminal.
+
+use std::cell::{Cell, RefCell};
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process;
+use std::rc::Rc;
+use std::thread;
+
+use super::*;
+
+use crate::caldav::CalDav;
+use crate::cli::{Args, Batch, STDIN_PATH};
+use crate::config::Config;
+use crate::git::AutoCommit;
+use crate::github::GitHub;
+use crate::hooks::Hooks;
+use crate::serve;
+use crate::setup::{self, Setup};
+use todo_rs::{ics, json};
+
+// What was on the screen the last time a frame was presented, a String per row
+type Screen = Rc<RefCell<Vec<String>>>;
+
+// The size of the screen at the start, the tests can only make it smaller
+const SIZE: Vec2 = Vec2 { x: 40, y: 12 };
+
+// The colors and the attributes are left out, the snapshots are plain text
+struct FakeRenderer {
+    cells: Vec<Vec<char>>,
+    cursor: Vec2,
+    size: Rc<Cell<Vec2>>,
+    screen: Screen,
+}
+
+impl FakeRenderer {
+    fn set(&mut self, pos: Vec2, c: char) {
+        let size = self.size.get();
+        if (0..size.x).contains(&pos.x) && (0..size.y).contains(&pos.y) {
+            self.cells[pos.y as usize][pos.x as usize] = c;
+        }
+    }
+}
+
+impl Renderer for FakeRenderer {
+    fn size(&self) -> Vec2 {
+        self.size.get()
+    }
+
+    fn clear(&mut self) {
+        for row in self.cells.iter_mut() {
+            row.fill(' ');
+        }
+    }
+
+    fn move_to(&mut self, pos: Vec2) {
+        self.cursor = pos;
+    }
+
+    fn put(&mut self, text: &str, _attrs: attr_t) {
+        for c in text.chars() {
+            self.set(self.cursor, c);
+            self.cursor.x += 1;
+        }
+    }
+
+    fn border(&mut self, pos: Vec2, size: Vec2, _attrs: attr_t) {
+        let right = pos.x + size.x - 1;
+        let bottom = pos.y + size.y - 1;
+        for x in pos.x + 1..right {
+            self.set(Vec2::new(x, pos.y), '-');
+            self.set(Vec2::new(x, bottom), '-');
+        }
+        for y in pos.y + 1..bottom {
+            self.set(Vec2::new(pos.x, y), '|');
+            self.set(Vec2::new(right, y), '|');
+        }
+        for (x, y) in [
+            (pos.x, pos.y),
+            (right, pos.y),
+            (pos.x, bottom),
+            (right, bottom),
+        ] {
+            self.set(Vec2::new(x, y), '+');
+        }
+    }
+
+    fn present(&mut self) {
+        *self.screen.borrow_mut() = self
+            .cells
+            .iter()
+            .map(|row| row.iter().collect::<String>().trim_end().to_string())
+            .collect();
+    }
+
+    fn read_input(&mut self) -> Option<Input> {
+        None
+    }
+
+    fn wait_for_input(&mut self, _deadline: Option<Instant>) {}
+
+    fn beep(&mut self) {}
+
+    fn resize(&mut self) {}
+
+    fn suspend(&mut self) {}
+}
+
+struct Harness {
+    session: Session,
+    size: Rc<Cell<Vec2>>,
+    screen: Screen,
+}
+
+impl Harness {
+    // The lists are not loaded from anywhere and never saved, unless the test asks for it
+    fn new(todos: &[&str], dones: &[&str]) -> Self {
+        Self::open(todos, dones, false)
+    }
+
+    // Like with --readonly
+    fn read_only(todos: &[&str], dones: &[&str]) -> Self {
+        Self::open(todos, dones, true)
+    }
+
+    fn open(todos: &[&str], dones: &[&str], all_read_only: bool) -> Self {
+        let lines = |items: &[&str]| items.iter().map(|item| item.to_string()).collect();
+        let mut board = Board::new(
+            "TODO".to_string(),
+            None,
+            None,
+            lines(todos),
+            lines(dones),
+            Extras::default(),
+        );
+        board.read_only = all_read_only;
+        Self::board(board, all_read_only)
+    }
+
+    // The file read the way the app reads it, in the directory of temp_dir()
+    fn file(file_path: &Path, data: &str) -> Self {
+        fs::write(file_path, data).unwrap();
+        let (mut todos, mut dones, mut extras) = (Vec::new(), Vec::new(), Extras::default());
+        let format = format::Kind::detect(&file_path.to_string_lossy(), data.as_bytes());
+        parse_state_lazily(&mut todos, &mut dones, &mut extras, data.as_bytes(), format);
+        let file_path = file_path.to_string_lossy().into_owned();
+        let mut board = Board::new(file_path, None, None, todos, dones, extras);
+        board.format = format;
+        Self::board(board, false)
+    }
+
+    fn board(board: Board, all_read_only: bool) -> Self {
+        let mut state = App::new(LineNumbers::Off, DEFAULT_SPLIT);
+        state.all_read_only = all_read_only;
+        state.boards.push(board);
+
+        let screen = Screen::default();
+        let size = Rc::new(Cell::new(SIZE));
+        let renderer = FakeRenderer {
+            cells: vec![vec![' '; SIZE.x as usize]; SIZE.y as usize],
+            cursor: Vec2::new(0, 0),
+            size: Rc::clone(&size),
+            screen: Rc::clone(&screen),
+        };
+        let keymap = Keymap::from_config(&Config::default(), None).unwrap();
+        let mut harness = Self {
+            session: Session::new(state, Ui::new(Box::new(renderer)), keymap, 0),
+            size,
+            screen,
+        };
+        harness.render();
+        harness
+    }
+
+    // Every character is a key of its own, like typing them one by one
+    fn keys(&mut self, keys: &str) -> &mut Self {
+        for c in keys.chars() {
+            self.key(c as i32);
+        }
+        self
+    }
+
+    fn key(&mut self, key: i32) -> &mut Self {
+        self.input(Input::Key(key))
+    }
+
+    // Like a paste of the terminal, or Ctrl+V from the clipboard
+    fn paste(&mut self, text: &str) -> &mut Self {
+        self.input(Input::Paste(text.to_string()))
+    }
+
+    fn input(&mut self, input: Input) -> &mut Self {
+        let recorded = self.session.macros.recording().is_some();
+        self.event(Event::from(&input));
+        if recorded && self.session.macros.recording().is_some() {
+            self.session.macros.record(&input);
+        }
+        while let Some(input) = self.session.macros.pop() {
+            self.event(Event::from(&input));
+        }
+        self
+    }
+
+    fn event(&mut self, event: Event) {
+        let prev = self.cursors();
+        self.session.dispatch(event);
+        self.render_after(prev);
+    }
+
+    fn render(&mut self) {
+        self.render_after(self.cursors());
+    }
+
+    // Like the main loop does after the events, with the cursors from before them
+    fn render_after(&mut self, (prev_todo_curr, prev_done_curr): (usize, usize)) {
+        self.session.update(prev_todo_curr, prev_done_curr);
+        self.session.frame();
+    }
+
+    fn cursors(&self) -> (usize, usize) {
+        let board = self.session.state.board();
+        (board.todo_curr, board.done_curr)
+    }
+
+    fn todos(&self) -> &[String] {
+        &self.session.state.board().todos
+    }
+
+    fn dones(&self) -> &[String] {
+        &self.session.state.board().dones
+    }
+
+    fn screen(&self) -> String {
+        self.screen.borrow().join("\n")
+    }
+
+    // Whether any of the notifications that are still up says it
+    fn notified(&self, text: &str) -> bool {
+        let notifications = &self.session.state.notifications;
+        notifications
+            .iter()
+            .any(|notice| notice.text.contains(text))
+    }
+}
+
+fn today() -> String {
+    Date::today().to_string()
+}
+
+// A directory of its own for the files of the test, which removes it when it's done with them
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("todo-rs-test-{}-{}", process::id(), name));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn transfer_marks_the_item_done_and_back() {
+    let mut app = Harness::new(&["buy milk", "write code"], &[]);
+    app.keys("j\n");
+    assert_eq!(app.todos(), ["buy milk"]);
+    assert_eq!(app.dones(), [format!("write code done:{}", today())]);
+
+    app.keys("\t\n");
+    assert_eq!(app.todos(), ["buy milk", "write code"]);
+    assert!(app.dones().is_empty());
+}
+
+#[test]
+fn the_done_items_at_the_end_of_the_file_are_read_when_needed() {
+    let dir = temp_dir("archive");
+    let file_path = dir.join("archive.todo");
+    let mut data = "TODO: buy milk\n".to_string();
+    for number in 1..=1000 {
+        data.push_str(&format!("DONE: task {}\n", number));
+    }
+    let mut app = Harness::file(&file_path, &data);
+    app.keys("jk");
+    assert!(app.screen().contains("- [x] task 1"));
+    assert!(app.screen().contains("1/1000"));
+    assert!(!app.session.state.board().dones.is_read());
+
+    app.keys("\n:w\n");
+    assert_eq!(app.dones().len(), 1001);
+    let saved = fs::read_to_string(&file_path).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    // Where it was in the file, like the items moved to the other list always are
+    let done = format!("DONE: buy milk | done:{}\n", today());
+    assert_eq!(saved, data.replacen("TODO: buy milk\n", &done, 1));
+}
+
+#[test]
+fn the_tags_of_the_done_items_left_in_the_file_complete_the_typed_one() {
+    let dir = temp_dir("tags");
+    let file_path = dir.join("tags.todo");
+    let mut data = "TODO: buy milk #shop\n".to_string();
+    for number in 1..=1000 {
+        data.push_str(&format!("DONE: task {}\n", number));
+    }
+    data.push_str("DONE: water the plants #garden\n");
+    let mut app = Harness::file(&file_path, &data);
+    fs::remove_dir_all(&dir).unwrap();
+    app.keys("ifeed the cat #ga");
+    assert!(app.screen().contains("#garden"));
+    app.keys("\t #s\t\n");
+    assert_eq!(
+        app.todos()[0],
+        format!("feed the cat #garden #shop created:{}", today())
+    );
+    assert!(!app.session.state.board().dones.is_read());
+}
+
+#[test]
+fn write_keeps_the_last_saves_as_backups() {
+    let dir = temp_dir("backups");
+    let file_path = dir.join("backups.todo");
+    let mut app = Harness::file(&file_path, "TODO: buy milk\n");
+    app.session.backups = 2;
+    app.keys("icall mom\n:w\n\n:w\n");
+    let done = format!("DONE: call mom | created:{} done:{}\n", today(), today());
+    let read = |extension: &str| {
+        let mut path = file_path.clone().into_os_string();
+        path.push(extension);
+        fs::read_to_string(path).ok()
+    };
+    let saved = read("");
+    let backups = [read(".bak.1"), read(".bak.2"), read(".tmp")];
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(saved.unwrap(), format!("TODO: buy milk\n{}", done));
+    let first = format!("TODO: call mom | created:{}\nTODO: buy milk\n", today());
+    assert_eq!(
+        backups,
+        [Some(first), Some("TODO: buy milk\n".to_string()), None]
+    );
+    assert!(!app.session.state.modified());
+}
+
+#[test]
+fn export_writes_a_copy_in_the_format_of_the_extension() {
+    let dir = temp_dir("export");
+    let path = dir.join("copy.json");
+    let mut app = Harness::new(&["buy milk"], &["call mom"]);
+    app.keys(&format!(":export {}\n", path.display()));
+    let json = fs::read_to_string(&path);
+    fs::remove_dir_all(&dir).unwrap();
+    let items = json::import(&json.unwrap()).unwrap();
+    assert_eq!(
+        items,
+        [
+            (Status::Todo, "buy milk".to_string()),
+            (Status::Done, "call mom".to_string())
+        ]
+    );
+}
+
+#[test]
+fn the_ill_formed_lines_are_fixed_when_asked() {
+    let dir = temp_dir("problems");
+    let file_path = dir.join("problems.todo");
+    let mut app = Harness::file(
+        &file_path,
+        "TODO: buy milk\nTODO call mom\nTODO: write code\n",
+    );
+    assert!(matches!(
+        app.session.state.confirmation,
+        Some(Confirmation::Problem)
+    ));
+    app.keys("y:w\n");
+    let saved = fs::read_to_string(&file_path);
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(app.todos(), ["buy milk", "call mom", "write code"]);
+    assert_eq!(
+        saved.unwrap(),
+        "TODO: buy milk\nTODO: call mom\nTODO: write code\n"
+    );
+}
+
+#[test]
+fn the_picked_sides_of_a_conflict_go_into_the_lists() {
+    let dir = temp_dir("conflict");
+    let file_path = dir.join("conflict.todo");
+    let data = "TODO: buy milk\n<<<<<<< HEAD\nTODO: call mom\n=======\nTODO: write code\n\
+                >>>>>>> other\nDONE: feed the cat\n";
+    let mut app = Harness::file(&file_path, data);
+    assert!(app.session.state.resolver.is_some());
+    assert!(app.screen().contains("Merge conflict at line 2"));
+    app.keys("\n:w\n");
+    let saved = fs::read_to_string(&file_path);
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(app.todos(), ["buy milk", "call mom", "write code"]);
+    assert_eq!(
+        saved.unwrap(),
+        "TODO: buy milk\nTODO: call mom\nTODO: write code\nDONE: feed the cat\n"
+    );
+}
+
+#[test]
+fn the_changes_on_disk_are_reloaded() {
+    let dir = temp_dir("reload");
+    let file_path = dir.join("reload.todo");
+    let mut app = Harness::file(&file_path, "TODO: buy milk\n");
+    fs::write(&file_path, "TODO: buy milk\nTODO: call mom\n").unwrap();
+    let path = file_path.to_string_lossy();
+    app.event(Event::FileChanged(file_mtime(&path)));
+    assert_eq!(app.todos(), ["buy milk", "call mom"]);
+
+    // Not without asking when there is something to lose
+    app.keys("\n");
+    fs::write(&file_path, "TODO: write code\n").unwrap();
+    app.event(Event::FileChanged(file_mtime(&path)));
+    assert!(matches!(
+        app.session.state.confirmation,
+        Some(Confirmation::Reload)
+    ));
+    app.keys("y");
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(app.todos(), ["write code"]);
+    assert!(app.dones().is_empty());
+}
+
+#[test]
+fn quit_saves_the_changes_once_asked() {
+    let dir = temp_dir("quit");
+    let file_path = dir.join("quit.todo");
+    let mut app = Harness::file(&file_path, "TODO: buy milk\n");
+    app.keys("\n:q\n");
+    assert!(app.notified("There are unsaved changes"));
+    assert!(!app.session.state.quit);
+
+    app.keys("Q");
+    assert!(matches!(
+        app.session.state.confirmation,
+        Some(Confirmation::Quit)
+    ));
+    app.keys("y");
+    let saved = fs::read_to_string(&file_path);
+    fs::remove_dir_all(&dir).unwrap();
+    assert!(app.session.state.quit);
+    assert_eq!(
+        saved.unwrap(),
+        format!("DONE: buy milk | done:{}\n", today())
+    );
+}
+
+#[test]
+fn open_switches_between_the_files_and_quit_saves_them_all() {
+    let dir = temp_dir("open");
+    let (first, second) = (dir.join("first.todo"), dir.join("second.todo"));
+    let mut app = Harness::file(&first, "TODO: buy milk\n");
+    app.keys(&format!(":open {}\n", second.display()));
+    assert_eq!(
+        app.session.state.board().file_path,
+        second.to_string_lossy()
+    );
+    app.keys("icall mom\n[");
+    assert_eq!(app.session.state.board().file_path, first.to_string_lossy());
+    app.keys("\nQy");
+    let saved = [fs::read_to_string(&first), fs::read_to_string(&second)];
+    fs::remove_dir_all(&dir).unwrap();
+    let [first, second] = saved.map(Result::unwrap);
+    assert_eq!(first, format!("DONE: buy milk | done:{}\n", today()));
+    assert_eq!(second, format!("TODO: call mom | created:{}\n", today()));
+}
+
+#[test]
+fn each_open_file_keeps_its_own_cursors_and_changes() {
+    let dir = temp_dir("boards");
+    let (first, second) = (dir.join("first.todo"), dir.join("second.todo"));
+    let mut app = Harness::file(&first, "TODO: buy milk\nTODO: call mom\nTODO: write code\n");
+    app.keys("j\n");
+    app.keys(&format!(":open {}\n", second.display()));
+    assert_eq!(app.cursors(), (0, 0));
+    app.keys(".");
+    assert!(app.notified("Nothing to repeat"));
+    app.keys("[");
+    assert_eq!(app.cursors(), (1, 0));
+    app.keys(".");
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(app.todos(), ["buy milk"]);
+}
+
+#[test]
+fn a_file_open_in_another_instance_is_left_to_it() {
+    let dir = temp_dir("lock");
+    let other = dir.join("other.todo").to_string_lossy().into_owned();
+    let lock = lock::acquire(&other).unwrap();
+    let mut app = Harness::new(&["buy milk"], &[]);
+    app.keys(&format!(":open {}\n", other));
+    drop(lock);
+    fs::remove_dir_all(&dir).unwrap();
+    assert!(app.notified("is open in another instance"));
+    assert_eq!(app.session.state.boards.len(), 1);
+    assert_eq!(app.todos(), ["buy milk"]);
+}
+
+#[test]
+fn a_batch_command_fails_on_a_file_open_in_another_instance() {
+    let dir = temp_dir("batch-lock");
+    let file_path = dir.join("todo.todo").to_string_lossy().into_owned();
+    let lock = lock::acquire(&file_path).unwrap();
+    let result = Setup::load(Args {
+        file_paths: vec![file_path.clone()],
+        batch: Some(Batch::Add("buy milk".to_string())),
+        ..Args::default()
+    });
+    drop(lock);
+    fs::remove_dir_all(&dir).unwrap();
+    let Err(setup::Error::Locked(locked)) = result else {
+        panic!("expected the file to be locked");
+    };
+    assert_eq!(locked.file_path, file_path);
+    assert_eq!(locked.owner, Some(process::id()));
+}
+
+#[test]
+fn the_lists_from_stdin_are_never_written_to_a_file() {
+    let board = Board::new(
+        STDIN_PATH.to_string(),
+        None,
+        None,
+        vec!["buy milk".to_string()],
+        Vec::new(),
+        Extras::default(),
+    );
+    let mut app = Harness::board(
+        Board {
+            detached: true,
+            ..board
+        },
+        false,
+    );
+    app.keys("\n:w\n");
+    assert!(app.notified("There is no file to write stdin to"));
+    assert!(!Path::new(STDIN_PATH).exists());
+
+    app.keys("Q");
+    assert!(app.session.state.quit);
+}
+
+#[test]
+fn autosave_saves_once_the_keys_stop() {
+    let dir = temp_dir("autosave");
+    let file_path = dir.join("autosave.todo");
+    let mut app = Harness::file(&file_path, "TODO: buy milk\n");
+    app.keys("\n");
+    let events = Events::default();
+    let mut tui = Tui::new(app.session, events, Some(Duration::ZERO), None);
+    tui.step();
+    let before = fs::read_to_string(&file_path);
+    tui.step();
+    let saved = fs::read_to_string(&file_path);
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(before.unwrap(), "TODO: buy milk\n");
+    assert_eq!(
+        saved.unwrap(),
+        format!("DONE: buy milk | done:{}\n", today())
+    );
+    assert!(tui.session.state.unsaved().is_empty());
+}
+
+#[test]
+fn a_signal_stops_the_app_with_the_changes_left_to_save() {
+    let mut app = Harness::new(&["buy milk"], &[]);
+    app.keys("\n");
+    let mut tui = Tui::new(app.session, Events::default(), None, None);
+    tui.events.push(Event::Signal);
+    tui.step();
+    assert!(!tui.running());
+    assert!(tui.session.state.confirmation.is_none());
+    assert_eq!(tui.session.state.unsaved(), ["TODO"]);
+}
+
+#[test]
+fn only_the_changes_touch_the_lists() {
+    let mut app = Harness::new(&["buy milk", "write code"], &["call mom"]);
+    let revisions = |app: &Harness| {
+        let board = app.session.state.board();
+        (board.todos.revision(), board.dones.revision())
+    };
+    let before = revisions(&app);
+    app.keys("jk\tk\t");
+    app.render();
+    assert_eq!(revisions(&app), before);
+    assert!(!app.session.state.modified());
+
+    app.keys("\n");
+    assert_ne!(revisions(&app), before);
+    assert!(app.session.state.modified());
+}
+
+#[test]
+fn insert_types_a_new_item() {
+    let mut app = Harness::new(&[], &[]);
+    app.keys("icall mom\n");
+    assert_eq!(app.todos(), [format!("call mom created:{}", today())]);
+    assert!(!app.session.state.editing);
+}
+
+#[test]
+fn insert_types_the_characters_outside_of_ascii() {
+    let mut app = Harness::new(&[], &[]);
+    app.keys("i");
+    // The way the terminal gives them, a byte at a time
+    for byte in "café 漢字".bytes() {
+        app.key(byte as i32);
+    }
+    app.keys("\n");
+    assert_eq!(app.todos(), [format!("café 漢字 created:{}", today())]);
+}
+
+#[test]
+fn ctrl_z_takes_back_what_was_typed_into_the_item() {
+    let mut app = Harness::new(&["buy milk"], &[]);
+    app.keys("r xy");
+    for _ in 0..3 {
+        app.key(CTRL_Z);
+    }
+    app.keys("\n");
+    assert_eq!(app.todos(), ["buy milk"]);
+}
+
+#[test]
+fn a_paste_inserts_an_item_per_line() {
+    let mut app = Harness::new(&["write code"], &["call mom"]);
+    app.paste("buy milk\r\n\n  feed the cat\n");
+    assert_eq!(
+        app.todos(),
+        [
+            format!("buy milk created:{}", today()),
+            format!("feed the cat created:{}", today()),
+            "write code".to_string()
+        ]
+    );
+
+    app.keys("\t").paste("walk the dog");
+    assert_eq!(app.todos().len(), 3);
+    assert_eq!(app.dones(), ["call mom"]);
+}
+
+#[test]
+fn a_paste_into_the_edited_item_stays_on_its_line() {
+    let mut app = Harness::new(&[], &[]);
+    app.keys("i").paste("buy\nmilk").keys("\n");
+    assert_eq!(app.todos(), [format!("buy milk created:{}", today())]);
+}
+
+#[test]
+fn the_edited_item_scrolls_within_its_panel() {
+    let mut app = Harness::new(&[], &[]);
+    app.keys(&format!("i{} end", "x".repeat(50)));
+    let screen = app.screen();
+    let row = screen.lines().find(|row| row.contains("end")).unwrap();
+    assert!(row.ends_with('|'), "{:?}", row);
+}
+
+#[test]
+fn the_edited_item_takes_the_keys_of_the_actions() {
+    let mut app = Harness::new(&["buy milk"], &["call mom"]);
+    app.keys("r jdQ\n");
+    assert_eq!(app.todos(), ["buy milk jdQ"]);
+    assert_eq!(app.dones(), ["call mom"]);
+    assert!(!app.session.state.quit);
+}
+
+#[test]
+fn the_done_items_are_edited_like_the_todo_ones() {
+    let mut app = Harness::new(&["write code #work"], &["call mom"]);
+    app.keys("\tr #w");
+    assert!(app.screen().contains(">#work"), "{}", app.screen());
+    app.keys("\t\n");
+    assert_eq!(app.dones(), ["call mom #work"]);
+    assert!(!app.session.state.editing);
+}
+
+#[test]
+fn delete_asks_first() {
+    let mut app = Harness::new(&[], &["buy milk", "call mom"]);
+    app.keys("\td");
+    assert!(app.screen().contains("Delete the item for good?"));
+    app.keys("n");
+    assert_eq!(app.dones(), ["buy milk", "call mom"]);
+
+    app.keys("dy");
+    assert_eq!(app.dones(), ["call mom"]);
+}
+
+#[test]
+fn clear_done_asks_first() {
+    let mut app = Harness::new(&["write code"], &["buy milk", "call mom"]);
+    app.keys("D");
+    let asked = matches!(
+        app.session.state.confirmation,
+        Some(Confirmation::ClearDone)
+    );
+    assert!(asked);
+    app.keys("n");
+    assert_eq!(app.dones(), ["buy milk", "call mom"]);
+
+    app.keys("Dy");
+    assert!(app.dones().is_empty());
+    assert_eq!(app.todos(), ["write code"]);
+}
+
+#[test]
+fn export_panel_asks_for_the_file() {
+    let mut app = Harness::new(&["buy milk"], &[]);
+    app.keys(":export panel\n");
+    assert!(app.screen().contains("Where to export to?"));
+    assert!(!std::path::Path::new("panel").exists());
+}
+
+#[test]
+fn repeat_and_replay_transfer_the_next_items() {
+    let mut app = Harness::new(&["a", "b", "c", "d"], &[]);
+    app.keys("\n.");
+    assert_eq!(app.todos(), ["c", "d"]);
+
+    app.keys("qa\nq");
+    assert_eq!(app.todos(), ["d"]);
+    app.keys("@a");
+    assert!(app.todos().is_empty());
+    assert_eq!(app.dones().len(), 4);
+}
+
+#[test]
+fn repeat_leaves_a_read_only_file_alone() {
+    let mut app = Harness::new(&["buy milk"], &[]);
+    app.keys("icall mom\n");
+    app.session.state.board_mut().read_only = true;
+    app.keys(".");
+    assert_eq!(
+        app.todos(),
+        [
+            format!("call mom created:{}", today()),
+            "buy milk".to_string()
+        ]
+    );
+    assert!(app.screen().contains("read-only"));
+
+    app.session.state.board_mut().last_change = Some(Change::Rename("write code".to_string()));
+    app.keys(".");
+    assert_eq!(app.todos()[1], "buy milk");
+}
+
+#[test]
+fn read_only_blocks_the_repeated_changes() {
+    let mut app = Harness::read_only(&["buy milk", "write code"], &["call mom"]);
+    app.session.state.board_mut().last_change = Some(Change::Action(Action::Transfer, 1));
+    app.keys(".");
+    app.session.state.board_mut().last_change = Some(Change::Insert("feed the cat".to_string()));
+    app.keys(".");
+    app.session.state.board_mut().last_change = Some(Change::Paste("feed the cat".to_string()));
+    app.keys(".");
+    assert_eq!(app.todos(), ["buy milk", "write code"]);
+    assert_eq!(app.dones(), ["call mom"]);
+    assert!(!app.session.state.modified());
+}
+
+#[test]
+fn replay_leaves_a_read_only_file_alone() {
+    let mut app = Harness::new(&["buy milk", "write code"], &[]);
+    app.keys("qa\nq");
+    app.session.state.board_mut().read_only = true;
+    app.keys("@a");
+    assert_eq!(app.todos(), ["write code"]);
+    assert_eq!(app.dones().len(), 1);
+    assert!(app.notified("read-only"));
+}
+
+#[test]
+fn read_only_blocks_the_changes() {
+    let mut app = Harness::read_only(&["buy milk", "write code"], &["call mom"]);
+    app.keys("i\nJ:sort\n").paste("feed the cat");
+    app.keys("\tdD");
+    assert_eq!(app.todos(), ["buy milk", "write code"]);
+    assert_eq!(app.dones(), ["call mom"]);
+    assert!(!app.session.state.editing);
+    assert!(app.session.state.confirmation.is_none());
+    assert!(!app.session.state.modified());
+}
+
+#[test]
+fn a_count_repeats_the_action() {
+    let mut app = Harness::new(&["a", "b", "c", "d"], &[]);
+    app.keys("2\n");
+    assert_eq!(app.todos(), ["c", "d"]);
+    assert_eq!(app.dones().len(), 2);
+
+    let mut app = Harness::new(&["a", "b", "c"], &[]);
+    app.keys("2J");
+    assert_eq!(app.todos(), ["b", "c", "a"]);
+    assert_eq!(app.session.state.board().todo_curr, 2);
+}
+
+#[test]
+fn sort_orders_the_current_panel() {
+    let mut app = Harness::new(&["call mom", "buy milk prio:10", "write code prio:2"], &[]);
+    app.keys(":sort prio\n");
+    assert_eq!(
+        app.todos(),
+        ["write code prio:2", "buy milk prio:10", "call mom"]
+    );
+    app.keys(":sort\n");
+    assert_eq!(
+        app.todos(),
+        ["buy milk prio:10", "call mom", "write code prio:2"]
+    );
+}
+
+#[test]
+fn snooze_puts_the_reminder_off() {
+    let mut app = Harness::new(&["call mom remind:00:00"], &[]);
+    app.event(Event::Tick);
+    assert!(app.notified("Reminder: call mom"));
+
+    app.keys(":snooze 30\n");
+    assert!(app.notified("Snoozed for 30 minutes"));
+    assert!(!app.notified("Reminder: call mom"));
+    assert!(item_meta(&app.todos()[0], "remind").is_some());
+}
+
+#[test]
+fn resize_scrolls_to_the_current_item_before_the_next_frame() {
+    let mut app = Harness::new(&["a", "b", "c"], &[]);
+    app.keys("jj");
+    assert_eq!(app.session.state.board().todo_scroll, 1);
+
+    app.size.set(Vec2::new(SIZE.x, SIZE.y - 2));
+    app.session.dispatch(Event::Resize);
+    assert_eq!(app.session.state.todo_view.rows, 1);
+    assert_eq!(app.session.state.board().todo_scroll, 2);
+}
+
+#[test]
+fn board_snapshot() {
+    let mut app = Harness::new(&["buy milk", "write code #work"], &["call mom"]);
+    app.keys("j");
+    assert_eq!(
+        app.screen(),
+        "
+[                             ] 1/3  33%
++ TODO ----------------------------- 2 +
+|- [ ] buy milk                        |
+|- [ ] write code #work                |
++--------------------------------------+
++ DONE ----------------------------- 1 +
+|- [x] call mom                        |
+|                                      |
+|                                      |
++--------------------------------------+
+ TODO   2 todo / 1 done | TODO | NORMAL"
+    );
+}
+
+// The config of the test as if it was read from config.toml
+fn config(source: &str) -> Config {
+    Config::parse(PathBuf::from("config.toml"), source).unwrap()
+}
+
+// Answers the requests one by one with the bodies, and gives back the method, the path and the body
+// of each of them once it's done. Gives up after a few seconds of nobody asking.
+fn fake_server(bodies: Vec<String>) -> (String, thread::JoinHandle<Vec<[String; 3]>>) {
+    let listener = serve::listen("127.0.0.1", 0).unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let server = thread::spawn(move || {
+        let mut requests = Vec::new();
+        for body in bodies {
+            let request = (0..500)
+                .find_map(|_| {
+                    let request = serve::accept(&listener).unwrap();
+                    if request.is_none() {
+                        thread::sleep(Duration::from_millis(10));
+                    }
+                    request
+                })
+                .expect("the app never asked");
+            requests.push([
+                request.method.clone(),
+                request.path.clone(),
+                request.body.clone(),
+            ]);
+            request.respond(serve::Response::new("200 OK", "application/json", body));
+        }
+        requests
+    });
+    (url, server)
+}
+
+#[cfg(unix)]
+#[test]
+fn the_hooks_get_the_added_and_the_done_items() {
+    let dir = temp_dir("hooks");
+    let (added, done) = (dir.join("added"), dir.join("done"));
+    let mut app = Harness::new(&["buy milk"], &[]);
+    let hooks = config(&format!(
+        "[hooks]\non_add = \"cat > '{}'\"\non_done = \"printf '%s' \\\"$TODO_ITEM\\\" > '{}'\"\n",
+        added.display(),
+        done.display()
+    ));
+    app.session.state.hooks = Hooks::from_config(&hooks).unwrap();
+    app.keys("icall mom\n\n");
+    // Waits for them to finish
+    app.session.state.hooks = Hooks::default();
+    let (added, done) = (fs::read_to_string(added), fs::read_to_string(done));
+    fs::remove_dir_all(&dir).unwrap();
+    let created = format!("call mom created:{}", today());
+    assert_eq!(added.unwrap(), format!("{}\n", created));
+    assert_eq!(done.unwrap(), format!("{} done:{}", created, today()));
+}
+
+#[test]
+fn write_commits_the_changes_with_autocommit() {
+    let dir = temp_dir("autocommit");
+    let git = |args: &[&str]| {
+        let output = process::Command::new("git")
+            .current_dir(&dir)
+            .args(args)
+            .output()
+            .unwrap();
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    };
+    git(&["init", "--quiet"]);
+    git(&["config", "user.name", "todo-rs"]);
+    git(&["config", "user.email", "todo-rs@localhost"]);
+    git(&["config", "commit.gpgsign", "false"]);
+    let mut app = Harness::file(&dir.join("git.todo"), "TODO: buy milk\n");
+    let autocommit = config("[git]\nautocommit = on\n");
+    app.session.state.autocommit = AutoCommit::from_config(&autocommit).unwrap();
+    app.keys("icall mom\n:w\n");
+    let log = git(&["log", "--format=%s"]);
+    let committed = git(&["show", "HEAD:git.todo"]);
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(log, "add: call mom\n");
+    assert_eq!(
+        committed,
+        format!("TODO: call mom | created:{}\nTODO: buy milk\n", today())
+    );
+}
+
+#[test]
+fn sync_pulls_the_new_items_of_the_calendar_and_pushes_ours() {
+    let dir = temp_dir("caldav");
+    let file_path = dir.join("sync.todo");
+    let multistatus = format!(
+        "<d:multistatus xmlns:d=\"DAV:\" xmlns:c=\"urn:ietf:params:xml:ns:caldav\">\
+         <d:response><d:href>/tasks/mom.ics</d:href><d:propstat><d:prop>\
+         <c:calendar-data>{}</c:calendar-data>\
+         </d:prop></d:propstat></d:response></d:multistatus>",
+        ics::calendar(Status::Todo, "call mom uid:mom", "mom")
+    );
+    let (url, server) = fake_server(vec![multistatus, String::new()]);
+    let mut app = Harness::file(&file_path, "TODO: buy milk\n");
+    let caldav = config(&format!("[caldav]\nurl = \"{}/tasks/\"\n", url));
+    app.session.state.caldav = CalDav::from_config(&caldav).unwrap();
+    app.keys(":sync\n");
+    let requests = server.join().unwrap();
+    let saved = fs::read_to_string(&file_path).unwrap();
+    let state = fs::read_to_string(dir.join("sync.todo.caldav"));
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(app.todos()[1], "call mom uid:mom");
+    let uid = item_meta(&app.todos()[0], "uid").unwrap();
+    assert_eq!(app.todos()[0], format!("buy milk uid:{}", uid));
+    assert_eq!(requests[0][..2], ["REPORT", "/tasks/"]);
+    assert_eq!(requests[1][0], "PUT");
+    assert!(requests[1][1].starts_with("/tasks/"));
+    assert!(requests[1][2].contains("SUMMARY:buy milk"));
+    assert!(saved.contains("call mom"));
+    assert_eq!(state.unwrap().lines().count(), 2);
+    assert!(!app.session.state.modified());
+}
+
+#[test]
+fn github_opens_issues_for_the_new_items_and_pulls_the_issues() {
+    let dir = temp_dir("github");
+    let file_path = dir.join("issues.todo");
+    let issues = r#"[{"number": 1, "title": "call mom", "state": "open"}]"#;
+    let (url, server) = fake_server(vec![issues.to_string(), r#"{"number": 2}"#.to_string()]);
+    let mut app = Harness::file(&file_path, "TODO: buy milk\n");
+    env::set_var("TODO_RS_TEST_GITHUB_TOKEN", "secret");
+    let github = config(&format!(
+        "[github]\nrepo = \"tsoding/todo-rs\"\ntoken_env = \"TODO_RS_TEST_GITHUB_TOKEN\"\napi = \"{}\"\n",
+        url
+    ));
+    app.session.state.github = GitHub::from_config(&github).unwrap();
+    app.keys(":github\n");
+    let requests = server.join().unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(app.todos(), ["buy milk issue:2", "call mom issue:1"]);
+    assert_eq!(requests[0][..2], ["GET", "/repos/tsoding/todo-rs/issues"]);
+    assert_eq!(requests[1][..2], ["POST", "/repos/tsoding/todo-rs/issues"]);
+    let title = json::parse(&requests[1][2]).unwrap();
+    assert!(matches!(title.get("title"), Some(json::Value::String(title)) if title == "buy milk"));
+}
+
+#[cfg(unix)]
+#[test]
+fn the_control_socket_adds_and_marks_done() {
+    use crate::control::Control;
+    use std::io::{BufRead, BufReader};
+    use std::os::unix::net::UnixStream;
+
+    let dir = temp_dir("control");
+    let socket = dir.join("control.sock");
+    let control = config(&format!("[control]\nsocket = \"{}\"\n", socket.display()));
+    let mut control = Control::from_config(&control).unwrap().0.unwrap();
+    let mut app = Harness::new(&["buy milk"], &[]);
+    let mut client = UnixStream::connect(&socket).unwrap();
+    client.write_all(b"add call mom\ndone buy\n").unwrap();
+    let mut requests = Vec::new();
+    for _ in 0..500 {
+        requests.extend(control.accept());
+        if requests.len() == 2 {
+            break;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+    for request in requests {
+        app.event(Event::Control(request));
+    }
+    let replies: Vec<String> = BufReader::new(client)
+        .lines()
+        .take(2)
+        .map(Result::unwrap)
+        .collect();
+    drop(control);
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(app.todos(), [format!("call mom created:{}", today())]);
+    assert_eq!(app.dones(), [format!("buy milk done:{}", today())]);
+    let done = format!("Done: buy milk done:{}", today());
+    assert_eq!(replies, ["Added item 2".to_string(), done]);
+}
+
+#[cfg(feature = "scripting")]
+#[test]
+fn the_commands_of_the_script_change_the_lists() {
+    let dir = temp_dir("script");
+    let path = dir.join("init.rhai");
+    fs::write(
+        &path,
+        "bind(\"T\", \"tag work\");\n\
+         fn tag(name) { set_todos(todos().map(|todo| todo + \" #\" + name)); }\n",
+    )
+    .unwrap();
+    let mut app = Harness::new(&["buy milk"], &[]);
+    let script = config(&format!("[script]\npath = \"{}\"\n", path.display()));
+    let script = crate::script::Script::load(&script, &mut app.session.keymap);
+    fs::remove_dir_all(&dir).unwrap();
+    app.session.state.script = script.unwrap();
+    app.keys("T:tag home\n");
+    assert_eq!(app.todos(), ["buy milk #work #home"]);
+}
+
+#[test]
+fn an_encrypted_file_is_saved_encrypted() {
+    let dir = temp_dir("encrypted");
+    let file_path = dir.join("secret.todo.asc");
+    let cipher = || crypt::Cipher::Gpg {
+        passphrase: "correct horse".to_string(),
+        armor: true,
+    };
+    let file = file_path.to_string_lossy().into_owned();
+    let todos = vec!["buy milk".to_string()];
+    let board = Board::new(
+        file,
+        None,
+        Some(cipher()),
+        todos,
+        Vec::new(),
+        Extras::default(),
+    );
+    let mut app = Harness::board(board, false);
+    app.keys("icall mom\n:w\n");
+    let saved = fs::read(&file_path).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert!(saved.starts_with(b"-----BEGIN PGP MESSAGE-----"));
+    let decrypted = String::from_utf8(cipher().decrypt(&saved).unwrap()).unwrap();
+    assert_eq!(
+        decrypted,
+        format!("TODO: call mom | created:{}\nTODO: buy milk\n", today())
+    );
+}
@@ -0,0 +1,896 @@
+use std::borrow::Cow;
+use std::cmp;
+use std::collections::HashMap;
+use std::ops::{Add, Mul};
+
+use crate::action::CTRL_Z;
+use crate::app::Resolver;
+use crate::render::Renderer;
+use crate::terminal::*;
+use crate::theme::*;
+use todo_rs::date::Date;
+use todo_rs::{item_date, item_tags, Conflict};
+
+#[derive(Default, Copy, Clone)]
+pub struct Vec2 {
+    pub x: i32,
+    pub y: i32,
+}
+
+impl Add for Vec2 {
+    type Output = Vec2;
+
+    fn add(self, rhs: Vec2) -> Vec2 {
+        Vec2 {
+            x: self.x + rhs.x,
+            y: self.y + rhs.y,
+        }
+    }
+}
+
+impl Mul for Vec2 {
+    type Output = Vec2;
+
+    fn mul(self, rhs: Vec2) -> Vec2 {
+        Vec2 {
+            x: self.x * rhs.x,
+            y: self.y * rhs.y,
+        }
+    }
+}
+
+impl Vec2 {
+    pub fn new(x: i32, y: i32) -> Self {
+        Self { x, y }
+    }
+}
+
+pub enum LayoutKind {
+    Vert,
+    Horz,
+}
+
+pub struct Layout {
+    pub kind: LayoutKind,
+    pub pos: Vec2,
+    pub size: Vec2,
+    // The layout takes at least this much space in its parent no matter what's inside of it
+    pub min_size: Vec2,
+    // Space around the layout reserved for things like frames
+    pub padding: i32,
+}
+
+impl Layout {
+    pub fn available_pos(&self) -> Vec2 {
+        use LayoutKind::*;
+        match self.kind {
+            Horz => self.pos + self.size * Vec2::new(1, 0),
+            Vert => self.pos + self.size * Vec2::new(0, 1),
+        }
+    }
+
+    pub fn add_widget(&mut self, size: Vec2) {
+        use LayoutKind::*;
+        match self.kind {
+            Horz => {
+                self.size.x += size.x;
+                self.size.y = cmp::max(self.size.y, size.y);
+            }
+            Vert => {
+                self.size.x = cmp::max(self.size.x, size.x);
+                self.size.y += size.y;
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+extern "C" {
+    fn wcwidth(c: libc::wchar_t) -> libc::c_int;
+}
+
+// How many columns the character takes on the screen: 2 for the wide ones like CJK, 0 for the
+// combining ones. Depends on the locale, so only meaningful after setlocale().
+#[cfg(unix)]
+pub fn char_width(c: char) -> usize {
+    cmp::max(unsafe { wcwidth(c as libc::wchar_t) }, 0) as usize
+}
+
+// There is no wcwidth() on Windows, so the widths come from the ranges of the characters instead.
+// Good enough for the combining ones we draw ourselves and the usual wide ones.
+#[cfg(not(unix))]
+pub fn char_width(c: char) -> usize {
+    match c as u32 {
+        0x0300..=0x036f | 0x1ab0..=0x1aff | 0x1dc0..=0x1dff | 0x200b..=0x200f | 0x20d0..=0x20ff => {
+            0
+        }
+        0xfe00..=0xfe0f | 0xfe20..=0xfe2f => 0,
+        0x1100..=0x115f | 0x2e80..=0x303e | 0x3041..=0xa4cf | 0xac00..=0xd7a3 | 0xf900..=0xfaff => {
+            2
+        }
+        0xfe30..=0xfe4f | 0xff00..=0xff60 | 0xffe0..=0xffe6 => 2,
+        0x1f300..=0x1f64f | 0x1f900..=0x1f9ff | 0x20000..=0x3fffd => 2,
+        _ => 1,
+    }
+}
+
+// How many columns the text takes on the screen, as opposed to how many bytes or chars it has
+pub fn text_width(text: &str) -> usize {
+    text.chars().map(char_width).sum()
+}
+
+pub const ELLIPSIS: char = '…';
+
+// Cuts the text down to the width columns, marking the cut with ELLIPSIS
+pub fn elide(text: &str, width: i32) -> Cow<'_, str> {
+    let width = cmp::max(width, 0) as usize;
+    if text_width(text) <= width {
+        return Cow::Borrowed(text);
+    }
+    if width == 0 {
+        return Cow::Borrowed("");
+    }
+    let mut elided = String::new();
+    let mut elided_width = 0;
+    for c in text.chars() {
+        elided_width += char_width(c);
+        if elided_width > width - 1 {
+            break;
+        }
+        elided.push(c);
+    }
+    elided.push(ELLIPSIS);
+    Cow::Owned(elided)
+}
+
+// Breaks the text into lines of at most width columns, preferably at the spaces
+pub fn wrap(text: &str, width: i32) -> Vec<String> {
+    let width = cmp::max(width, 1) as usize;
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    let mut line_width = 0;
+    for word in text.split(' ') {
+        let word_width = text_width(word);
+        if line_width > 0 && line_width + 1 + word_width > width {
+            lines.push(std::mem::take(&mut line));
+            line_width = 0;
+        }
+        if line_width > 0 {
+            line.push(' ');
+            line_width += 1;
+        }
+        // Words that don't fit even into an empty line are broken wherever the line ends
+        for c in word.chars() {
+            let c_width = char_width(c);
+            if line_width > 0 && line_width + c_width > width {
+                lines.push(std::mem::take(&mut line));
+                line_width = 0;
+            }
+            line.push(c);
+            line_width += c_width;
+        }
+    }
+    lines.push(line);
+    lines
+}
+
+// U+0336 COMBINING LONG STROKE OVERLAY. Terminals have no strike-through attribute that ncurses
+// knows about, so we strike the text through by attaching this to every character of it.
+pub const STRIKE: char = '\u{0336}';
+
+#[derive(Clone, Copy)]
+pub struct Style {
+    pub pair: i16,
+    pub attrs: attr_t,
+    pub strike: bool,
+}
+
+impl From<i16> for Style {
+    fn from(pair: i16) -> Self {
+        Self {
+            pair,
+            attrs: A_NORMAL(),
+            strike: false,
+        }
+    }
+}
+
+impl Style {
+    pub fn attrs(&self) -> attr_t {
+        COLOR_PAIR(self.pair) | self.attrs
+    }
+
+    pub fn decorate<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        if self.strike {
+            Cow::Owned(text.chars().flat_map(|c| [c, STRIKE]).collect())
+        } else {
+            Cow::Borrowed(text)
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct EditField {
+    pub cursor: usize,
+    // Where the visible part of the buffer starts when it's wider than the field, a byte index
+    // like the cursor
+    pub scroll: usize,
+    pub tag_curr: usize,
+    // Snapshots of the buffer and the cursor taken before each change since the editing began
+    pub undo: Vec<(String, usize)>,
+    // Both backends give a typed character outside of ASCII as its UTF-8 bytes, a key each. The
+    // bytes of the one being typed until it's whole.
+    pending: Vec<u8>,
+}
+
+impl EditField {
+    pub fn at(cursor: usize) -> Self {
+        Self {
+            cursor,
+            ..Self::default()
+        }
+    }
+
+    pub fn snapshot(&mut self, buffer: &str) {
+        self.undo.push((buffer.to_string(), self.cursor));
+    }
+
+    // The buffer may have changed under the cursor since it was put there
+    pub fn clamp(&mut self, buffer: &str) {
+        if self.cursor > buffer.len() {
+            self.cursor = buffer.len();
+        }
+        while !buffer.is_char_boundary(self.cursor) {
+            self.cursor -= 1;
+        }
+    }
+
+    pub fn paste(&mut self, buffer: &mut String, text: &str) {
+        self.clamp(buffer);
+        // The edited item is a single line, so all the line breaks of the pasted text are flattened
+        let text: String = text
+            .chars()
+            .map(|c| if c.is_control() { ' ' } else { c })
+            .collect();
+        self.snapshot(buffer);
+        buffer.insert_str(self.cursor, &text);
+        self.cursor += text.len();
+    }
+
+    // Returns false for the keys that are not for the field, like Enter, so the caller can take them
+    pub fn type_key(&mut self, buffer: &mut String, key: i32) -> bool {
+        self.clamp(buffer);
+        if !(128..=255).contains(&key) {
+            self.pending.clear();
+        }
+        let cursor = &mut self.cursor;
+        match key {
+            128..=255 => {
+                self.pending.push(key as u8);
+                match std::str::from_utf8(&self.pending) {
+                    Ok(text) => {
+                        self.undo.push((buffer.clone(), *cursor));
+                        buffer.insert_str(*cursor, text);
+                        *cursor += text.len();
+                        self.pending.clear();
+                    }
+                    // Not a character at all, rather than one that has more bytes to come
+                    Err(error) if error.error_len().is_some() => self.pending.clear(),
+                    Err(_) => {}
+                }
+            }
+            32..=126 => {
+                self.undo.push((buffer.clone(), *cursor));
+                if *cursor >= buffer.len() {
+                    buffer.push(key as u8 as char);
+                } else {
+                    buffer.insert(*cursor, key as u8 as char);
+                }
+                *cursor += 1;
+            }
+            constants::KEY_LEFT => {
+                if let Some(c) = buffer[..*cursor].chars().next_back() {
+                    *cursor -= c.len_utf8();
+                }
+            }
+            constants::KEY_RIGHT => {
+                if let Some(c) = buffer[*cursor..].chars().next() {
+                    *cursor += c.len_utf8();
+                }
+            }
+            constants::KEY_BACKSPACE => {
+                if let Some(c) = buffer[..*cursor].chars().next_back() {
+                    self.undo.push((buffer.clone(), *cursor));
+                    *cursor -= c.len_utf8();
+                    buffer.remove(*cursor);
+                }
+            }
+            constants::KEY_DC => {
+                if *cursor < buffer.len() {
+                    self.undo.push((buffer.clone(), *cursor));
+                    buffer.remove(*cursor);
+                }
+            }
+            CTRL_Z => {
+                if let Some((prev_buffer, prev_cursor)) = self.undo.pop() {
+                    *buffer = prev_buffer;
+                    *cursor = prev_cursor;
+                }
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    // Tab and the arrow keys pick one of the tags that complete the word under the cursor, while
+    // there are any. Returns false for the other keys, which are for Self::type_key().
+    pub fn complete_tag(&mut self, buffer: &mut String, key: i32, tags: &[String]) -> bool {
+        self.clamp(buffer);
+        let Some((prefix_start, candidates)) = tag_candidates(buffer, self, tags) else {
+            self.tag_curr = 0;
+            return false;
+        };
+        self.tag_curr = cmp::min(self.tag_curr, candidates.len() - 1);
+        match key {
+            constants::KEY_UP => self.tag_curr = self.tag_curr.saturating_sub(1),
+            constants::KEY_DOWN => {
+                self.tag_curr = cmp::min(self.tag_curr + 1, candidates.len() - 1)
+            }
+            9 => {
+                let tag = &candidates[self.tag_curr];
+                self.snapshot(buffer);
+                buffer.replace_range(prefix_start..self.cursor, tag);
+                self.cursor = prefix_start + tag.len();
+                self.tag_curr = 0;
+            }
+            _ => return false,
+        }
+        true
+    }
+}
+
+// The word under the cursor if it's the start of a tag, and where the word starts
+pub fn tag_prefix<'a>(buffer: &'a str, field: &EditField) -> Option<(usize, &'a str)> {
+    let before = buffer.get(..field.cursor)?;
+    let after = buffer.get(field.cursor..)?;
+    let prefix_start = before.rfind(' ').map(|i| i + 1).unwrap_or(0);
+    let prefix = &before[prefix_start..];
+    let at_word_end = after.is_empty() || after.starts_with(' ');
+    if at_word_end && prefix.starts_with(['#', '@', '+']) {
+        Some((prefix_start, prefix))
+    } else {
+        None
+    }
+}
+
+// The tags that complete the word under the cursor if it's the start of one, and where the word starts
+pub fn tag_candidates(
+    buffer: &str,
+    field: &EditField,
+    tags: &[String],
+) -> Option<(usize, Vec<String>)> {
+    const MAX_CANDIDATES: usize = 8;
+
+    let (prefix_start, prefix) = tag_prefix(buffer, field)?;
+
+    let candidates: Vec<String> = tags
+        .iter()
+        .filter(|tag| tag.starts_with(prefix) && tag.as_str() != prefix)
+        .take(MAX_CANDIDATES)
+        .cloned()
+        .collect();
+    if candidates.is_empty() {
+        None
+    } else {
+        Some((prefix_start, candidates))
+    }
+}
+
+pub struct Popup {
+    pub pos: Vec2,
+    pub items: Vec<String>,
+    pub curr: Option<usize>,
+}
+
+pub struct Ui {
+    pub renderer: Box<dyn Renderer>,
+    pub layouts: Vec<Layout>,
+    // Popups are rendered in Ui::end() so nothing that comes after them in the layout overdraws them
+    pub popup: Option<Popup>,
+}
+
+impl Ui {
+    pub fn new(renderer: Box<dyn Renderer>) -> Self {
+        Self {
+            renderer,
+            layouts: Vec::new(),
+            popup: None,
+        }
+    }
+
+    pub fn begin(&mut self, pos: Vec2, kind: LayoutKind) {
+        assert!(self.layouts.is_empty());
+        self.layouts.push(Layout {
+            kind,
+            pos,
+            size: Vec2::new(0, 0),
+            min_size: Vec2::new(0, 0),
+            padding: 0,
+        })
+    }
+
+    pub fn begin_layout(&mut self, kind: LayoutKind) {
+        self.begin_layout_sized(kind, Vec2::new(0, 0));
+    }
+
+    pub fn begin_layout_sized(&mut self, kind: LayoutKind, min_size: Vec2) {
+        let layout = self
+            .layouts
+            .last()
+            .expect("Can't create a layout outside of Ui::begin() and Ui::end()");
+        let pos = layout.available_pos();
+        self.layouts.push(Layout {
+            kind,
+            pos,
+            size: Vec2::new(0, 0),
+            min_size,
+            padding: 0,
+        });
+    }
+
+    // A box around the area of the given size with the title embedded into its top border and the
+    // info in its top right corner. The content goes inside of the box. Closed by Ui::end_layout().
+    pub fn begin_frame(&mut self, size: Vec2, title: &str, info: &str, pair: i16) {
+        let layout = self
+            .layouts
+            .last()
+            .expect("Can't create a frame outside of Ui::begin() and Ui::end()");
+        let pos = layout.available_pos();
+
+        if size.x < 2 || size.y < 2 {
+            self.begin_layout_sized(LayoutKind::Vert, size);
+            return;
+        }
+
+        let right = pos.x + size.x - 1;
+        self.renderer.border(pos, size, COLOR_PAIR(HEADER_PAIR));
+
+        let info = format!(" {} ", info);
+        let info_width = text_width(&info) as i32;
+        self.renderer.move_to(Vec2::new(right - info_width, pos.y));
+        self.renderer
+            .put(&elide(&info, size.x - 2), COLOR_PAIR(HEADER_PAIR));
+
+        self.renderer.move_to(Vec2::new(pos.x + 1, pos.y));
+        let title = format!(" {} ", title);
+        self.renderer
+            .put(&elide(&title, size.x - 3 - info_width), COLOR_PAIR(pair));
+
+        self.layouts.push(Layout {
+            kind: LayoutKind::Vert,
+            pos: pos + Vec2::new(1, 1),
+            size: Vec2::new(0, 0),
+            min_size: Vec2::new(size.x - 2, size.y - 2),
+            padding: 1,
+        });
+    }
+
+    // Must be called right after Ui::begin_frame(). Draws the scrollbar over the right border of the
+    // frame if not all of the total items are visible.
+    pub fn frame_scrollbar(&mut self, first: usize, visible: usize, total: usize) {
+        let layout = self
+            .layouts
+            .last()
+            .expect("Trying to render scrollbar outside of any layout");
+        let height = layout.min_size.y as usize;
+        if layout.padding == 0 || visible >= total || height == 0 {
+            return;
+        }
+
+        let thumb_height = cmp::max(height * visible / total, 1);
+        let thumb_pos = cmp::min(height * first / total, height - thumb_height);
+        let x = layout.pos.x + layout.min_size.x;
+        for row in thumb_pos..thumb_pos + thumb_height {
+            self.renderer
+                .move_to(Vec2::new(x, layout.pos.y + row as i32));
+            self.renderer.put(" ", COLOR_PAIR(HIGHLIGHT_PAIR));
+        }
+    }
+
+    pub fn end_layout(&mut self) {
+        let layout = self
+            .layouts
+            .pop()
+            .expect("Unbalanced Ui::begin_layout() and Ui::end_layout() calls.");
+        let size = Vec2::new(
+            cmp::max(layout.size.x, layout.min_size.x) + layout.padding * 2,
+            cmp::max(layout.size.y, layout.min_size.y) + layout.padding * 2,
+        );
+        self.layouts
+            .last_mut()
+            .expect("Unbalanced Ui::begin_layout() and Ui::end_layout() calls.")
+            .add_widget(size);
+    }
+
+    pub fn label_fixed_width(&mut self, text: &str, width: i32, style: impl Into<Style>) {
+        let style = style.into();
+        let layout = self
+            .layouts
+            .last_mut()
+            .expect("Trying to render label outside of any layout");
+        let pos = layout.available_pos();
+
+        self.renderer.move_to(pos);
+        self.renderer
+            .put(&style.decorate(&elide(text, width)), style.attrs());
+
+        layout.add_widget(Vec2::new(width, 1));
+    }
+
+    pub fn label_wrapped(&mut self, text: &str, width: i32, style: impl Into<Style>) {
+        let style = style.into();
+        let layout = self
+            .layouts
+            .last_mut()
+            .expect("Trying to render label outside of any layout");
+        let pos = layout.available_pos();
+
+        let lines = wrap(text, width);
+        for (row, line) in lines.iter().enumerate() {
+            self.renderer.move_to(pos + Vec2::new(0, row as i32));
+            self.renderer.put(&style.decorate(line), style.attrs());
+        }
+
+        layout.add_widget(Vec2::new(width, lines.len() as i32));
+    }
+
+    // Items are either elided or wrapped depending on the display mode the user picked
+    pub fn item_label(&mut self, text: &str, width: i32, style: impl Into<Style>, wrapped: bool) {
+        if wrapped {
+            self.label_wrapped(text, width, style);
+        } else {
+            self.label_fixed_width(text, width, style);
+        }
+    }
+
+    // Scrolls sideways to keep the cursor in sight when the buffer is wider than the field
+    pub fn edit_field(&mut self, buffer: &str, field: &mut EditField, width: i32) {
+        field.clamp(buffer);
+
+        let layout = self
+            .layouts
+            .last_mut()
+            .expect("Trying to render edit field outside of any layout");
+        let pos = layout.available_pos();
+
+        // By the columns, the cursor takes one of its own past the end of the buffer
+        let columns = cmp::max(width, 1) as usize;
+        field.scroll = cmp::min(field.scroll, field.cursor);
+        while !buffer.is_char_boundary(field.scroll) {
+            field.scroll -= 1;
+        }
+        while text_width(&buffer[field.scroll..field.cursor]) >= columns {
+            field.scroll += buffer[field.scroll..]
+                .chars()
+                .next()
+                .map_or(1, char::len_utf8);
+        }
+
+        // Buffer
+        {
+            let mut visible = String::new();
+            let mut visible_width = 0;
+            for c in buffer[field.scroll..].chars() {
+                visible_width += char_width(c);
+                if visible_width > columns {
+                    break;
+                }
+                visible.push(c);
+            }
+            self.renderer.move_to(pos);
+            self.renderer.put(&visible, COLOR_PAIR(REGULAR_PAIR));
+            layout.add_widget(Vec2::new(width, 1));
+        }
+
+        // Cursor
+        {
+            let (before, after) = buffer.split_at(field.cursor);
+            let under_cursor = after.chars().next().map(String::from);
+            let before = &before[field.scroll..];
+            self.renderer
+                .move_to(pos + Vec2::new(text_width(before) as i32, 0));
+            let under_cursor = under_cursor.as_deref().unwrap_or(" ");
+            self.renderer.put(under_cursor, COLOR_PAIR(HIGHLIGHT_PAIR));
+        }
+    }
+
+    // Dimmed lines centered in the area of the given size, for the places that have nothing else to show
+    pub fn placeholder(&mut self, lines: &[String], size: Vec2) {
+        let style = Style {
+            attrs: A_DIM(),
+            ..Style::from(REGULAR_PAIR)
+        };
+        self.label_centered(lines, size, style);
+    }
+
+    // The lines are centered in the area of the given size both horizontally and vertically
+    pub fn label_centered(&mut self, lines: &[String], size: Vec2, style: impl Into<Style>) {
+        let style = style.into();
+        let layout = self
+            .layouts
+            .last_mut()
+            .expect("Trying to render centered label outside of any layout");
+        let pos = layout.available_pos();
+
+        let top = cmp::max((size.y - lines.len() as i32) / 2, 0);
+        for (row, line) in lines.iter().take(size.y.max(0) as usize).enumerate() {
+            let line = elide(line, size.x);
+            let left = cmp::max((size.x - text_width(&line) as i32) / 2, 0);
+            self.renderer
+                .move_to(pos + Vec2::new(left, top + row as i32));
+            self.renderer.put(&style.decorate(&line), style.attrs());
+        }
+
+        layout.add_widget(size);
+    }
+
+    pub fn gauge(&mut self, value: usize, total: usize, width: i32) {
+        let layout = self
+            .layouts
+            .last_mut()
+            .expect("Trying to render gauge outside of any layout");
+        let pos = layout.available_pos();
+
+        let percent = (value * 100).checked_div(total).unwrap_or(0);
+        let caption = format!(" {}/{} {:>3}%", value, total, percent);
+        let bar_width = cmp::max(width - text_width(&caption) as i32 - 2, 0) as usize;
+        let filled = (bar_width * value).checked_div(total).unwrap_or(0);
+
+        self.renderer.move_to(pos);
+        self.renderer.put("[", COLOR_PAIR(REGULAR_PAIR));
+        self.renderer
+            .put(&" ".repeat(filled), COLOR_PAIR(HIGHLIGHT_PAIR));
+        let rest = format!("{}]{}", " ".repeat(bar_width - filled), caption);
+        self.renderer.put(&rest, COLOR_PAIR(REGULAR_PAIR));
+
+        layout.add_widget(Vec2::new(width, 1));
+    }
+
+    // Unlike the rest of the widgets it is pinned to the row instead of flowing with the layout
+    pub fn status_bar(&mut self, left: &str, right: &str, row: i32, width: i32) {
+        let gap = width - text_width(left) as i32 - text_width(right) as i32;
+        let text = format!("{}{}{}", left, " ".repeat(cmp::max(gap, 1) as usize), right);
+
+        self.renderer.move_to(Vec2::new(0, row));
+        self.renderer
+            .put(&elide(&text, width), COLOR_PAIR(HIGHLIGHT_PAIR));
+    }
+
+    // The tags that complete the word under the cursor, in a popup under it. Must be called right
+    // before the Ui::edit_field() it completes, so the popup lands under the edited text.
+    pub fn tag_completion(&mut self, buffer: &str, field: &EditField, tags: &[String]) {
+        let layout = self
+            .layouts
+            .last()
+            .expect("Trying to render tag completion outside of any layout");
+        let pos = layout.available_pos();
+
+        let Some((prefix_start, candidates)) = tag_candidates(buffer, field, tags) else {
+            return;
+        };
+        let curr = cmp::min(field.tag_curr, candidates.len() - 1);
+
+        // Under the start of the tag, or the start of the field if it's scrolled out of sight
+        let shown = buffer.get(field.scroll..prefix_start).unwrap_or_default();
+        self.popup = Some(Popup {
+            pos: Vec2::new(pos.x + text_width(shown) as i32, pos.y + 1),
+            items: candidates,
+            curr: Some(curr),
+        });
+    }
+
+    // A modal dialog with the question, see Answer::from_key() for the answers to it
+    pub fn dialog(&mut self, question: &str, screen: Vec2) {
+        self.popup_centered(
+            vec![
+                String::new(),
+                format!(" {} ", question),
+                String::new(),
+                " [y]es   [n]o   [Esc] cancel ".to_string(),
+                String::new(),
+            ],
+            screen,
+        );
+    }
+
+    // A modal month calendar with the number of the items due/done on each day, see calendar_key()
+    // for moving its cursor around
+    pub fn calendar(&mut self, cursor: Date, counts: &HashMap<Date, (usize, usize)>, screen: Vec2) {
+        const CELL_WIDTH: usize = 7;
+        const WEEKDAYS: [&str; 7] = ["Mo", "Tu", "We", "Th", "Fr", "Sa", "Su"];
+
+        let grid_width = (CELL_WIDTH + 2) * WEEKDAYS.len();
+        let title = format!("{} {}", cursor.month_name(), cursor.year);
+        let mut lines = vec![
+            String::new(),
+            format!("{:^width$}", title, width = grid_width),
+            String::new(),
+            WEEKDAYS
+                .iter()
+                .map(|weekday| format!(" {:<width$} ", weekday, width = CELL_WIDTH))
+                .collect(),
+        ];
+        let first = cursor.first_of_month();
+        let mut day = first.add_days(-i64::from(first.weekday()));
+        while day.month == first.month || day < first {
+            let mut line = String::new();
+            for _ in 0..WEEKDAYS.len() {
+                let cell = match counts.get(&day) {
+                    _ if day.month != first.month => String::new(),
+                    Some((due, done)) => format!("{:>2} {}/{}", day.day, due, done),
+                    None => format!("{:>2}", day.day),
+                };
+                let (left, right) = if day == cursor {
+                    ('[', ']')
+                } else {
+                    (' ', ' ')
+                };
+                line.push_str(&format!(
+                    "{}{:<width$}{}",
+                    left,
+                    cell,
+                    right,
+                    width = CELL_WIDTH
+                ));
+                day = day.add_days(1);
+            }
+            lines.push(line);
+        }
+        lines.push(String::new());
+        lines.push(" due/done   h/j/k/l move   Enter show the day   Esc close ".to_string());
+        lines.push(String::new());
+
+        self.popup_centered(lines, screen);
+    }
+
+    // The two sides of a merge conflict next to each other, see Resolver::key() for picking the
+    // items to keep from either of them
+    pub fn resolver(&mut self, conflict: &Conflict, resolver: &Resolver, screen: Vec2) {
+        let sides = [&conflict.ours, &conflict.theirs];
+        let column = cmp::max(screen.x / 2 - 4, 10);
+        let cell = |text: &str| {
+            let text = elide(text, column);
+            format!(
+                "{}{}",
+                text,
+                " ".repeat(column as usize - text_width(&text))
+            )
+        };
+        let mut lines = vec![
+            String::new(),
+            format!(" Merge conflict at line {}", conflict.line),
+            String::new(),
+            format!(
+                " {}  {}",
+                cell(&conflict.labels[0]),
+                cell(&conflict.labels[1])
+            ),
+        ];
+        for row in 0..cmp::max(conflict.ours.len(), conflict.theirs.len()) {
+            let mut line = String::new();
+            for (side, items) in sides.iter().enumerate() {
+                let text = match items.get(row) {
+                    Some((status, title)) => format!(
+                        "{}[{}] {}: {}",
+                        if side == resolver.side && row == resolver.curr {
+                            '>'
+                        } else {
+                            ' '
+                        },
+                        if resolver.picked[side][row] { 'x' } else { ' ' },
+                        status.name(),
+                        title
+                    ),
+                    None => String::new(),
+                };
+                line.push_str(&format!(" {} ", cell(&text)));
+            }
+            lines.push(line);
+        }
+        lines.push(String::new());
+        lines.push(
+            " j/k move   h/l side   Space pick   o/t ours/theirs only   Enter resolve   Esc later "
+                .to_string(),
+        );
+        lines.push(String::new());
+
+        self.popup_centered(lines, screen);
+    }
+
+    // A modal list to pick one of the items from, see picker_key()
+    pub fn picker(&mut self, title: &str, items: &[String], curr: usize, screen: Vec2) {
+        let width = cmp::max(screen.x - 12, 10);
+        let mut lines = vec![String::new(), format!(" {}", title), String::new()];
+        for (index, item) in items.iter().enumerate() {
+            let line = format!(
+                "{} {}. {}",
+                if index == curr { '>' } else { ' ' },
+                index + 1,
+                item
+            );
+            lines.push(format!(" {} ", elide(&line, width)));
+        }
+        lines.push(String::new());
+        lines.push(" j/k move   Enter or 1-9 open   Esc close ".to_string());
+        lines.push(String::new());
+
+        self.popup_centered(lines, screen);
+    }
+
+    pub fn popup_centered(&mut self, items: Vec<String>, screen: Vec2) {
+        // Minus the marker and the padding of Ui::end()
+        let items: Vec<String> = items
+            .iter()
+            .map(|item| elide(item, screen.x - 2).into_owned())
+            .collect();
+        let width = items.iter().map(|item| text_width(item)).max().unwrap_or(0) as i32;
+        let height = items.len() as i32;
+        self.popup = Some(Popup {
+            pos: Vec2::new(
+                cmp::max((screen.x - width) / 2, 0),
+                cmp::max((screen.y - height) / 2, 0),
+            ),
+            items,
+            curr: None,
+        });
+    }
+
+    pub fn label(&mut self, text: &str, style: impl Into<Style>) {
+        self.label_fixed_width(text, text_width(text) as i32, style);
+    }
+
+    pub fn end(&mut self) {
+        self.layouts
+            .pop()
+            .expect("Unbalanced Ui::begin() and Ui::end() calls.");
+
+        if let Some(popup) = self.popup.take() {
+            let width = popup
+                .items
+                .iter()
+                .map(|item| text_width(item))
+                .max()
+                .unwrap_or(0);
+            for (row, item) in popup.items.iter().enumerate() {
+                let marker = if Some(row) == popup.curr { '>' } else { ' ' };
+                // Padding by hand, since format!() pads by chars rather than by columns
+                let padding = " ".repeat(width - text_width(item));
+                self.renderer.move_to(popup.pos + Vec2::new(0, row as i32));
+                let line = format!("{}{}{} ", marker, item, padding);
+                self.renderer.put(&line, COLOR_PAIR(HIGHLIGHT_PAIR));
+            }
+        }
+    }
+}
+
+// The full title of the item followed by whatever else is known about it, for the details pane
+pub fn item_details(title: &str, width: i32) -> Vec<String> {
+    let tags: Vec<&str> = item_tags(title).collect();
+    let mut lines = wrap(title, width);
+    lines.push(String::new());
+    lines.push(format!(
+        "Tags: {}",
+        if tags.is_empty() {
+            "none".to_string()
+        } else {
+            tags.join(" ")
+        }
+    ));
+    if let Some(due) = item_date(title, "due") {
+        lines.push(format!("Due: {}", due));
+    }
+    if let Some(done) = item_date(title, "done") {
+        lines.push(format!("Done: {}", done));
+    }
+    lines
+}
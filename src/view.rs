@@ -0,0 +1,296 @@
+use std::cmp;
+
+use crate::action::*;
+use crate::config::Config;
+use crate::terminal::*;
+use crate::theme::*;
+use crate::ui::*;
+use todo_rs::items::Items;
+use todo_rs::*;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum PanelLayout {
+    Split,
+    Stacked,
+    // Only the active panel on the whole screen
+    Single,
+}
+
+impl PanelLayout {
+    pub fn next(self) -> Self {
+        match self {
+            PanelLayout::Split => PanelLayout::Stacked,
+            PanelLayout::Stacked => PanelLayout::Single,
+            PanelLayout::Single => PanelLayout::Split,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            PanelLayout::Split => "split",
+            PanelLayout::Stacked => "stacked",
+            PanelLayout::Single => "single panel",
+        }
+    }
+
+    // One of header::LAYOUTS
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "split" => Some(PanelLayout::Split),
+            "stacked" => Some(PanelLayout::Stacked),
+            "single" => Some(PanelLayout::Single),
+            _ => None,
+        }
+    }
+
+    pub fn from_config(config: &Config) -> Result<Self, String> {
+        match config.get("ui", "layout") {
+            None | Some("split") => Ok(PanelLayout::Split),
+            Some("stacked") => Ok(PanelLayout::Stacked),
+            Some("single") => Ok(PanelLayout::Single),
+            Some(value) => Err(config.error(
+                "ui",
+                "layout",
+                &format!(
+                    "expected `split`, `stacked` or `single` but got `{}`",
+                    value
+                ),
+            )),
+        }
+    }
+}
+
+// Rows taken by the details pane, frame included
+pub const DETAILS_HEIGHT: i32 = 8;
+
+// Narrower than that the panels are too cramped to be shown side by side
+pub const MIN_SPLIT_WIDTH: i32 = 60;
+
+// How much of the screen goes to the TODO panel, in percent
+pub const DEFAULT_SPLIT: i32 = 50;
+pub const MIN_SPLIT: i32 = 20;
+pub const MAX_SPLIT: i32 = 80;
+pub const SPLIT_STEP: i32 = 5;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum LineNumbers {
+    Off,
+    Absolute,
+    // Distance to the current item like vim's relativenumber, so count prefixes are easy to type
+    Relative,
+}
+
+impl LineNumbers {
+    pub fn from_config(config: &Config) -> Result<Self, String> {
+        match config.get("ui", "line_numbers") {
+            None | Some("off") => Ok(LineNumbers::Off),
+            Some("absolute") => Ok(LineNumbers::Absolute),
+            Some("relative") => Ok(LineNumbers::Relative),
+            Some(value) => Err(config.error(
+                "ui",
+                "line_numbers",
+                &format!(
+                    "expected `off`, `absolute` or `relative` but got `{}`",
+                    value
+                ),
+            )),
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            LineNumbers::Off => LineNumbers::Absolute,
+            LineNumbers::Absolute => LineNumbers::Relative,
+            LineNumbers::Relative => LineNumbers::Off,
+        }
+    }
+}
+
+pub fn gutter_width(mode: LineNumbers, len: usize) -> i32 {
+    match mode {
+        LineNumbers::Off => 0,
+        _ => cmp::max(len, 1).to_string().len() as i32 + 1,
+    }
+}
+
+pub fn gutter_style(current: bool) -> Style {
+    Style {
+        attrs: if current { A_BOLD() } else { A_DIM() },
+        ..Style::from(REGULAR_PAIR)
+    }
+}
+
+pub fn line_number(mode: LineNumbers, index: usize, curr: usize, width: i32) -> String {
+    let number = match mode {
+        LineNumbers::Off => return String::new(),
+        LineNumbers::Relative if index != curr => cmp::max(index, curr) - cmp::min(index, curr),
+        _ => index + 1,
+    };
+    format!(
+        "{:>width$} ",
+        number,
+        width = cmp::max(width - 1, 0) as usize
+    )
+}
+
+pub fn item_height(text: &str, width: i32, wrapped: bool) -> i32 {
+    if wrapped {
+        wrap(text, width).len() as i32
+    } else {
+        1
+    }
+}
+
+// The rows the items of a list take, 0 for the ones the filter hides. They are worked out one item
+// at a time as they are asked for, and the scrolling only asks for the ones around the visible
+// items, so a frame takes as long with tens of thousands of items in the archive as with a few.
+pub struct Heights<'a> {
+    pub items: &'a Items,
+    pub status: Status,
+    pub filter: &'a Filter,
+    pub width: i32,
+    pub wrapped: bool,
+}
+
+impl Heights<'_> {
+    fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    // 0 past the end too, which is where the cursor of an empty list is
+    fn get(&self, index: usize) -> i32 {
+        let item = match self.items.title(index) {
+            Some(item) if self.filter.shows(&item) => item,
+            _ => return 0,
+        };
+        let checkbox = match self.status {
+            Status::Todo => "- [ ]",
+            Status::Done => "- [x]",
+        };
+        item_height(&format!("{} {}", checkbox, item), self.width, self.wrapped)
+    }
+}
+
+// Adjusts the scroll of the list so its current item is visible within the height rows
+pub fn list_scroll(heights: &Heights, curr: usize, scroll: &mut usize, height: i32) {
+    if *scroll > curr {
+        *scroll = curr;
+    }
+    // The first item from the scroll on the current one still fits after
+    let mut first = curr;
+    let mut rows = heights.get(curr);
+    while first > *scroll && rows + heights.get(first - 1) <= height {
+        first -= 1;
+        rows += heights.get(first);
+    }
+    *scroll = first;
+    // Don't leave empty space at the bottom when the list can be scrolled back, e.g. when the
+    // window got taller
+    let mut last_page = heights.len();
+    let mut rows = 0;
+    while last_page > *scroll && rows + heights.get(last_page - 1) <= height {
+        last_page -= 1;
+        rows += heights.get(last_page);
+    }
+    if last_page == *scroll {
+        while *scroll > 0 && rows + heights.get(*scroll - 1) <= height {
+            *scroll -= 1;
+            rows += heights.get(*scroll);
+        }
+    }
+}
+
+// Index right after the last item that fits into the height rows starting from the scroll. The
+// first item is always considered fitting so a single huge item does not hide everything.
+pub fn list_visible_end(heights: &Heights, scroll: usize, height: i32) -> usize {
+    let mut end = scroll;
+    let mut rows = 0;
+    while end < heights.len() && (end == scroll || rows + heights.get(end) <= height) {
+        rows += heights.get(end);
+        end += 1;
+    }
+    end
+}
+
+// What to show in the corner of the list's panel: just the size if the whole list is visible
+// and the position of the cursor otherwise
+pub fn list_position(curr: usize, visible: usize, len: usize) -> String {
+    if visible < len {
+        format!("{}/{}", curr + 1, len)
+    } else {
+        len.to_string()
+    }
+}
+
+// How far the pages move: the rows of the list, half of them or the count of either
+pub fn page_rows(height: i32, action: Action, count: usize) -> i32 {
+    let rows = match action {
+        Action::HalfPageUp | Action::HalfPageDown => height / 2,
+        _ => height,
+    };
+    cmp::max(rows, 1).saturating_mul(count as i32)
+}
+
+// Index of the item the rows away from the index, moving over whole items only but at least one
+pub fn rows_forward(heights: &Heights, index: usize, rows: i32) -> usize {
+    let mut index = index;
+    let mut moved = 0;
+    while index + 1 < heights.len() && (moved == 0 || moved + heights.get(index) <= rows) {
+        moved += heights.get(index);
+        index += 1;
+    }
+    index
+}
+
+pub fn rows_backward(heights: &Heights, index: usize, rows: i32) -> usize {
+    let mut index = index;
+    let mut moved = 0;
+    while index > 0 && (moved == 0 || moved + heights.get(index - 1) <= rows) {
+        moved += heights.get(index - 1);
+        index -= 1;
+    }
+    index
+}
+
+// Both the cursor and the viewport move by the rows, so the cursor stays where it was on the screen
+// unless the list ends. list_scroll() takes care of the end of the list on the next frame.
+pub fn list_page_up(heights: &Heights, list_curr: &mut usize, scroll: &mut usize, rows: i32) {
+    *list_curr = rows_backward(heights, *list_curr, rows);
+    *scroll = rows_backward(heights, *scroll, rows);
+}
+
+pub fn list_page_down(heights: &Heights, list_curr: &mut usize, scroll: &mut usize, rows: i32) {
+    *list_curr = rows_forward(heights, *list_curr, rows);
+    *scroll = rows_forward(heights, *scroll, rows);
+}
+
+// The width of the items of a list as of the last frame and the rows there were for them, for
+// the actions that depend on what the user sees
+#[derive(Default)]
+pub struct ListView {
+    pub width: i32,
+    pub rows: i32,
+}
+
+// Where one of the lists goes on the screen, see App::layout_list()
+pub struct Panel {
+    // The size of the panel, the frame and the header included, and what it really takes in the
+    // single panel layout
+    pub outer: Vec2,
+    pub size: Vec2,
+    pub list_height: i32,
+    pub gutter: i32,
+    pub width: i32,
+    // Right after the last visible item
+    pub end: usize,
+}
+
+// Where everything goes on the screen, see App::layout()
+pub struct Panels {
+    pub details_height: i32,
+    pub panels_kind: LayoutKind,
+    pub todo: Panel,
+    pub done: Panel,
+}
+
+// A file open in the app. The fields of the current one live in App, the others wait here